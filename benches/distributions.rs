@@ -0,0 +1,62 @@
+/*!
+
+Compares three ways for a manager to get the exponential distribution it samples from on every event, for 50 million
+draws. Run with
+
+```text
+cargo bench --bench distributions
+```
+
+ - Inline: construct the `Exp` from the rate at every draw.
+ - Cached: look it up in a `Distributions` resource at every draw, as a system taking `ResMut<Distributions>` would.
+ - Kept: construct it once with `Distributions::validated_exp` and copy it at every draw, as the example managers do.
+
+Each approach draws from its own generator with the same seed, so they draw the same numbers, and the sum of the draws
+is printed to check that.
+
+*/
+
+use std::{
+  hint::black_box,
+  time::{Duration as WallDuration, Instant}
+};
+
+use bevy_ecs::prelude::*;
+use rand::{rngs::SmallRng, SeedableRng};
+use rand_distr::Distribution;
+
+use ecs_disease_models::{
+  distributions::Distributions,
+  units::{Duration, Rate}
+};
+
+const DRAWS: usize = 50_000_000;
+const INFECTION_DURATION: Duration = Duration(5.0);
+
+/// Times `DRAWS` draws of `draw`, returning the elapsed time and the sum of the draws.
+fn time(mut draw: impl FnMut(&mut SmallRng) -> f64) -> (WallDuration, f64) {
+  let mut rng = SmallRng::seed_from_u64(1);
+  let start = Instant::now();
+  let sum: f64 = (0..DRAWS).map(|_| black_box(draw(&mut rng))).sum();
+  (start.elapsed(), sum)
+}
+
+fn main() {
+  let inline = time(|rng| {
+    Rate::from_mean(black_box(INFECTION_DURATION)).exp().unwrap().sample(rng)
+  });
+
+  let mut world = World::default();
+  world.init_resource::<Distributions>();
+  let cached = time(|rng| {
+    world.resource_mut::<Distributions>().exp(Rate::from_mean(black_box(INFECTION_DURATION))).unwrap().sample(rng)
+  });
+
+  let kept_exp = Distributions::validated_exp(Rate::from_mean(INFECTION_DURATION), "the recovery rate").unwrap();
+  let kept = time(|rng| black_box(kept_exp).sample(rng));
+
+  println!("{DRAWS} draws");
+  for (name, (elapsed, sum)) in [("inline", inline), ("cached", cached), ("kept", kept)] {
+    println!("{:>8} {:>9.1} ms   sum {sum:.6e}", name, elapsed.as_secs_f64() * 1e3);
+  }
+}
@@ -0,0 +1,104 @@
+/*!
+
+Compares the two ways of recording infection times, the `InfectedAt` and `RecoveredAt` components inserted at each
+transition and a `LifeHistory` component mutated in place, on a million people. Run with
+
+```text
+cargo bench --bench life_history
+```
+
+Two thirds of the people are infected, in a random order, and half of those recover, so with separate components the
+population ends up in three archetypes. Each approach is timed on the transitions and on iterating every person to sum
+the realized infectious periods. The iteration is timed on the second pass, after the first has warmed the caches.
+
+*/
+
+use std::{
+  hint::black_box,
+  time::{Duration as WallDuration, Instant}
+};
+
+use bevy_ecs::prelude::*;
+use ordered_float::OrderedFloat;
+use rand::{rngs::SmallRng, seq::SliceRandom, SeedableRng};
+
+use ecs_disease_models::infection_times::{record_infection, record_recovery, InfectedAt, LifeHistory, RecoveredAt};
+
+const PEOPLE: usize = 1_000_000;
+
+#[derive(Component)]
+struct Person;
+
+/// The order in which people are infected, and which of them recover.
+fn transitions(people: &[Entity]) -> (Vec<Entity>, Vec<Entity>) {
+  let mut infected = people.to_vec();
+  infected.shuffle(&mut SmallRng::seed_from_u64(1));
+  infected.truncate(2 * PEOPLE / 3);
+  let recovered = infected.iter().step_by(2).copied().collect();
+  (infected, recovered)
+}
+
+/// Times the transitions with `InfectedAt` and `RecoveredAt`, then the iteration.
+fn separate_components() -> (WallDuration, WallDuration) {
+  let mut world = World::default();
+  let people: Vec<Entity> = world.spawn_batch((0..PEOPLE).map(|_| Person)).collect();
+  let (infected, recovered) = transitions(&people);
+
+  let start = Instant::now();
+  for person in &infected {
+    world.entity_mut(*person).insert(InfectedAt(OrderedFloat(1.0)));
+  }
+  for person in &recovered {
+    world.entity_mut(*person).insert(RecoveredAt(OrderedFloat(3.5)));
+  }
+  let transitions = start.elapsed();
+
+  let mut query = world.query_filtered::<(Option<&InfectedAt>, Option<&RecoveredAt>), With<Person>>();
+  let mut iterate = || -> f64 {
+    query.iter(&world)
+         .filter_map(|(infected_at, recovered_at)| Some((recovered_at?.0 - infected_at?.0).0))
+         .sum()
+  };
+  black_box(iterate());
+  let start = Instant::now();
+  black_box(iterate());
+  let iteration = start.elapsed();
+  (transitions, iteration)
+}
+
+/// Times the transitions with a `LifeHistory` inserted at spawn, then the iteration.
+fn life_history() -> (WallDuration, WallDuration) {
+  let mut world = World::default();
+  let people: Vec<Entity> = world.spawn_batch((0..PEOPLE).map(|_| (Person, LifeHistory::default()))).collect();
+  let (infected, recovered) = transitions(&people);
+
+  let start = Instant::now();
+  for person in &infected {
+    record_infection(&mut world, *person, OrderedFloat(1.0));
+  }
+  for person in &recovered {
+    record_recovery(&mut world, *person, OrderedFloat(3.5));
+  }
+  let transitions = start.elapsed();
+
+  let mut query = world.query_filtered::<&LifeHistory, With<Person>>();
+  let mut iterate = || -> f64 {
+    query.iter(&world).filter_map(|history| history.infectious_period()).map(|period| period.0).sum()
+  };
+  black_box(iterate());
+  let start = Instant::now();
+  black_box(iterate());
+  let iteration = start.elapsed();
+  (transitions, iteration)
+}
+
+fn main() {
+  println!("{PEOPLE} people");
+  println!("{:>20} {:>14} {:>14}", "", "transitions", "iteration");
+  for (name, (transitions, iteration)) in [
+    ("separate components", separate_components()),
+    ("life history", life_history()),
+  ] {
+    println!("{:>20} {:>11.1} ms {:>11.1} ms", name, transitions.as_secs_f64() * 1e3, iteration.as_secs_f64() * 1e3);
+  }
+}
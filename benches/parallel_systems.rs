@@ -0,0 +1,88 @@
+/*!
+
+Compares running the systems of a phase one at a time and in parallel, on a model with many independent reporter-like
+systems, each of which reads every person and writes only its own resource, so that none of them conflict. Run with
+
+```text
+cargo bench --bench parallel_systems --features multi_threaded
+```
+
+Without the `multi_threaded` feature, both runs are single-threaded. Each executor is timed on ticks of the systems
+only, without events, after a first tick has warmed the caches.
+
+*/
+
+use std::{
+  hint::black_box,
+  time::{Duration as WallDuration, Instant}
+};
+
+use bevy_ecs::prelude::*;
+
+use ecs_disease_models::model::{ExecutionPhase, Model};
+
+const PEOPLE: usize = 100_000;
+const TICKS: u32 = 50;
+
+#[derive(Component)]
+struct Value(f64);
+
+/// The result of the `N`th reporter-like system.
+#[derive(Resource, Default)]
+struct Total<const N: usize>(f64);
+
+/// A system that sums a function of every person's value, standing in for a reporter that summarizes the population.
+fn summarize<const N: usize>(people: Query<&Value>, mut total: ResMut<Total<N>>) {
+  total.0 = people.iter().map(|value| (value.0 * (N + 1) as f64).sin()).sum();
+}
+
+fn add_summary<const N: usize>(model: &mut Model) {
+  model.world_mut().init_resource::<Total<N>>();
+  model.add_system_to_phase(summarize::<N>, ExecutionPhase::Normal);
+}
+
+fn model(multithreaded: bool) -> Model {
+  let mut model = Model::with_random_seed(1);
+  model.set_multithreaded(multithreaded);
+  model.world_mut().spawn_batch((0..PEOPLE).map(|person| Value(person as f64)));
+  add_summary::<0>(&mut model);
+  add_summary::<1>(&mut model);
+  add_summary::<2>(&mut model);
+  add_summary::<3>(&mut model);
+  add_summary::<4>(&mut model);
+  add_summary::<5>(&mut model);
+  add_summary::<6>(&mut model);
+  add_summary::<7>(&mut model);
+  add_summary::<8>(&mut model);
+  add_summary::<9>(&mut model);
+  add_summary::<10>(&mut model);
+  add_summary::<11>(&mut model);
+  add_summary::<12>(&mut model);
+  add_summary::<13>(&mut model);
+  add_summary::<14>(&mut model);
+  add_summary::<15>(&mut model);
+  model
+}
+
+/// Times `TICKS` ticks of the systems.
+fn time_ticks(multithreaded: bool) -> WallDuration {
+  let mut model = model(multithreaded);
+  model.tick_systems_only();
+  let start = Instant::now();
+  for _ in 0..TICKS {
+    model.tick_systems_only();
+  }
+  let elapsed = start.elapsed();
+  black_box(model.world().resource::<Total<15>>().0);
+  elapsed
+}
+
+fn main() {
+  if !cfg!(feature = "multi_threaded") {
+    println!("The multi_threaded feature is off, so both runs are single-threaded.");
+  }
+  println!("{PEOPLE} people, 16 systems, {TICKS} ticks");
+  for (name, elapsed) in [("single-threaded", time_ticks(false)), ("multithreaded", time_ticks(true))] {
+    println!("{:>16} {:>9.1} ms", name, elapsed.as_secs_f64() * 1e3);
+  }
+}
@@ -0,0 +1,69 @@
+/*!
+
+Compares the `TimelineBackend`s on a uniform-arrival workload, the "hold" model of event queue benchmarks: the queue
+starts with `n` events at times uniform on `[0, HORIZON)`, and each step pops the earliest event and schedules one at a
+uniform time up to `HORIZON` after it, so the queue stays at `n` events. Run with
+
+```text
+cargo bench --bench timeline_backends
+```
+
+The bucket queue's width is `HORIZON / n`, for about one event per bucket, except that the horizon is never split into
+more than half of `BucketQueue::MAX_BUCKETS` buckets, since events are scheduled up to twice the horizon after the
+earliest.
+
+*/
+
+use std::{
+  hint::black_box,
+  time::{Duration as WallDuration, Instant}
+};
+
+use ordered_float::OrderedFloat;
+use rand::{rngs::SmallRng, Rng, SeedableRng};
+
+use ecs_disease_models::{
+  timeline::Timeline,
+  timeline_backend::BucketQueue,
+  timeline_event::{Event, EventCommand},
+  units::Duration
+};
+
+const HORIZON: f64 = 100.0;
+const STEPS: usize = 1_000_000;
+
+fn event(time: f64) -> Event {
+  Event{ time: OrderedFloat(time), command: EventCommand::infallible(|_| {}), entity: None, label: "hold" }
+}
+
+/// Runs the hold workload with `queue_len` events on `timeline`, returning the wall-clock time of the steps.
+fn hold(mut timeline: Timeline, queue_len: usize) -> WallDuration {
+  let mut rng = SmallRng::seed_from_u64(1);
+  for _ in 0..queue_len {
+    timeline.push(event(rng.random::<f64>() * HORIZON)).unwrap();
+  }
+
+  let start = Instant::now();
+  for _ in 0..STEPS {
+    let popped = timeline.pop().expect("the queue is never empty");
+    timeline.push(event(popped.time.0 + rng.random::<f64>() * HORIZON)).unwrap();
+    black_box(popped);
+  }
+  start.elapsed()
+}
+
+fn main() {
+  println!("{STEPS} hold steps, uniform arrivals over a horizon of {HORIZON}");
+  println!("{:>10} {:>14} {:>14}", "queue", "binary heap", "bucket queue");
+  for queue_len in [1_000, 10_000, 100_000, 1_000_000] {
+    let heap = hold(Timeline::default(), queue_len);
+    let width = Duration(HORIZON / queue_len.min(BucketQueue::MAX_BUCKETS / 2) as f64);
+    let buckets = hold(Timeline::with_backend(BucketQueue::new(width).unwrap()), queue_len);
+    println!(
+      "{:>10} {:>11.1} ns {:>11.1} ns",
+      queue_len,
+      heap.as_nanos() as f64 / STEPS as f64,
+      buckets.as_nanos() as f64 / STEPS as f64
+    );
+  }
+}
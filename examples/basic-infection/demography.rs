@@ -0,0 +1,210 @@
+/*!
+
+The `Demography` module makes the population open: people are born and die in the background, independently of the
+infection. Without it (or with `Demography::closed`, which has no births or deaths) the population is closed and the
+model runs exactly as it would without the module.
+
+Rates are per capita, so each population of size `N` has births at rate `birth_rate * N` and deaths at rate
+`death_rate * N`, and grows at the net rate `birth_rate - death_rate`. With equal rates, its size stays roughly
+constant. Every birth and death is its own event on the `Timeline`, and each population has a single chain of them,
+like its chain of infection attempts. Events are scheduled until the `max_time` of the model's `SimConstants`.
+
+Newborns are susceptible, and since susceptible people are not entities in this model, a birth only adds to the
+`PopulationStatistics`. A death removes someone chosen uniformly from the population, whatever their status. An
+infected or recovered person who dies is marked `Deceased` and despawned along with their scheduled events, which
+takes them out of the `PopulationStatistics`, and a susceptible person is just no longer counted. Either way, the
+population is one smaller, so the denominator of the probability of infection used by the `TransmissionManager`
+follows births and deaths.
+
+The chain of infection attempts in a population ends once nobody in it is susceptible. People born after that are
+never infected.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use rand::distr::{StandardUniform, Uniform};
+
+use ecs_disease_models::{
+  errors::IxaError,
+  metapopulation::PopulationId,
+  model::AbortReason,
+  module::Module,
+  random::RngResource,
+  sim_constants::SimConstants,
+  timeline::Timeline,
+  timeline_event::Event,
+  units::Rate
+};
+
+use crate::{
+  population_statistics::{Deceased, PopulationStatistics, StatisticsByPopulation, StatisticsValidation},
+  InfectionStatus
+};
+
+/// Background births and deaths. See the module documentation.
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct Demography {
+  birth_rate: Rate,
+  death_rate: Rate,
+}
+
+impl Demography {
+  /// Births and deaths at the given per capita rates.
+  pub fn new(birth_rate: Rate, death_rate: Rate) -> Self {
+    Demography{ birth_rate, death_rate }
+  }
+
+  /// Births and deaths at the same per capita `rate`, which keeps the population size roughly constant.
+  pub fn balanced(rate: Rate) -> Self {
+    Self::new(rate, rate)
+  }
+
+  /// No births or deaths.
+  pub fn closed() -> Self {
+    Self::new(Rate(0.0), Rate(0.0))
+  }
+
+  /// The per capita rate of births and deaths together.
+  fn turnover(&self) -> Rate {
+    Rate(self.birth_rate.0 + self.death_rate.0)
+  }
+}
+
+/// Schedules the next birth or death in `population`, whose statistics are `stats`, if there is one before the end.
+/// Fails if the `Timeline` refuses the event.
+fn schedule_next(world: &mut World, population: PopulationId, stats: PopulationStatistics) -> Result<(), IxaError> {
+  let this = *world.resource::<Demography>();
+  let rate = this.turnover().0 * stats.size() as f64;
+  if rate <= 0.0 {
+    return Ok(());
+  }
+
+  let now = world.resource::<Timeline>().now();
+  let max_time = world.resource::<SimConstants>().max_time;
+  let wait = Rate(rate).exp().expect("Invalid demographic rate");
+  let time = now + world.resource_mut::<RngResource>().sample_labeled(now, "demography", wait);
+  if time > max_time {
+    return Ok(());
+  }
+
+  let event = Event::builder(time)
+      .fallible_command(move |world| demographic_event(world, population))
+      .label("demography")
+      .build();
+  world.resource_mut::<Timeline>().push(event)
+}
+
+/// A birth or a death in `population`, followed by scheduling the next one.
+fn demographic_event(world: &mut World, population: PopulationId) -> Result<(), IxaError> {
+  let this = *world.resource::<Demography>();
+  let stats = world.resource::<StatisticsByPopulation>().get(population);
+  if stats.size() == 0 {
+    // Everyone migrated away, so nobody is left to be born to or to die.
+    return Ok(());
+  }
+  let now = world.resource::<Timeline>().now();
+
+  let (event_draw, person): (f64, u32) = {
+    let mut rng = world.resource_mut::<RngResource>();
+    let person = Uniform::new(0, stats.size()).unwrap();
+    (rng.sample_labeled(now, "demography", StandardUniform), rng.sample_labeled(now, "demography", person))
+  };
+
+  if event_draw * this.turnover().0 < this.birth_rate.0 {
+    #[cfg(feature = "print_messages")]
+    println!("Birth in population {} at time {:.4}", population.0, now);
+    update_counts(world, population, |stats| {
+      stats.add(InfectionStatus::Susceptible);
+      Ok(())
+    })?;
+  } else {
+    // People are ordered by status, susceptible first.
+    let status = if person < stats.susceptible {
+      InfectionStatus::Susceptible
+    } else if person < stats.susceptible + stats.infected {
+      InfectionStatus::Infected
+    } else {
+      InfectionStatus::Recovered
+    };
+    #[cfg(feature = "print_messages")]
+    println!("Death of a {} person in population {} at time {:.4}", status, population.0, now);
+    die(world, population, status)?;
+  }
+
+  let stats = world.resource::<StatisticsByPopulation>().get(population);
+  schedule_next(world, population, stats)
+}
+
+/// Removes a random person with `status` from `population`.
+fn die(world: &mut World, population: PopulationId, status: InfectionStatus) -> Result<(), IxaError> {
+  if status == InfectionStatus::Susceptible {
+    return update_counts(world, population, |stats| stats.remove(InfectionStatus::Susceptible));
+  }
+
+  let mut people: Vec<Entity> = world.query::<(Entity, &InfectionStatus, Option<&PopulationId>)>()
+                                     .iter(world)
+                                     .filter(|(_, other, other_population)| {
+                                       **other == status && other_population.copied().unwrap_or_default() == population
+                                     })
+                                     .map(|(person, _, _)| person)
+                                     .collect();
+  // Query order depends on the archetype layout, so sort for reproducibility.
+  people.sort();
+
+  if people.is_empty() {
+    if status == InfectionStatus::Infected {
+      return Err(IxaError::IxaError(format!("no infected person to die in population {}", population.0)));
+    }
+    // Recovered people who were despawned are only counted.
+    return update_counts(world, population, |stats| stats.remove(InfectionStatus::Recovered));
+  }
+
+  let now = world.resource::<Timeline>().now();
+  let index = world.resource_mut::<RngResource>()
+                   .sample_labeled(now, "demography", Uniform::new(0, people.len()).unwrap());
+  let person = people[index];
+  world.resource_mut::<Timeline>().cancel_events_for(person);
+  // The `PopulationStatistics` stop counting a `Deceased` person when they are despawned.
+  world.entity_mut(person).insert(Deceased);
+  world.despawn(person);
+  Ok(())
+}
+
+/// Applies `update` to the statistics of `population` and to the totals.
+fn update_counts(
+  world: &mut World,
+  population: PopulationId,
+  update: impl Fn(&mut PopulationStatistics) -> Result<(), IxaError>,
+) -> Result<(), IxaError> {
+  let size = world.resource::<PopulationStatistics>().size();
+  update(world.resource_mut::<StatisticsByPopulation>().get_mut(population))?;
+  update(&mut world.resource_mut::<PopulationStatistics>())?;
+  let new_size = world.resource::<PopulationStatistics>().size();
+  if let Some(mut validation) = world.get_resource_mut::<StatisticsValidation>() {
+    validation.resize(size, new_size);
+  }
+  Ok(())
+}
+
+impl Module for Demography {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    let populations: Vec<(PopulationId, PopulationStatistics)> =
+        world.resource::<StatisticsByPopulation>().iter().collect();
+    for (population, stats) in populations {
+      if let Err(error) = schedule_next(world, population, stats) {
+        AbortReason::abort(world, format!("the first birth or death could not be scheduled: {error}"));
+        break;
+      }
+    }
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module Demography");
+
+    None // No systems
+  }
+}
@@ -10,7 +10,8 @@ use serde::{Deserialize, Serialize};
 
 use ecs_disease_models::{
   timeline::Timeline,
-  report::Reporter
+  report::Reporter,
+  status_transition::StatusTransitions
 };
 use crate::InfectionStatus;
 
@@ -34,14 +35,14 @@ impl Display for IncidenceReportItem {
 pub fn track_status_changes(
   mut incidence_reporter: ResMut<IncidenceReporter>,
   timeline: Res<Timeline>,
-  query: Query<(Entity, &InfectionStatus), Changed<InfectionStatus>>,
+  mut transitions: StatusTransitions<InfectionStatus>,
 ) {
-  // Track the changes in infection status.
-  for (entity, new_status) in query.iter() {
+  // Track the changes in infection status. Spawning a person is their infection, so we want the spawns too.
+  for transition in transitions.iter() {
     let report_item = IncidenceReportItem{
       time: timeline.now().0,
-      person_id: entity.index(),
-      infection_status: new_status.clone(),
+      person_id: transition.entity.index(),
+      infection_status: transition.to,
     };
 
     #[cfg(feature = "print_messages")]
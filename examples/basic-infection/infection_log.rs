@@ -0,0 +1,21 @@
+/*!
+
+An example consumer of the `InfectionOccurred` Bevy events sent by the transmission manager. It logs each infection,
+and the transmission manager knows nothing about it. Other consumers, like an estimator of the reproduction number,
+would read the same events in the same way, independently of each other.
+
+*/
+
+use bevy_ecs::prelude::*;
+
+use crate::transmission_manager::InfectionOccurred;
+
+/// A system that logs every infection.
+pub fn log_infections(mut infections: EventReader<InfectionOccurred>) {
+  for infection in infections.read() {
+    #[cfg(feature = "print_messages")]
+    println!("Observed infection of entity {} at time {:.4}", infection.infectee, infection.time);
+    #[cfg(not(feature = "print_messages"))]
+    let _ = infection;
+  }
+}
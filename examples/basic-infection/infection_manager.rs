@@ -2,22 +2,47 @@
 
 The _infection manager_ is the business logic related to how existing infections evolve.
 
+What happens to a person when their infection ends is set by the `RecoveryOutcome`. They either become
+`InfectionStatus::Recovered`, or, for models that don't track recovered people, they are despawned. The
+`PopulationStatistics` count despawned people as recovered (that is, removed), so the stop condition and statistics
+work the same either way. The incidence report, however, only records changes of status, so it has no rows for people
+who are despawned.
+
+An infection can also end in death, with the probability given by the `InfectionFatality` (the infection fatality
+ratio, or IFR), which is zero by default. The IFR is either the same for everyone or depends on the person's `Age` by
+age band. Whether the person dies is drawn from the model's RNG when the infection ends, and a person who dies is marked
+`Deceased` and despawned whatever the `RecoveryOutcome`, which takes them out of the `PopulationStatistics`. With the
+default IFR of zero nothing is drawn, so models without deaths are unchanged. The transmission manager does not give
+people an age, so a model with an age-dependent IFR must add an `Age` to everyone who can be infected; the infection of
+a person without one fails, aborting the model.
+
+The infection duration must be positive and finite, and the `InfectionFatality` valid. `InfectionManager::new` and
+`InfectionManager::with_infection_fatality` check them and return an error otherwise.
+
 */
 
 use bevy_ecs::prelude::*;
 use bevy_ecs::schedule::SystemConfigs;
-use rand::distr::Distribution;
+use rand::distr::StandardUniform;
 use rand_distr::Exp;
+use serde::Deserialize;
 
 use ecs_disease_models::{
-  model::ExecutionPhase,
+  distributions::Distributions,
+  errors::IxaError,
+  infection_times::record_recovery,
+  model::{AbortReason, ExecutionPhase},
   module::Module,
   random::RngResource,
-  timeline::Timeline,
+  timeline::{Timeline, TimelineSystem},
   timeline_event::Event,
+  units::{Duration, Rate},
 };
 
-use crate::InfectionStatus;
+use crate::{
+  population_statistics::Deceased,
+  InfectionStatus
+};
 
 /// A system that handles the case when a person transitions from `Susceptible` to `Infected`, which occurs
 /// if and only if an entity is spawned.
@@ -26,25 +51,37 @@ fn schedule_recovery(
   mut rng: ResMut<RngResource>,
   this: Res<InfectionManager>,
   query: Query<(&InfectionStatus, Entity), Added<InfectionStatus>>,
+  mut commands: Commands,
 ) {
   // New entities should only ever be spawned with the `InfectionStation::Infected` status in this model.
   // It is a good practice to actually check this invariant and emit an error if it is violated.
   for (new_status, entity) in query.iter() {
     if *new_status == InfectionStatus::Infected{
       // When a new infection occurs, we schedule the person's recovery on the `Timeline`.
-      let time = timeline.now() + Exp::new(1.0 / this.infection_duration).unwrap().sample(&mut rng.rng);
-
-      timeline.push(
-        Event{
-          time,
-          command: Box::new(move | world | {
-            let mut status = world.get_mut::<InfectionStatus>(entity).expect("An entity was removed before it was recovered.");
-            *status = InfectionStatus::Recovered;
-            #[cfg(feature = "print_messages")]
-            println!("Entity {} recovered at time {:.4}", entity, time);
-          }),
-        }
+      let duration: f64 = rng.sample_labeled(
+        timeline.now(),
+        "recovery",
+        this.recovery
+      );
+      let time = timeline.now() + duration;
+
+      let scheduled = timeline.push(
+        Event::builder(time)
+            .fallible_command(move | world | {
+              end_infection(world, entity)?;
+              #[cfg(feature = "print_messages")]
+              println!("Infection of entity {} ended at time {:.4}", entity, time);
+              Ok(())
+            })
+            .entity(entity)
+            .label("recovery")
+            .build()
       );
+      if let Err(error) = scheduled {
+        let reason = format!("the recovery of entity {entity} could not be scheduled: {error}");
+        commands.queue(move |world: &mut World| AbortReason::abort(world, reason));
+        return;
+      }
 
 
       #[cfg(feature = "print_messages")]
@@ -53,14 +90,162 @@ fn schedule_recovery(
   }
 }
 
-#[derive(Resource, Copy, Clone, Debug)]
+/// Ends the infection of `entity`, in death with the probability given by the `InfectionFatality` and otherwise in
+/// recovery according to the `RecoveryOutcome`. Fails if `entity` no longer exists or has no `Age` when the IFR depends
+/// on it.
+fn end_infection(world: &mut World, entity: Entity) -> Result<(), IxaError> {
+  let this = world.resource::<InfectionManager>();
+  let recovery_outcome = this.recovery_outcome;
+  let ifr = this.infection_fatality.ifr(world.get::<Age>(entity).copied()).map_err(|error| {
+    IxaError::IxaError(format!("the infection of entity {entity} cannot end: {error}"))
+  })?;
+
+  if ifr > 0.0 {
+    let now = world.resource::<Timeline>().now();
+    let draw: f64 = world.resource_mut::<RngResource>().sample_labeled(now, "fatality", StandardUniform);
+    if draw < ifr {
+      if world.get_entity(entity).is_err() {
+        return Err(IxaError::IxaError(format!("entity {entity} was removed before its infection ended")));
+      }
+      world.resource_mut::<Timeline>().cancel_events_for(entity);
+      world.entity_mut(entity).insert(Deceased);
+      world.despawn(entity);
+
+      #[cfg(feature = "print_messages")]
+      println!("Entity {} died at time {:.4}", entity, now);
+      return Ok(());
+    }
+  }
+  recover(world, entity, recovery_outcome)
+}
+
+/// Ends the infection of `entity` according to `recovery_outcome`. Fails if `entity` no longer exists.
+fn recover(world: &mut World, entity: Entity, recovery_outcome: RecoveryOutcome) -> Result<(), IxaError> {
+  match recovery_outcome {
+
+    RecoveryOutcome::BecomeRecovered => {
+      let now = world.resource::<Timeline>().now();
+      let mut person = world.get_entity_mut(entity).map_err(|_| IxaError::IxaError(format!(
+        "entity {entity} was removed before it was recovered"
+      )))?;
+      // Inserted rather than assigned, so that the `PopulationStatistics` hooks count the recovery.
+      person.insert(InfectionStatus::Recovered);
+      record_recovery(world, entity, now);
+    }
+
+    RecoveryOutcome::Despawn => {
+      // Nothing else may act on the entity once it is gone.
+      world.resource_mut::<Timeline>().cancel_events_for(entity);
+      world.despawn(entity);
+    }
+
+  }
+  Ok(())
+}
+
+/// What happens to a person when their infection ends.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, Deserialize)]
+pub enum RecoveryOutcome {
+  /// The person's status becomes `InfectionStatus::Recovered`.
+  #[default]
+  BecomeRecovered,
+  /// The person is despawned.
+  Despawn,
+}
+
+/// A person's age in years. Only the age-dependent `InfectionFatality` uses it.
+#[derive(Component, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Debug, Hash)]
+pub struct Age(pub u8);
+
+/// The probability that an infection ends in death, the infection fatality ratio. See the module documentation.
+#[derive(Clone, PartialEq, Debug, Deserialize)]
+pub enum InfectionFatality {
+  /// The same IFR for everyone.
+  Constant(f64),
+  /// The IFR by age band, as `(youngest age, IFR)` pairs in increasing order of age, the first of which must start at
+  /// age 0. Each band extends to the start of the next, and the last has no upper bound.
+  ByAge(Vec<(u8, f64)>),
+}
+
+impl Default for InfectionFatality {
+  fn default() -> Self {
+    InfectionFatality::Constant(0.0)
+  }
+}
+
+impl InfectionFatality {
+  /// Checks that every IFR is a probability and that the age bands start at 0 and increase.
+  pub fn validate(&self) -> Result<(), IxaError> {
+    let ratios: Vec<f64> = match self {
+      InfectionFatality::Constant(ifr) => vec![*ifr],
+      InfectionFatality::ByAge(bands) => {
+        if bands.first().is_none_or(|(age, _)| *age != 0) {
+          return Err(IxaError::IxaError("the first age band of the IFR must start at age 0".to_string()));
+        }
+        if bands.windows(2).any(|pair| pair[0].0 >= pair[1].0) {
+          return Err(IxaError::IxaError("the age bands of the IFR must be in increasing order of age".to_string()));
+        }
+        bands.iter().map(|(_, ifr)| *ifr).collect()
+      }
+    };
+    match ratios.into_iter().find(|ifr| !(0.0..=1.0).contains(ifr)) {
+      Some(ifr) => Err(IxaError::IxaError(format!("the IFR must be between 0 and 1, but it is {ifr}"))),
+      None => Ok(()),
+    }
+  }
+
+  /// The IFR of a person with the given age, which is only needed if the IFR depends on age.
+  pub fn ifr(&self, age: Option<Age>) -> Result<f64, IxaError> {
+    match self {
+      InfectionFatality::Constant(ifr) => Ok(*ifr),
+      InfectionFatality::ByAge(bands) => {
+        let Some(Age(age)) = age else {
+          return Err(IxaError::IxaError("the IFR depends on age, but the person has no `Age`".to_string()));
+        };
+        // The bands start at age 0, so some band contains every age.
+        Ok(bands.iter().rev().find(|(youngest, _)| *youngest <= age).map_or(0.0, |(_, ifr)| *ifr))
+      }
+    }
+  }
+}
+
+#[derive(Resource, Clone, Debug)]
 pub struct InfectionManager {
-  infection_duration: f64
+  /// The distribution of the infection duration, constructed once.
+  recovery: Exp<f64>,
+  recovery_outcome: RecoveryOutcome,
+  infection_fatality: InfectionFatality,
 }
 
 impl InfectionManager {
-  pub fn new(infection_duration: f64) -> InfectionManager {
-    InfectionManager{ infection_duration }
+  /// A manager whose infections last `infection_duration` on average. Fails if `infection_duration` is not a positive,
+  /// finite duration.
+  pub fn new(infection_duration: Duration) -> Result<InfectionManager, IxaError> {
+    infection_duration.validate("the infection duration")?;
+    Ok(InfectionManager{
+      recovery: Distributions::validated_exp(Rate::from_mean(infection_duration), "the recovery rate")?,
+      recovery_outcome: RecoveryOutcome::default(),
+      infection_fatality: InfectionFatality::default(),
+    })
+  }
+
+  /// Sets the probability that an infection ends in death. See the module documentation. Fails if
+  /// `infection_fatality` is not valid.
+  pub fn with_infection_fatality(mut self, infection_fatality: InfectionFatality) -> Result<Self, IxaError> {
+    infection_fatality.validate()?;
+    self.infection_fatality = infection_fatality;
+    Ok(self)
+  }
+
+  /// What happens to a person when their infection ends.
+  pub fn recovery_outcome(&self) -> RecoveryOutcome {
+    self.recovery_outcome
+  }
+
+  /// Sets what happens to a person when their infection ends.
+  pub fn with_recovery_outcome(mut self, recovery_outcome: RecoveryOutcome) -> InfectionManager {
+    self.recovery_outcome = recovery_outcome;
+    self
   }
 }
 
@@ -72,7 +257,9 @@ impl Module for InfectionManager {
     // Insert a new instance into the world
     world.insert_resource(self);
 
-    // Schedule the listener for new infections
-    Some(schedule_recovery.in_set(ExecutionPhase::Normal))
+    // Schedule the listener for new infections. It runs after the `Timeline`, so that it sees an infection in the
+    // iteration of the event that caused it, even though its commands, which abort the model if a recovery cannot be
+    // scheduled, are applied before the `Last` phase.
+    Some(schedule_recovery.after(TimelineSystem).in_set(ExecutionPhase::Normal))
   }
 }
@@ -22,7 +22,8 @@ Neither of these is a big deal, but they are both things the ECS is already doin
 
 This issue boils down to having to work with the data "online", for example via an iterator,
 instead of via random access through indexing. There are usually efficient-enough ways
-of working around this issue, like using Reservoir Sampling to select a random sample.
+of working around this issue, like using Reservoir Sampling to select a random sample, which is what
+`Model::random_entity_where` (or `random::random_entity_where` from within an event) does.
 
 We take a different approach, instead considering the probability that a susceptible person
 will be added for an infection "attempt." In the original example, a person was selected
@@ -33,19 +34,38 @@ infection attempt is
     #{people who are susceptible} / #{population size}.
 
 We will keep track of the numerator in a summary statistics resource. The denominator is
-the constant `POPULATION`.
+the population size of the model's `SimConstants`, which `main` sets to the constant
+`POPULATION`. The `PopulationStatistics` and `TransmissionManager` modules read the
+population size and the maximum time from the `SimConstants`, not from the constants
+here, so the same modules run under any configuration.
 
 Strictly speaking, we don't actually have to store the entities at all for an example this
 simple. All we need to do is store the count of people within each `InfectionStatus`
 category. But more sophisticated models will certainly need to store individual people as
 entities, so we do so here for the purpose of illustration.
 
+# Metapopulations
+
+The same modules run a metapopulation model if the `PopulationStatistics` module is replaced by the
+`StatisticsByPopulation` module with the size of each population, in which case each population has its own chain of
+infection attempts. Adding the `Migration` module moves people between populations. Only infected and recovered
+people are entities, so only they migrate. See `two_patch_model` in the tests for a two-patch example.
+
+# Scenarios
+
+Given the path of a scenario file, the model is built from the scenario instead of the constants below. The report is
+written either way. See the `scenario` module for the modules a scenario can list, and `sir_scenario.json` for the
+model built here.
+
 */
 
 pub mod transmission_manager;
 pub mod population_statistics;
-mod infection_manager;
+pub mod infection_manager;
+pub mod demography;
+pub mod scenario;
 mod incidence_reporter;
+mod infection_log;
 
 use std::fmt::{Display, Formatter};
 use std::path::PathBuf;
@@ -54,24 +74,28 @@ use ordered_float::OrderedFloat;
 use serde::{Deserialize, Serialize};
 
 use ecs_disease_models::{
+  errors::IxaError,
   model::Model,
-  timeline::Time
+  sim_constants::SimConstants,
+  timeline::Time,
+  units::{Duration, Rate}
 };
 use ecs_disease_models::model::ExecutionPhase;
-use ecs_disease_models::report::ReporterConfiguration;
+use ecs_disease_models::report::{FilenamePolicy, ReporterConfiguration};
 use crate::{
   population_statistics::PopulationStatistics,
-  infection_manager::InfectionManager,
+  infection_manager::{InfectionManager, RecoveryOutcome},
   transmission_manager::TransmissionManager,
   incidence_reporter::IncidenceReporter
 };
 
-static POPULATION        : u32  = 1000;
-static SEED              : u64  = 123;
-static MAX_TIME          : Time = OrderedFloat(303.0);
-static FOI               : f64  = 0.1;
-static INFECTION_DURATION: f64  = 5.0;
-static OUTPUT_DIR        : &'static str = "./examples/basic-infection";
+static POPULATION        : u32             = 1000;
+static SEED              : u64             = 123;
+static MAX_TIME          : Time            = OrderedFloat(303.0);
+static FOI               : Rate            = Rate(0.1);
+static INFECTION_DURATION: Duration        = Duration::days(5.0);
+static RECOVERY_OUTCOME  : RecoveryOutcome = RecoveryOutcome::BecomeRecovered;
+static OUTPUT_DIR        : &str            = "./examples/basic-infection";
 
 /**
 All people have exactly one of these states. In fact, because this is the only property
@@ -79,7 +103,7 @@ of a person within this model, an entity in our ECS _is_ an `InfectionStatus`–
 we don't bother creating an entity until a person's `InfectionStatus` changes to
 `InfectionStatus::Infected`.
 */
-#[derive(Component, Clone, Copy, PartialEq, Eq, Default, Debug, Hash, Serialize, Deserialize)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Hash, Serialize, Deserialize)]
 pub enum InfectionStatus {
   #[default]
   Susceptible,
@@ -93,26 +117,927 @@ impl Display for InfectionStatus {
   }
 }
 
+/// The simulation-wide constants of this model, with the given seed.
+fn sim_constants(seed: u64) -> SimConstants {
+  SimConstants::builder().population_size(POPULATION).max_time(MAX_TIME).seed(seed).build()
+}
 
-fn main() {
-  let mut model = Model::with_random_seed(SEED);
-  // `Model`'s constructor automatically adds the `Random` and `Timeline` modules.
-  model.add_module(PopulationStatistics::with_size(POPULATION));
-  model.add_module(TransmissionManager::new(MAX_TIME, FOI));
-  model.add_module(InfectionManager::new(INFECTION_DURATION));
+fn main() -> Result<(), IxaError> {
+  let mut model = match std::env::args().nth(1) {
+    Some(scenario_path) => Model::from_scenario(scenario_path, &scenario::module_registry())?,
+    None => {
+      let mut model = Model::with_constants(sim_constants(SEED));
+      // `Model`'s constructor automatically adds the `Random` and `Timeline` modules.
+      model.add_module(PopulationStatistics::from_constants());
+      model.add_module(TransmissionManager::new(FOI)?);
+      model.add_module(InfectionManager::new(INFECTION_DURATION)?.with_recovery_outcome(RECOVERY_OUTCOME));
+      model.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+      model
+    }
+  };
 
   // A more thought-through API would make this less awkward.
   let report_config = ReporterConfiguration::new(
     "basic_infection_".to_string(),
     PathBuf::from(OUTPUT_DIR),
-    true
+    FilenamePolicy::Overwrite
   );
   model.add_module(report_config);
 
   model.add_module(IncidenceReporter::new("incidence".to_string()));
   // ToDo: Having to add this separately is an awkward pattern.
-  model.add_systems(incidence_reporter::track_status_changes.in_set(ExecutionPhase::Normal));
+  model.add_system_to_phase(incidence_reporter::track_status_changes, ExecutionPhase::Last);
+  // Reacts to the `InfectionOccurred` events sent by the `TransmissionManager`.
+  model.add_system_to_phase(infection_log::log_infections, ExecutionPhase::Normal);
+
+
+  model.run();
+  Ok(())
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+  use ecs_disease_models::{
+    compare::compare_runs,
+    compartments::{CompartmentCounts, Infectiousness},
+    infection_times::infectious_period,
+    model::ModelControl,
+    timeline::Timeline,
+    timeline_event::EventCommand
+  };
+  use ecs_disease_models::metapopulation::{Migration, PopulationId};
+  use ecs_disease_models::result_hash::HashStatusChanges;
+  use ecs_disease_models::status_callbacks::StatusCallbacks;
+  use ecs_disease_models::status_history::{RecordStatusHistory, StatusHistory};
+  use crate::{
+    infection_manager::{Age, InfectionFatality},
+    population_statistics::{
+      Deceased, DepletionCurve, DepletionPoint, StatisticsByPopulation, StatisticsErrorPolicy, StatisticsHistory,
+      StatisticsValidation
+    },
+    demography::Demography,
+    transmission_manager::{DenominatorPolicy, InfectionProbability, InitialAttemptTime}
+  };
+  use super::*;
+
+  /// The model as in `main`, without the report.
+  fn model(seed: u64, recovery_outcome: RecoveryOutcome) -> Model {
+    let mut model = Model::with_constants(sim_constants(seed));
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(FOI).unwrap());
+    model.add_module(InfectionManager::new(INFECTION_DURATION).unwrap().with_recovery_outcome(recovery_outcome));
+    model.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+    model
+  }
+
+  #[test]
+  fn test_runs_are_reproducible() {
+    let diff = compare_runs::<PopulationStatistics>(
+      &mut model(SEED, RecoveryOutcome::BecomeRecovered),
+      &mut model(SEED, RecoveryOutcome::BecomeRecovered)
+    );
+    assert!(diff.is_match(), "{diff}");
+  }
+
+  #[test]
+  fn test_result_hash() {
+    let result_hash = |mut model: Model| {
+      model.add_module(HashStatusChanges::<InfectionStatus>::new());
+      model.run();
+      model.result_hash().unwrap()
+    };
+    assert_eq!(
+      result_hash(model(SEED, RecoveryOutcome::BecomeRecovered)),
+      result_hash(model(SEED, RecoveryOutcome::BecomeRecovered))
+    );
+    assert_ne!(
+      result_hash(model(SEED, RecoveryOutcome::BecomeRecovered)),
+      result_hash(model(SEED + 1, RecoveryOutcome::BecomeRecovered))
+    );
+  }
+
+  #[test]
+  fn test_recovery_outcome_does_not_change_results() {
+    // Despawning recovered people changes nothing but how they are represented.
+    let diff = compare_runs::<PopulationStatistics>(
+      &mut model(SEED, RecoveryOutcome::BecomeRecovered),
+      &mut model(SEED, RecoveryOutcome::Despawn)
+    );
+    assert!(diff.is_match(), "{diff}");
+  }
+
+  #[test]
+  fn test_statistics_history() {
+    let mut model = Model::with_constants(sim_constants(SEED));
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(FOI).unwrap());
+    model.add_module(InfectionManager::new(INFECTION_DURATION).unwrap());
+    model.add_module(StatisticsHistory::new());
+    model.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+    model.run();
+
+    let history = model.world().resource::<StatisticsHistory>();
+    assert!(!history.is_empty());
+    assert!(history.iter().zip(history.iter().skip(1)).all(|((earlier, _), (later, _))| earlier <= later));
+    for (_, stats) in history.iter() {
+      assert_eq!(stats.size(), POPULATION);
+    }
+    assert!(history.iter().last().unwrap().1.all_recovered());
+
+    let curve = history.epidemic_curve();
+    assert_eq!(curve.compartments(), ["susceptible", "infected", "recovered"]);
+    assert_eq!(curve.len(), history.len());
+    assert!(curve.iter().all(|(_, counts)| counts.iter().sum::<u64>() == POPULATION as u64));
+  }
+
+  #[test]
+  fn test_recover_by_despawning() {
+    let mut model = Model::with_constants(sim_constants(SEED));
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(FOI).unwrap());
+    model.add_module(
+      InfectionManager::new(INFECTION_DURATION).unwrap().with_recovery_outcome(RecoveryOutcome::Despawn)
+    );
+    model.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+    model.run();
+
+    assert!(model.world().resource::<PopulationStatistics>().all_recovered());
+    assert_eq!(model.world().entities().len(), 0);
+    assert!(model.world().resource::<Timeline>().pending().all(|(_, entity, _)| entity.is_none()));
+  }
+
+  #[test]
+  fn test_size_counts_living_people() {
+    let mut model = Model::new();
+    model.add_module(PopulationStatistics::with_size(10));
+    let size = |model: &Model| model.world().resource::<PopulationStatistics>().size();
+
+    let people: Vec<Entity> = (0..4).map(|_| model.world_mut().spawn(InfectionStatus::Infected).id()).collect();
+    model.tick_systems_only();
+    assert_eq!(*model.world().resource::<PopulationStatistics>(), PopulationStatistics{
+      susceptible: 6, infected: 4, recovered: 0
+    });
+
+    // Recovering, whether or not the person stays an entity, changes nothing.
+    model.world_mut().entity_mut(people[0]).insert(InfectionStatus::Recovered);
+    model.tick_systems_only();
+    model.world_mut().despawn(people[1]);
+    assert_eq!(size(&model), 10);
+
+    // Deaths of recovered and infected people do.
+    for person in [people[0], people[2]] {
+      model.world_mut().entity_mut(person).insert(Deceased);
+      model.world_mut().despawn(person);
+    }
+    model.tick_systems_only();
+    assert_eq!(*model.world().resource::<PopulationStatistics>(), PopulationStatistics{
+      susceptible: 6, infected: 1, recovered: 1
+    });
+    // The susceptible people, the one infected entity, and the person who recovered by despawning.
+    assert_eq!(size(&model), 6 + model.world().entities().len() + 1);
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Running);
+  }
+
+  #[test]
+  fn test_statistics_are_updated_by_hooks() {
+    let mut model = Model::new();
+    model.add_module(PopulationStatistics::with_size(5));
+    let stats = |model: &Model| *model.world().resource::<PopulationStatistics>();
+    let stats_of = |susceptible, infected, recovered| PopulationStatistics{ susceptible, infected, recovered };
+
+    // Spawning counts as an infection at once, without a tick.
+    let people: Vec<Entity> = (0..3).map(|_| model.world_mut().spawn(InfectionStatus::Infected).id()).collect();
+    assert_eq!(stats(&model), stats_of(2, 3, 0));
+
+    // Inserting a new status in place counts the transition from the old one, and inserting the same one is no change.
+    model.world_mut().entity_mut(people[0]).insert(InfectionStatus::Recovered);
+    assert_eq!(stats(&model), stats_of(2, 2, 1));
+    model.world_mut().entity_mut(people[0]).insert(InfectionStatus::Recovered);
+    assert_eq!(stats(&model), stats_of(2, 2, 1));
+
+    // Despawning an infected person counts them as removed, and despawning a recovered person changes nothing.
+    model.world_mut().despawn(people[1]);
+    model.world_mut().despawn(people[0]);
+    assert_eq!(stats(&model), stats_of(2, 1, 2));
 
+    // A status assigned through `get_mut` runs no hook, so it is counted only in the next tick, and only once, and the
+    // validation finds nothing amiss.
+    model.add_module(StatisticsValidation::new());
+    *model.world_mut().get_mut::<InfectionStatus>(people[2]).unwrap() = InfectionStatus::Recovered;
+    assert_eq!(stats(&model), stats_of(2, 1, 2));
+    model.tick_systems_only();
+    assert_eq!(stats(&model), stats_of(2, 0, 3));
+    model.tick_systems_only();
+    assert_eq!(stats(&model), stats_of(2, 0, 3));
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Running);
+
+    // Despawning them afterward, or inserting the status they already have, changes nothing.
+    model.world_mut().entity_mut(people[2]).insert(InfectionStatus::Recovered);
+    model.world_mut().despawn(people[2]);
+    assert_eq!(stats(&model), stats_of(2, 0, 3));
+
+    // A status inserted over one assigned through `get_mut` is counted from the status last counted, at once.
+    let person = model.world_mut().spawn(InfectionStatus::Infected).id();
+    *model.world_mut().get_mut::<InfectionStatus>(person).unwrap() = InfectionStatus::Recovered;
+    model.world_mut().entity_mut(person).insert(InfectionStatus::Recovered);
+    assert_eq!(stats(&model), stats_of(1, 0, 4));
+    model.tick_systems_only();
+    assert_eq!(stats(&model), stats_of(1, 0, 4));
+  }
+
+  #[test]
+  fn test_despawned_infected_people_leave_the_infected_count() {
+    let mut model = Model::new();
+    model.add_module(StatisticsByPopulation::with_sizes([(PopulationId(0), 5), (PopulationId(1), 5)]));
+    let infected = |model: &Model| {
+      let by_population = model.world().resource::<StatisticsByPopulation>();
+      (model.world().resource::<PopulationStatistics>().infected, by_population.get(PopulationId(1)).infected)
+    };
+
+    // Removed from the infection, and still counted as recovered.
+    let person = model.world_mut().spawn((InfectionStatus::Infected, PopulationId(1))).id();
+    assert_eq!(infected(&model), (1, 1));
+    model.world_mut().despawn(person);
+    assert_eq!(infected(&model), (0, 0));
+    assert_eq!(model.world().resource::<StatisticsByPopulation>().get(PopulationId(1)).recovered, 1);
+
+    // Dead, and no longer counted at all.
+    let person = model.world_mut().spawn((InfectionStatus::Infected, PopulationId(1))).id();
+    model.world_mut().entity_mut(person).insert(Deceased);
+    model.world_mut().despawn(person);
+    assert_eq!(infected(&model), (0, 0));
+    assert_eq!(model.world().resource::<StatisticsByPopulation>().get(PopulationId(1)).size(), 4);
+    assert_eq!(model.world().resource::<PopulationStatistics>().size(), 9);
+  }
+
+  /// Infects `ages.len()` people with the given ages, or none, with no transmission, and runs until every infection has
+  /// ended.
+  fn run_infections(infection_fatality: InfectionFatality, ages: &[Option<u8>]) -> Model {
+    let mut model = Model::with_random_seed(SEED);
+    model.add_module(PopulationStatistics::with_size(ages.len() as u32));
+    let infection_manager = InfectionManager::new(INFECTION_DURATION).unwrap();
+    model.add_module(infection_manager.with_infection_fatality(infection_fatality).unwrap());
+    for age in ages {
+      let mut person = model.world_mut().spawn(InfectionStatus::Infected);
+      if let Some(age) = age {
+        person.insert(Age(*age));
+      }
+    }
+    // Schedules the recoveries before the run, which would otherwise stop at once for want of events.
+    model.tick_systems_only();
+    model.run();
+    model
+  }
+
+  #[test]
+  fn test_infection_fatality() {
+    const PEOPLE: usize = 4000;
+    let model = run_infections(InfectionFatality::Constant(0.3), &[None; PEOPLE]);
+    let stats = *model.world().resource::<PopulationStatistics>();
+    assert_eq!(stats.infected, 0);
+    assert_eq!(stats.size(), stats.recovered);
+    assert_eq!(model.world().entities().len(), stats.recovered);
+    let death_fraction = (PEOPLE as u32 - stats.size()) as f64 / PEOPLE as f64;
+    assert!((death_fraction - 0.3).abs() < 0.03, "death fraction {death_fraction}");
+
+    // Half of the people are 20 and half are 70.
+    let ages: Vec<Option<u8>> = (0..PEOPLE).map(|person| Some(if person % 2 == 0 { 20 } else { 70 })).collect();
+    let mut model = run_infections(InfectionFatality::ByAge(vec![(0, 0.0), (18, 0.05), (65, 0.5)]), &ages);
+    let world = model.world_mut();
+    let mut deaths = |age: u8| PEOPLE / 2 - world.query::<&Age>().iter(world).filter(|other| other.0 == age).count();
+    let (young_deaths, old_deaths) = (deaths(20), deaths(70));
+    assert!(young_deaths > 0 && old_deaths > 5 * young_deaths, "{young_deaths} young and {old_deaths} old deaths");
+
+    // An age-dependent IFR needs ages.
+    let model = run_infections(InfectionFatality::ByAge(vec![(0, 0.1)]), &[None]);
+    assert!(model.abort_reason().unwrap().contains("has no `Age`"));
+
+    let invalid = [
+      InfectionFatality::Constant(1.5),
+      InfectionFatality::ByAge(vec![(5, 0.1)]),
+      InfectionFatality::ByAge(vec![(0, 0.1), (50, 0.2), (50, 0.3)]),
+    ];
+    for infection_fatality in invalid {
+      assert!(InfectionManager::new(INFECTION_DURATION).unwrap().with_infection_fatality(infection_fatality).is_err());
+    }
+  }
+
+  #[test]
+  fn test_statistics_history_max_len() {
+    let mut model = Model::with_constants(sim_constants(SEED));
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(FOI).unwrap());
+    model.add_module(InfectionManager::new(INFECTION_DURATION).unwrap());
+    model.add_module(StatisticsHistory::with_max_len(10));
+    model.run();
+
+    let history = model.world().resource::<StatisticsHistory>();
+    assert_eq!(history.len(), 10);
+    assert!(history.iter().last().unwrap().1.all_recovered());
+  }
+
+  #[test]
+  fn test_status_callbacks() {
+    let events = Arc::new(Mutex::new(Vec::new()));
+    let recorded = events.clone();
+    let mut model = model(SEED, RecoveryOutcome::BecomeRecovered);
+    model.add_module(StatusCallbacks::<InfectionStatus>::new().with_callback(move |event| {
+      recorded.lock().unwrap().push(*event);
+    }));
+    model.run();
+
+    // Everyone is infected, which is their first status seen, and then recovers.
+    let events = events.lock().unwrap();
+    assert_eq!(events.len(), 2 * POPULATION as usize);
+    assert!(events.iter().zip(events.iter().skip(1)).all(|(earlier, later)| earlier.time <= later.time));
+    let recoveries = events.iter().filter(|event| event.new_status == InfectionStatus::Recovered);
+    assert!(recoveries.clone().all(|event| event.old_status == Some(InfectionStatus::Infected)));
+    assert_eq!(recoveries.count(), POPULATION as usize);
+  }
+
+  /// An SIR model, in which infection attempts succeed with probability `R0 * (S / N) * (I / N)` once the first ten
+  /// people are infected from outside, runs until there is nobody infected. With an attempt rate of 1 and a mean
+  /// infection duration of 1, its basic reproduction number is `R0`, so the final fraction susceptible is `1 - z`, where
+  /// `z = 1 - exp(-R0 * z)` is the final size.
+  #[test]
+  fn test_depletion_curve_final_size() {
+    const R0: f64 = 3.0;
+    const SEEDS: u32 = 10;
+    let constants = SimConstants::builder().population_size(2000).max_time(1000.0).seed(SEED).build();
+    let mut model = Model::with_constants(constants);
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(Rate(1.0)).unwrap().with_infection_probability(
+      InfectionProbability::custom("sir", |stats, infectious, _| {
+        let size = stats.size() as f64;
+        let susceptible = stats.susceptible as f64 / size;
+        if stats.infected + stats.recovered < SEEDS {
+          susceptible
+        } else {
+          R0 * susceptible * infectious / size
+        }
+      })
+    ));
+    model.add_module(InfectionManager::new(Duration(1.0)).unwrap());
+    model.add_module(DepletionCurve::new());
+    model.add_stop_condition(|world| {
+      let stats = world.resource::<PopulationStatistics>();
+      stats.infected == 0 && stats.recovered > 0
+    });
+    model.run();
+
+    let curve = model.world().resource::<DepletionCurve>();
+    assert!(curve.iter().zip(curve.iter().skip(1)).all(|(earlier, later)| {
+      earlier.time <= later.time
+          && earlier.fraction_susceptible > later.fraction_susceptible
+          && earlier.cumulative_infected + 1 == later.cumulative_infected
+    }));
+    let last = curve.last().unwrap();
+    let stats = model.world().resource::<PopulationStatistics>();
+    assert_eq!(last.cumulative_infected, u64::from(stats.recovered));
+    assert_eq!(curve.len() as u64, last.cumulative_infected);
+
+    let mut final_size = 1.0;
+    for _ in 0..100 {
+      final_size = 1.0 - (-R0 * final_size).exp();
+    }
+    assert!(
+      (last.fraction_susceptible - (1.0 - final_size)).abs() < 0.025,
+      "final fraction susceptible {} is not close to {}", last.fraction_susceptible, 1.0 - final_size
+    );
+  }
+
+  /// The stage of a person's infection, splitting the infected compartment in two for `test_equal_weight_stages`.
+  #[derive(Component, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+  enum Stage {
+    Early,
+    Late,
+    Over,
+  }
+
+  /// Puts the newly infected people in the early stage, scheduling their late stage, and takes the recovered people out
+  /// of the infectious stages, so that the people in the infectious stages are exactly the infected people.
+  fn update_stages(world: &mut World) {
+    let now = world.resource::<Timeline>().now();
+    let newly_infected: Vec<Entity> = world.query_filtered::<Entity, (With<InfectionStatus>, Without<Stage>)>()
+                                           .iter(world)
+                                           .collect();
+    for person in newly_infected {
+      world.entity_mut(person).insert(Stage::Early);
+      let late = EventCommand::infallible(move |world: &mut World| {
+        if world.get::<Stage>(person) == Some(&Stage::Early) {
+          world.entity_mut(person).insert(Stage::Late);
+        }
+      });
+      // Not a multiple of anything else in the model, so that no stage ends at the same time as another event.
+      world.resource_mut::<Timeline>().schedule_at(now + 0.4321, late, "late_stage").unwrap();
+    }
+    for (status, mut stage) in world.query::<(&InfectionStatus, &mut Stage)>().iter_mut(world) {
+      if *status == InfectionStatus::Recovered && *stage != Stage::Over {
+        *stage = Stage::Over;
+      }
+    }
+  }
+
+  /// Splitting the infected compartment into two stages with the same weight changes nothing about transmission, with
+  /// the density-dependent part of the probability of `test_depletion_curve_final_size`, which depends on the weighted
+  /// number of infectious people. Nor does counting the infected people with `CompartmentCounts` rather than the
+  /// population's statistics.
+  #[test]
+  fn test_equal_weight_stages() {
+    const SEEDS: u32 = 10;
+    fn depletion_curve(manager: TransmissionManager, staged: bool) -> Vec<DepletionPoint> {
+      let constants = SimConstants::builder().population_size(POPULATION).max_time(1000.0).seed(SEED).build();
+      let mut model = Model::with_constants(constants);
+      model.add_module(PopulationStatistics::from_constants());
+      model.add_module(manager.with_infection_probability(InfectionProbability::custom("sir", |stats, infectious, _| {
+        let size = stats.size() as f64;
+        let susceptible = stats.susceptible as f64 / size;
+        if stats.infected + stats.recovered < SEEDS { susceptible } else { 3.0 * susceptible * infectious / size }
+      })));
+      model.add_module(InfectionManager::new(Duration(1.0)).unwrap());
+      model.add_module(DepletionCurve::new());
+      if staged {
+        model.add_module(CompartmentCounts::<Stage>::new());
+        model.add_system_to_phase(update_stages, ExecutionPhase::First);
+      } else {
+        model.add_module(CompartmentCounts::<InfectionStatus>::new());
+      }
+      model.add_stop_condition(|world| {
+        let stats = world.resource::<PopulationStatistics>();
+        stats.infected == 0 && stats.recovered > 0
+      });
+      model.run();
+      model.world().resource::<DepletionCurve>().iter().copied().collect()
+    }
+
+    let manager = TransmissionManager::new(Rate(1.0)).unwrap();
+    let statistics = depletion_curve(manager.clone(), false);
+    let single = Infectiousness::single(InfectionStatus::Infected);
+    let single = depletion_curve(manager.clone().with_infectiousness(single), false);
+    let stages = Infectiousness::new([(Stage::Early, 1.0), (Stage::Late, 1.0)]).unwrap();
+    let staged = depletion_curve(manager.with_infectiousness(stages), true);
+
+    assert!(statistics.len() > POPULATION as usize / 2, "{}", statistics.len());
+    assert_eq!(single, statistics);
+    assert_eq!(staged, statistics);
+  }
+
+  /// Two patches of 600 and 400 people that exchange five people in each direction every ten days until day 50.
+  ///
+  /// Migration ends well before the epidemic does. A move in the last iteration of the event loop would happen after
+  /// the statistics were updated for the last time, just like any other event in the last iteration.
+  fn two_patch_model(seed: u64) -> Model {
+    let mut model = Model::with_constants(sim_constants(seed));
+    model.add_module(StatisticsByPopulation::with_sizes([(PopulationId(0), 600), (PopulationId(1), 400)]));
+    model.add_module(TransmissionManager::new(FOI).unwrap());
+    model.add_module(InfectionManager::new(INFECTION_DURATION).unwrap());
+    model.add_module(
+      Migration::until(50.0)
+          .route(PopulationId(0), PopulationId(1), 5, Duration(10.0))
+          .route(PopulationId(1), PopulationId(0), 5, Duration(10.0))
+    );
+    model.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+    model
+  }
 
-  model.run()
+  #[test]
+  fn test_two_patch_metapopulation() {
+    let mut model = two_patch_model(SEED);
+    model.run();
+
+    let world = model.world_mut();
+    let stats_by_population = world.resource::<StatisticsByPopulation>().clone();
+    let total = *world.resource::<PopulationStatistics>();
+    assert_eq!(stats_by_population.total(), total);
+    assert_eq!(total.size(), POPULATION);
+    assert!(total.all_recovered());
+
+    // The statistics follow people as they migrate.
+    let mut people = world.query::<(&InfectionStatus, &PopulationId)>();
+    for (population, stats) in stats_by_population.iter() {
+      let recovered = people.iter(world)
+                            .filter(|(status, id)| **status == InfectionStatus::Recovered && **id == population)
+                            .count();
+      assert_eq!(recovered as u32, stats.recovered, "population {population:?}");
+    }
+  }
+
+  #[test]
+  fn test_statistics_underflow_is_an_error() {
+    let mut stats = PopulationStatistics::with_size(0);
+    let error = stats.update_stats(InfectionStatus::Infected).unwrap_err();
+    assert!(error.to_string().contains("a transition to Infected"), "{error}");
+    assert_eq!(stats, PopulationStatistics::with_size(0));
+  }
+
+  #[test]
+  fn test_failed_update_changes_no_counts() {
+    let mut model = Model::with_random_seed(SEED);
+    model.add_module(StatisticsByPopulation::with_sizes([(PopulationId(0), 5)]));
+    model.world_mut().insert_resource(StatisticsErrorPolicy::Warn);
+    // Totals that disagree with the population's, so that an infection can be counted in the population but not in
+    // the totals.
+    *model.world_mut().resource_mut::<PopulationStatistics>() = PopulationStatistics::with_size(0);
+    model.world_mut().spawn(InfectionStatus::Infected);
+
+    let population_stats = model.world().resource::<StatisticsByPopulation>().get(PopulationId(0));
+    assert_eq!(population_stats, PopulationStatistics::with_size(5));
+    assert_eq!(*model.world().resource::<PopulationStatistics>(), PopulationStatistics::with_size(0));
+  }
+
+  #[test]
+  fn test_double_counting_aborts() {
+    let mut model = Model::with_random_seed(SEED);
+    model.add_module(PopulationStatistics::with_size(1));
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    for time in [1.0, 2.0] {
+      timeline.schedule_at(time, EventCommand::infallible(|_| {}), "").unwrap();
+    }
+    // Two infections in a population of one.
+    model.world_mut().spawn(InfectionStatus::Infected);
+    model.world_mut().spawn(InfectionStatus::Infected);
+    model.run();
+
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world().resource::<Timeline>().events_run(), 1);
+    assert_eq!(
+      *model.world().resource::<PopulationStatistics>(),
+      PopulationStatistics{ susceptible: 0, infected: 1, recovered: 0 }
+    );
+
+    // With `StatisticsErrorPolicy::Warn`, the model carries on.
+    let mut model = Model::with_random_seed(SEED);
+    model.add_module(PopulationStatistics::with_size(0));
+    model.world_mut().insert_resource(StatisticsErrorPolicy::Warn);
+    model.world_mut().resource_mut::<Timeline>().schedule_at(1.0, EventCommand::infallible(|_| {}), "").unwrap();
+    model.world_mut().spawn(InfectionStatus::Infected);
+    model.run();
+
+    assert_eq!(model.world().resource::<Timeline>().events_run(), 1);
+    assert_eq!(*model.world().resource::<PopulationStatistics>(), PopulationStatistics::with_size(0));
+  }
+
+  /// A buggy system that counts spawns as infections a second time.
+  fn count_spawns_again(mut population_stats: ResMut<PopulationStatistics>, spawned: Query<(), Added<InfectionStatus>>) {
+    for _ in &spawned {
+      population_stats.update_stats(InfectionStatus::Infected).unwrap();
+    }
+  }
+
+  /// A population of 10, one of whom is infected at time 1, with every spawn counted twice.
+  fn double_counting_model() -> Model {
+    let mut model = Model::with_random_seed(SEED);
+    model.add_module(PopulationStatistics::with_size(10));
+    model.add_module(StatisticsValidation::new());
+    model.add_system_to_phase(count_spawns_again, ExecutionPhase::Normal);
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    let infect = EventCommand::infallible(|world| { world.spawn(InfectionStatus::Infected); });
+    timeline.schedule_at(1.0, infect, "").unwrap();
+    timeline.schedule_at(2.0, EventCommand::infallible(|_| {}), "").unwrap();
+    model
+  }
+
+  #[test]
+  fn test_statistics_validation_catches_double_counting() {
+    // No count goes negative, so only the validation notices.
+    let mut model = double_counting_model();
+    model.run();
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(
+      *model.world().resource::<PopulationStatistics>(),
+      PopulationStatistics{ susceptible: 8, infected: 2, recovered: 0 }
+    );
+
+    // The size is checked too.
+    let mut model = Model::with_random_seed(SEED);
+    model.add_module(PopulationStatistics::with_size(10));
+    model.add_module(StatisticsValidation::new());
+    model.world_mut().resource_mut::<PopulationStatistics>().recovered += 1;
+    model.tick_systems_only();
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Aborted);
+  }
+
+  #[test]
+  #[should_panic(expected = "do not agree with the world: the sum of the statistics by population is { susceptible: 9, \
+                             infected: 1, recovered: 0 }; the world has 1 infected people")]
+  fn test_statistics_validation_message() {
+    let mut model = double_counting_model();
+    model.world_mut().insert_resource(StatisticsErrorPolicy::Panic);
+    model.run();
+  }
+
+  /// The time of the first infection attempt of a model whose first attempt is at `initial_attempt_time`.
+  fn first_attempt_time(seed: u64, initial_attempt_time: InitialAttemptTime) -> f64 {
+    let mut model = Model::with_constants(sim_constants(seed));
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(FOI).unwrap().with_initial_attempt_time(initial_attempt_time));
+    let timeline = model.world().resource::<Timeline>();
+    timeline.pending().next().unwrap().0.0
+  }
+
+  #[test]
+  fn test_initial_attempt_time() {
+    let jitter = InitialAttemptTime::Uniform{ max: Duration(7.0) };
+    assert_eq!(first_attempt_time(SEED, InitialAttemptTime::default()), 0.0);
+    assert_eq!(first_attempt_time(SEED, InitialAttemptTime::Fixed(Duration(2.0))), 2.0);
+    assert_eq!(first_attempt_time(SEED, jitter), first_attempt_time(SEED, jitter));
+    assert_ne!(first_attempt_time(SEED, jitter), first_attempt_time(SEED + 1, jitter));
+    assert!((0.0..=7.0).contains(&first_attempt_time(SEED, jitter)));
+
+    // No offset reproduces the default exactly.
+    let mut default = model(SEED, RecoveryOutcome::BecomeRecovered);
+    let mut fixed = Model::with_constants(sim_constants(SEED));
+    fixed.add_module(PopulationStatistics::with_size(POPULATION));
+    fixed.add_module(
+      TransmissionManager::new(FOI).unwrap().with_initial_attempt_time(InitialAttemptTime::Fixed(Duration(0.0)))
+    );
+    fixed.add_module(InfectionManager::new(INFECTION_DURATION).unwrap());
+    fixed.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+    let diff = compare_runs::<PopulationStatistics>(&mut default, &mut fixed);
+    assert!(diff.is_match(), "{diff}");
+  }
+
+  /// The model as in `main`, without the report, with the given infection probability.
+  fn model_with_infection_probability(infection_probability: InfectionProbability) -> Model {
+    let mut model = Model::with_constants(sim_constants(SEED));
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(FOI).unwrap().with_infection_probability(infection_probability));
+    model.add_module(InfectionManager::new(INFECTION_DURATION).unwrap());
+    model.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+    model
+  }
+
+  #[test]
+  fn test_sir_scenario() {
+    let scenario_path = std::path::Path::new(OUTPUT_DIR).join("sir_scenario.json");
+    let mut from_scenario = Model::from_scenario(&scenario_path, &scenario::module_registry()).unwrap();
+    assert_eq!(*from_scenario.constants(), sim_constants(SEED));
+    from_scenario.run();
+
+    let mut from_code = model(SEED, RecoveryOutcome::BecomeRecovered);
+    from_code.run();
+    let stats = *from_scenario.world().resource::<PopulationStatistics>();
+    assert!(stats.all_recovered());
+    assert_eq!(stats, *from_code.world().resource::<PopulationStatistics>());
+    assert_eq!(
+      from_scenario.world().resource::<Timeline>().now(),
+      from_code.world().resource::<Timeline>().now()
+    );
+
+    let mut scenario = ecs_disease_models::scenario::Scenario::from_path(&scenario_path).unwrap();
+    scenario.modules[0].module = "population".to_string();
+    let Err(error) = scenario.build(&scenario::module_registry()) else {
+      panic!("building a scenario with an unknown module should fail");
+    };
+    assert!(error.to_string().contains("population_statistics"), "{error}");
+
+    // Invalid configurations are errors, not panics.
+    let mut zero_foi = ecs_disease_models::scenario::Scenario::from_path(&scenario_path).unwrap();
+    zero_foi.modules[1].config = serde_json::json!({ "foi": 0.0 });
+    let Err(error) = zero_foi.build(&scenario::module_registry()) else {
+      panic!("building a scenario with a zero force of infection should fail");
+    };
+    assert!(error.to_string().contains("the force of infection must be a positive, finite rate"), "{error}");
+    let mut zero_duration = ecs_disease_models::scenario::Scenario::from_path(&scenario_path).unwrap();
+    zero_duration.modules[2].config = serde_json::json!({ "infection_duration": 0.0 });
+    assert!(zero_duration.build(&scenario::module_registry()).is_err());
+    let mut invalid_ifr = ecs_disease_models::scenario::Scenario::from_path(&scenario_path).unwrap();
+    invalid_ifr.modules[2].config =
+        serde_json::json!({ "infection_duration": 5.0, "infection_fatality": { "Constant": 1.5 } });
+    let Err(error) = invalid_ifr.build(&scenario::module_registry()) else {
+      panic!("building a scenario with an IFR above 1 should fail");
+    };
+    assert!(error.to_string().contains("IFR"), "{error}");
+
+    // Scenarios do not give people ages, which an age-dependent IFR needs.
+    let mut by_age = ecs_disease_models::scenario::Scenario::from_path(&scenario_path).unwrap();
+    by_age.modules[2].config =
+        serde_json::json!({ "infection_duration": 5.0, "infection_fatality": { "ByAge": [[0, 0.001], [65, 0.05]] } });
+    let Err(error) = by_age.build(&scenario::module_registry()) else {
+      panic!("building a scenario with an age-dependent IFR should fail");
+    };
+    assert!(error.to_string().contains("needs people with an `Age`"), "{error}");
+  }
+
+  #[test]
+  fn test_invalid_rates() {
+    let error = TransmissionManager::new(Rate(0.0)).unwrap_err();
+    assert!(error.to_string().contains("the force of infection must be a positive, finite rate, but is 0"), "{error}");
+    let error = InfectionManager::new(Duration(0.0)).unwrap_err();
+    assert!(error.to_string().contains("the infection duration must be a positive, finite duration"), "{error}");
+    assert!(TransmissionManager::new(Rate(f64::INFINITY)).is_err());
+    assert!(InfectionManager::new(Duration(f64::NAN)).is_err());
+    assert!(TransmissionManager::new(FOI).is_ok());
+    assert!(InfectionManager::new(INFECTION_DURATION).is_ok());
+  }
+
+  #[test]
+  fn test_infection_probability() {
+    // Density dependence is mass action, `S * I / N0`.
+    let density_dependent = InfectionProbability::density_dependent();
+    let constants = sim_constants(SEED);
+    let stats = PopulationStatistics{ susceptible: 900, infected: 1, recovered: 99 };
+    assert_eq!(density_dependent.probability(&stats, 1.0, &constants, stats.size()), 0.9);
+    let stats = PopulationStatistics{ susceptible: 1000, infected: 0, recovered: 0 };
+    assert_eq!(density_dependent.probability(&stats, 0.0, &constants, stats.size()), 0.0);
+    // Nobody is infected at the start of the model, so with density dependence nobody ever is.
+    let mut nobody_infected = model_with_infection_probability(density_dependent);
+    nobody_infected.run();
+    let stats = *nobody_infected.world().resource::<PopulationStatistics>();
+    assert_eq!(stats, PopulationStatistics::with_size(POPULATION));
+
+    // Probabilities above 1 are clamped.
+    let diff = compare_runs::<PopulationStatistics>(
+      &mut model_with_infection_probability(InfectionProbability::custom("always", |_, _, _| 1.0)),
+      &mut model_with_infection_probability(InfectionProbability::custom("too_high", |_, _, _| 2.0))
+    );
+    assert!(diff.is_match(), "{diff}");
+
+    // Nobody is infected once nobody is susceptible, even if the probability is 1.
+    let mut nobody_susceptible = Model::with_constants(sim_constants(SEED));
+    let everyone_recovered = PopulationStatistics{ susceptible: 0, infected: 0, recovered: POPULATION };
+    nobody_susceptible.add_module(everyone_recovered);
+    let always = InfectionProbability::custom("always", |_, _, _| 1.0);
+    nobody_susceptible.add_module(TransmissionManager::new(FOI).unwrap().with_infection_probability(always));
+    nobody_susceptible.run();
+    assert_eq!(nobody_susceptible.abort_reason(), None);
+    assert_eq!(*nobody_susceptible.world().resource::<PopulationStatistics>(), everyone_recovered);
+    let world = nobody_susceptible.world_mut();
+    assert_eq!(world.query::<&InfectionStatus>().iter(world).count(), 0);
+
+    // A large half saturation constant makes infections rarer, so the epidemic takes longer.
+    let mut frequency_dependent = model(SEED, RecoveryOutcome::BecomeRecovered);
+    frequency_dependent.run();
+    let mut saturating = model_with_infection_probability(InfectionProbability::saturating(POPULATION as f64));
+    saturating.run();
+    let final_time = |model: &Model| model.world().resource::<Timeline>().now();
+    assert!(final_time(&saturating) > final_time(&frequency_dependent));
+  }
+
+  /// The model as in `main`, without the report, with the given denominator policy and recovery outcome.
+  fn model_with_denominator(denominator: DenominatorPolicy, recovery_outcome: RecoveryOutcome) -> Model {
+    let mut model = Model::with_constants(sim_constants(SEED));
+    model.add_module(PopulationStatistics::from_constants());
+    model.add_module(TransmissionManager::new(FOI).unwrap().with_denominator(denominator));
+    model.add_module(InfectionManager::new(INFECTION_DURATION).unwrap().with_recovery_outcome(recovery_outcome));
+    model.add_stop_condition(|world| world.resource::<PopulationStatistics>().all_recovered());
+    model
+  }
+
+  #[test]
+  fn test_denominator_policy() {
+    let stats = PopulationStatistics{ susceptible: 2, infected: 3, recovered: 5 };
+    assert_eq!(DenominatorPolicy::TotalPopulation.denominator(&stats, true), 10);
+    assert_eq!(DenominatorPolicy::TotalPopulation.denominator(&stats, false), 10);
+    assert_eq!(DenominatorPolicy::LivingPopulation.denominator(&stats, true), 10);
+    assert_eq!(DenominatorPolicy::LivingPopulation.denominator(&stats, false), 5);
+    assert_eq!(DenominatorPolicy::SusceptiblePlusInfectious.denominator(&stats, true), 5);
+
+    // The default is the total population.
+    let diff = compare_runs::<PopulationStatistics>(
+      &mut model(SEED, RecoveryOutcome::BecomeRecovered),
+      &mut model_with_denominator(DenominatorPolicy::TotalPopulation, RecoveryOutcome::BecomeRecovered)
+    );
+    assert!(diff.is_match(), "{diff}");
+    // Recovered people who stay in the model are living.
+    let diff = compare_runs::<PopulationStatistics>(
+      &mut model(SEED, RecoveryOutcome::BecomeRecovered),
+      &mut model_with_denominator(DenominatorPolicy::LivingPopulation, RecoveryOutcome::BecomeRecovered)
+    );
+    assert!(diff.is_match(), "{diff}");
+    // Recovered people who are despawned are not.
+    let diff = compare_runs::<PopulationStatistics>(
+      &mut model_with_denominator(DenominatorPolicy::SusceptiblePlusInfectious, RecoveryOutcome::Despawn),
+      &mut model_with_denominator(DenominatorPolicy::LivingPopulation, RecoveryOutcome::Despawn)
+    );
+    assert!(diff.is_match(), "{diff}");
+
+    // Without the recovered people diluting the contacts, the last susceptible people are infected sooner.
+    let mut total = model(SEED, RecoveryOutcome::BecomeRecovered);
+    total.run();
+    let mut susceptible_plus_infectious =
+        model_with_denominator(DenominatorPolicy::SusceptiblePlusInfectious, RecoveryOutcome::BecomeRecovered);
+    susceptible_plus_infectious.run();
+    let final_time = |model: &Model| model.world().resource::<Timeline>().now();
+    assert!(final_time(&susceptible_plus_infectious) < final_time(&total));
+  }
+
+  #[test]
+  fn test_removing_the_transmission_manager_stops_infections() {
+    let infections = |model: &Model| {
+      let stats = model.world().resource::<PopulationStatistics>();
+      stats.infected + stats.recovered
+    };
+    let mut model = model(SEED, RecoveryOutcome::BecomeRecovered);
+    let pause = EventCommand::infallible(|world| world.insert_resource(ModelControl::Paused));
+    model.world_mut().resource_mut::<Timeline>().schedule_at(5.0, pause, "pause").unwrap();
+    model.run();
+    let infected_before = infections(&model);
+    assert!(infected_before > 0);
+    assert!(model.world().resource::<Timeline>().pending().any(|(_, _, label)| label == "infection_attempt"));
+
+    assert!(model.remove_resource::<TransmissionManager>().is_some());
+    *model.world_mut().resource_mut::<ModelControl>() = ModelControl::Running;
+    model.run();
+    // The pending attempt ends the chain, so the infected people recover and nobody else is infected.
+    assert_eq!(infections(&model), infected_before);
+    let stats = *model.world().resource::<PopulationStatistics>();
+    assert_eq!(stats.infected, 0);
+    assert!(stats.susceptible > 0, "{stats}");
+    assert_eq!(model.world().resource::<Timeline>().pending().count(), 0);
+  }
+
+  #[test]
+  fn test_closed_demography_changes_nothing() {
+    let mut closed = model(SEED, RecoveryOutcome::BecomeRecovered);
+    closed.add_module(Demography::closed());
+    let diff = compare_runs::<PopulationStatistics>(&mut model(SEED, RecoveryOutcome::BecomeRecovered), &mut closed);
+    assert!(diff.is_match(), "{diff}");
+  }
+
+  #[test]
+  fn test_balanced_demography_keeps_the_population_size() {
+    // Without infections. Over 200 days, there are about 4,000 births and deaths, so the size wanders by about 60.
+    let constants = SimConstants::builder().population_size(POPULATION).max_time(200.0).seed(SEED).build();
+    let mut model = Model::with_constants(constants);
+    model.add_module(PopulationStatistics::with_size(POPULATION));
+    model.add_module(Demography::balanced(Rate(0.01)));
+    model.run();
+
+    let size = model.world().resource::<PopulationStatistics>().size();
+    assert!((750..=1250).contains(&size), "population size {size}");
+    assert!(model.world().resource::<Timeline>().events_run() > 2000);
+  }
+
+  #[test]
+  fn test_open_population_statistics_match_people() {
+    // Run for 100 days only, because with births, the chain of infection attempts does not end by itself.
+    let constants = SimConstants::builder().population_size(POPULATION).max_time(100.0).seed(SEED).build();
+    for recovery_outcome in [RecoveryOutcome::BecomeRecovered, RecoveryOutcome::Despawn] {
+      let mut model = Model::with_constants(constants);
+      model.add_module(PopulationStatistics::with_size(POPULATION));
+      model.add_module(TransmissionManager::new(FOI).unwrap());
+      model.add_module(InfectionManager::new(INFECTION_DURATION).unwrap().with_recovery_outcome(recovery_outcome));
+      model.add_module(Demography::new(Rate(0.004), Rate(0.002)));
+      model.add_module(StatisticsValidation::new());
+      model.run();
+      assert_eq!(model.abort_reason(), None);
+
+      let stats = *model.world().resource::<PopulationStatistics>();
+      let mut count = |status: InfectionStatus| {
+        let world = model.world_mut();
+        world.query::<&InfectionStatus>().iter(world).filter(|other| **other == status).count() as u32
+      };
+      assert_eq!(count(InfectionStatus::Infected), stats.infected);
+      if recovery_outcome == RecoveryOutcome::BecomeRecovered {
+        assert_eq!(count(InfectionStatus::Recovered), stats.recovered);
+      }
+      // Births outpace deaths.
+      assert!(stats.size() > POPULATION, "{stats}");
+    }
+  }
+
+  #[test]
+  fn test_realized_infectious_period() {
+    let mut model = model(SEED, RecoveryOutcome::BecomeRecovered);
+    model.run();
+
+    let world = model.world_mut();
+    let people: Vec<Entity> = world.query_filtered::<Entity, With<InfectionStatus>>().iter(world).collect();
+    let periods: Vec<f64> = people.iter()
+                                  .filter_map(|&person| infectious_period(world, person))
+                                  .map(|period| period.0)
+                                  .collect();
+    assert_eq!(periods.len(), POPULATION as usize);
+    assert!(periods.iter().all(|&period| period >= 0.0));
+
+    // The standard error of the mean of 1000 exponential periods with mean 5 is about 0.16.
+    let mean = periods.iter().sum::<f64>() / periods.len() as f64;
+    assert!((mean - INFECTION_DURATION.0).abs() < 0.5, "mean infectious period {mean}");
+  }
+
+  type InfectionHistory = StatusHistory<InfectionStatus>;
+
+  #[test]
+  fn test_infection_history() {
+    let mut model = model(SEED, RecoveryOutcome::BecomeRecovered);
+    model.add_module(RecordStatusHistory::<InfectionStatus>::new().for_all_entities());
+    model.run();
+
+    // The infectious period of each person's history is the time from their infection to the recovery the
+    // `InfectionManager` scheduled for them, which the `LifeHistory` records.
+    let world = model.world_mut();
+    let people: Vec<(Entity, InfectionHistory)> = world.query::<(Entity, &InfectionHistory)>()
+                                                       .iter(world)
+                                                       .map(|(person, history)| (person, history.clone()))
+                                                       .collect();
+    assert_eq!(people.len(), POPULATION as usize);
+    for (person, history) in people {
+      let statuses: Vec<InfectionStatus> = history.transitions().iter().map(|&(_, status)| status).collect();
+      assert_eq!(statuses, [InfectionStatus::Infected, InfectionStatus::Recovered]);
+      assert_eq!(history.sojourn(InfectionStatus::Infected), infectious_period(world, person));
+    }
+  }
 }
@@ -1,27 +1,107 @@
 /*!
 
 We keep track of summary statistics for the population within a `Resource`. Instead of having to remember
-to update this every single place the population is mutated, we attach observers to change events so that
-the resource is updated automatically regardless of how the mutation happens. In our case, we only have
-two situations in which we must monitor for changes:
+to update this every single place the population is mutated, component hooks on `InfectionStatus` update the resource
+at the moment the status changes, regardless of which code makes the change. There is no polling, so the counts are
+always current, and a spawn is never mistaken for a change or the other way around. In our case, there are three
+situations in which the counts change:
 
-1. When an entity is spawned. This occurs if and only if a person (not represented in code directly) transitions from susceptible to infected.
-2. When an entity is changed. This occurs if and only if an infected person recovers.
+1. When an entity is spawned, or given an `InfectionStatus`, the `on_insert` hook counts a transition from susceptible
+   to its status. This occurs if and only if a person (not represented in code directly) transitions from susceptible
+   to infected.
+2. When an entity's status is replaced by inserting a new one, the `on_replace` hook remembers the old status and the
+   `on_insert` hook counts the transition from it to the new one. This occurs if and only if an infected person
+   recovers.
+3. When an entity is despawned, the `on_remove` hook takes the person out of the counts of their status. This occurs
+   when an infected person recovers and the `InfectionManager` is configured to despawn recovered people, or when a
+   person dies.
+
+Hooks run when a component is inserted or removed, not when it is mutated in place through `get_mut`, so a person's
+status should be changed by inserting the new status, as in
+`world.entity_mut(person).insert(InfectionStatus::Recovered)`. A change made through `get_mut` is still counted, as a
+fallback, by a system that looks for changed statuses after the `Timeline` runs the event of the iteration and before
+the `Last` phase. It compares each with the status the person was last counted with, which the hooks record, so a
+change is never counted twice. Until then, though, the counts are behind the world. Looking for changes visits every
+person in every iteration, as `StatusTransitions` do, which the hooks alone would not. A person's population is the
+`PopulationId` they have when their status is inserted, so spawn people with it. Moves between populations are found by
+a system in the `Normal` phase.
+
+Since a change is counted when the event that makes it runs, a stop condition on the counts, like `all_recovered`,
+stops the model in that iteration of the event loop. `Normal` phase systems see the effects of an event only in the
+next iteration (see `Model::enable_sparse_mode`), so they would miss the last change. Systems that must see every
+change, like the `StatisticsHistory` below and the incidence reporter, run in the `Last` phase instead.
+
+A despawned person is either removed from the infection, and still counted as recovered, or dead, and no longer counted
+at all. The two are told apart by the `Deceased` marker: whatever despawns a person who died, like the `Demography`,
+inserts `Deceased` first, and the hook that watches for despawns takes them out of the counts of their status. So
+`PopulationStatistics::size` is the number of living people, including recovered people who are no longer entities,
+however people enter and leave the model, as long as deaths are marked. Susceptible people are not entities in this
+model, so their births and deaths are counted directly, with `PopulationStatistics::add` and
+`PopulationStatistics::remove`.
+
+For metapopulation models, the `StatisticsByPopulation` resource keeps the same statistics separately for each
+`PopulationId`, counting people without one in the default population. It is kept up to date alongside the
+`PopulationStatistics`, which always hold the totals, and also follows people as they migrate between populations.
+Add either the `PopulationStatistics` module, for a single population, or the `StatisticsByPopulation` module, not
+both; each sets up the other's resource.
+
+The counts are unsigned, so a transition that would make one negative, e.g. because two systems both counted the same
+transition, is an error rather than a wrapped or panicking subtraction. The counts are left as they were and the
+`StatisticsErrorPolicy` resource decides what happens next: by default the error is printed and the model aborts.
+
+A double-counted transition that does not make a count negative, e.g. a spawn counted as an infection twice while there
+are still susceptible people, goes unnoticed by the counts themselves. The optional `StatisticsValidation` module
+checks, after the statistics are updated in every iteration of the event loop, that they still agree with the world:
+
+ - `S + I + R`, the `PopulationStatistics::size`, is the initial size, changed only by births and deaths;
+ - the `PopulationStatistics` are the sum of the `StatisticsByPopulation`;
+ - the infected count is the number of people whose status is infected, and the recovered count is at least the
+   number whose status is recovered (recovered people may have been despawned).
+
+A violation is handled by the `StatisticsErrorPolicy`, like a count that would go negative, with a message listing every
+invariant that does not hold. The check queries every person, so it is opt-in, e.g. for tests and debugging.
+
+The optional `StatisticsHistory` module additionally keeps a snapshot of the `PopulationStatistics` each time they
+change, which is the in-memory, aggregated analog of the incidence report. After the run, the history can be read from
+`Model::world()`, e.g. to check the epidemic curve, or exported for plotting with `StatisticsHistory::epidemic_curve`.
+Memory can be bounded with a maximum length, in which case
+only the most recent snapshots are kept.
+
+The optional `DepletionCurve` module records the inputs of the final-size relationship used to fit an SIR model and
+estimate R0: the fraction of the population that is susceptible and the cumulative number of infections, sampled at
+each infection. It counts the `InfectionOccurred` events of the `TransmissionManager`, which must also be added.
 
 */
 
+use std::collections::{BTreeMap, VecDeque};
+use serde::Serialize;
 use std::fmt::Display;
 use bevy_ecs::prelude::*;
-use bevy_ecs::schedule::SystemConfigs;
+use bevy_ecs::{
+  component::ComponentId,
+  entity::EntityHashMap,
+  schedule::SystemConfigs,
+  world::DeferredWorld
+};
 use ecs_disease_models::{
+  compartments::CompartmentCounts,
+  epidemic_curve::EpidemicCurve,
+  errors::IxaError,
+  metapopulation::PopulationId,
   model::{ExecutionPhase, ModelControl},
-  module::Module
+  module::Module,
+  sim_constants::SimConstants,
+  status_transition::StatusTransitions,
+  timeline::{Time, Timeline, TimelineSystem}
 };
 
-use crate::InfectionStatus;
+use crate::{
+  transmission_manager::InfectionOccurred,
+  InfectionStatus
+};
 
 /// Tracks summary statistics for the world.
-#[derive(Resource, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default)]
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Default, Serialize)]
 pub struct PopulationStatistics {
   pub susceptible: u32,
   pub infected: u32,
@@ -37,24 +117,46 @@ impl PopulationStatistics {
     }
   }
 
-  /// Returns a total count of people in this population
+  /// The `PopulationStatistics` module for everyone in the `population_size` of the model's `SimConstants`, which is
+  /// read when the module is initialized.
+  pub fn from_constants() -> PopulationStatisticsFromConstants {
+    PopulationStatisticsFromConstants
+  }
+
+  /// The number of living people in this population, including recovered people who were despawned when they recovered
+  /// but not people who died. See the module documentation.
   pub fn size(&self) -> u32 {
     self.infected + self.recovered + self.susceptible
   }
 
-  /// Updates the population statistics based on the new infection status.
+  /// The counts by `InfectionStatus`, e.g. for weighting the infectious people by their `Infectiousness`.
+  pub fn compartment_counts(&self) -> CompartmentCounts<InfectionStatus> {
+    [
+      (InfectionStatus::Susceptible, self.susceptible as usize),
+      (InfectionStatus::Infected, self.infected as usize),
+      (InfectionStatus::Recovered, self.recovered as usize),
+    ].into_iter().collect()
+  }
+
+  /// Whether everyone has recovered, at which point the simulation is over.
+  pub fn all_recovered(&self) -> bool {
+    self.recovered == self.size()
+  }
+
+  /// Updates the population statistics based on the new infection status. Fails, leaving the statistics unchanged, if
+  /// the transition would make a count negative.
   ///
   /// In this model, the previous status is implicit, but this may not be the case in more sophisticated models.
-  pub(crate) fn update_stats(&mut self, new_status: InfectionStatus) {
+  pub(crate) fn update_stats(&mut self, new_status: InfectionStatus) -> Result<(), IxaError> {
     match new_status {
 
       InfectionStatus::Infected => {
+        self.susceptible = self.decrement(self.susceptible, "a transition to Infected")?;
         self.infected += 1;
-        self.susceptible -= 1;
       }
 
       InfectionStatus::Recovered => {
-        self.infected -= 1;
+        self.infected = self.decrement(self.infected, "a transition to Recovered")?;
         self.recovered += 1;
       }
 
@@ -64,6 +166,77 @@ impl PopulationStatistics {
       }
 
     }
+    Ok(())
+  }
+
+  /// Updates the population statistics for a person whose status changed from `from` to `to`. Fails, leaving the
+  /// statistics unchanged, if nobody in this population has the status `from`.
+  pub(crate) fn transition(&mut self, from: InfectionStatus, to: InfectionStatus) -> Result<(), IxaError> {
+    if from == to {
+      return Ok(());
+    }
+    let count = *self.count_mut(from);
+    *self.count_mut(from) = self.decrement(count, &format!("a transition from {from} to {to}"))?;
+    *self.count_mut(to) += 1;
+    Ok(())
+  }
+
+  /// Counts a person with the given status who joined this population.
+  pub(crate) fn add(&mut self, status: InfectionStatus) {
+    *self.count_mut(status) += 1;
+  }
+
+  /// Stops counting a person with the given status who left this population. Fails, leaving the statistics unchanged,
+  /// if nobody in this population has the status.
+  pub(crate) fn remove(&mut self, status: InfectionStatus) -> Result<(), IxaError> {
+    let count = *self.count_mut(status);
+    *self.count_mut(status) = self.decrement(count, &format!("a person with status {status} leaving"))?;
+    Ok(())
+  }
+
+  fn count_mut(&mut self, status: InfectionStatus) -> &mut u32 {
+    match status {
+      InfectionStatus::Susceptible => &mut self.susceptible,
+      InfectionStatus::Infected    => &mut self.infected,
+      InfectionStatus::Recovered   => &mut self.recovered,
+    }
+  }
+
+  /// `count - 1`, or an error saying that counting `change` would have made a count negative.
+  fn decrement(&self, count: u32, change: &str) -> Result<u32, IxaError> {
+    count.checked_sub(1).ok_or_else(|| IxaError::IxaError(format!(
+      "counting {change} would make a count negative in {self}"
+    )))
+  }
+}
+
+/// What happens when updating the `PopulationStatistics` fails because a count would go negative. The statistics are
+/// left unchanged in every case.
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Default, Debug, Hash)]
+pub enum StatisticsErrorPolicy {
+  /// Print the error and abort the model.
+  #[default]
+  Abort,
+  /// Print the error and carry on.
+  Warn,
+  /// Panic with the error.
+  Panic,
+}
+
+impl StatisticsErrorPolicy {
+  fn handle(self, error: IxaError, model_control: &mut ModelControl) {
+    match self {
+      StatisticsErrorPolicy::Abort => {
+        #[cfg(feature = "print_messages")]
+        println!("Error: {error}. Aborting.");
+        *model_control = ModelControl::Aborted;
+      }
+      StatisticsErrorPolicy::Warn => {
+        #[cfg(feature = "print_messages")]
+        println!("Warning: {error}");
+      }
+      StatisticsErrorPolicy::Panic => panic!("{error}"),
+    }
   }
 }
 
@@ -73,75 +246,482 @@ impl Display for PopulationStatistics {
   }
 }
 
-// The following is unneeded, because for Bevy ECS a newly spawned entity is counted as a change.
-/*
-/// A system that handles the case when a person transitions from `Susceptible` to `Infected`, which occurs
-/// if and only if an entity is spawned.
-fn handle_spawned_infected(
-  mut population_stats: ResMut<PopulationStatistics>,
-  query: Query<(&InfectionStatus, Entity), Added<InfectionStatus>>,
+/// The `PopulationStatistics` of each population. Their sum is the `PopulationStatistics` resource.
+#[derive(Resource, Clone, Eq, PartialEq, Debug, Default, Serialize)]
+pub struct StatisticsByPopulation(BTreeMap<PopulationId, PopulationStatistics>);
+
+impl StatisticsByPopulation {
+  /// Creates statistics for populations of the given sizes, in which everyone is susceptible.
+  pub fn with_sizes(sizes: impl IntoIterator<Item = (PopulationId, u32)>) -> Self {
+    StatisticsByPopulation(
+      sizes.into_iter().map(|(population, size)| (population, PopulationStatistics::with_size(size))).collect()
+    )
+  }
+
+  /// The statistics of `population`, which are all zero for a population nobody belongs to.
+  pub fn get(&self, population: PopulationId) -> PopulationStatistics {
+    self.0.get(&population).copied().unwrap_or_default()
+  }
+
+  /// The populations and their statistics in order of `PopulationId`.
+  pub fn iter(&self) -> impl Iterator<Item = (PopulationId, PopulationStatistics)> + '_ {
+    self.0.iter().map(|(population, stats)| (*population, *stats))
+  }
+
+  /// The statistics of the whole metapopulation.
+  pub fn total(&self) -> PopulationStatistics {
+    self.0.values().fold(PopulationStatistics::default(), |total, stats| PopulationStatistics{
+      susceptible: total.susceptible + stats.susceptible,
+      infected   : total.infected + stats.infected,
+      recovered  : total.recovered + stats.recovered,
+    })
+  }
+
+  pub(crate) fn get_mut(&mut self, population: PopulationId) -> &mut PopulationStatistics {
+    self.0.entry(population).or_default()
+  }
+}
+
+/// Marks a person who died. Insert it before despawning the person, so that they are no longer counted. See the module
+/// documentation.
+#[derive(Component, Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub struct Deceased;
+
+/// The statuses that the `on_replace` hook saw being replaced, by entity, until the `on_insert` or `on_remove` hook
+/// that follows counts the change.
+#[derive(Resource, Default)]
+struct ReplacedStatuses(EntityHashMap<InfectionStatus>);
+
+/// The status each person was last counted with, by entity, so that a change made through `get_mut`, which runs no
+/// hook, can be counted later by `count_mutated_statuses`.
+#[derive(Resource, Default)]
+struct CountedStatuses(EntityHashMap<InfectionStatus>);
+
+/// Applies `update` to the statistics of `population` and to the totals, both or neither, handling an error with the
+/// `StatisticsErrorPolicy`.
+fn update_statistics(
+  world: &mut DeferredWorld,
+  population: PopulationId,
+  update: impl Fn(&mut PopulationStatistics) -> Result<(), IxaError>,
 ) {
-  // New entities should only ever be spawned with the `InfectionStation::Infected` status in this model.
-  // It is a good practice to actually check this invariant and emit an error if it is violated.
-  for (new_status, _) in query.iter() {
-    if *new_status == InfectionStatus::Infected{
-      population_stats.update_stats(InfectionStatus::Infected);
-      #[cfg(feature = "print_messages")]
-      println!("Spawn change detected (Infected). Updating PopulationStatistics: {:?}", population_stats);
+  let mut population_stats = world.resource::<StatisticsByPopulation>().get(population);
+  let mut total_stats = *world.resource::<PopulationStatistics>();
+  match update(&mut population_stats).and_then(|_| update(&mut total_stats)) {
+    Ok(()) => {
+      *world.resource_mut::<StatisticsByPopulation>().get_mut(population) = population_stats;
+      *world.resource_mut::<PopulationStatistics>() = total_stats;
+    }
+    Err(error) => {
+      let policy = *world.resource::<StatisticsErrorPolicy>();
+      policy.handle(error, &mut world.resource_mut::<ModelControl>());
     }
   }
 }
-*/
 
-/// A system that monitors for infection transitions to adjust the stats correctly.
+/// An `on_replace` hook that remembers the status a person had, for the `on_insert` hook to count the transition from
+/// it to the new status. It also runs, before the `on_remove` hook, when the status is removed.
+fn remember_replaced_status(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+  let status = *world.get::<InfectionStatus>(entity).expect("the status is being replaced");
+  world.resource_mut::<ReplacedStatuses>().0.insert(entity, status);
+}
+
+/// An `on_insert` hook that counts a person's transition to their new status, from the status they were last counted
+/// with, which differs from the status replaced if that was changed through `get_mut` since, or else from the status
+/// replaced, or from susceptible for a person who had none.
+///
+/// These hooks would more naturally be observers, but observers are entities, and spawning one would shift the entity
+/// indices that the incidence report uses as person IDs.
+fn count_inserted_status(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+  let to = *world.get::<InfectionStatus>(entity).expect("the status was just inserted");
+  let replaced = world.resource_mut::<ReplacedStatuses>().0.remove(&entity);
+  let counted = world.resource_mut::<CountedStatuses>().0.insert(entity, to);
+  let from = counted.or(replaced).unwrap_or(InfectionStatus::Susceptible);
+  if from == to {
+    return;
+  }
+  let population = world.get::<PopulationId>(entity).copied().unwrap_or_default();
+  update_statistics(&mut world, population, |stats| stats.transition(from, to));
+
+  #[cfg(feature = "print_messages")]
+  println!("Change to {} detected. Updated PopulationStatistics: {}", to, world.resource::<PopulationStatistics>());
+}
+
+/// An `on_remove` hook that takes a `Deceased` person who is despawned out of the counts of their status, and counts
+/// anyone else who is despawned (or otherwise loses their status) while infected as recovered, that is, removed. The
+/// status is the one the person was last counted with, if any.
+fn count_removed_people(mut world: DeferredWorld, entity: Entity, _: ComponentId) {
+  world.resource_mut::<ReplacedStatuses>().0.remove(&entity);
+  let counted = world.resource_mut::<CountedStatuses>().0.remove(&entity);
+  let Some(&status) = counted.as_ref().or(world.get::<InfectionStatus>(entity)) else {
+    return;
+  };
+  let died = world.get::<Deceased>(entity).is_some();
+  if died || status == InfectionStatus::Infected {
+    let population = world.get::<PopulationId>(entity).copied().unwrap_or_default();
+    let size = world.resource::<PopulationStatistics>().size();
+    update_statistics(&mut world, population, |stats| {
+      if died { stats.remove(status) } else { stats.update_stats(InfectionStatus::Recovered) }
+    });
+    let new_size = world.resource::<PopulationStatistics>().size();
+    if let Some(mut validation) = world.get_resource_mut::<StatisticsValidation>() {
+      validation.resize(size, new_size);
+    }
+
+    #[cfg(feature = "print_messages")]
+    println!(
+      "Removal of a person detected. Updated PopulationStatistics: {}",
+      world.resource::<PopulationStatistics>()
+    );
+  }
+}
+
+/// A system that counts the changes of status made through `get_mut` since it last ran, which the hooks do not see. A
+/// status the hooks did not count, because it was inserted before they were registered, is left alone.
+fn count_mutated_statuses(
+  world: &mut World,
+  changed: &mut QueryState<(Entity, &InfectionStatus, Option<&PopulationId>), Changed<InfectionStatus>>,
+) {
+  let counted_statuses = world.resource::<CountedStatuses>();
+  let mutated: Vec<(Entity, InfectionStatus, InfectionStatus, PopulationId)> = changed
+      .iter(world)
+      .filter_map(|(entity, &to, population)| {
+        let from = *counted_statuses.0.get(&entity)?;
+        (from != to).then(|| (entity, from, to, population.copied().unwrap_or_default()))
+      })
+      .collect();
+
+  for (entity, from, to, population) in mutated {
+    world.resource_mut::<CountedStatuses>().0.insert(entity, to);
+    update_statistics(&mut world.into(), population, |stats| stats.transition(from, to));
+
+    #[cfg(feature = "print_messages")]
+    println!("Change to {} through `get_mut` detected. Updated PopulationStatistics: {}", to,
+             world.resource::<PopulationStatistics>());
+  }
+}
+
+/// A system that monitors for migrations to adjust the stats by population. Changes of status are counted by the hooks.
 fn track_population_changes(
-  mut population_stats: ResMut<PopulationStatistics>,
+  mut stats_by_population: ResMut<StatisticsByPopulation>,
+  mut migrations: StatusTransitions<PopulationId>,
+  people: Query<&InfectionStatus>,
+  policy: Res<StatisticsErrorPolicy>,
   mut model_control: ResMut<ModelControl>,
-  query: Query<(&InfectionStatus, &InfectionStatus), Changed<InfectionStatus>>,
 ) {
-  // Track the changes in infection status.
-  for (new_status, _) in query.iter() {
-    // In our model, the only change of status is a transition from infected to recovered. However,
-    // Bevy ECS counts spawning an `Entity` as a "change". Oops.
-    population_stats.update_stats(*new_status);
+  // Only moves between populations, not a person's first population, which was counted when their status was inserted.
+  for migration in migrations.changes() {
+    if let (Some(from), Ok(status)) = (migration.from, people.get(migration.entity)) {
+      // Both populations are updated, or neither.
+      let (mut from_stats, mut to_stats) = (stats_by_population.get(from), stats_by_population.get(migration.to));
+      match from_stats.remove(*status) {
+        Ok(()) => {
+          to_stats.add(*status);
+          *stats_by_population.get_mut(from) = from_stats;
+          *stats_by_population.get_mut(migration.to) = to_stats;
+        }
+        Err(error) => policy.handle(error, &mut model_control),
+      }
+    }
+  }
+}
 
-    match new_status {
+/// Inserts both statistics resources and returns the system that keeps them up to date.
+fn initialize_statistics(
+  world: &mut World,
+  population_stats: PopulationStatistics,
+  stats_by_population: StatisticsByPopulation,
+) -> SystemConfigs {
+  world.insert_resource(population_stats);
+  world.insert_resource(stats_by_population);
+  world.init_resource::<StatisticsErrorPolicy>();
+  world.init_resource::<ReplacedStatuses>();
+  world.init_resource::<CountedStatuses>();
+  world.register_component_hooks::<InfectionStatus>()
+       .on_replace(remember_replaced_status)
+       .on_insert(count_inserted_status)
+       .on_remove(count_removed_people);
 
-      InfectionStatus::Susceptible => {
-        /* This case should never happen in this model. */
-      }
+  // Also set up a monitor of migrations, which keeps the statistics by population up to date, and the fallback for
+  // changes of status that the hooks do not see, which are made by events, so that the `Last` phase sees them counted.
+  (
+    track_population_changes.in_set(ExecutionPhase::Normal),
+    count_mutated_statuses.after(TimelineSystem).before(ExecutionPhase::Last)
+  ).into_configs()
+}
 
-      InfectionStatus::Infected => {
-        #[cfg(feature = "print_messages")]
-        println!("Change to Infected detected. Updated PopulationStatistics: {}", population_stats.as_ref());
-      }
+impl Module for PopulationStatistics {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs>{
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module PopulationStatistics");
 
-      InfectionStatus::Recovered => {
-        #[cfg(feature = "print_messages")]
-        println!("Change to Recovered detected. Updated PopulationStatistics: {}", population_stats.as_ref());
-      }
+    // Everyone is in the default population.
+    let stats_by_population = StatisticsByPopulation(BTreeMap::from([(PopulationId::default(), self)]));
+    Some(initialize_statistics(world, self, stats_by_population))
+  }
+}
+
+impl Module for StatisticsByPopulation {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs>{
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module StatisticsByPopulation");
+
+    let population_stats = self.total();
+    Some(initialize_statistics(world, population_stats, self))
+  }
+}
+
+/// Adds the `PopulationStatistics` module with the population size of the `SimConstants`. See
+/// `PopulationStatistics::from_constants`.
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct PopulationStatisticsFromConstants;
+
+impl Module for PopulationStatisticsFromConstants {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    let population_size = world.resource::<SimConstants>().population_size;
+    PopulationStatistics::with_size(population_size).initialize_with_world(world)
+  }
+}
+
+/// Checks that the `PopulationStatistics` agree with the world. See the module documentation.
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Debug, Default)]
+pub struct StatisticsValidation {
+  /// The initial size of the population, plus births, minus deaths.
+  expected_size: u32,
+}
+
+impl StatisticsValidation {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records a birth or death that changed the size of the population from `size` to `new_size`.
+  pub(crate) fn resize(&mut self, size: u32, new_size: u32) {
+    self.expected_size = (self.expected_size + new_size).saturating_sub(size);
+  }
 
+  /// The invariants that do not hold, if any.
+  fn violations(
+    &self,
+    population_stats: PopulationStatistics,
+    total: PopulationStatistics,
+    infected: u32,
+    recovered: u32,
+  ) -> Vec<String> {
+    let mut violations = Vec::new();
+    if population_stats.size() != self.expected_size {
+      violations.push(format!(
+        "the size is {}, but births and deaths leave {}",
+        population_stats.size(),
+        self.expected_size
+      ));
+    }
+    if population_stats != total {
+      violations.push(format!("the sum of the statistics by population is {total}"));
+    }
+    if population_stats.infected != infected {
+      violations.push(format!("the world has {infected} infected people"));
     }
+    if population_stats.recovered < recovered {
+      violations.push(format!("the world has {recovered} recovered people"));
+    }
+    violations
+  }
+}
 
+/// A system that checks the `PopulationStatistics` against the world.
+fn validate_statistics(
+  mut validation: ResMut<StatisticsValidation>,
+  population_stats: Res<PopulationStatistics>,
+  stats_by_population: Res<StatisticsByPopulation>,
+  people: Query<&InfectionStatus>,
+  policy: Res<StatisticsErrorPolicy>,
+  mut model_control: ResMut<ModelControl>,
+) {
+  let count = |status: InfectionStatus| people.iter().filter(|&&other| other == status).count() as u32;
+  let violations = validation.violations(
+    *population_stats,
+    stats_by_population.total(),
+    count(InfectionStatus::Infected),
+    count(InfectionStatus::Recovered),
+  );
+  if violations.is_empty() {
+    return;
   }
+  // Report a change of size once, rather than in every iteration from now on.
+  validation.expected_size = population_stats.size();
+  let error = IxaError::IxaError(format!(
+    "the population statistics {} do not agree with the world: {}",
+    *population_stats,
+    violations.join("; ")
+  ));
+  policy.handle(error, &mut model_control);
+}
 
-  // This is a reasonable place to detect if the simulation should stop.
-  if population_stats.recovered == population_stats.size() {
+impl Module for StatisticsValidation {
+  fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs>{
     #[cfg(feature = "print_messages")]
-    println!("Requesting ModelControl::Finished");
-    *model_control = ModelControl::Finished;
+    println!("Initialized module StatisticsValidation");
+
+    self.expected_size = world.get_resource::<PopulationStatistics>()
+                              .expect("The `PopulationStatistics` must be added before `StatisticsValidation`")
+                              .size();
+    world.insert_resource(self);
+
+    Some(
+      validate_statistics.after(track_population_changes)
+                         .after(count_mutated_statuses)
+                         .in_set(ExecutionPhase::Normal)
+    )
   }
+}
 
+/// The `PopulationStatistics` at each time they changed, oldest first.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct StatisticsHistory {
+  snapshots: VecDeque<(Time, PopulationStatistics)>,
+  /// If set, only this many of the most recent snapshots are kept.
+  max_len: Option<usize>,
 }
 
-impl Module for PopulationStatistics {
+impl StatisticsHistory {
+  /// Creates a `StatisticsHistory` that keeps every snapshot.
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Creates a `StatisticsHistory` that keeps only the `max_len` most recent snapshots.
+  pub fn with_max_len(max_len: usize) -> Self {
+    StatisticsHistory{
+      snapshots: VecDeque::with_capacity(max_len),
+      max_len: Some(max_len),
+    }
+  }
+
+  /// The snapshots in time order.
+  pub fn iter(&self) -> impl Iterator<Item = &(Time, PopulationStatistics)> {
+    self.snapshots.iter()
+  }
+
+  pub fn len(&self) -> usize {
+    self.snapshots.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.snapshots.is_empty()
+  }
+
+  /// The snapshots as an `EpidemicCurve` of the compartments `susceptible`, `infected`, and `recovered`.
+  pub fn epidemic_curve(&self) -> EpidemicCurve {
+    let mut curve = EpidemicCurve::new(["susceptible", "infected", "recovered"]);
+    for (time, stats) in &self.snapshots {
+      curve.push(*time, [stats.susceptible, stats.infected, stats.recovered].map(u64::from))
+           .expect("snapshots are in time order");
+    }
+    curve
+  }
+
+  /// Records `stats` at `time` unless they are the same as the last snapshot.
+  fn record(&mut self, time: Time, stats: PopulationStatistics) {
+    if self.snapshots.back().is_some_and(|(_, last)| *last == stats) {
+      return;
+    }
+    if self.max_len.is_some_and(|max_len| self.snapshots.len() >= max_len) {
+      self.snapshots.pop_front();
+    }
+    self.snapshots.push_back((time, stats));
+  }
+}
+
+/// A system that records a snapshot of the `PopulationStatistics` whenever they have changed.
+fn record_statistics_history(
+  mut history: ResMut<StatisticsHistory>,
+  population_stats: Res<PopulationStatistics>,
+  timeline: Res<Timeline>,
+) {
+  if population_stats.is_changed() {
+    history.record(timeline.now(), *population_stats);
+  }
+}
+
+impl Module for StatisticsHistory {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs>{
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module StatisticsHistory");
+
+    world.insert_resource(self);
+
+    Some(record_statistics_history.after(track_population_changes).in_set(ExecutionPhase::Last))
+  }
+}
+
+
+/// A sample of the `DepletionCurve`.
+#[derive(Copy, Clone, PartialEq, Debug, Serialize)]
+pub struct DepletionPoint {
+  pub time                : Time,
+  /// The susceptible people as a fraction of the whole population, after the infection.
+  pub fraction_susceptible: f64,
+  /// The number of infections so far, including this one.
+  pub cumulative_infected : u64,
+}
+
+/// The fraction of the population that is susceptible and the cumulative number of infections at each infection,
+/// oldest first.
+#[derive(Resource, Clone, Debug, Default)]
+pub struct DepletionCurve {
+  points: Vec<DepletionPoint>,
+  cumulative_infected: u64,
+}
+
+impl DepletionCurve {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The samples in time order.
+  pub fn iter(&self) -> impl Iterator<Item = &DepletionPoint> {
+    self.points.iter()
+  }
+
+  /// The most recent sample, which after a run is the final size of the epidemic.
+  pub fn last(&self) -> Option<&DepletionPoint> {
+    self.points.last()
+  }
+
+  pub fn len(&self) -> usize {
+    self.points.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.points.is_empty()
+  }
+}
+
+/// A system that samples the `DepletionCurve` at each infection. Each infection is its own event, so there is at most
+/// one per iteration of the event loop.
+fn record_depletion_curve(
+  mut curve: ResMut<DepletionCurve>,
+  mut infections: EventReader<InfectionOccurred>,
+  population_stats: Res<PopulationStatistics>,
+) {
+  for infection in infections.read() {
+    curve.cumulative_infected += 1;
+    let point = DepletionPoint{
+      time                : infection.time,
+      fraction_susceptible: population_stats.susceptible as f64 / population_stats.size() as f64,
+      cumulative_infected : curve.cumulative_infected,
+    };
+    curve.points.push(point);
+  }
+}
+
+impl Module for DepletionCurve {
   fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs>{
-    println!("Initialized module PopulationStatistics");
     #[cfg(feature = "print_messages")]
+    println!("Initialized module DepletionCurve");
 
     world.insert_resource(self);
 
-    // Also set up change monitors that keep these statistics up to date.
-    Some(track_population_changes.in_set(ExecutionPhase::Normal))
+    Some(record_depletion_curve.after(track_population_changes).in_set(ExecutionPhase::Normal))
   }
 }
@@ -0,0 +1,105 @@
+/*!
+
+The modules of this model that a scenario file can list, for running it without editing `main`:
+
+```text
+cargo run --example basic-infection -- examples/basic-infection/sir_scenario.json
+```
+
+`sir_scenario.json` is the SIR model of `main`. The registered modules and their configurations are:
+ - `population_statistics`: the `PopulationStatistics`, with the `population_size` of the scenario. No configuration.
+ - `transmission_manager`: the `TransmissionManager`, configured with `{ "foi": <rate> }`.
+ - `infection_manager`: the `InfectionManager`, configured with `{ "infection_duration": <duration> }` and optionally
+   `"recovery_outcome"`, `"BecomeRecovered"` (the default) or `"Despawn"`, and `"infection_fatality"`, e.g.
+   `{ "Constant": 0.01 }`, which is no deaths by default. Nothing in a scenario gives people an `Age`, so an
+   age-dependent `{ "ByAge": ... }` infection fatality is rejected.
+ - `demography`: the `Demography`, configured with `{ "birth_rate": <rate>, "death_rate": <rate> }`.
+ - `stop_when_all_recovered`: stops the model once nobody is infected or susceptible. No configuration.
+
+A configuration the module's constructor rejects, like a force of infection of 0, makes building the scenario fail with
+the constructor's error.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::Deserialize;
+
+use ecs_disease_models::{
+  errors::IxaError,
+  module::{Module, ModuleRegistry},
+  stop_condition::StopConditions,
+  units::{Duration, Rate}
+};
+
+use crate::{
+  demography::Demography,
+  infection_manager::{InfectionFatality, InfectionManager, RecoveryOutcome},
+  population_statistics::PopulationStatistics,
+  transmission_manager::TransmissionManager
+};
+
+/// Adds the stop condition of `main`.
+struct StopWhenAllRecovered;
+
+impl Module for StopWhenAllRecovered {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.resource_mut::<StopConditions>()
+         .push(|world| world.resource::<PopulationStatistics>().all_recovered());
+    None // No systems
+  }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct TransmissionConfig {
+  foi: Rate,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct InfectionConfig {
+  infection_duration: Duration,
+  #[serde(default)]
+  recovery_outcome: RecoveryOutcome,
+  #[serde(default)]
+  infection_fatality: InfectionFatality,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DemographyConfig {
+  birth_rate: Rate,
+  death_rate: Rate,
+}
+
+/// The modules a scenario file can list. See the module documentation.
+pub fn module_registry() -> ModuleRegistry {
+  let mut registry = ModuleRegistry::new();
+  registry.register("population_statistics", |_| Ok(Box::new(PopulationStatistics::from_constants())));
+  registry.register("transmission_manager", |config| {
+    let config: TransmissionConfig = serde_json::from_value(config.clone())?;
+    Ok(Box::new(TransmissionManager::new(config.foi)?))
+  });
+  registry.register("infection_manager", |config| {
+    let config: InfectionConfig = serde_json::from_value(config.clone())?;
+    if matches!(config.infection_fatality, InfectionFatality::ByAge(_)) {
+      return Err(IxaError::IxaError(
+        "an age-dependent infection fatality needs people with an `Age`, which scenarios do not give".to_string()
+      ));
+    }
+    Ok(Box::new(
+      InfectionManager::new(config.infection_duration)?
+          .with_recovery_outcome(config.recovery_outcome)
+          .with_infection_fatality(config.infection_fatality)?
+    ))
+  });
+  registry.register("demography", |config| {
+    let config: DemographyConfig = serde_json::from_value(config.clone())?;
+    Ok(Box::new(Demography::new(config.birth_rate, config.death_rate)))
+  });
+  registry.register("stop_when_all_recovered", |_| Ok(Box::new(StopWhenAllRecovered)));
+  registry
+}
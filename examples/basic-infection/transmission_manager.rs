@@ -2,67 +2,147 @@
 
 The _transmission manager_ is the business logic related to how new infections occur.
 
+In a metapopulation, each population has its own chain of infection attempts, and the probability that an attempt
+succeeds depends on the statistics of that population. People infected in a population are spawned with its
+`PopulationId`. Attempts stop once there is nobody left to infect or the next attempt would be after the `max_time` of
+the model's `SimConstants`. They also stop, at the next attempt, if the `TransmissionManager` resource is removed,
+which turns transmission off from then on.
+
+Whether an attempt succeeds is decided by the manager's `InfectionProbability`, a function of the population's
+statistics and the model's `SimConstants`. The default, `InfectionProbability::frequency_dependent`, is the fraction of
+the population that is susceptible. `InfectionProbability::density_dependent` and `InfectionProbability::saturating`
+are the other common choices, and `InfectionProbability::custom` takes any function. A probability outside `[0, 1]` is
+clamped, with a warning the first time.
+
+The probabilities that depend on the number of infectious people, like the density-dependent one, are given the
+infectiousness-weighted count of the `compartments` module. By default, as in SIR, the only infectious compartment is
+`InfectionStatus::Infected`, with weight 1, counted by the population's statistics. A model whose infections go through
+stages that transmit at different rates keeps the stages in a compartment component of its own and sets
+`TransmissionManager::with_infectiousness` with their weights. The count is then weighted over the `CompartmentCounts`
+of that component, which must be added too. Those are counts for the whole model, so this is for models of a single
+population.
+
+The denominator of the frequency-dependent probability, the population a susceptible person is a fraction of, is a
+modeling choice, set with `TransmissionManager::with_denominator` as a `DenominatorPolicy`:
+ - `DenominatorPolicy::TotalPopulation`, the default, is everyone counted in the population's statistics, `S + I + R`.
+   Contacts are spread over the whole population, recovered people included, so immunity dilutes transmission: a
+   contact with a recovered person is a wasted contact.
+ - `DenominatorPolicy::LivingPopulation` leaves out the people who no longer take part in contacts. People who die are
+   not counted anyway (see `Deceased`), but with `RecoveryOutcome::Despawn` the recovered people are removed from the
+   model, as when the infection ends in death or permanent removal, and they are left out too, making it `S + I`.
+   Otherwise it is the same as `TotalPopulation`.
+ - `DenominatorPolicy::SusceptiblePlusInfectious` is `S + I` regardless. Recovered people are assumed to withdraw from
+   the contacts that matter for transmission, as in effective-contact models, so immunity does not dilute
+   transmission and the epidemic runs faster.
+
+The other probabilities ignore the denominator.
+
+The first attempt in each population is at time 0 by default. For ensembles of runs that should not all start their
+epidemics in lockstep, `TransmissionManager::with_initial_attempt_time` offsets the first attempt by a random draw
+(one per population) from the model's RNG, so the offset is reproducible for a given seed.
+
+The force of infection must be positive and finite. `TransmissionManager::new` checks it and returns an error
+otherwise.
+
 */
 
+use std::any::type_name;
+use std::fmt::{Debug, Formatter};
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Arc
+};
+
 use bevy_ecs::prelude::*;
+use bevy_ecs::event::EventRegistry;
 use bevy_ecs::schedule::SystemConfigs;
 use ordered_float::OrderedFloat;
-use rand::Rng;
-use rand_distr::{Distribution, Exp};
+use rand::distr::{StandardUniform, Uniform};
+use rand_distr::Exp;
 
 use ecs_disease_models::{
+  compartments::{CompartmentCounts, Infectiousness},
+  distributions::Distributions,
+  errors::IxaError,
+  infection_times::LifeHistory,
+  metapopulation::PopulationId,
+  model::AbortReason,
   module::Module,
   random::RngResource,
+  sim_constants::SimConstants,
   timeline::Timeline,
-  timeline_event
+  timeline_event,
+  transmission_tree::TransmissionTree,
+  units::{Duration, Rate}
 };
 use ecs_disease_models::timeline::Time;
 use crate::{
-  population_statistics::PopulationStatistics,
+  infection_manager::{InfectionManager, RecoveryOutcome},
+  population_statistics::{PopulationStatistics, StatisticsByPopulation},
   InfectionStatus,
 };
 
 
 /// This free function serves as the system that is stored in the `Timeline`. It just retrieves the
-/// `TransmissionManager` from the world and calls its member function.
-fn attempt_infection(world: &mut World) {
+/// `TransmissionManager` from the world and attempts an infection in `population`.
+fn attempt_infection(world: &mut World, population: PopulationId) -> Result<(), IxaError> {
+  // Removing the manager, e.g. with `Model::remove_resource`, ends the chain of attempts.
+  if !world.contains_resource::<TransmissionManager>() {
+    return Ok(());
+  }
+  world.resource_scope(|world, this: Mut<TransmissionManager>| attempt_infection_with(world, &this, population))
+}
+
+/// Attempts an infection in `population` with the manager `this`, which is out of the world during the attempt. Fails
+/// if the next attempt cannot be scheduled.
+fn attempt_infection_with(
+  world: &mut World,
+  this: &TransmissionManager,
+  population: PopulationId
+) -> Result<(), IxaError> {
   // Too noisy
   // #[cfg(feature = "print_messages")]
   // print!("Attempting infection... ");
 
   // We scope the mutable barrows of `world` so the compiler doesn't complain. Hence, the predeclarations.
-  // Alternatively we could have used `world.resource_scope(..)`.
 
   let mut stats: PopulationStatistics;
   let uniform_sample: f64;
   let exponential_sample: f64;
   let next_attempt_time: OrderedFloat<f64>;
-  let this: TransmissionManager;
-
-  {
-    this = world.get_resource::<TransmissionManager>().unwrap().clone();
-  }
 
   { // scope of stats
-    stats = world.get_resource::<PopulationStatistics>().unwrap().clone();
+    stats = world.get_resource::<StatisticsByPopulation>().unwrap().get(population);
   }
 
-  let probability_of_infection: f64 = (stats.susceptible as f64) / (stats.size() as f64);
+  let recovered_are_living = world.get_resource::<InfectionManager>()
+                                  .is_none_or(|manager| manager.recovery_outcome() != RecoveryOutcome::Despawn);
+  let denominator = this.denominator.denominator(&stats, recovered_are_living);
+  let infectious = this.infectious.count(world, &stats)?;
+  let probability_of_infection: f64 =
+      this.infection_probability.probability(&stats, infectious, world.resource::<SimConstants>(), denominator);
+
+  let now = world.get_resource::<Timeline>().unwrap().now();
 
   { // scope of rng_resource
     let mut rng_resource = world.get_resource_mut::<RngResource>().unwrap();
     // Sample uniformly from [0.0, 1.0). This is used to determine if we span an infection.
-    uniform_sample =  rng_resource.rng.random::<f64>();
+    uniform_sample = rng_resource.sample_labeled(now, "infection_attempt", StandardUniform);
     // While we have the RNG in scope, we sample the exponential distribution for use below.
-    exponential_sample = Exp::new(this.foi).unwrap().sample(&mut rng_resource.rng);
+    exponential_sample = rng_resource.sample_labeled(now, "next_attempt", this.next_attempt);
   }
 
-  if uniform_sample < probability_of_infection {
-    let entity = world.spawn(InfectionStatus::Infected);
+  // Nobody can be infected once nobody is susceptible, whatever the probability says.
+  if uniform_sample < probability_of_infection && stats.susceptible > 0 {
+    let entity = world.spawn((InfectionStatus::Infected, population, LifeHistory::infected(now))).id();
+    // The force of infection is constant rather than driven by the people currently infected, so every infection in
+    // this model comes from outside the population and is a root of the transmission tree.
+    TransmissionTree::record_infection(world, None, entity, now);
+    world.send_event(InfectionOccurred{ infectee: entity, infector: None, time: now });
     #[cfg(feature = "print_messages")]
-    println!("Infection of entity {} succeeded ({:.6} < {:.6})", entity.id(), uniform_sample, probability_of_infection);
+    println!("Infection of entity {} succeeded ({:.6} < {:.6})", entity, uniform_sample, probability_of_infection);
     // We use this below instead of pulling out the resource again.
-    stats.update_stats(InfectionStatus::Infected);
+    stats.update_stats(InfectionStatus::Infected)?;
   } else {
     // Too noisy
     // #[cfg(feature = "print_messages")]
@@ -79,41 +159,318 @@ fn attempt_infection(world: &mut World) {
       // #[cfg(feature = "print_messages")]
       // println!("Scheduling next infection attempt at {}", next_attempt_time);
 
-      let event = timeline_event::Event {
-        time: next_attempt_time,
-        command: Box::new(attempt_infection),
-      };
-      timeline.push(event);
+      let event = timeline_event::Event::builder(next_attempt_time)
+          .fallible_command(move |world| attempt_infection(world, population))
+          .label("infection_attempt")
+          .build();
+      timeline.push(event)?;
+    }
+  }
+
+  Ok(())
+}
+
+/// Sent whenever a person is infected, so that other systems can react to infections without the transmission manager
+/// knowing about them.
+#[derive(Event, Copy, Clone, Debug)]
+pub struct InfectionOccurred {
+  pub infectee: Entity,
+  /// `None` if the infection came from outside the population, which in this model is always.
+  pub infector: Option<Entity>,
+  pub time    : Time,
+}
+
+/// When the first infection attempt in each population happens, as an offset from the start of the model.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum InitialAttemptTime {
+  /// Exactly this long after the start. The default is no offset.
+  Fixed(Duration),
+  /// Uniformly distributed between no offset and `max`, inclusive.
+  Uniform{ max: Duration },
+  /// Exponentially distributed with the given mean.
+  Exponential{ mean: Duration },
+}
+
+impl Default for InitialAttemptTime {
+  fn default() -> Self {
+    InitialAttemptTime::Fixed(Duration(0.0))
+  }
+}
+
+impl InitialAttemptTime {
+  /// Draws an offset. `Fixed` draws nothing from `rng`, so it does not shift any later draws.
+  fn sample(self, rng: &mut RngResource, now: Time) -> Duration {
+    match self {
+      InitialAttemptTime::Fixed(offset) => offset,
+      InitialAttemptTime::Uniform{ max } => {
+        let uniform = Uniform::new_inclusive(0.0, max.0).expect("Invalid maximum initial attempt offset");
+        Duration(rng.sample_labeled(now, "initial_attempt", uniform))
+      }
+      InitialAttemptTime::Exponential{ mean } => {
+        let exp = Rate::from_mean(mean).exp().expect("Invalid mean initial attempt offset");
+        Duration(rng.sample_labeled(now, "initial_attempt", exp))
+      }
+    }
+  }
+}
+
+/// Which people the frequency-dependent infection probability divides the susceptible people by. See the module
+/// documentation.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, Hash)]
+pub enum DenominatorPolicy {
+  /// `S + I + R`.
+  #[default]
+  TotalPopulation,
+  /// `S + I + R`, or `S + I` if recovered people are despawned.
+  LivingPopulation,
+  /// `S + I`.
+  SusceptiblePlusInfectious,
+}
+
+impl DenominatorPolicy {
+  /// The denominator for `stats`. `recovered_are_living` is false if recovered people are despawned.
+  pub fn denominator(self, stats: &PopulationStatistics, recovered_are_living: bool) -> u32 {
+    match self {
+      DenominatorPolicy::TotalPopulation => stats.size(),
+      DenominatorPolicy::LivingPopulation if recovered_are_living => stats.size(),
+      DenominatorPolicy::LivingPopulation | DenominatorPolicy::SusceptiblePlusInfectious => {
+        stats.susceptible + stats.infected
+      }
     }
   }
+}
+
+/// A function from a population's statistics, its infectiousness-weighted number of infectious people, and the
+/// `SimConstants` to the probability that an infection attempt in the population succeeds.
+pub type InfectionProbabilityFn = dyn Fn(&PopulationStatistics, f64, &SimConstants) -> f64 + Send + Sync;
 
+/// The function of an `InfectionProbability`.
+#[derive(Clone)]
+enum ProbabilityFunction {
+  /// `S` over the denominator of the `DenominatorPolicy`.
+  FrequencyDependent,
+  Custom(Arc<InfectionProbabilityFn>),
 }
 
-#[derive(Resource, Copy, Clone, Debug)]
+/// How the probability that an infection attempt succeeds depends on the population. See the module documentation.
+#[derive(Clone)]
+pub struct InfectionProbability {
+  name    : String,
+  function: ProbabilityFunction,
+  /// Whether a probability outside `[0, 1]` has been warned about.
+  warned  : Arc<AtomicBool>,
+}
+
+impl InfectionProbability {
+  /// The probability is the fraction of the population that is susceptible, `S / N`, where `N` is given by the
+  /// manager's `DenominatorPolicy`. This is the default.
+  pub fn frequency_dependent() -> Self {
+    InfectionProbability{
+      name: "frequency_dependent".to_string(),
+      function: ProbabilityFunction::FrequencyDependent,
+      warned: Arc::default(),
+    }
+  }
+
+  /// Mass action: contacts between susceptible and infectious people grow with the density of both, so the
+  /// probability is `S * I / N0`, where `I` is the weighted number of infectious people and `N0` is the reference
+  /// population size of the `SimConstants`. It is 0 while nobody is infectious, so an epidemic needs people infected by
+  /// other means to start, and exceeds 1, and is clamped, once `S * I` exceeds `N0`.
+  pub fn density_dependent() -> Self {
+    Self::custom("density_dependent", |stats, infectious, constants| {
+      stats.susceptible as f64 * infectious / constants.population_size as f64
+    })
+  }
+
+  /// A Holling type II functional response, `S / (half_saturation + S)`, which grows linearly with the number of
+  /// susceptible people while there are few and saturates at 1 when there are many.
+  pub fn saturating(half_saturation: f64) -> Self {
+    Self::custom(&format!("saturating({half_saturation})"), move |stats, _, _| {
+      stats.susceptible as f64 / (half_saturation + stats.susceptible as f64)
+    })
+  }
+
+  /// Any function, named `name` for debug output.
+  pub fn custom(
+    name: &str,
+    function: impl Fn(&PopulationStatistics, f64, &SimConstants) -> f64 + Send + Sync + 'static
+  ) -> Self {
+    InfectionProbability{
+      name: name.to_string(),
+      function: ProbabilityFunction::Custom(Arc::new(function)),
+      warned: Arc::default(),
+    }
+  }
+
+  /// The probability for `stats` with `infectious` weighted infectious people, clamped to `[0, 1]`. NaN is taken to
+  /// be 0. `denominator` is the denominator of the frequency-dependent probability, which is 0 if the denominator is.
+  pub fn probability(
+    &self,
+    stats: &PopulationStatistics,
+    infectious: f64,
+    constants: &SimConstants,
+    denominator: u32
+  ) -> f64 {
+    let probability = match &self.function {
+      ProbabilityFunction::FrequencyDependent if denominator == 0 => 0.0,
+      ProbabilityFunction::FrequencyDependent => stats.susceptible as f64 / denominator as f64,
+      ProbabilityFunction::Custom(function) => function(stats, infectious, constants),
+    };
+    if (0.0..=1.0).contains(&probability) {
+      return probability;
+    }
+    if !self.warned.swap(true, Ordering::Relaxed) {
+      #[cfg(feature = "print_messages")]
+      println!(
+        "Warning: the infection probability {} is {probability} for {stats}, which is clamped to [0, 1]. Later \
+         probabilities outside [0, 1] are clamped without a warning.",
+        self.name
+      );
+    }
+    if probability.is_nan() { 0.0 } else { probability.clamp(0.0, 1.0) }
+  }
+}
+
+impl Default for InfectionProbability {
+  fn default() -> Self {
+    Self::frequency_dependent()
+  }
+}
+
+impl Debug for InfectionProbability {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "InfectionProbability({})", self.name)
+  }
+}
+
+/// A function from the world to the weighted number of infectious people in it, which fails if it cannot be counted.
+type WeightedInfectiousFn = dyn Fn(&World) -> Result<f64, IxaError> + Send + Sync;
+
+/// How the `TransmissionManager` counts the infectious people of a population. See the module documentation.
+#[derive(Clone)]
+enum InfectiousCount {
+  /// Weighted over the `InfectionStatus` counts of the population's statistics.
+  Statistics(Infectiousness<InfectionStatus>),
+  /// Weighted over the `CompartmentCounts` of the compartment component named here, for the whole model.
+  Compartments(&'static str, Arc<WeightedInfectiousFn>),
+}
+
+impl InfectiousCount {
+  /// The weighted number of infectious people in the population with the statistics `stats`.
+  fn count(&self, world: &World, stats: &PopulationStatistics) -> Result<f64, IxaError> {
+    match self {
+      InfectiousCount::Statistics(infectiousness) => {
+        Ok(infectiousness.weighted_infectious(&stats.compartment_counts()))
+      }
+      InfectiousCount::Compartments(_, count) => count(world),
+    }
+  }
+}
+
+impl Default for InfectiousCount {
+  /// The SIR model's single infectious compartment, `InfectionStatus::Infected`, with weight 1.
+  fn default() -> Self {
+    InfectiousCount::Statistics(Infectiousness::single(InfectionStatus::Infected))
+  }
+}
+
+impl Debug for InfectiousCount {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      InfectiousCount::Statistics(infectiousness) => write!(f, "InfectiousCount::Statistics({infectiousness:?})"),
+      InfectiousCount::Compartments(component, _) => write!(f, "InfectiousCount::Compartments({component})"),
+    }
+  }
+}
+
+#[derive(Resource, Clone, Debug)]
 pub struct TransmissionManager{
+  /// Read from the `SimConstants` when the module is initialized.
   max_time: Time,
-  foi: f64
+  /// The distribution of the time to the next infection attempt, constructed from the force of infection once.
+  next_attempt: Exp<f64>,
+  initial_attempt_time: InitialAttemptTime,
+  infection_probability: InfectionProbability,
+  infectious: InfectiousCount,
+  denominator: DenominatorPolicy,
 }
 
 impl TransmissionManager {
-  pub fn new(max_time: Time, foi: f64) -> Self {
-    Self {max_time, foi}
+  /// A manager with the force of infection `foi`. Fails if `foi` is not a positive, finite rate.
+  pub fn new(foi: Rate) -> Result<Self, IxaError> {
+    Ok(Self {
+      max_time: Time::default(),
+      next_attempt: Distributions::validated_exp(foi, "the force of infection")?,
+      initial_attempt_time: InitialAttemptTime::default(),
+      infection_probability: InfectionProbability::default(),
+      infectious: InfectiousCount::default(),
+      denominator: DenominatorPolicy::default(),
+    })
+  }
+
+  /// Sets the denominator of the frequency-dependent infection probability. See the module documentation.
+  pub fn with_denominator(mut self, denominator: DenominatorPolicy) -> TransmissionManager {
+    self.denominator = denominator;
+    self
+  }
+
+  /// Sets how the probability that an infection attempt succeeds depends on the population.
+  pub fn with_infection_probability(mut self, infection_probability: InfectionProbability) -> TransmissionManager {
+    self.infection_probability = infection_probability;
+    self
+  }
+
+  /// Weights the infectious people with `infectiousness` over the `CompartmentCounts` of `C`, which must be added too,
+  /// instead of counting the infected people of the population's statistics. See the module documentation.
+  pub fn with_infectiousness<C>(mut self, infectiousness: Infectiousness<C>) -> TransmissionManager
+    where C: Component + Copy + Ord
+  {
+    let count = move |world: &World| match world.get_resource::<CompartmentCounts<C>>() {
+      Some(counts) => Ok(infectiousness.weighted_infectious(counts)),
+      None => Err(IxaError::IxaError(format!(
+        "the infectious people are weighted over the `CompartmentCounts` of {}, but there are none",
+        type_name::<C>()
+      ))),
+    };
+    self.infectious = InfectiousCount::Compartments(type_name::<C>(), Arc::new(count));
+    self
+  }
+
+  /// Sets when the first infection attempt in each population happens.
+  pub fn with_initial_attempt_time(mut self, initial_attempt_time: InitialAttemptTime) -> TransmissionManager {
+    self.initial_attempt_time = initial_attempt_time;
+    self
   }
 }
 
 impl Module for TransmissionManager {
-  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs>{
+  fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs>{
+    self.max_time = world.resource::<SimConstants>().max_time;
+    let initial_attempt_time = self.initial_attempt_time;
     // Insert a new instance into the world
     world.insert_resource(self);
+    world.init_resource::<TransmissionTree>();
+    EventRegistry::register_event::<InfectionOccurred>(world);
 
-    // Schedule the first infection attempt
-    let mut timeline = world.get_resource_mut::<Timeline>().unwrap();
-    timeline.push(
-      timeline_event::Event {
-        time: 0.0.into(),
-        command: Box::new(attempt_infection)
+    // Schedule the first infection attempt in each population
+    let populations: Vec<PopulationId> = match world.get_resource::<StatisticsByPopulation>() {
+      Some(stats_by_population) => stats_by_population.iter().map(|(population, _)| population).collect(),
+      None => vec![PopulationId::default()],
+    };
+    let now = world.resource::<Timeline>().now();
+    for population in populations {
+      let offset = initial_attempt_time.sample(&mut world.resource_mut::<RngResource>(), now);
+      let scheduled = world.resource_mut::<Timeline>().push(
+        timeline_event::Event::builder(now + offset)
+            .fallible_command(move |world| attempt_infection(world, population))
+            .label("infection_attempt")
+            .build()
+      );
+      if let Err(error) = scheduled {
+        AbortReason::abort(world, format!("the first infection attempt could not be scheduled: {error}"));
+        break;
       }
-    );
+    }
 
     #[cfg(feature = "print_messages")]
     println!("Initialized module TransmissionManager");
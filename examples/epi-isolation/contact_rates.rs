@@ -0,0 +1,87 @@
+/*!
+
+The `ContactRates` module gives every person a `ContactRate` drawn from a distribution, to model heterogeneous activity.
+Contact rates are relative, so every distribution is scaled to have mean 1, which keeps `r_0` the mean number of
+infection attempts across the population.
+
+The power law (Pareto) distribution with shape `α` has a heavier tail the closer `α` is to 1: most people have a rate
+somewhat below 1, and a few have rates many times the mean. The shape must be greater than 1 for the mean to exist.
+
+It must be added after the `PopulationLoader`, since it can only assign rates to people who exist.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use rand_distr::Pareto;
+
+use ecs_disease_models::{
+  errors::IxaError,
+  module::Module,
+  random::RngResource,
+  timeline::Timeline
+};
+
+use crate::person::{ContactRate, InfectionStatus};
+
+/// The distribution the contact rates are drawn from.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub enum ContactRateDistribution {
+  /// Everyone has rate 1.
+  Homogeneous,
+  /// A power law with the given shape, scaled to mean 1.
+  Pareto{ shape: f64 },
+}
+
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct ContactRates {
+  distribution: ContactRateDistribution,
+}
+
+impl ContactRates {
+  pub fn new(distribution: ContactRateDistribution) -> Result<Self, IxaError> {
+    if let ContactRateDistribution::Pareto{ shape } = distribution
+        && (shape.is_nan() || shape <= 1.0)
+    {
+      return Err(IxaError::IxaError(format!("the contact rate power law shape must exceed 1, but it is {shape}")));
+    }
+    Ok(ContactRates{ distribution })
+  }
+
+  /// Contact rates from a power law with the given shape, scaled to mean 1.
+  pub fn pareto(shape: f64) -> Result<Self, IxaError> {
+    Self::new(ContactRateDistribution::Pareto{ shape })
+  }
+}
+
+impl Module for ContactRates {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    let mut people: Vec<Entity> = world.query_filtered::<Entity, With<InfectionStatus>>().iter(world).collect();
+    // Query order depends on the archetype layout, so sort for reproducibility.
+    people.sort();
+
+    match self.distribution {
+      ContactRateDistribution::Homogeneous => {
+        for person in people {
+          world.entity_mut(person).insert(ContactRate::default());
+        }
+      }
+      ContactRateDistribution::Pareto{ shape } => {
+        // The mean of `Pareto(scale, shape)` is `scale * shape / (shape - 1)`.
+        let pareto = Pareto::new((shape - 1.0) / shape, shape).unwrap();
+        let now = world.resource::<Timeline>().now();
+        for person in people {
+          let rate = world.resource_mut::<RngResource>().sample_labeled(now, "contact_rate", pareto);
+          world.entity_mut(person).insert(ContactRate(rate));
+        }
+      }
+    }
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module ContactRates");
+
+    None // No systems
+  }
+}
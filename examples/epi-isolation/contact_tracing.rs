@@ -0,0 +1,175 @@
+/*!
+
+Contact tracing. Every newly infected person is detected with the `detection_probability`, and a detected person's
+contacts are traced `tracing_delay` after their infection. Tracing isolates the detected person and everyone they have
+infected by then, as recorded in the `TransmissionTree`, for the `quarantine_duration`. People are isolated with an
+`Isolated` component, and an isolated person makes no infection attempts: the attempts that fall in the quarantine are
+dropped, though the person still recovers when they would have. When the quarantine ends, an event removes the
+component. Isolating someone who is already isolated extends their quarantine if it would end later.
+
+The population mixes at random, so there is no contact network to trace. The people a case has infected are the
+contacts that matter for onward transmission, so those are the ones traced; contacts who escaped infection are not.
+Traced people are not themselves detected unless their own detection draw succeeds, so tracing does not cascade.
+
+The parameters are the `contact_tracing` section of the `Parameters`, and the module is only added if there is one.
+Invalid parameters abort the model when the module is initialized. Contact tracing can be turned off during a run by
+removing the `ContactTracing` resource: cases are no longer detected, scheduled tracing does nothing, and the
+quarantines already under way run their course.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use rand::distr::StandardUniform;
+use serde::{Deserialize, Serialize};
+
+use ecs_disease_models::{
+  errors::IxaError,
+  model::{AbortReason, ExecutionPhase},
+  module::Module,
+  random::RngResource,
+  status_transition::StatusTransitions,
+  timeline::{Time, Timeline},
+  timeline_event::Event,
+  transmission_tree::TransmissionTree,
+  units::Duration
+};
+
+use crate::person::InfectionStatus;
+
+/// A person in quarantine until `until`, who makes no infection attempts.
+#[derive(Component, Copy, Clone, PartialEq, Debug)]
+pub struct Isolated {
+  pub until: Time,
+}
+
+/// The parameters of contact tracing. See the module documentation.
+#[derive(Resource, Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ContactTracing {
+  /// The probability that an infected person is detected, in `[0, 1]`.
+  pub detection_probability: f64,
+  /// The time from the infection of a detected person to the tracing of their contacts.
+  pub tracing_delay: Duration,
+  /// How long traced people are isolated.
+  pub quarantine_duration: Duration,
+}
+
+impl ContactTracing {
+  pub fn validate(&self) -> Result<(), IxaError> {
+    if !(0.0..=1.0).contains(&self.detection_probability) {
+      return Err(IxaError::IxaError(format!(
+        "the detection probability must be between 0 and 1, but it is {}",
+        self.detection_probability
+      )));
+    }
+    if !(self.tracing_delay.0 >= 0.0 && self.tracing_delay.0.is_finite()) {
+      return Err(IxaError::IxaError(format!(
+        "the tracing delay must be a non-negative, finite duration, but it is {}",
+        self.tracing_delay.0
+      )));
+    }
+    if !(self.quarantine_duration.0 >= 0.0 && self.quarantine_duration.0.is_finite()) {
+      return Err(IxaError::IxaError(format!(
+        "the quarantine duration must be a non-negative, finite duration, but it is {}",
+        self.quarantine_duration.0
+      )));
+    }
+    Ok(())
+  }
+}
+
+/// A system that schedules the tracing of every newly infected person who is detected.
+fn detect_cases(
+  contact_tracing: Res<ContactTracing>,
+  mut timeline: ResMut<Timeline>,
+  mut rng: ResMut<RngResource>,
+  mut transitions: StatusTransitions<InfectionStatus>,
+  mut commands: Commands,
+) {
+  let now = timeline.now();
+  for transition in transitions.iter() {
+    if transition.to != InfectionStatus::Infected {
+      continue;
+    }
+    let detection_draw: f64 = rng.sample_labeled(now, "detection", StandardUniform);
+    if detection_draw >= contact_tracing.detection_probability {
+      continue;
+    }
+    let case = transition.entity;
+    let event = Event::builder(now + contact_tracing.tracing_delay)
+        .fallible_command(move |world| trace_contacts(world, case))
+        .entity(case)
+        .label("contact_tracing")
+        .build();
+    if let Err(error) = timeline.push(event) {
+      let reason = format!("the contact tracing of entity {case} could not be scheduled: {error}");
+      commands.queue(move |world: &mut World| AbortReason::abort(world, reason));
+      return;
+    }
+  }
+}
+
+/// Isolates `case` and the people they have infected so far. Fails if the end of a quarantine cannot be scheduled.
+fn trace_contacts(world: &mut World, case: Entity) -> Result<(), IxaError> {
+  let Some(&contact_tracing) = world.get_resource::<ContactTracing>() else {
+    return Ok(());
+  };
+  let mut traced: Vec<Entity> = world.resource::<TransmissionTree>()
+                                     .edges()
+                                     .filter(|(infector, ..)| *infector == Some(case))
+                                     .map(|(_, infectee, _)| infectee)
+                                     .collect();
+  traced.push(case);
+
+  let now = world.resource::<Timeline>().now();
+  let until = now + contact_tracing.quarantine_duration;
+  for person in traced {
+    let Ok(mut entity) = world.get_entity_mut(person) else {
+      continue;
+    };
+    if entity.get::<Isolated>().is_some_and(|isolated| isolated.until >= until) {
+      continue;
+    }
+    entity.insert(Isolated{ until });
+    let event = Event::builder(until)
+        .command(move |world| end_quarantine(world, person))
+        .entity(person)
+        .label("end_quarantine")
+        .build();
+    world.resource_mut::<Timeline>().push(event)?;
+
+    #[cfg(feature = "print_messages")]
+    println!("Entity {} isolated until time {:.4}", person, until);
+  }
+  Ok(())
+}
+
+/// Removes `person`'s `Isolated` component, unless their quarantine has been extended.
+fn end_quarantine(world: &mut World, person: Entity) {
+  let now = world.resource::<Timeline>().now();
+  if let Ok(mut entity) = world.get_entity_mut(person)
+      && entity.get::<Isolated>().is_some_and(|isolated| isolated.until <= now)
+  {
+    entity.remove::<Isolated>();
+  }
+}
+
+impl Module for ContactTracing {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    if let Err(error) = self.validate() {
+      AbortReason::abort(world, format!("the contact tracing parameters are invalid: {error}"));
+      return None;
+    }
+    world.insert_resource(self);
+    world.init_resource::<TransmissionTree>();
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module ContactTracing");
+
+    // In the `Last` phase, so that its random draws do not compete with those of the transmission manager's system.
+    Some(detect_cases.run_if(resource_exists::<ContactTracing>).in_set(ExecutionPhase::Last))
+  }
+}
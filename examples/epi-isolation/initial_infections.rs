@@ -0,0 +1,71 @@
+/*!
+
+The `InitialInfections` module seeds the epidemic. At time zero, it infects the fraction of the population given by
+`Parameters::initial_infected_fraction`, rounded to the nearest whole person, choosing people uniformly at random from
+everyone who is susceptible, with draws labeled `initial_infection` in the `RngTrace`. Seeded infections come from
+outside the population, so they are the roots of the `TransmissionTree`.
+
+It must be added after the `PopulationLoader`, since it can only infect people who exist. The `TransmissionManager`
+schedules the infection attempts and recovery of the people it infects, as for anyone else who becomes infected.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use ecs_disease_models::{
+  infection_times::record_infection,
+  module::Module,
+  random::RngResource,
+  timeline::Timeline,
+  transmission_tree::TransmissionTree
+};
+
+use crate::{
+  parameters::Parameters,
+  person::InfectionStatus
+};
+
+#[derive(Copy, Clone, Default, Debug)]
+pub struct InitialInfections;
+
+impl InitialInfections {
+  /// Infects `fraction` of the susceptible people, returning how many were infected.
+  fn seed(world: &mut World, fraction: f64) -> usize {
+    let mut susceptible: Vec<Entity> = world.query::<(Entity, &InfectionStatus)>()
+                                            .iter(world)
+                                            .filter(|(_, status)| **status == InfectionStatus::Susceptible)
+                                            .map(|(person, _)| person)
+                                            .collect();
+    // Query order depends on the archetype layout, so sort for reproducibility.
+    susceptible.sort();
+
+    let count = (fraction * susceptible.len() as f64).round() as usize;
+    let now = world.resource::<Timeline>().now();
+    let chosen = world.resource_mut::<RngResource>().sample_indices(now, "initial_infection", susceptible.len(), count);
+    for index in chosen {
+      let person = susceptible[index];
+      *world.get_mut::<InfectionStatus>(person).unwrap() = InfectionStatus::Infected;
+      record_infection(world, person, now);
+      TransmissionTree::record_infection(world, None, person, now);
+    }
+    count
+  }
+}
+
+impl Module for InitialInfections {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.init_resource::<TransmissionTree>();
+    let fraction = world.get_resource::<Parameters>()
+                        .expect("The `Parameters` must be added before `InitialInfections`")
+                        .initial_infected_fraction;
+    let _count = Self::seed(world, fraction);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module InitialInfections, infecting {_count} people");
+
+    None // No systems
+  }
+}
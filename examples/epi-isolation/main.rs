@@ -1,55 +1,704 @@
+mod contact_rates;
+mod contact_tracing;
+mod initial_infections;
 mod parameters;
 mod periodic_reporter;
 mod population_loader;
 mod person;
+mod renewal_transmission;
+mod susceptibility;
+mod transmission_manager;
 
-use std::{
-  fmt::Display,
-  path::PathBuf
-};
-use bevy_ecs::{
-  component::Component,
-  prelude::IntoSystemConfigs
-};
-use serde::{Deserialize, Serialize};
+use std::path::Path;
 
 use ecs_disease_models::{
-  model::{ExecutionPhase, Model},
-  report::ReporterConfiguration
+  aggregate_incidence::AggregateIncidenceReporter,
+  cohort_report::CohortReporter,
+  model::Model,
+  sim_constants::SimConstants
 };
 
 use crate::{
-  parameters::Parameters,
-  periodic_reporter::PeriodicReporter
+  contact_rates::{ContactRateDistribution, ContactRates},
+  initial_infections::InitialInfections,
+  parameters::{Parameters, TransmissionModel},
+  person::{CensusTract, InfectionStatus},
+  periodic_reporter::{PeriodicReportSchedule, PeriodicReporter},
+  population_loader::PopulationLoader,
+  renewal_transmission::RenewalTransmission,
+  susceptibility::SusceptibilityProfile,
+  transmission_manager::TransmissionManager
 };
 
 const PARAMETERS_PATH: &str = "./examples/epi-isolation/input/input.json";
-const OUTPUT_DIRECTORY: &str = "./examples/epi-isolation/output";
-const OUTPUT_FILE_PREFIX: &str = "epi-isolation";
 const OUTPUT_FILE_NAME: &str = "incidence";
+const COHORT_FILE_NAME: &str = "census_tract_counts";
+/// The number of cases after which a branching-process model finishes, since it has no population to run out of.
+const BRANCHING_CASE_CAP: usize = 100_000;
+const EPIDEMIC_CURVE_FILE_NAME: &str = "new_cases";
 
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-  let parameters = Parameters::from_file(&PathBuf::from(PARAMETERS_PATH))?;
+  let parameters = Parameters::from_file(Path::new(PARAMETERS_PATH), None)?;
+  parameters.validate_with_population_file()?;
 
   // `Model`'s constructor automatically adds the `Random` and `Timeline` modules.
-  let mut model = Model::with_random_seed(parameters.seed);
+  let mut model = Model::with_constants(
+    SimConstants::builder().max_time(parameters.max_time).seed(parameters.seed).build()
+  );
+  let (report_period, report_offset) = (parameters.report_period, parameters.report_offset);
+  let report_config = parameters.reporter_configuration()?;
+  let (contact_tracing, transmission) = (parameters.contact_tracing, parameters.transmission);
+  let bad_population_rows = parameters.bad_population_rows;
+
+  let susceptibility_profile = match &parameters.susceptibility_profile {
+    Some(path) => SusceptibilityProfile::from_file(path)?,
+    None => SusceptibilityProfile::default(),
+  };
+  let contact_rates = match parameters.contact_rate_shape {
+    Some(shape) => ContactRates::pareto(shape)?,
+    None => ContactRates::new(ContactRateDistribution::Homogeneous)?,
+  };
 
   model.add_module(parameters);
+  // The loader reads the population file named in the `Parameters` added above. Checking that everyone was loaded with
+  // all of their components is cheap next to reading the file, so we do it in release builds too.
+  model.add_module(PopulationLoader::new().with_validation(true).with_bad_row_policy(bad_population_rows));
+  model.add_module(contact_rates);
+  model.add_module(InitialInfections);
+  model.add_module(susceptibility_profile);
+  match transmission {
+    TransmissionModel::Contacts => model.add_module(TransmissionManager),
+    TransmissionModel::Renewal => model.add_module(RenewalTransmission::new()),
+    TransmissionModel::Branching => model.add_module(RenewalTransmission::new().spawning(BRANCHING_CASE_CAP)),
+  }
+  if let Some(contact_tracing) = contact_tracing {
+    model.add_module(contact_tracing);
+  }
 
-  // A more thought-through API would make this less awkward.
-  let report_config = ReporterConfiguration::new(
-    OUTPUT_FILE_PREFIX.to_string(),
-    PathBuf::from(OUTPUT_DIRECTORY),
-    true
-  );
   model.add_module(report_config);
 
-  model.add_module(PeriodicReporter::new("incidence".to_string()));
-  // ToDo: Having to add this separately is an awkward pattern.
-  model.add_systems(periodic_reporter::track_status_changes.in_set(ExecutionPhase::Normal));
+  model.add_module(PeriodicReporter::new(OUTPUT_FILE_NAME.to_string()));
+  model.add_module(
+    PeriodicReportSchedule::new(report_period.into(), model.constants().max_time).with_offset(report_offset.into())
+  );
+  // Counts of people in each infection status, by census tract.
+  model.add_module(CohortReporter::<InfectionStatus, CensusTract>::new(
+    COHORT_FILE_NAME.to_string(),
+    |world, person| *world.get::<CensusTract>(person).expect("Person has no CensusTract")
+  ));
+  // New infections in each `report_period`.
+  model.add_module(AggregateIncidenceReporter::new(
+    EPIDEMIC_CURVE_FILE_NAME.to_string(),
+    InfectionStatus::Infected,
+    report_period.into()
+  )?);
 
+  #[cfg(feature = "indicatif")]
+  model.run_with_bar(model.constants().max_time);
+  #[cfg(not(feature = "indicatif"))]
   model.run();
 
   Ok(())
 }
+
+
+#[cfg(test)]
+mod tests {
+  use std::{env, fs, path::PathBuf};
+  use ecs_disease_models::{
+    errors::IxaError,
+    model::{ModelControl, ModelWarnings},
+    report::{FilenamePolicy, ReportSettings, ReporterConfiguration},
+    timeline::{Time, Timeline},
+    transmission_tree::TransmissionTree,
+    units::Duration
+  };
+  use bevy_ecs::world::World;
+  use crate::{
+    contact_tracing::{ContactTracing, Isolated},
+    population_loader::BadRowPolicy,
+    person::{Age, Alive, ContactRate, HomeId, InfectionStatus, PersonComponents},
+    susceptibility::SusceptibilityRule
+  };
+  use super::*;
+
+  const PARAMETERS: &str = r#"{
+    "max_time": 200.0,
+    "seed": 123,
+    "r_0": 5.0,
+    "infection_duration": 5.0,
+    "generation_interval": 5.0,
+    "report_period": 1.0,
+    "synth_population_file": "unused.csv"
+  }"#;
+
+  #[test]
+  fn test_zero_susceptibility_is_never_infected() {
+    let mut model = Model::with_random_seed(123);
+    model.add_module(Parameters::from_json(PARAMETERS, None).unwrap());
+    model.add_module(SusceptibilityProfile::new(vec![
+      SusceptibilityRule{ min_age: None, max_age: Some(17), census_tract: None, multiplier: 0.0 }
+    ]).unwrap());
+    model.add_module(TransmissionManager);
+
+    let world = model.world_mut();
+    for age in 0..200 {
+      world.spawn((Age(age % 80), CensusTract(1), InfectionStatus::Susceptible));
+    }
+    world.spawn((Age(40), CensusTract(1), InfectionStatus::Infected));
+    model.run();
+
+    let world = model.world_mut();
+    let people: Vec<(u8, InfectionStatus)> = world.query::<(&Age, &InfectionStatus)>()
+                                                  .iter(world)
+                                                  .map(|(age, status)| (age.0, *status))
+                                                  .collect();
+    assert!(people.iter().any(|&(age, status)| age > 17 && status == InfectionStatus::Recovered));
+    assert!(people.iter().filter(|&&(age, _)| age <= 17).all(|&(_, status)| status == InfectionStatus::Susceptible));
+  }
+
+  #[test]
+  fn test_people_missing_components_are_reported() {
+    let mut world = World::default();
+    let home_id = HomeId::parse(b"360610001001").unwrap();
+    let complete = world.spawn((Age(30), home_id, home_id.census_tract(), Alive::default(), InfectionStatus::default()))
+                        .id();
+    let incomplete = world.spawn((Age(40), CensusTract(1), Alive::default(), InfectionStatus::default())).id();
+    assert!(PopulationLoader::validate_components::<PersonComponents>(&mut world, &[complete]).is_ok());
+
+    let error = PopulationLoader::validate_components::<PersonComponents>(&mut world, &[complete, incomplete])
+        .unwrap_err()
+        .to_string();
+    assert!(error.contains(&format!("{incomplete} (missing HomeId)")), "{error}");
+    assert!(!error.contains(&complete.to_string()), "{error}");
+  }
+
+  #[test]
+  fn test_home_ids_keep_leading_zeros() {
+    let home_id = HomeId::parse(b"0100100020100042").unwrap();
+    assert_eq!(home_id.to_string(), "0100100020100042");
+    assert_eq!(home_id.census_tract(), CensusTract(1_001_000_201));
+    assert_eq!(home_id.census_tract().to_string(), "01001000201");
+    // Leading zeros are part of the id, so the same number with fewer digits is a different home.
+    assert_ne!(HomeId::parse(b"100100020100042").unwrap(), home_id);
+
+    let tract = HomeId::parse(b"01001000201").unwrap().census_tract();
+    assert_eq!(tract, CensusTract::parse(b"01001000201").unwrap());
+    assert_eq!(serde_json::to_string(&tract).unwrap(), "\"01001000201\"");
+    assert_eq!(serde_json::from_str::<HomeId>("\"0100100020100042\"").unwrap(), home_id);
+  }
+
+  #[test]
+  fn test_malformed_home_ids_are_errors() {
+    let error = HomeId::parse(b"0100100").unwrap_err().to_string();
+    assert!(error.contains("0100100") && error.contains("must have from 11 to 19 digits, but has 7"), "{error}");
+    assert!(HomeId::parse(b"").is_err());
+    assert!(HomeId::parse(b"01001000201000000000").is_err());
+    assert!(HomeId::parse(b"0100100020A").unwrap_err().to_string().contains("must have only digits"));
+    assert!(HomeId::parse(b"+1001000201").is_err());
+    assert!(CensusTract::parse(b"010010002010").is_err());
+  }
+
+  /// Loads `csv` into a new world with `policy`, returning the world, or the error message.
+  fn load_population(csv: &str, policy: BadRowPolicy) -> Result<World, String> {
+    let mut world = World::default();
+    match PopulationLoader::new().with_bad_row_policy(policy).load_from_reader(&mut world, csv.as_bytes()) {
+      Ok(()) => Ok(world),
+      Err(IxaError::IxaError(message)) => Err(message),
+      Err(error) => panic!("unexpected error {error:?}"),
+    }
+  }
+
+  /// Writes `contents` to a population file in the temporary directory named `name`, and loads it into a new world.
+  fn load_population_file(name: &str, contents: &[u8]) -> Result<World, IxaError> {
+    let directory = env::temp_dir().join("ecs_disease_models_tests").join("population");
+    fs::create_dir_all(&directory).unwrap();
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    parameters.synth_population_file = directory.join(name);
+    fs::write(&parameters.synth_population_file, contents).unwrap();
+
+    let mut world = World::default();
+    world.insert_resource(parameters);
+    PopulationLoader::new().load_population_data(&mut world)?;
+    Ok(world)
+  }
+
+  #[cfg(feature = "gzip")]
+  #[test]
+  fn test_gzip_population_file() {
+    use std::io::Write;
+    use flate2::{write::GzEncoder, Compression};
+
+    let csv = "age,homeId\n30,360610001001\n40,360610001002\n";
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(csv.as_bytes()).unwrap();
+    let compressed = encoder.finish().unwrap();
+
+    // Compressed input is recognized by its extension or, failing that, by its magic bytes.
+    for name in ["people.csv.gz", "people_gzip.csv"] {
+      let mut world = load_population_file(name, &compressed).unwrap();
+      let mut ages: Vec<u8> = world.query::<&Age>().iter(&world).map(|age| age.0).collect();
+      ages.sort();
+      assert_eq!(ages, [30, 40], "{name}");
+    }
+  }
+
+  #[cfg(not(feature = "gzip"))]
+  #[test]
+  fn test_gzip_population_file_needs_the_feature() {
+    let error = load_population_file("people_no_gzip.csv.gz", &[0x1f, 0x8b, 0x08, 0x00]).unwrap_err();
+    assert!(error.to_string().contains("the `gzip` feature is not enabled"), "{error}");
+  }
+
+  #[test]
+  fn test_population_file_missing_a_column_is_an_error() {
+    let error = load_population("age,home
+30,360610001001
+", BadRowPolicy::Skip).unwrap_err();
+    assert!(error.contains(r#"missing the column(s) ["homeId"]; its header is ["age", "home"]"#), "{error}");
+    // A file without a header row is missing every column.
+    let error = load_population("30,360610001001
+", BadRowPolicy::Fail).unwrap_err();
+    assert!(error.contains(r#"missing the column(s) ["age", "homeId"]"#), "{error}");
+    // The columns may be in any order, and other columns are ignored.
+    let mut world = load_population("name,homeId,age
+Ada,360610001001,30
+", BadRowPolicy::Fail).unwrap();
+    assert_eq!(world.query::<&Age>().single(&world).0, 30);
+  }
+
+  #[test]
+  fn test_bad_population_rows_are_errors_or_skipped() {
+    let csv = "age,homeId
+30,360610001001
+300,360610001002
+40,
+50,360610001003
+";
+    let error = load_population(csv, BadRowPolicy::Fail).unwrap_err();
+    assert!(error.contains(r#"line 3: invalid age "300""#), "{error}");
+    let error = load_population("age,homeId
+40,
+", BadRowPolicy::Fail).unwrap_err();
+    assert!(error.contains("line 2: the homeId is empty"), "{error}");
+
+    let mut world = load_population(csv, BadRowPolicy::Skip).unwrap();
+    let mut ages: Vec<u8> = world.query::<&Age>().iter(&world).map(|age| age.0).collect();
+    ages.sort();
+    assert_eq!(ages, [30, 50]);
+    let warnings = &world.resource::<ModelWarnings>().0;
+    assert_eq!(warnings.len(), 1);
+    assert!(
+      warnings[0].starts_with("skipped 2 bad row(s) of the population file: line 3: invalid age"),
+      "{warnings:?}"
+    );
+    assert!(warnings[0].ends_with("line 4: the homeId is empty"), "{warnings:?}");
+
+    // So is a row with the wrong number of fields.
+    let csv = "age,homeId
+30,360610001001
+40,360610001002,extra
+50
+60,360610001003
+";
+    let error = load_population(csv, BadRowPolicy::Fail).unwrap_err();
+    assert!(error.contains("line 3: it has 3 field(s) where the header has 2"), "{error}");
+    let mut world = load_population(csv, BadRowPolicy::Skip).unwrap();
+    let mut ages: Vec<u8> = world.query::<&Age>().iter(&world).map(|age| age.0).collect();
+    ages.sort();
+    assert_eq!(ages, [30, 60]);
+    let warnings = &world.resource::<ModelWarnings>().0;
+    assert!(warnings[0].starts_with("skipped 2 bad row(s) of the population file: line 3: it has 3"), "{warnings:?}");
+    assert!(warnings[0].ends_with("line 4: it has 1 field(s) where the header has 2"), "{warnings:?}");
+  }
+
+  #[test]
+  fn test_susceptibility_profile_from_csv() {
+    let csv = "min_age,max_age,census_tract,multiplier\n0,4,,1.5\n65,,,2.0\n,,7,0.5\n";
+    let profile = SusceptibilityProfile::from_csv(csv.as_bytes()).unwrap();
+    assert_eq!(profile.multiplier(Some(3), Some(7)), 0.75);
+    assert_eq!(profile.multiplier(Some(70), Some(1)), 2.0);
+    assert_eq!(profile.multiplier(Some(30), None), 1.0);
+
+    assert!(SusceptibilityProfile::from_json(r#"[{"multiplier": -1.0}]"#).is_err());
+  }
+
+  /// A model of 100 susceptible people, `fraction` of whom are infected at the start.
+  fn model_with_initial_infections(fraction: f64) -> Model {
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    parameters.initial_infected_fraction = fraction;
+    parameters.validate_inputs().unwrap();
+
+    let mut model = Model::with_random_seed(123);
+    model.add_module(parameters);
+    for age in 0..100 {
+      model.world_mut().spawn((Age(age), CensusTract(1), InfectionStatus::Susceptible));
+    }
+    model.add_module(InitialInfections);
+    model.add_module(TransmissionManager);
+    model
+  }
+
+  fn count_people(model: &mut Model, status: InfectionStatus) -> usize {
+    let world = model.world_mut();
+    world.query::<&InfectionStatus>().iter(world).filter(|&&other| other == status).count()
+  }
+
+  #[test]
+  fn test_initial_infected_fraction() {
+    let mut model = model_with_initial_infections(0.25);
+    assert_eq!(count_people(&mut model, InfectionStatus::Infected), 25);
+    assert_eq!(model.world().resource::<TransmissionTree>().edges().count(), 25);
+    model.run();
+    assert_eq!(count_people(&mut model, InfectionStatus::Infected), 0);
+    assert!(count_people(&mut model, InfectionStatus::Recovered) >= 25);
+  }
+
+  #[test]
+  fn test_no_initial_infections() {
+    // Nobody is infected, so there is never anything to do.
+    let mut model = model_with_initial_infections(0.0);
+    model.run();
+    assert_eq!(count_people(&mut model, InfectionStatus::Susceptible), 100);
+    assert_eq!(model.world().resource::<Timeline>().events_run(), 0);
+  }
+
+  #[test]
+  fn test_everyone_initially_infected() {
+    // Everyone is infected, so every contact fails and everyone recovers.
+    let mut model = model_with_initial_infections(1.0);
+    model.run();
+    assert_eq!(count_people(&mut model, InfectionStatus::Recovered), 100);
+    assert_eq!(model.world().resource::<TransmissionTree>().edges().count(), 100);
+  }
+
+  #[test]
+  fn test_initial_infected_fraction_is_validated() {
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    for fraction in [-0.1, 1.5, f64::NAN] {
+      parameters.initial_infected_fraction = fraction;
+      assert!(parameters.validate_inputs().is_err(), "{fraction}");
+    }
+  }
+
+  #[test]
+  fn test_every_invalid_parameter_is_reported() {
+    let json = r#"{
+      "max_time": 0.0,
+      "seed": 123,
+      "r_0": -1.0,
+      "infection_duration": -5.0,
+      "generation_interval": 0.0,
+      "report_period": -1.0,
+      "report_offset": -1.0,
+      "synth_population_file": "",
+      "initial_infected_fraction": 2.0,
+      "contact_rate_shape": 0.5,
+      "susceptibility_profile": "",
+      "report": {"output_directory": "", "file_prefix": "", "overwrite": false},
+      "contact_tracing": {"detection_probability": 2.0, "tracing_delay": 1.0, "quarantine_duration": 1.0}
+    }"#;
+    let error = Parameters::from_json(json, None).unwrap_err().to_string();
+    for problem in [
+      "12 invalid parameters",
+      "the maximum time must be positive, but it is 0",
+      "r_0 must be a non-negative number, but it is -1",
+      "the infection duration must be positive, but it is -5",
+      "the generation interval must be positive, but it is 0",
+      "the report period must be positive, but it is -1",
+      "the report offset must be non-negative and finite, but it is -1",
+      "the population file is empty",
+      "the initial infected fraction must be between 0 and 1, but it is 2",
+      "the contact rate shape must be greater than 1, but it is 0.5",
+      "the susceptibility profile file is empty",
+      "the report output directory is empty",
+      "the detection probability must be between 0 and 1, but it is 2",
+    ] {
+      assert!(error.contains(problem), "{problem} not in {error}");
+    }
+
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    parameters.max_time = f64::INFINITY;
+    let error = parameters.validate_inputs().unwrap_err().to_string();
+    assert!(error.contains("invalid parameters: the maximum time must be positive, but it is inf"), "{error}");
+
+    // The population file is only checked on request.
+    parameters.max_time = 10.0;
+    parameters.validate_inputs().unwrap();
+    let error = parameters.validate_with_population_file().unwrap_err().to_string();
+    assert!(error.contains("the population file unused.csv does not exist or is not a file"), "{error}");
+    parameters.synth_population_file = PathBuf::from(PARAMETERS_PATH);
+    parameters.validate_with_population_file().unwrap();
+  }
+
+  #[test]
+  fn test_parameters_from_reader_and_str() {
+    let expected = Parameters::from_json(PARAMETERS, None).unwrap();
+    let from_reader = Parameters::from_reader(PARAMETERS.as_bytes(), None).unwrap();
+    let from_str: Parameters = PARAMETERS.parse().unwrap();
+    for parameters in [&from_reader, &from_str] {
+      assert_eq!(parameters.max_time, expected.max_time);
+      assert_eq!((parameters.seed, parameters.r_0), (expected.seed, expected.r_0));
+      assert_eq!(parameters.synth_population_file, expected.synth_population_file);
+    }
+
+    // Both parse and validate like `from_json`.
+    assert!(Parameters::from_reader("{".as_bytes(), None).is_err());
+    assert!("not json".parse::<Parameters>().is_err());
+    let invalid = PARAMETERS.replace("\"r_0\": 5.0", "\"r_0\": -1.0");
+    let error = Parameters::from_reader(invalid.as_bytes(), None).unwrap_err().to_string();
+    assert!(error.contains("r_0"), "{error}");
+    assert_eq!(invalid.parse::<Parameters>().unwrap_err().to_string(), error);
+  }
+
+  #[test]
+  fn test_keyed_and_flat_parameters() {
+    let keyed = |key: &str| format!("{{\"{key}\": {PARAMETERS}}}");
+    let flat = Parameters::from_json(PARAMETERS, None).unwrap();
+    assert_eq!(flat.r_0, 5.0);
+
+    // Under the default key, as in the example's parameters file, or under any other.
+    assert_eq!(keyed(parameters::PARAMETERS_KEY).parse::<Parameters>().unwrap().r_0, 5.0);
+    let other = Parameters::from_json(&keyed("other_scenario.Parameters"), Some("other_scenario.Parameters")).unwrap();
+    assert_eq!(other.r_0, 5.0);
+    // A flat document is read without a key, but a key that is asked for must be there.
+    assert_eq!(Parameters::from_json(PARAMETERS, None).unwrap().r_0, 5.0);
+    let error = Parameters::from_json(PARAMETERS, Some("other_scenario.Parameters")).unwrap_err();
+    assert!(error.to_string().contains("parameters not found under the key `other_scenario.Parameters`"), "{error}");
+    assert_eq!(Parameters::from_reader(PARAMETERS.as_bytes(), None).unwrap().r_0, 5.0);
+    // Under a key other than the one asked for, the document is not flat parameters.
+    let error = Parameters::from_json(&keyed("other_scenario.Parameters"), None).unwrap_err();
+    assert!(error.to_string().contains("missing field"), "{error}");
+  }
+
+  #[test]
+  fn test_reporter_configuration_from_parameters() {
+    // The parameters file configures the reports of `main`.
+    let parameters = Parameters::from_file(Path::new(PARAMETERS_PATH), None).unwrap();
+    let config = parameters.reporter_configuration().unwrap();
+    assert_eq!(config.output_directory, PathBuf::from("./examples/epi-isolation/output"));
+    assert_eq!(config.file_prefix, "epi-isolation");
+    assert_eq!(config.filename_policy, FilenamePolicy::Overwrite);
+
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    assert!(parameters.report.is_none());
+    parameters.report = Some(ReportSettings{
+      file_prefix: String::new(),
+      output_directory: PathBuf::from(PARAMETERS_PATH),
+      overwrite: false,
+    });
+    let Err(error) = parameters.validate_inputs() else {
+      panic!("a file is not a valid output directory");
+    };
+    assert!(error.to_string().contains("is not a directory"), "{error}");
+  }
+
+  /// The number of rows of the periodic report of a population of 3, with snapshots every `period` from `offset` up
+  /// to time 10.
+  fn periodic_report_rows(period: f64, offset: f64) -> usize {
+    let directory = env::temp_dir().join("ecs_disease_models_tests").join("periodic_report");
+    fs::create_dir_all(&directory).unwrap();
+    let mut model = Model::with_random_seed(123);
+    let prefix = format!("{period}_{offset}_");
+    model.add_module(ReporterConfiguration::new(prefix, directory, FilenamePolicy::Overwrite));
+    for age in 0..3 {
+      model.world_mut().spawn((Age(age), CensusTract(1), InfectionStatus::Susceptible));
+    }
+    model.add_module(PeriodicReporter::new("periodic".to_string()));
+    model.add_module(PeriodicReportSchedule::new(Duration(period), 10.0).with_offset(Duration(offset)));
+    model.run();
+
+    let path = model.world().resource::<PeriodicReporter>().output_path().unwrap().to_path_buf();
+    // Dropping the reporter flushes it.
+    model.world_mut().remove_resource::<PeriodicReporter>();
+    fs::read_to_string(path).unwrap().lines().count() - 1
+  }
+
+  #[test]
+  fn test_periodic_report_schedule() {
+    // `floor(max_time / report_period) + 1` snapshots of 3 people, including one at time 0 and, if it is a multiple of
+    // the period, at time 10. Ten periods of 0.1 add up to a little more than 1, but the snapshot at 10 is not lost.
+    for period in [1.0, 2.5, 3.0, 0.1] {
+      assert_eq!(periodic_report_rows(period, 0.0), 3 * ((10.0 / period).floor() as usize + 1), "{period}");
+    }
+    // Without the snapshot at time 0.
+    assert_eq!(periodic_report_rows(1.0, 1.0), 3 * 10);
+    // At 1, 4, 7, and 10.
+    assert_eq!(periodic_report_rows(3.0, 1.0), 3 * 4);
+
+    assert!(PeriodicReportSchedule::new(Duration(0.0), 10.0).validate().is_err());
+    assert!(PeriodicReportSchedule::new(Duration(1.0), f64::INFINITY).validate().is_err());
+    assert!(PeriodicReportSchedule::new(Duration(1.0), 10.0).with_offset(Duration(-1.0)).validate().is_err());
+    let mut invalid_schedule = Model::with_random_seed(123);
+    invalid_schedule.add_module(PeriodicReportSchedule::new(Duration(0.0), 10.0));
+    invalid_schedule.run();
+    let reason = invalid_schedule.abort_reason().unwrap();
+    assert!(reason.contains("the report period must be positive, but it is 0"), "{reason}");
+
+    // A reporter that was never initialized has nowhere to write the first snapshot.
+    let mut unwritable = Model::with_random_seed(123);
+    unwritable.world_mut().spawn((Age(30), CensusTract(1), InfectionStatus::Susceptible));
+    unwritable.world_mut().insert_resource(PeriodicReporter::new("periodic".to_string()));
+    unwritable.add_module(PeriodicReportSchedule::new(Duration(1.0), 10.0));
+    unwritable.run();
+    let reason = unwritable.abort_reason().unwrap();
+    assert!(reason.contains("was not initialized"), "{reason}");
+  }
+
+  /// A model with the `RenewalTransmission` and one infected person, who is the root of the `TransmissionTree`.
+  fn renewal_model(seed: u64, r_0: f64, renewal_transmission: RenewalTransmission) -> Model {
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    parameters.r_0 = r_0;
+    let mut model = Model::with_random_seed(seed);
+    model.add_module(parameters);
+    model.add_module(renewal_transmission);
+    let index_case = model.world_mut().spawn((Age(30), CensusTract(7), InfectionStatus::Infected)).id();
+    TransmissionTree::record_infection(model.world_mut(), None, index_case, Time::from(0.0));
+    model
+  }
+
+  #[test]
+  fn test_renewal_secondary_cases_average_r_0() {
+    // Below 1, every outbreak dies out, so every case has had all of their secondary cases by the end of the run.
+    let r_0 = 0.8;
+    let (mut cases, mut secondary_cases) = (0, 0);
+    for seed in 0..300 {
+      let mut model = renewal_model(seed, r_0, RenewalTransmission::new().spawning(usize::MAX));
+      model.run();
+      // Spawned cases are like their infectors.
+      let world = model.world_mut();
+      let mut people = world.query::<(&Age, &CensusTract)>();
+      assert!(people.iter(world).all(|(age, tract)| (age.0, *tract) == (30, CensusTract(7))));
+      cases += count_people(&mut model, InfectionStatus::Recovered);
+      secondary_cases += model.world().resource::<TransmissionTree>().edges().filter(|(infector, ..)| infector.is_some())
+                                                                        .count();
+    }
+    let mean = secondary_cases as f64 / cases as f64;
+    assert!((mean - r_0).abs() < 0.1, "mean secondary cases {mean} over {cases} cases");
+
+    // From the population, everyone infected is someone who was susceptible, and nobody is infected twice.
+    let mut model = renewal_model(123, 3.0, RenewalTransmission::new());
+    for age in 0..50 {
+      model.world_mut().spawn((Age(age), CensusTract(1), InfectionStatus::Susceptible));
+    }
+    model.run();
+    let infected = 51 - count_people(&mut model, InfectionStatus::Susceptible);
+    assert_eq!(count_people(&mut model, InfectionStatus::Recovered), infected);
+    assert_eq!(model.world().resource::<TransmissionTree>().edges().count(), infected);
+    assert!(infected > 1);
+
+    // Above 1, a branching process grows without bound, and the model finishes at the cap on cases.
+    let mut model = renewal_model(123, 3.0, RenewalTransmission::new().spawning(200));
+    model.run();
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Finished);
+    assert_eq!(model.world().resource::<TransmissionTree>().edges().count(), 200);
+  }
+
+  #[test]
+  fn test_contact_rates_have_mean_one() {
+    let mut model = Model::with_random_seed(123);
+    for age in 0..2000 {
+      model.world_mut().spawn((Age((age % 80) as u8), CensusTract(1), InfectionStatus::Susceptible));
+    }
+    model.add_module(ContactRates::pareto(3.0).unwrap());
+
+    let world = model.world_mut();
+    let rates: Vec<f64> = world.query::<&ContactRate>().iter(world).map(|rate| rate.0).collect();
+    assert_eq!(rates.len(), 2000);
+    let mean = rates.iter().sum::<f64>() / rates.len() as f64;
+    assert!((mean - 1.0).abs() < 0.1, "mean contact rate {mean}");
+
+    assert!(ContactRates::pareto(1.0).is_err());
+  }
+
+  /// The variance-to-mean ratio of the final outbreak sizes of many runs in a population of 200 with one initial
+  /// infection, where person `i` has contact rate `contact_rate(i)`.
+  fn outbreak_size_dispersion(contact_rate: impl Fn(usize) -> f64) -> f64 {
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    parameters.r_0 = 2.0;
+    parameters.initial_infected_fraction = 0.005;
+
+    let sizes: Vec<f64> = (0..100).map(|seed| {
+      let mut model = Model::with_random_seed(seed);
+      model.add_module(parameters.clone());
+      for person in 0..200 {
+        model.world_mut().spawn(
+          (Age(40), CensusTract(1), InfectionStatus::Susceptible, ContactRate(contact_rate(person)))
+        );
+      }
+      model.add_module(InitialInfections);
+      model.add_module(TransmissionManager);
+      model.run();
+      (200 - count_people(&mut model, InfectionStatus::Susceptible)) as f64
+    }).collect();
+
+    let mean = sizes.iter().sum::<f64>() / sizes.len() as f64;
+    let variance = sizes.iter().map(|size| (size - mean).powi(2)).sum::<f64>() / sizes.len() as f64;
+    variance / mean
+  }
+
+  #[test]
+  fn test_superspreaders_overdisperse_outbreaks() {
+    let homogeneous = outbreak_size_dispersion(|_| 1.0);
+    // Ten people with twenty times the contact rate of everyone else, keeping the mean rate at 1.
+    let superspreaders = outbreak_size_dispersion(|person| if person < 10 { 10.0 } else { 0.5 / 0.95 });
+    assert!(superspreaders > 2.0 * homogeneous, "{superspreaders} <= 2 * {homogeneous}");
+  }
+
+  /// The total final size of outbreaks in a population of 200, with 10 initial infections, over 20 seeds, with
+  /// contact tracing that detects cases with `detection_probability`.
+  fn total_final_size(detection_probability: f64) -> usize {
+    let contact_tracing = ContactTracing{
+      detection_probability,
+      tracing_delay: Duration(1.0),
+      quarantine_duration: Duration(30.0),
+    };
+    let mut parameters = Parameters::from_json(PARAMETERS, None).unwrap();
+    parameters.initial_infected_fraction = 0.05;
+
+    (0..20).map(|seed| {
+      let mut model = Model::with_random_seed(seed);
+      model.add_module(parameters.clone());
+      for age in 0..200 {
+        model.world_mut().spawn((Age((age % 80) as u8), CensusTract(1), InfectionStatus::Susceptible));
+      }
+      model.add_module(InitialInfections);
+      model.add_module(TransmissionManager);
+      model.add_module(contact_tracing);
+      model.run();
+      // Every quarantine has ended.
+      assert_eq!(model.world_mut().query::<&Isolated>().iter(model.world()).count(), 0);
+      200 - count_people(&mut model, InfectionStatus::Susceptible)
+    }).sum()
+  }
+
+  #[test]
+  fn test_contact_tracing_reduces_final_size() {
+    let untraced = total_final_size(0.0);
+    let half_traced = total_final_size(0.5);
+    let traced = total_final_size(1.0);
+    assert!(traced < half_traced && half_traced < untraced, "{traced}, {half_traced}, {untraced}");
+
+    let contact_tracing = ContactTracing{
+      detection_probability: 1.5,
+      tracing_delay: Duration(1.0),
+      quarantine_duration: Duration(30.0),
+    };
+    assert!(contact_tracing.validate().is_err());
+    let mut model = Model::with_random_seed(0);
+    model.add_module(contact_tracing);
+    model.run();
+    let reason = model.abort_reason().unwrap();
+    assert!(reason.contains("the detection probability must be between 0 and 1, but it is 1.5"), "{reason}");
+    let json = PARAMETERS.replace(
+      "\"max_time\"",
+      concat!(
+        r#""contact_tracing": {"detection_probability": 0.5, "tracing_delay": 1.0, "#,
+        r#""quarantine_duration": -2.0}, "max_time""#
+      )
+    );
+    let error = Parameters::from_json(&json, None).unwrap_err();
+    assert!(error.to_string().contains("the quarantine duration must be"), "{error}");
+  }
+}
@@ -2,12 +2,36 @@
 
 Contrary to the version in Ixa, this example doesn't use any special general infrastructure (outside of Bevy ECS primitives.
 
+Parameters are read from JSON, either from under a key of the top-level object, which names the scenario, or from a flat
+document that is the parameters object itself. A key given by the caller must be in the document, so the same loader
+reads parameters files of any scenario. Without one, the parameters are under `PARAMETERS_KEY`, as in this example's
+parameters file, or, if the document has no such key, flat. Parameters can be read from a file, from any reader (e.g.
+stdin), or from a string (which is handy for tests), and all of these share the same parsing and validation. Validation
+checks every parameter and reports all of the problems it finds in one error. It does not check that the population
+file exists, so that parameters can be read without it, e.g. in tests; `Parameters::validate_with_population_file`
+does, and `main` calls it before building the model.
+
+The optional `report` section configures where reports are written, as a `ReportSettings`: the `output_directory`, the
+`file_prefix`, and whether to `overwrite` existing reports. `Parameters::reporter_configuration` turns it into the
+`ReporterConfiguration` of the model, which is the default configuration if there is no section.
+
+The optional `contact_tracing` section turns on contact tracing with the given parameters, as a `ContactTracing`.
+
+The optional `transmission` parameter chooses how infections spread: `"contacts"`, the default, with the
+`TransmissionManager`; `"renewal"`, with the `RenewalTransmission`, in which every infection attempt infects someone
+susceptible; or `"branching"`, in which every attempt spawns a new infected person, as in a branching process, until
+`main`'s cap on the number of cases is reached.
+
+The optional `bad_population_rows` parameter chooses what a bad row of the population file does, as a `BadRowPolicy`:
+`"fail"`, the default, fails the load, and `"skip"` skips the row with a warning (see `PopulationLoader`).
+
 */
 
 use std::{
   io::Read,
   fs::File,
-  path::PathBuf
+  path::{Path, PathBuf},
+  str::FromStr
 };
 use serde::{Deserialize, Serialize};
 
@@ -16,9 +40,16 @@ use bevy_ecs::{
   schedule::SystemConfigs
 };
 
+use crate::{
+  contact_tracing::ContactTracing,
+  population_loader::BadRowPolicy
+};
+
 use ecs_disease_models::{
   module::Module,
-  errors::IxaError
+  errors::IxaError,
+  report::{ReportSettings, ReporterConfiguration},
+  units::Duration
 };
 
 #[derive(Resource, Serialize, Deserialize, Debug, Clone)]
@@ -26,45 +57,202 @@ pub struct Parameters{
   pub max_time: f64,
   pub seed: u64,
   pub r_0: f64,
-  pub infection_duration: f64,
-  pub generation_interval: f64,
+  pub infection_duration: Duration,
+  pub generation_interval: Duration,
   pub report_period: f64,
+  /// The time of the first snapshot of the periodic report, 0 by default. See `PeriodicReportSchedule`.
+  #[serde(default)]
+  pub report_offset: f64,
   pub synth_population_file: PathBuf,
+  /// A CSV or JSON file of susceptibility multipliers (see `SusceptibilityProfile`). Everyone is equally susceptible
+  /// if unset.
+  #[serde(default)]
+  pub susceptibility_profile: Option<PathBuf>,
+  /// The fraction of the population infected at time zero, in `[0, 1]`. Without any initial infections there is no
+  /// epidemic, so this defaults to zero only for backward compatibility.
+  #[serde(default)]
+  pub initial_infected_fraction: f64,
+  /// If set, contact rates are drawn from a power law with this shape, which must exceed 1 (see `ContactRates`).
+  /// Everyone has the same contact rate if unset.
+  #[serde(default)]
+  pub contact_rate_shape: Option<f64>,
+  /// Where reports are written. See the module documentation.
+  #[serde(default)]
+  pub report: Option<ReportSettings>,
+  /// Contact tracing, if any. See the module documentation.
+  #[serde(default)]
+  pub contact_tracing: Option<ContactTracing>,
+  /// How infections spread. See the module documentation.
+  #[serde(default)]
+  pub transmission: TransmissionModel,
+  /// What a bad row of the population file does. See the module documentation.
+  #[serde(default)]
+  pub bad_population_rows: BadRowPolicy,
 }
 
+/// The module that spreads infections. See the module documentation.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum TransmissionModel {
+  /// Random contacts, which may fail to infect, with the `TransmissionManager`.
+  #[default]
+  Contacts,
+  /// A renewal process, in which every attempt infects someone susceptible, with the `RenewalTransmission`.
+  Renewal,
+  /// A branching process, in which every attempt spawns a new infected person, with the `RenewalTransmission`.
+  Branching,
+}
+
+
+/// The key of the top-level JSON object under which the parameters are found if no other key is given.
+pub const PARAMETERS_KEY: &str = "epi_isolation.Parameters";
 
 impl Parameters {
-  pub fn from_file(path: &PathBuf) -> Result<Parameters, IxaError>{
-    let mut file = File::open(path)?;
-    let mut file_contents = String::new();
-    file.read_to_string(&mut file_contents)?;
+  /// Reads the parameters from the JSON file at `path`, under `key`, or, if `None`, under `PARAMETERS_KEY` or flat.
+  /// See the module documentation.
+  pub fn from_file(path: &Path, key: Option<&str>) -> Result<Parameters, IxaError>{
+    Self::from_reader(File::open(path)?, key)
+  }
+
+  /// Reads the parameters from JSON read from `reader`, under `key`, or, if `None`, under `PARAMETERS_KEY` or flat.
+  pub fn from_reader(mut reader: impl Read, key: Option<&str>) -> Result<Parameters, IxaError>{
+    let mut contents = String::new();
+    reader.read_to_string(&mut contents)?;
+    Self::from_json(&contents, key)
+  }
 
-    let json_data: serde_json::Value = serde_json::from_str(&file_contents)?;
-    let parameters: Parameters = serde_json::from_value(json_data["epi_isolation.Parameters"].clone())?;
+  /// Parses and validates parameters from a JSON string. If `key` is given, the parameters are the value of that key
+  /// of the top-level object, which is an error if there is none. Otherwise, they are the value of `PARAMETERS_KEY` if
+  /// the top-level object has it, or else the whole document.
+  pub fn from_json(json: &str, key: Option<&str>) -> Result<Parameters, IxaError>{
+    let mut json_data: serde_json::Value = serde_json::from_str(json)?;
+
+    match key {
+      Some(key) => {
+        json_data = json_data
+            .get_mut(key)
+            .map(serde_json::Value::take)
+            .ok_or_else(|| IxaError::IxaError(format!("parameters not found under the key `{key}`")))?;
+      }
+      None => {
+        if let Some(keyed) = json_data.get_mut(PARAMETERS_KEY) {
+          json_data = keyed.take();
+        }
+      }
+    }
+
+    let parameters: Parameters = serde_json::from_value(json_data)?;
 
     parameters.validate_inputs()?;
 
     Ok(parameters)
   }
 
+  /// Checks every parameter, and reports every problem found in one error rather than only the first. The population
+  /// file is not looked at, so that parameters can be checked, e.g. in tests, without it; see
+  /// `validate_with_population_file`.
   pub fn validate_inputs(&self) -> Result<(), IxaError> {
-    if self.r_0 < 0.0 {
-      return Err(IxaError::IxaError(
-        "r_0 must be a non-negative number.".to_string(),
-      ));
+    Self::into_result(self.problems())
+  }
+
+  /// Like `validate_inputs`, and also checks that the population file exists and is a file.
+  pub fn validate_with_population_file(&self) -> Result<(), IxaError> {
+    let mut problems = self.problems();
+    let path = &self.synth_population_file;
+    if !path.as_os_str().is_empty() && !path.is_file() {
+      problems.push(format!("the population file {} does not exist or is not a file", path.display()));
     }
-    if self.generation_interval <= 0.0 {
-      return Err(IxaError::IxaError(
-        "The generation interval must be positive.".to_string(),
-      ));
+    Self::into_result(problems)
+  }
+
+  /// The problems with the parameters, other than a missing population file.
+  fn problems(&self) -> Vec<String> {
+    let mut problems = Vec::new();
+    let mut check = |valid: bool, problem: String| {
+      if !valid {
+        problems.push(problem);
+      }
+    };
+    let positive = |value: f64| value > 0.0 && value.is_finite();
+
+    check(positive(self.max_time), format!("the maximum time must be positive, but it is {}", self.max_time));
+    check(
+      self.r_0 >= 0.0 && self.r_0.is_finite(),
+      format!("r_0 must be a non-negative number, but it is {}", self.r_0)
+    );
+    check(
+      positive(self.infection_duration.0),
+      format!("the infection duration must be positive, but it is {}", self.infection_duration.0)
+    );
+    check(
+      positive(self.generation_interval.0),
+      format!("the generation interval must be positive, but it is {}", self.generation_interval.0)
+    );
+    check(
+      positive(self.report_period),
+      format!("the report period must be positive, but it is {}", self.report_period)
+    );
+    check(
+      self.report_offset >= 0.0 && self.report_offset.is_finite(),
+      format!("the report offset must be non-negative and finite, but it is {}", self.report_offset)
+    );
+    check(!self.synth_population_file.as_os_str().is_empty(), "the population file is empty".to_string());
+    check(
+      (0.0..=1.0).contains(&self.initial_infected_fraction),
+      format!("the initial infected fraction must be between 0 and 1, but it is {}", self.initial_infected_fraction)
+    );
+    if let Some(shape) = self.contact_rate_shape {
+      check(shape > 1.0, format!("the contact rate shape must be greater than 1, but it is {shape}"));
+    }
+    if let Some(path) = &self.susceptibility_profile {
+      check(!path.as_os_str().is_empty(), "the susceptibility profile file is empty".to_string());
+    }
+    if let Some(Err(error)) = self.report.as_ref().map(ReportSettings::validate) {
+      problems.push(Self::message(error));
+    }
+    if let Some(Err(error)) = self.contact_tracing.as_ref().map(ContactTracing::validate) {
+      problems.push(Self::message(error));
+    }
+    problems
+  }
+
+  /// The message of an error from the validation of a section.
+  fn message(error: IxaError) -> String {
+    match error {
+      IxaError::IxaError(message) => message,
+      error => error.to_string(),
+    }
+  }
+
+  fn into_result(problems: Vec<String>) -> Result<(), IxaError> {
+    match problems.len() {
+      0 => Ok(()),
+      1 => Err(IxaError::IxaError(format!("invalid parameters: {}", problems[0]))),
+      count => Err(IxaError::IxaError(format!("{count} invalid parameters: {}", problems.join("; ")))),
+    }
+  }
+
+  /// The `ReporterConfiguration` of the `report` section, or the default configuration if there is none.
+  pub fn reporter_configuration(&self) -> Result<ReporterConfiguration, IxaError> {
+    match &self.report {
+      Some(report) => ReporterConfiguration::from_settings(report),
+      None => Ok(ReporterConfiguration::default()),
     }
-    Ok(())
   }
 
 }
 
+/// Parses parameters stored under `PARAMETERS_KEY` or flat, just like a parameters file.
+impl FromStr for Parameters {
+  type Err = IxaError;
+
+  fn from_str(json: &str) -> Result<Self, Self::Err> {
+    Self::from_json(json, None)
+  }
+}
+
 impl Module for Parameters {
-  fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs>{
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs>{
     // Insert a new instance into the world
     world.insert_resource(self);
 
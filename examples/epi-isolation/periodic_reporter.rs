@@ -7,6 +7,17 @@ The original epi-isolation code created a weird report in which for every sample
 We don't do this. Instead we just print the time and `Age, CensusTract, InfectiousStatus` for every entity. Even this
 information is odd when the number of entities is small (less than a few thousand). It would be much more efficient to
 just record the time of each status change. But presumably periodic reports like this are for large populations.
+The counts by census tract are written to a separate report by a `CohortReporter`.
+
+When the report is written is set by the `PeriodicReportSchedule` module, which is added along with the
+`PeriodicReporter`: a snapshot of every person at `offset`, and then every `period`, up to and including `until`, which
+is the `max_time` of the model. Each snapshot is its own event on the `Timeline`, scheduled by the one before, so the
+report has `floor((until - offset) / period) + 1` snapshots however often other events happen, and a snapshot due at
+exactly `until` is written. The model keeps running until the last snapshot, even if nothing else is left to happen.
+With the default offset of 0, the first snapshot is of the initial state; an offset of one period skips it. Snapshot
+times are computed as `offset + k * period` rather than by adding up periods, so they do not drift, and a time within a
+rounding error of `until` counts as `until`. An invalid schedule, or a snapshot that cannot be written, aborts the
+model.
 
 ToDo: Periodic reporting should be generic and built-in, unified with `Reporter<Marker>`.
 
@@ -15,13 +26,21 @@ ToDo: Periodic reporting should be generic and built-in, unified with `Reporter<
 use std::{
   fmt::{Display, Formatter}
 };
-use bevy_ecs::prelude::*;
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
 use serde::{Deserialize, Serialize};
 
 use ecs_disease_models::{
+  errors::IxaError,
+  model::AbortReason,
+  module::Module,
   timeline::Timeline,
+  timeline_event::Event,
   report::Reporter,
-  timeline::Time
+  timeline::Time,
+  units::Duration
 };
 use crate::person::{Age, CensusTract, InfectionStatus};
 
@@ -50,27 +69,105 @@ impl Display for IncidenceReportItem {
   }
 }
 
-/// The command that writes out a row of the periodic report.
-pub fn write_periodic_report(
-  mut periodic_reporter: ResMut<PeriodicReporter>,
-  timeline: Res<Timeline>,
-  query: Query<(Entity, &Age, &CensusTract, &InfectionStatus)>,
-) {
-  let time = timeline.now();
-
-  // (Age, CensusTract, InfectiousStatus, Count)
-  // Track the changes in infection status.
-  for (_, age, census_tract, infection_status) in query.iter() {
-    let report_item = IncidenceReportItem{
-      time,
-      age: *age,
-      census_tract: *census_tract,
-      infection_status: *infection_status,
-    };
+/// When the periodic report is written. See the module documentation.
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct PeriodicReportSchedule {
+  /// The time of the first snapshot.
+  pub offset: Duration,
+  pub period: Duration,
+  /// The time of the last snapshot, at most.
+  pub until : Time,
+}
+
+impl PeriodicReportSchedule {
+  /// Snapshots every `period` from time 0 up to and including `until`.
+  pub fn new(period: Duration, until: impl Into<Time>) -> Self {
+    PeriodicReportSchedule{ offset: Duration(0.0), period, until: until.into() }
+  }
 
-    #[cfg(feature = "print_messages")]
-    println!("Writing change to report {}", report_item);
-    periodic_reporter.write_row(report_item).expect("Failed to write row.");
+  /// Takes the first snapshot at `offset` instead of at time 0.
+  #[must_use]
+  pub fn with_offset(mut self, offset: Duration) -> Self {
+    self.offset = offset;
+    self
+  }
+
+  pub fn validate(&self) -> Result<(), IxaError> {
+    if !(self.period.0 > 0.0 && self.period.0.is_finite()) {
+      return Err(IxaError::IxaError(format!("the report period must be positive, but it is {}", self.period.0)));
+    }
+    if !(self.offset.0 >= 0.0 && self.offset.0.is_finite()) {
+      return Err(IxaError::IxaError(format!(
+        "the report offset must be non-negative and finite, but it is {}",
+        self.offset.0
+      )));
+    }
+    if !self.until.0.is_finite() {
+      return Err(IxaError::IxaError("the periodic report must end at a finite time".to_string()));
+    }
+    Ok(())
+  }
+
+  /// The time of snapshot `k`, counting from 0, if it is not after `until`.
+  fn snapshot_time(&self, k: u64) -> Option<Time> {
+    let time = self.offset.0 + k as f64 * self.period.0;
+    // Allow for rounding error, so that a snapshot due at `until` is not lost.
+    (time <= self.until.0 + self.period.0 * 1e-9).then(|| Time::from(time.min(self.until.0)))
+  }
+
+  /// Schedules snapshot `k`, if it is not after `until`. Fails if the `Timeline` refuses the event.
+  fn schedule(&self, timeline: &mut Timeline, k: u64) -> Result<(), IxaError> {
+    match self.snapshot_time(k) {
+      Some(time) => timeline.push(
+        Event::builder(time)
+            .fallible_command(move |world| write_periodic_report(world, k))
+            .label("periodic_report")
+            .build()
+      ),
+      None => Ok(()),
+    }
   }
+}
+
+/// The command that writes snapshot `k` of the periodic report and schedules the next one.
+fn write_periodic_report(world: &mut World, k: u64) -> Result<(), IxaError> {
+  let time = world.resource::<Timeline>().now();
 
+  world.resource_scope(|world, mut periodic_reporter: Mut<PeriodicReporter>| -> Result<(), IxaError> {
+    let mut query = world.query::<(&Age, &CensusTract, &InfectionStatus)>();
+    for (age, census_tract, infection_status) in query.iter(world) {
+      let report_item = IncidenceReportItem{
+        time,
+        age: *age,
+        census_tract: *census_tract,
+        infection_status: *infection_status,
+      };
+
+      #[cfg(feature = "print_messages")]
+      println!("Writing change to report {}", report_item);
+      periodic_reporter.write_row(report_item)?;
+    }
+    Ok(())
+  })?;
+
+  let schedule = *world.resource::<PeriodicReportSchedule>();
+  schedule.schedule(&mut world.resource_mut::<Timeline>(), k + 1)
+}
+
+impl Module for PeriodicReportSchedule {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    if let Err(error) = self.validate() {
+      AbortReason::abort(world, format!("the periodic report schedule is invalid: {error}"));
+      return None;
+    }
+    world.insert_resource(self);
+    if let Err(error) = self.schedule(&mut world.resource_mut::<Timeline>(), 0) {
+      AbortReason::abort(world, format!("the first periodic report could not be scheduled: {error}"));
+    }
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module PeriodicReportSchedule");
+
+    None
+  }
 }
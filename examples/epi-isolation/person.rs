@@ -2,7 +2,9 @@
 
 These are the components that make up an entity, a person.
 
-The population loader should be kept in sync with this so that all the components are loaded for each person.
+The population loader should be kept in sync with this so that all the components are loaded for each person. The
+components every loaded person must have are listed in `PersonComponents`, which the loader checks after loading (see
+`PopulationLoader::with_validation`), so a component added here but not to the loader is caught at load time.
 
 */
 
@@ -10,12 +12,14 @@ use bevy_ecs::component::Component;
 use serde::{Deserialize, Serialize};
 use std::fmt::{Display, Formatter};
 
+use ecs_disease_models::errors::IxaError;
+
 /// All people have exactly one of these states.
 /// These states refer to the person's infectiousness at a given time
 /// and are not related to the person's health status. How long an agent
 /// spends in the infectious compartment is determined entirely from their
 /// number of infection attempts and draws from the generation interval.
-#[derive(Component, Clone, Copy, PartialEq, Eq, Default, Debug, Hash, Serialize, Deserialize)]
+#[derive(Component, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Default, Debug, Hash, Serialize, Deserialize)]
 pub enum InfectionStatus {
   #[default]
   Susceptible,
@@ -29,21 +33,140 @@ impl Display for InfectionStatus {
   }
 }
 
+/// The components every person loaded by the `PopulationLoader` has. Add a component here when the loader should load
+/// it for everyone.
+pub type PersonComponents = (Age, HomeId, CensusTract, Alive, InfectionStatus);
+
 // The components of our entities, people.
 #[derive(Component, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
-pub struct Age(u8);
+pub struct Age(pub u8);
 
-#[derive(Component, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
-pub struct HomeId(u64);
+/// A home id from the input file: a string of digits that begins with the person's census tract GEOID. It is stored
+/// as an integer together with its number of digits, so that leading zeros, which real GEOIDs have, are kept. It
+/// displays and serializes as the original string of digits.
+#[derive(Component, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Hash, Debug)]
+#[serde(into = "String", try_from = "String")]
+pub struct HomeId {
+  id    : u64,
+  digits: u8,
+}
 
-#[derive(Component, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
-pub struct CensusTract(u64);
+impl HomeId {
+  /// The most digits a home id can have and still fit in a `u64`.
+  pub const MAX_DIGITS: usize = 19;
+
+  /// Parses a home id, which must have from `CensusTract::DIGITS` to `HomeId::MAX_DIGITS` ASCII digits.
+  pub fn parse(bytes: &[u8]) -> Result<Self, IxaError> {
+    if !(CensusTract::DIGITS..=Self::MAX_DIGITS).contains(&bytes.len()) {
+      return Err(IxaError::IxaError(format!(
+        "home id {:?} must have from {} to {} digits, but has {}",
+        String::from_utf8_lossy(bytes),
+        CensusTract::DIGITS,
+        Self::MAX_DIGITS,
+        bytes.len()
+      )));
+    }
+    Ok(HomeId{ id: parse_digits(bytes, "home id")?, digits: bytes.len() as u8 })
+  }
+
+  /// The census tract, which is the first `CensusTract::DIGITS` digits of the home id.
+  pub fn census_tract(&self) -> CensusTract {
+    CensusTract(self.id / 10u64.pow(u32::from(self.digits) - CensusTract::DIGITS as u32))
+  }
+}
+
+impl Display for HomeId {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:0width$}", self.id, width = usize::from(self.digits))
+  }
+}
+
+impl From<HomeId> for String {
+  fn from(home_id: HomeId) -> Self {
+    home_id.to_string()
+  }
+}
+
+impl TryFrom<String> for HomeId {
+  type Error = IxaError;
+
+  fn try_from(home_id: String) -> Result<Self, Self::Error> {
+    HomeId::parse(home_id.as_bytes())
+  }
+}
+
+/// An 11 digit census tract GEOID: two digits of state, three of county, and six of tract. Since every GEOID has the
+/// same width, the integer is enough to keep leading zeros. It displays and serializes as the zero-padded GEOID.
+#[derive(Component, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, PartialOrd, Ord, Hash, Debug)]
+#[serde(into = "String", try_from = "String")]
+pub struct CensusTract(pub u64);
+
+impl CensusTract {
+  /// The number of digits of a census tract GEOID.
+  pub const DIGITS: usize = 11;
+
+  /// Parses a census tract GEOID, which must have exactly `CensusTract::DIGITS` ASCII digits.
+  pub fn parse(bytes: &[u8]) -> Result<Self, IxaError> {
+    if bytes.len() != Self::DIGITS {
+      return Err(IxaError::IxaError(format!(
+        "census tract {:?} must have {} digits, but has {}",
+        String::from_utf8_lossy(bytes),
+        Self::DIGITS,
+        bytes.len()
+      )));
+    }
+    Ok(CensusTract(parse_digits(bytes, "census tract")?))
+  }
+}
+
+impl Display for CensusTract {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{:0width$}", self.0, width = Self::DIGITS)
+  }
+}
+
+impl From<CensusTract> for String {
+  fn from(census_tract: CensusTract) -> Self {
+    census_tract.to_string()
+  }
+}
+
+impl TryFrom<String> for CensusTract {
+  type Error = IxaError;
+
+  fn try_from(census_tract: String) -> Result<Self, Self::Error> {
+    CensusTract::parse(census_tract.as_bytes())
+  }
+}
+
+/// Parses a string of ASCII digits (no sign or whitespace) that fits in a `u64`. `what` names it in errors.
+fn parse_digits(bytes: &[u8], what: &str) -> Result<u64, IxaError> {
+  if !bytes.iter().all(u8::is_ascii_digit) {
+    return Err(IxaError::IxaError(format!(
+      "{what} {:?} must have only digits",
+      String::from_utf8_lossy(bytes)
+    )));
+  }
+  bytes.iter().try_fold(0u64, |value, digit| value.checked_mul(10)?.checked_add(u64::from(digit - b'0')))
+       .ok_or_else(|| IxaError::IxaError(format!("{what} {:?} is too large", String::from_utf8_lossy(bytes))))
+}
 
 #[derive(Component, Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug)]
-pub struct Alive(bool);
+pub struct Alive(pub bool);
 
 impl Default for Alive {
   fn default() -> Self {
     Alive(true)
   }
 }
+
+/// How often a person initiates contacts, relative to the population average of 1. A person's number of infection
+/// attempts is scaled by it, so people with a high contact rate are superspreaders. People without one have rate 1.
+#[derive(Component, Serialize, Deserialize, Copy, Clone, PartialEq, PartialOrd, Debug)]
+pub struct ContactRate(pub f64);
+
+impl Default for ContactRate {
+  fn default() -> Self {
+    ContactRate(1.0)
+  }
+}
@@ -6,29 +6,63 @@ a path to an input file has not been provided, the `PopulationLoader` will look
 
 This module is a little different from the others in that it adds no resources or systems, only entities.
 
+Each person's `homeId` is parsed with `HomeId::parse`, and their `CensusTract` is its first 11 digits. A malformed id,
+one that is too short or too long or has anything but digits, fails the load with an error naming the id.
+
+The input file may be gzip-compressed. A file is treated as compressed if its extension is `.gz` or if it begins
+with the gzip magic bytes. Reading compressed input requires the `gzip` feature.
+
+The header is checked before any row is read: it must have the columns `age` and `homeId`, in any order, and may have
+others, which are ignored. A file without them, including one without a header row at all, fails the load with an error
+listing the missing columns and the header found. Each row is then checked as it is read: it must have as many fields
+as the header, its age must be a whole number from 0 to 255, and its `homeId` must be present and well formed. What a
+bad row does is governed by the `BadRowPolicy`: by default it fails the load with an error naming its line, and with
+`BadRowPolicy::Skip` it is skipped and the load goes on, with one model warning (see `ModelWarnings`) listing the rows
+skipped.
+
+After loading, the loader can check that every person it spawned has all of the `PersonComponents`, failing with a
+list of the people missing any. The check is on by default in debug builds and can be turned on or off with
+`PopulationLoader::with_validation`.
+
 */
 
 use std::{
   fs::File,
-  path::PathBuf
+  io::{BufRead, BufReader, Read},
+  path::{Path, PathBuf}
 };
-use serde::Deserialize;
-use csv::ReaderBuilder;
+use serde::{Deserialize, Serialize};
+use csv::{ByteRecord, ErrorKind, ReaderBuilder};
 
 use bevy_ecs::{
+  component::ComponentId,
   prelude::*,
   schedule::SystemConfigs
 };
 use ecs_disease_models::{
   errors::IxaError,
+  infection_times::LifeHistory,
+  model::ModelWarnings,
   module::Module
 };
 use crate::{
   parameters::Parameters,
-  person::{Age, CensusTract, HomeId}
+  person::{Age, HomeId, PersonComponents}
 };
 use crate::person::{Alive, InfectionStatus};
 
+/// The first two bytes of every gzip stream.
+const GZIP_MAGIC_BYTES: [u8; 2] = [0x1f, 0x8b];
+
+/// At most this many people missing components are listed in a validation error.
+const MAX_LISTED_PEOPLE: usize = 10;
+
+/// At most this many skipped rows are listed in the warning about them.
+const MAX_LISTED_ROWS: usize = 10;
+
+/// The columns the input file must have.
+const REQUIRED_COLUMNS: [&str; 2] = ["age", "homeId"];
+
 /// A person record as read from the input file. This is immediately parsed into components to become an entity.
 #[derive(Deserialize, Debug)]
 #[allow(non_snake_case)]
@@ -37,60 +71,264 @@ pub struct PeopleRecord<'a> {
   homeId: &'a [u8],
 }
 
+/// What the `PopulationLoader` does with a row of the input file that is not a valid person.
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Default, Debug)]
+#[serde(rename_all = "snake_case")]
+pub enum BadRowPolicy {
+  /// Fail the load with an error naming the row.
+  #[default]
+  Fail,
+  /// Skip the row, and warn about every row skipped once the file is loaded.
+  Skip,
+}
+
 pub struct PopulationLoader{
   input_file: Option<PathBuf>,
+  /// Whether to check that every loaded person has all of the `PersonComponents`.
+  validate: bool,
+  bad_row_policy: BadRowPolicy,
 }
 
 impl PopulationLoader {
   pub fn new() -> Self {
     PopulationLoader{
       input_file: None,
+      validate: cfg!(debug_assertions),
+      bad_row_policy: BadRowPolicy::default(),
     }
   }
 
+  /// Sets what a bad row of the input file does. See the module documentation.
+  pub fn with_bad_row_policy(mut self, bad_row_policy: BadRowPolicy) -> Self {
+    self.bad_row_policy = bad_row_policy;
+    self
+  }
+
+  /// Turns the check that every loaded person has all of the `PersonComponents` on or off.
+  pub fn with_validation(mut self, validate: bool) -> Self {
+    self.validate = validate;
+    self
+  }
+
   /// Parse a person record and insert it into the world.
   fn create_person_entity_from_record(
     world: &mut World,
     person_record: &PeopleRecord,
-  ) -> Result<(), IxaError> {
-    let tract: String = String::from_utf8(person_record.homeId[..11].to_owned())?;
-    let home_id: String = String::from_utf8(person_record.homeId.to_owned())?;
+  ) -> Result<Entity, IxaError> {
+    if person_record.homeId.is_empty() {
+      return Err(IxaError::IxaError("the homeId is empty".to_string()));
+    }
+    let home_id = HomeId::parse(person_record.homeId)?;
 
-    world.spawn((
+    let person = world.spawn((
       Age(person_record.age),
-      HomeId(home_id.parse()?),
-      CensusTract(tract.parse()?),
+      home_id,
+      home_id.census_tract(),
       Alive::default(),
-      InfectionStatus::default()
+      InfectionStatus::default(),
+      LifeHistory::default()
     ));
 
-    Ok(())
+    Ok(person.id())
+  }
+
+  /// Checks that `headers` has every one of the `REQUIRED_COLUMNS`.
+  fn validate_headers(headers: &ByteRecord) -> Result<(), IxaError> {
+    let missing: Vec<&str> = REQUIRED_COLUMNS.into_iter()
+                                             .filter(|column| !headers.iter().any(|header| header == column.as_bytes()))
+                                             .collect();
+    if missing.is_empty() {
+      return Ok(());
+    }
+    Err(IxaError::IxaError(format!(
+      "the population file is missing the column(s) {:?}; its header is {:?}",
+      missing,
+      headers.iter().map(String::from_utf8_lossy).collect::<Vec<_>>()
+    )))
+  }
+
+  /// Deserializes `raw_record`, with `headers`, into a person and inserts them into the world, failing with an error
+  /// that names the offending column and value if it can.
+  fn create_person_entity_from_raw_record(
+    world: &mut World,
+    headers: &ByteRecord,
+    raw_record: &ByteRecord,
+  ) -> Result<Entity, IxaError> {
+    let record: PeopleRecord = raw_record.deserialize(Some(headers)).map_err(|error| {
+      let csv::ErrorKind::Deserialize{ err, .. } = error.kind() else {
+        return IxaError::CsvError(error);
+      };
+      match err.field().and_then(|field| Some((headers.get(field as usize)?, raw_record.get(field as usize)?))) {
+        Some((column, value)) => IxaError::IxaError(format!(
+          "invalid {} {:?}: {}",
+          String::from_utf8_lossy(column),
+          String::from_utf8_lossy(value),
+          err.kind()
+        )),
+        None => IxaError::IxaError(err.kind().to_string()),
+      }
+    })?;
+    Self::create_person_entity_from_record(world, &record)
+  }
+
+  /// Checks that every one of `people` has all of the components in the bundle `B`, listing the people that don't.
+  pub fn validate_components<B: Bundle>(world: &mut World, people: &[Entity]) -> Result<(), IxaError> {
+    let expected: Vec<ComponentId> = world.register_bundle::<B>().explicit_components().to_vec();
+    let component_name = |id: ComponentId| {
+      let name = world.components().get_name(id).unwrap_or("unknown");
+      name.rsplit("::").next().unwrap_or(name).to_string()
+    };
+
+    let incomplete: Vec<String> = people
+        .iter()
+        .filter_map(|&person| {
+          let missing: Vec<String> = match world.get_entity(person) {
+            Ok(entity) => expected.iter().copied().filter(|&id| !entity.contains_id(id)).map(component_name).collect(),
+            Err(_) => vec!["the entity itself".to_string()],
+          };
+          (!missing.is_empty()).then(|| format!("{person} (missing {})", missing.join(", ")))
+        })
+        .collect();
+
+    if incomplete.is_empty() {
+      return Ok(());
+    }
+    let more = match incomplete.len().saturating_sub(MAX_LISTED_PEOPLE) {
+      0 => String::new(),
+      more => format!(", and {more} more"),
+    };
+    Err(IxaError::IxaError(format!(
+      "{} loaded people are missing components: {}{more}",
+      incomplete.len(),
+      incomplete[..incomplete.len().min(MAX_LISTED_PEOPLE)].join("; ")
+    )))
+  }
+
+  /// Opens the input file, transparently decompressing it if it is gzip-compressed.
+  fn open_input(path: &Path) -> Result<Box<dyn Read>, IxaError> {
+    let mut reader = BufReader::new(File::open(path)?);
+
+    // Sniff the magic bytes without consuming them.
+    let is_gzip = path.extension().is_some_and(|extension| extension == "gz")
+        || reader.fill_buf()?.starts_with(&GZIP_MAGIC_BYTES);
+
+    if !is_gzip {
+      return Ok(Box::new(reader));
+    }
+
+    #[cfg(feature = "gzip")]
+    {
+      Ok(Box::new(flate2::read::GzDecoder::new(reader)))
+    }
+
+    #[cfg(not(feature = "gzip"))]
+    {
+      Err(IxaError::IxaError(format!(
+        "{} is gzip-compressed, but the `gzip` feature is not enabled",
+        path.display()
+      )))
+    }
   }
 
   /// Loads the population data from the CSV file into the world.
-  fn load_population_data(&self, world: &mut World) -> Result<(), IxaError> {
-    let file = // Open a file using either self or the path in the global parameters
+  pub fn load_population_data(&self, world: &mut World) -> Result<(), IxaError> {
+    let input = // Open a file using either self or the path in the global parameters
         {
           if let Some(input_file_path) = &self.input_file {
-            File::open(input_file_path)?
+            Self::open_input(input_file_path)?
           } else {
             let parameters = world
                 .get_resource::<Parameters>()
                 .ok_or(IxaError::IxaError("no input file provided or global Parameters object".to_string()))?;
-            File::open(&parameters.synth_population_file)?
+            Self::open_input(&parameters.synth_population_file)?
           }
         };
+    self.load_from_reader(world, input)
+  }
 
-
+  /// Loads the population data from CSV read from `input` into the world. See the module documentation.
+  pub fn load_from_reader(&self, world: &mut World, input: impl Read) -> Result<(), IxaError> {
     let mut csv_reader = ReaderBuilder::new()
         .has_headers(true)  // CSV has headers
-        .from_reader(file);
+        .from_reader(input);
+
+    // `PeopleRecord` borrows from the raw record, so we reuse a single `ByteRecord` buffer rather than
+    // using `csv_reader.deserialize()`, which requires an owned record type.
+    let headers = csv_reader.byte_headers()?.clone();
+    Self::validate_headers(&headers)?;
+    let mut raw_record = ByteRecord::new();
 
+    // We don't know the row count without a second pass over the file, so we show a spinner rather than a bar.
+    #[cfg(feature = "indicatif")]
+    let spinner = {
+      let spinner = indicatif::ProgressBar::new_spinner();
+      spinner.set_style(
+        indicatif::ProgressStyle::with_template("{spinner} Loading population: {human_pos} people")
+            .expect("progress bar template is valid")
+      );
+      spinner
+    };
+
+    let mut people = Vec::new();
+    let mut skipped = Vec::new();
     // Deserialize each record into a PeopleRecord
-    for result in csv_reader.deserialize() {
-      let record: PeopleRecord = result?;
-      // Insert into world
-      Self::create_person_entity_from_record(world, &record)?;
+    loop {
+      let person = match csv_reader.read_byte_record(&mut raw_record) {
+        Ok(false) => break,
+        // Insert into world
+        Ok(true) => Self::create_person_entity_from_raw_record(world, &headers, &raw_record).map_err(|error| {
+          let line = raw_record.position().map_or(0, |position| position.line());
+          format!("line {line}: {}", match error {
+            IxaError::IxaError(message) => message,
+            error => error.to_string(),
+          })
+        }),
+        Err(error) => match error.kind() {
+          // A row with the wrong number of fields is a bad row like any other, and the reader goes on after it.
+          ErrorKind::UnequalLengths { pos, expected_len, len } => {
+            let line = pos.as_ref().map_or(0, |position| position.line());
+            Err(format!("line {line}: it has {len} field(s) where the header has {expected_len}"))
+          }
+          _ => return Err(error.into()),
+        },
+      };
+      let person = match person {
+        Ok(person) => person,
+        Err(error) => {
+          match self.bad_row_policy {
+            BadRowPolicy::Fail => return Err(IxaError::IxaError(format!("bad row in the population file, {error}"))),
+            BadRowPolicy::Skip => {
+              skipped.push(error);
+              continue;
+            }
+          }
+        }
+      };
+      if self.validate {
+        people.push(person);
+      }
+
+      #[cfg(feature = "indicatif")]
+      spinner.inc(1);
+    }
+
+    #[cfg(feature = "indicatif")]
+    spinner.finish();
+
+    if !skipped.is_empty() {
+      let more = match skipped.len().saturating_sub(MAX_LISTED_ROWS) {
+        0 => String::new(),
+        more => format!(", and {more} more"),
+      };
+      ModelWarnings::warn(world, format!(
+        "skipped {} bad row(s) of the population file: {}{more}",
+        skipped.len(),
+        skipped[..skipped.len().min(MAX_LISTED_ROWS)].join("; ")
+      ));
+    }
+
+    if self.validate {
+      Self::validate_components::<PersonComponents>(world, &people)?;
     }
 
     Ok(())
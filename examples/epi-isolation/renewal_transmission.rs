@@ -0,0 +1,160 @@
+/*!
+
+Transmission as a renewal process, the branching-process model that `r_0` and `generation_interval` describe. It is
+an alternative to the `TransmissionManager`, and a model adds one or the other (see `Parameters::transmission`).
+
+As with the `TransmissionManager`, a newly infected person is given a number of infection attempts drawn from a Poisson
+distribution with mean `r_0` times their `ContactRate`, each after a delay drawn from the generation interval
+distribution, and they recover at the time of their last attempt. Here, though, every attempt is a secondary case:
+
+- By default, an attempt infects a susceptible person chosen uniformly at random from the population, regardless of
+  their susceptibility, with `random_entity_where`. Once nobody is susceptible, attempts infect nobody.
+- With `RenewalTransmission::spawning`, an attempt spawns a new person who is infected, as if the population were
+  infinite. The new person is given the `Age`, `HomeId`, `CensusTract`, and `Alive` of the infector, those of them the
+  infector has, so that they show in the reports by age and census tract like the infector would. They have the
+  average contact rate. Above an `r_0` of 1, the number of cases then grows without bound, so `spawning` takes a cap
+  on the number of cases, including the initial ones, and the model finishes once it is reached, as with
+  `Model::stop_after_infections`.
+
+Either way, a person infects `r_0` people on average while there are susceptible people to infect. A person who is
+`Isolated` by contact tracing infects nobody at their attempts. Transmission can be turned off during a run by removing
+the `RenewalTransmission` resource: the attempts already scheduled then infect nobody.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use ecs_disease_models::{
+  infection_times::record_infection,
+  model::{AbortReason, ExecutionPhase},
+  module::Module,
+  random::{random_entity_where, RngResource},
+  status_transition::StatusTransitions,
+  stop_condition::InfectionCap,
+  timeline::Timeline,
+  transmission_tree::TransmissionTree
+};
+
+use crate::{
+  contact_tracing::Isolated,
+  parameters::Parameters,
+  person::{Age, Alive, CensusTract, ContactRate, HomeId, InfectionStatus},
+  transmission_manager::{schedule_attempts, GenerationInterval}
+};
+
+/// Transmission in which every infection attempt is a secondary case. See the module documentation.
+#[derive(Resource, Copy, Clone, Default, Debug)]
+pub struct RenewalTransmission {
+  /// The cap on the number of cases if secondary cases are spawned, or `None` if they are infected from the population.
+  spawned_case_cap: Option<usize>,
+}
+
+impl RenewalTransmission {
+  /// Infects susceptible people from the population.
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Spawns a new person for every secondary case instead of infecting someone from the population, and finishes the
+  /// model once there have been `case_cap` cases.
+  #[must_use]
+  pub fn spawning(mut self, case_cap: usize) -> Self {
+    self.spawned_case_cap = Some(case_cap);
+    self
+  }
+}
+
+/// A system that schedules the infection attempts and recovery of every newly infected person.
+fn schedule_renewals(
+  mut timeline: ResMut<Timeline>,
+  mut rng: ResMut<RngResource>,
+  generation_interval: Res<GenerationInterval>,
+  parameters: Res<Parameters>,
+  mut transitions: StatusTransitions<InfectionStatus>,
+  contact_rates: Query<&ContactRate>,
+  mut commands: Commands,
+) {
+  for transition in transitions.iter() {
+    if transition.to != InfectionStatus::Infected {
+      continue;
+    }
+    let infector = transition.entity;
+    let mean_attempts = parameters.r_0 * contact_rates.get(infector).copied().unwrap_or_default().0;
+    let scheduled = schedule_attempts(
+      &mut timeline,
+      &mut rng,
+      infector,
+      mean_attempts,
+      generation_interval.0,
+      infect_secondary_case
+    );
+    if let Err(error) = scheduled {
+      let reason = format!("the infection attempts of entity {infector} could not be scheduled: {error}");
+      commands.queue(move |world: &mut World| AbortReason::abort(world, reason));
+      return;
+    }
+  }
+}
+
+/// Unless `infector` is isolated or the `RenewalTransmission` was removed, infects a new secondary case of `infector`.
+fn infect_secondary_case(world: &mut World, infector: Entity) {
+  let Some(&renewal_transmission) = world.get_resource::<RenewalTransmission>() else {
+    return;
+  };
+  if world.get::<Isolated>(infector).is_some() {
+    return;
+  }
+
+  let now = world.resource::<Timeline>().now();
+  let infectee = if renewal_transmission.spawned_case_cap.is_some() {
+    let infectee = world.spawn(InfectionStatus::Infected).id();
+    copy_component::<Age>(world, infector, infectee);
+    copy_component::<HomeId>(world, infector, infectee);
+    copy_component::<CensusTract>(world, infector, infectee);
+    copy_component::<Alive>(world, infector, infectee);
+    infectee
+  } else {
+    let susceptible = |status: &InfectionStatus| *status == InfectionStatus::Susceptible;
+    let Some(infectee) = random_entity_where(world, susceptible) else {
+      return;
+    };
+    *world.get_mut::<InfectionStatus>(infectee).unwrap() = InfectionStatus::Infected;
+    infectee
+  };
+  record_infection(world, infectee, now);
+  TransmissionTree::record_infection(world, Some(infector), infectee, now);
+
+  #[cfg(feature = "print_messages")]
+  println!("Entity {} infected entity {} at time {:.4}", infector, infectee, now);
+}
+
+/// Gives `to` a copy of the `C` component of `from`, if `from` has one.
+fn copy_component<C: Component + Copy>(world: &mut World, from: Entity, to: Entity) {
+  if let Some(&component) = world.get::<C>(from) {
+    world.entity_mut(to).insert(component);
+  }
+}
+
+impl Module for RenewalTransmission {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+    world.init_resource::<TransmissionTree>();
+    GenerationInterval::initialize(world);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module RenewalTransmission");
+
+    // In the `First` phase, for the same reason as the `TransmissionManager`'s system.
+    let schedule_renewals = schedule_renewals.in_set(ExecutionPhase::First);
+    match self.spawned_case_cap {
+      Some(case_cap) => InfectionCap::new(InfectionStatus::Infected, case_cap)
+          .initialize_with_world(world)
+          .map(|check_case_cap| (schedule_renewals, check_case_cap).into_configs()),
+      None => Some(schedule_renewals.into_configs()),
+    }
+  }
+}
@@ -0,0 +1,127 @@
+/*!
+
+Heterogeneous susceptibility. A `SusceptibilityProfile` is a list of rules, each of which applies a multiplier to the
+per-contact infection probability of the people it matches. A rule matches by age band (inclusive; either end may be
+left open), by census tract, or both. A person's multiplier is the product of the multipliers of every rule that
+matches them, so an age band rule and a tract rule compose, and a person no rule matches has multiplier 1.
+
+The profile multiplies the per-contact infection probability in the transmission manager. Any other per-contact
+weight, such as an entry of a mixing matrix, composes the same way: the probability of infection on contact is the
+product of all of the weights and multipliers, clamped to `[0, 1]`.
+
+A profile is read from JSON, as an array of rules, or from CSV with the columns `min_age`, `max_age`, `census_tract`,
+and `multiplier`, where any of the first three may be empty. For example:
+
+```csv
+min_age,max_age,census_tract,multiplier
+0,4,,1.5
+65,,,2.0
+,,36061000100,0.5
+```
+
+*/
+
+use std::{
+  fs::File,
+  io::Read,
+  path::Path
+};
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::{Deserialize, Serialize};
+
+use ecs_disease_models::{
+  errors::IxaError,
+  module::Module
+};
+
+/// A multiplier applied to everyone matching the age band and census tract. Unset fields match everyone.
+#[derive(Serialize, Deserialize, Copy, Clone, PartialEq, Debug)]
+pub struct SusceptibilityRule {
+  #[serde(default)]
+  pub min_age     : Option<u8>,
+  #[serde(default)]
+  pub max_age     : Option<u8>,
+  #[serde(default)]
+  pub census_tract: Option<u64>,
+  pub multiplier  : f64,
+}
+
+impl SusceptibilityRule {
+  fn matches(&self, age: Option<u8>, census_tract: Option<u64>) -> bool {
+    let age_matches = match age {
+      Some(age) => {
+        self.min_age.is_none_or(|min_age| age >= min_age) && self.max_age.is_none_or(|max_age| age <= max_age)
+      }
+      None => self.min_age.is_none() && self.max_age.is_none(),
+    };
+    let tract_matches = self.census_tract.is_none() || self.census_tract == census_tract;
+    age_matches && tract_matches
+  }
+}
+
+/// Multipliers of the per-contact infection probability by age band and census tract.
+#[derive(Resource, Clone, PartialEq, Default, Debug)]
+pub struct SusceptibilityProfile {
+  rules: Vec<SusceptibilityRule>,
+}
+
+impl SusceptibilityProfile {
+  /// Creates a profile from `rules`, checking that every multiplier is non-negative.
+  pub fn new(rules: Vec<SusceptibilityRule>) -> Result<Self, IxaError> {
+    if let Some(rule) = rules.iter().find(|rule| rule.multiplier.is_nan() || rule.multiplier < 0.0) {
+      return Err(IxaError::IxaError(format!(
+        "susceptibility multipliers must be non-negative, but found {rule:?}"
+      )));
+    }
+    Ok(SusceptibilityProfile{ rules })
+  }
+
+  /// Reads a profile from a CSV file if the extension of `path` is `.csv`, and from a JSON file otherwise.
+  pub fn from_file(path: &Path) -> Result<Self, IxaError> {
+    let mut file = File::open(path)?;
+    if path.extension().is_some_and(|extension| extension.eq_ignore_ascii_case("csv")) {
+      Self::from_csv(file)
+    } else {
+      let mut json = String::new();
+      file.read_to_string(&mut json)?;
+      Self::from_json(&json)
+    }
+  }
+
+  /// Parses a profile from a JSON array of rules.
+  pub fn from_json(json: &str) -> Result<Self, IxaError> {
+    Self::new(serde_json::from_str(json)?)
+  }
+
+  /// Reads a profile from CSV with a header row.
+  pub fn from_csv(reader: impl Read) -> Result<Self, IxaError> {
+    let rules = csv::Reader::from_reader(reader)
+        .deserialize()
+        .collect::<Result<Vec<SusceptibilityRule>, csv::Error>>()?;
+    Self::new(rules)
+  }
+
+  /// The multiplier for a person of the given age and census tract, either of which may be unknown.
+  pub fn multiplier(&self, age: Option<u8>, census_tract: Option<u64>) -> f64 {
+    self.rules
+        .iter()
+        .filter(|rule| rule.matches(age, census_tract))
+        .map(|rule| rule.multiplier)
+        .product()
+  }
+}
+
+impl Module for SusceptibilityProfile {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module SusceptibilityProfile");
+
+    None // No systems
+  }
+}
@@ -0,0 +1,208 @@
+/*!
+
+The _transmission manager_ is the business logic related to how infections spread from person to person.
+
+When a person becomes infected, they are given a number of infection attempts drawn from a Poisson distribution with
+mean `r_0` times their `ContactRate` (1 if they have none), each at a time after their infection drawn from an
+exponential distribution with mean `generation_interval`. They recover at the time of their last attempt (immediately,
+if they have none).
+
+At each attempt, the infectious person contacts someone chosen uniformly at random from the rest of the population.
+If the contact is susceptible, they are infected with probability given by their `SusceptibilityProfile` multiplier,
+clamped to `[0, 1]`. Without a `SusceptibilityProfile`, every susceptible contact is infected.
+
+A person who is `Isolated` by contact tracing makes no contact at their attempts. See `ContactTracing`.
+
+Since a contact may already be infected or may escape infection, a person infects fewer than `r_0` people on average,
+and fewer still as the susceptible population is depleted. The `RenewalTransmission` is the alternative in which every
+attempt is a secondary case.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use rand::distr::StandardUniform;
+use rand_distr::{Exp, Poisson};
+
+use ecs_disease_models::{
+  distributions::Distributions,
+  errors::IxaError,
+  infection_times::{record_infection, record_recovery},
+  model::{AbortReason, ExecutionPhase},
+  module::Module,
+  random::RngResource,
+  status_transition::StatusTransitions,
+  timeline::{Time, Timeline},
+  timeline_event::Event,
+  transmission_tree::TransmissionTree,
+  units::{Duration, Rate}
+};
+
+use crate::{
+  contact_tracing::Isolated,
+  parameters::Parameters,
+  person::{Age, CensusTract, ContactRate, InfectionStatus},
+  susceptibility::SusceptibilityProfile
+};
+
+/// A system that schedules the infection attempts and recovery of every newly infected person.
+fn schedule_infection_attempts(
+  mut timeline: ResMut<Timeline>,
+  mut rng: ResMut<RngResource>,
+  generation_interval: Res<GenerationInterval>,
+  parameters: Res<Parameters>,
+  mut transitions: StatusTransitions<InfectionStatus>,
+  contact_rates: Query<&ContactRate>,
+  mut commands: Commands,
+) {
+  for transition in transitions.iter() {
+    if transition.to != InfectionStatus::Infected {
+      continue;
+    }
+    let infector = transition.entity;
+    let mean_attempts = parameters.r_0 * contact_rates.get(infector).copied().unwrap_or_default().0;
+    let scheduled = schedule_attempts(
+      &mut timeline,
+      &mut rng,
+      infector,
+      mean_attempts,
+      generation_interval.0,
+      attempt_infection
+    );
+    if let Err(error) = scheduled {
+      let reason = format!("the infection attempts of entity {infector} could not be scheduled: {error}");
+      commands.queue(move |world: &mut World| AbortReason::abort(world, reason));
+      return;
+    }
+  }
+}
+
+/// The distribution of the generation interval, the time from a person's infection to each of their infection attempts.
+/// It is constructed from the `Parameters` once, when the `TransmissionManager` or the `RenewalTransmission` is
+/// initialized, rather than for every infected person.
+#[derive(Resource, Copy, Clone, Debug)]
+pub struct GenerationInterval(pub Exp<f64>);
+
+impl GenerationInterval {
+  /// Inserts the generation interval distribution of the `Parameters` into `world`, aborting the model if there are no
+  /// `Parameters` or their generation interval is not a positive, finite duration.
+  pub fn initialize(world: &mut World) {
+    let Some(parameters) = world.get_resource::<Parameters>() else {
+      let reason = "the generation interval is read from the `Parameters`, but there are none".to_string();
+      AbortReason::abort(world, reason);
+      return;
+    };
+    let rate = Rate::from_mean(parameters.generation_interval);
+    match Distributions::validated_exp(rate, "the generation interval rate") {
+      Ok(generation_interval) => world.insert_resource(GenerationInterval(generation_interval)),
+      Err(error) => AbortReason::abort(world, format!("the generation interval is invalid: {error}")),
+    }
+  }
+}
+
+/// Schedules a number of `attempt`s by `infector`, infected now, drawn from a Poisson distribution with mean
+/// `mean_attempts`, each at a time after now drawn from the `generation_interval` distribution, and their recovery at
+/// the time of the last attempt (now, if there are none). Fails if the `Timeline` refuses any of the events. The
+/// `RenewalTransmission` shares this with the `TransmissionManager`.
+pub fn schedule_attempts(
+  timeline: &mut Timeline,
+  rng: &mut RngResource,
+  infector: Entity,
+  mean_attempts: f64,
+  generation_interval: Exp<f64>,
+  attempt: fn(&mut World, Entity),
+) -> Result<(), IxaError> {
+  let now = timeline.now();
+  // `Poisson` requires a positive mean.
+  let attempt_count = if mean_attempts > 0.0 {
+    rng.sample_labeled(now, "attempt_count", Poisson::new(mean_attempts).unwrap()) as usize
+  } else {
+    0
+  };
+  let mut attempt_times: Vec<Time> = (0..attempt_count)
+      .map(|_| now + Duration(rng.sample_labeled(now, "generation_interval", generation_interval)))
+      .collect();
+  attempt_times.sort();
+
+  let recovery_time = attempt_times.last().copied().unwrap_or(now);
+  for time in attempt_times {
+    let event = Event::builder(time)
+        .command(move |world| attempt(world, infector))
+        .entity(infector)
+        .label("infection_attempt")
+        .build();
+    timeline.push(event)?;
+  }
+  let recovery = Event::builder(recovery_time)
+      .fallible_command(move |world| {
+        let mut status = world.get_mut::<InfectionStatus>(infector).ok_or_else(|| IxaError::IxaError(format!(
+          "entity {infector} was removed before it was recovered"
+        )))?;
+        *status = InfectionStatus::Recovered;
+        let now = world.resource::<Timeline>().now();
+        record_recovery(world, infector, now);
+        Ok(())
+      })
+      .entity(infector)
+      .label("recovery")
+      .build();
+  timeline.push(recovery)
+}
+
+/// Unless `infector` is isolated, contacts a random person other than `infector`, infecting them if they are
+/// susceptible and the draw succeeds.
+fn attempt_infection(world: &mut World, infector: Entity) {
+  if world.get::<Isolated>(infector).is_some() {
+    return;
+  }
+  let now = world.resource::<Timeline>().now();
+  // Chosen in a single pass over the people, without collecting them.
+  let mut people = world.query_filtered::<Entity, With<InfectionStatus>>();
+  let Some((contact, infection_draw)) = world.resource_scope(|world, mut rng: Mut<RngResource>| {
+    let contact = rng.sample_one(now, "contact", people.iter(world).filter(|&person| person != infector))?;
+    let infection_draw: f64 = rng.sample_labeled(now, "infection", StandardUniform);
+    Some((contact, infection_draw))
+  }) else {
+    return;
+  };
+
+  if world.get::<InfectionStatus>(contact) != Some(&InfectionStatus::Susceptible) {
+    return;
+  }
+
+  let probability = match world.get_resource::<SusceptibilityProfile>() {
+    Some(profile) => profile.multiplier(
+      world.get::<Age>(contact).map(|age| age.0),
+      world.get::<CensusTract>(contact).map(|tract| tract.0),
+    ),
+    None => 1.0,
+  };
+  if infection_draw < probability.clamp(0.0, 1.0) {
+    *world.get_mut::<InfectionStatus>(contact).unwrap() = InfectionStatus::Infected;
+    record_infection(world, contact, now);
+    TransmissionTree::record_infection(world, Some(infector), contact, now);
+
+    #[cfg(feature = "print_messages")]
+    println!("Entity {} infected entity {} at time {:.4}", infector, contact, now);
+  }
+}
+
+#[derive(Resource, Copy, Clone, Default, Debug)]
+pub struct TransmissionManager;
+
+impl Module for TransmissionManager {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+    world.init_resource::<TransmissionTree>();
+    GenerationInterval::initialize(world);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module TransmissionManager");
+
+    // In the `First` phase, so that the events of people infected in the last iteration (or before the run) are
+    // scheduled before the `Timeline` pops the next event.
+    Some(schedule_infection_attempts.in_set(ExecutionPhase::First))
+  }
+}
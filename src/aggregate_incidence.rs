@@ -0,0 +1,246 @@
+/*!
+
+The `AggregateIncidenceReporter` module writes an epidemic curve: the number of new cases in each reporting window,
+one `(window_start, new_cases)` row per window. A new case is a transition of the compartment component `C` into the
+target value, e.g. into `InfectionStatus::Infected`. Following `StatusTransitions::iter`, an entity that is first seen
+already in the target compartment, like a person spawned infected, is a new case too.
+
+Windows are `period` long and start when the module is initialized. A window is written by an event scheduled on the
+`Timeline` at its end, so every window is written, including windows without any new cases, which get a row with a count
+of 0. After writing a window, the reporter schedules the next one only if events other than the windows of aggregate
+incidence reporters are still pending, so the report ends with the window in which the last other event happened,
+however many reporters there are. If the model stops early, e.g. because of a `StopCondition`, the window in progress is
+not written. An event at exactly the end of a window may be counted in either window, depending on the order in which
+the `Timeline` pops events with equal times. If a window cannot be written, the model is aborted.
+
+*/
+
+use std::path::Path;
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::Serialize;
+
+use crate::{
+  errors::IxaError,
+  model::{AbortReason, ExecutionPhase},
+  module::Module,
+  report::{Reporter, ReporterConfiguration},
+  status_transition::StatusTransitions,
+  timeline::{Time, Timeline},
+  timeline_event::{Event, EventCommand},
+  units::Duration
+};
+
+/// A row of the aggregate incidence report.
+#[derive(Serialize, Copy, Clone, PartialEq, Debug)]
+pub struct AggregateIncidenceItem {
+  pub window_start: Time,
+  pub new_cases   : usize,
+}
+
+/// Counts transitions of `C` into a target value per reporting window and writes them to a CSV file.
+#[derive(Resource)]
+pub struct AggregateIncidenceReporter<C> {
+  reporter    : Reporter<()>,
+  target      : C,
+  period      : Duration,
+  window_start: Time,
+  /// New cases in the current window so far.
+  new_cases   : usize,
+}
+
+impl<C> AggregateIncidenceReporter<C>
+  where C: Component + Copy + PartialEq
+{
+  /// Creates an `AggregateIncidenceReporter` with the provided short name that counts transitions into `target` in
+  /// windows of length `period`, which must be positive and finite.
+  pub fn new(short_name: String, target: C, period: Duration) -> Result<Self, IxaError> {
+    if !(period.0 > 0.0 && period.0.is_finite()) {
+      return Err(IxaError::IxaError(format!(
+        "the reporting period must be positive and finite, but is {}",
+        period.0
+      )));
+    }
+    Ok(AggregateIncidenceReporter {
+      reporter: Reporter::new(short_name),
+      target,
+      period,
+      window_start: Time::default(),
+      new_cases: 0,
+    })
+  }
+
+  /// The path of the report file. See `Reporter::output_path`.
+  #[must_use]
+  pub fn output_path(&self) -> Option<&Path> {
+    self.reporter.output_path()
+  }
+}
+
+/// The label of the events that end a window, by which the reporters tell them from other events.
+const WINDOW_END_LABEL: &str = "aggregate_incidence";
+
+/// Schedules the end of the window starting at `window_start`. Fails if the `Timeline` refuses the event.
+fn schedule_window_end<C>(timeline: &mut Timeline, window_start: Time, period: Duration) -> Result<(), IxaError>
+  where C: Component + Copy + PartialEq
+{
+  timeline.push(Event{
+    time: window_start + period,
+    command: EventCommand::fallible(write_window::<C>),
+    entity: None,
+    label: WINDOW_END_LABEL,
+  })
+}
+
+/// Adds the transitions into the target since the last iteration to the current window.
+fn count_new_cases<C>(
+  mut aggregate_reporter: ResMut<AggregateIncidenceReporter<C>>,
+  mut transitions: StatusTransitions<C>,
+)
+  where C: Component + Copy + PartialEq
+{
+  let target = aggregate_reporter.target;
+  aggregate_reporter.new_cases += transitions.iter().filter(|transition| transition.to == target).count();
+}
+
+/// Writes the current window and starts the next one.
+fn write_window<C>(world: &mut World) -> Result<(), IxaError>
+  where C: Component + Copy + PartialEq
+{
+  // This event has already been popped. The windows of other reporters, including those for other components, do not
+  // count, or the reporters would keep each other going forever.
+  let events_pending = world.resource::<Timeline>().pending().any(|(_, _, label)| label != WINDOW_END_LABEL);
+
+  let mut aggregate_reporter = world.resource_mut::<AggregateIncidenceReporter<C>>();
+  let item = AggregateIncidenceItem{
+    window_start: aggregate_reporter.window_start,
+    new_cases: aggregate_reporter.new_cases,
+  };
+  aggregate_reporter.reporter.write_row(item)?;
+  aggregate_reporter.window_start = aggregate_reporter.window_start + aggregate_reporter.period;
+  aggregate_reporter.new_cases = 0;
+
+  if events_pending {
+    let (window_start, period) = (aggregate_reporter.window_start, aggregate_reporter.period);
+    schedule_window_end::<C>(&mut world.resource_mut::<Timeline>(), window_start, period)?;
+  }
+  Ok(())
+}
+
+impl<C> Module for AggregateIncidenceReporter<C>
+  where C: Component + Copy + PartialEq
+{
+  fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs> {
+    #[cfg(feature = "print_messages")]
+    println!("Initializing module AggregateIncidenceReporter");
+
+    let config = ReporterConfiguration::for_reporter(world);
+    self.reporter.initialize(config).expect("Failed to initialize AggregateIncidenceReporter");
+
+    let mut timeline = world.resource_mut::<Timeline>();
+    self.window_start = timeline.now();
+    let scheduled = schedule_window_end::<C>(&mut timeline, self.window_start, self.period);
+    world.insert_resource(self);
+    if let Err(error) = scheduled {
+      AbortReason::abort(world, format!("the end of the first reporting window could not be scheduled: {error}"));
+    }
+
+    // In the `Last` phase, so that the count is up to date when the command of a window's end is applied.
+    Some(count_new_cases::<C>.in_set(ExecutionPhase::Last))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use ordered_float::OrderedFloat;
+  use crate::{
+    model::Model,
+    report::FilenamePolicy
+  };
+  use super::*;
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+  enum Status {
+    Susceptible,
+    Infected,
+  }
+
+  #[test]
+  fn test_aggregate_incidence() {
+    let output_directory = std::env::temp_dir().join("ecs_disease_models_tests").join("aggregate_incidence");
+    fs::create_dir_all(&output_directory).unwrap();
+
+    let mut model = Model::with_random_seed(1);
+    model.add_module(ReporterConfiguration::new(String::new(), output_directory, FilenamePolicy::Overwrite));
+    let world = model.world_mut();
+    world.spawn(Status::Infected);
+    let people: Vec<Entity> = (0..5).map(|_| world.spawn(Status::Susceptible).id()).collect();
+    let mut timeline = world.resource_mut::<Timeline>();
+    for (person, time) in people.into_iter().zip([0.5, 0.7, 2.2, 2.4, 2.9]) {
+      timeline.push(Event{
+        time: OrderedFloat(time),
+        command: EventCommand::infallible(move |world: &mut World| {
+          *world.get_mut::<Status>(person).unwrap() = Status::Infected;
+        }),
+        entity: Some(person),
+        label: "infection",
+      }).unwrap();
+    }
+    model.add_module(AggregateIncidenceReporter::new("curve".to_string(), Status::Infected, Duration(1.0)).unwrap());
+    model.run();
+
+    // The person spawned infected counts in the first window, and the empty second window still has a row.
+    let path = model.world().resource::<AggregateIncidenceReporter<Status>>().output_path().unwrap().to_path_buf();
+    model.world_mut().remove_resource::<AggregateIncidenceReporter<Status>>();
+    assert_eq!(fs::read_to_string(path).unwrap(), "window_start,new_cases\n0.0,3\n1.0,0\n2.0,3\n");
+
+    assert!(AggregateIncidenceReporter::new("curve".to_string(), Status::Infected, Duration(0.0)).is_err());
+  }
+
+  #[test]
+  fn test_two_aggregate_incidence_reporters() {
+    #[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+    struct Vaccinated(bool);
+
+    let output_directory = std::env::temp_dir().join("ecs_disease_models_tests").join("two_aggregate_incidences");
+    fs::create_dir_all(&output_directory).unwrap();
+
+    let mut model = Model::with_random_seed(1);
+    model.add_module(ReporterConfiguration::new(String::new(), output_directory, FilenamePolicy::Overwrite));
+    let person = model.world_mut().spawn((Status::Susceptible, Vaccinated(false))).id();
+    let command = EventCommand::infallible(move |world: &mut World| {
+      world.entity_mut(person).insert((Status::Infected, Vaccinated(true)));
+    });
+    model.world_mut().resource_mut::<Timeline>().schedule_at(1.5, command, "infection").unwrap();
+    model.add_module(AggregateIncidenceReporter::new("cases".to_string(), Status::Infected, Duration(1.0)).unwrap());
+    model.add_module(AggregateIncidenceReporter::new("doses".to_string(), Vaccinated(true), Duration(1.0)).unwrap());
+    // Each reporter's windows would keep the other's going if they counted as pending events.
+    model.run();
+
+    let world = model.world_mut();
+    let cases = world.remove_resource::<AggregateIncidenceReporter<Status>>().unwrap();
+    let doses = world.remove_resource::<AggregateIncidenceReporter<Vaccinated>>().unwrap();
+    let paths = [cases.output_path().unwrap().to_owned(), doses.output_path().unwrap().to_owned()];
+    // Dropping the reporters flushes their reports.
+    drop((cases, doses));
+    for path in paths {
+      assert_eq!(fs::read_to_string(path).unwrap(), "window_start,new_cases\n0.0,0\n1.0,1\n");
+    }
+  }
+
+  #[test]
+  fn test_unwritable_window_is_an_error() {
+    // A reporter that was never initialized has nowhere to write its rows.
+    let mut world = World::default();
+    world.insert_resource(Timeline::default());
+    let aggregate_reporter = AggregateIncidenceReporter::new("curve".to_string(), Status::Infected, Duration(1.0));
+    world.insert_resource(aggregate_reporter.unwrap());
+    let error = write_window::<Status>(&mut world).unwrap_err();
+    assert!(error.to_string().contains("was not initialized"), "{error}");
+  }
+}
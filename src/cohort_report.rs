@@ -0,0 +1,170 @@
+/*!
+
+The `CohortReporter` module writes counts of entities broken down by cohort. Each time it samples, it groups every
+entity that has a compartment component `C` (typically an infection status) by a user-supplied cohort key `K` and
+writes one row per `(time, cohort, compartment, count)`.
+
+The cohort key is an arbitrary function of the entity, so the user chooses the stratification: census tract, age band,
+household, or any combination of them that serializes to a single CSV field. Only combinations that occur are written;
+a cohort with nobody in a given compartment has no row for it. Rows are written in sorted `(cohort, compartment)` order
+so that reports are reproducible.
+
+The report is sampled once per iteration of the model, in the `Last` phase, after all `Normal` systems have run.
+
+*/
+
+use std::{
+  collections::BTreeMap,
+  marker::PhantomData,
+  path::Path
+};
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::Serialize;
+
+use crate::{
+  model::ExecutionPhase,
+  module::Module,
+  report::{Reporter, ReporterConfiguration},
+  timeline::{Time, Timeline}
+};
+
+/// Computes the cohort an entity belongs to.
+pub type CohortKey<K> = Box<dyn Fn(&World, Entity) -> K + Send + Sync>;
+
+/// A row of the cohort report.
+#[derive(Serialize, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct CohortReportItem<K, C> {
+  pub time       : Time,
+  pub cohort     : K,
+  pub compartment: C,
+  pub count      : usize,
+}
+
+/// Counts entities with a `C` component grouped by the cohort key `K` and writes them to a CSV file.
+#[derive(Resource)]
+pub struct CohortReporter<C, K> {
+  reporter  : Reporter<()>,
+  cohort_key: CohortKey<K>,
+  // `C` is not stored; the reporter only reads it from the world.
+  compartment: PhantomData<fn() -> C>,
+}
+
+impl<C, K> CohortReporter<C, K>
+  where C: Component + Copy + Ord + Serialize,
+        K: Ord + Serialize + Send + Sync + 'static
+{
+  /// Creates a `CohortReporter` with the provided short name that groups entities by `cohort_key`.
+  pub fn new(short_name: String, cohort_key: impl Fn(&World, Entity) -> K + Send + Sync + 'static) -> Self {
+    CohortReporter {
+      reporter: Reporter::new(short_name),
+      cohort_key: Box::new(cohort_key),
+      compartment: PhantomData,
+    }
+  }
+
+  /// The path of the report file. See `Reporter::output_path`.
+  #[must_use]
+  pub fn output_path(&self) -> Option<&Path> {
+    self.reporter.output_path()
+  }
+
+  /// Counts the entities in each `(cohort, compartment)` combination, in sorted order.
+  pub fn count(&self, world: &mut World) -> BTreeMap<(K, C), usize> {
+    let members: Vec<(Entity, C)> = world.query::<(Entity, &C)>()
+                                         .iter(world)
+                                         .map(|(entity, compartment)| (entity, *compartment))
+                                         .collect();
+
+    let mut counts = BTreeMap::new();
+    for (entity, compartment) in members {
+      *counts.entry(((self.cohort_key)(world, entity), compartment)).or_insert(0) += 1;
+    }
+    counts
+  }
+}
+
+/// Writes the current cohort counts to the report.
+fn write_cohort_report<C, K>(world: &mut World)
+  where C: Component + Copy + Ord + Serialize,
+        K: Ord + Serialize + Send + Sync + 'static
+{
+  let time = world.resource::<Timeline>().now();
+  world.resource_scope(|world, mut cohort_reporter: Mut<CohortReporter<C, K>>| {
+    for ((cohort, compartment), count) in cohort_reporter.count(world) {
+      cohort_reporter.reporter
+                     .write_row(CohortReportItem{ time, cohort, compartment, count })
+                     .expect("Failed to write row.");
+    }
+  });
+}
+
+impl<C, K> Module for CohortReporter<C, K>
+  where C: Component + Copy + Ord + Serialize,
+        K: Ord + Serialize + Send + Sync + 'static
+{
+  fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs> {
+    #[cfg(feature = "print_messages")]
+    println!("Initializing module CohortReporter");
+
+    let config = ReporterConfiguration::for_reporter(world);
+    self.reporter.initialize(config).expect("Failed to initialize CohortReporter");
+    world.insert_resource(self);
+
+    Some(write_cohort_report::<C, K>.in_set(ExecutionPhase::Last))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use crate::report::FilenamePolicy;
+  use super::*;
+
+  #[derive(Component, Serialize, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+  enum Status {
+    Susceptible,
+    Infected,
+  }
+
+  #[derive(Component, Copy, Clone)]
+  struct Age(u8);
+
+  #[test]
+  fn test_cohort_report() {
+    let output_directory = std::env::temp_dir().join("ecs_disease_models_tests").join("cohort_report");
+    fs::create_dir_all(&output_directory).unwrap();
+    let config = ReporterConfiguration::new(String::new(), output_directory, FilenamePolicy::Overwrite);
+
+    let mut world = World::default();
+    world.insert_resource(Timeline::default());
+    world.insert_resource(config);
+    world.spawn((Status::Infected, Age(70)));
+    world.spawn((Status::Susceptible, Age(30)));
+    world.spawn((Status::Susceptible, Age(10)));
+    world.spawn((Status::Susceptible, Age(40)));
+
+    let age_band = |world: &World, entity: Entity| {
+      if world.get::<Age>(entity).unwrap().0 < 65 { "under_65" } else { "65_plus" }
+    };
+    let mut schedule = Schedule::default();
+    schedule.add_systems(
+      CohortReporter::<Status, &'static str>::new("cohorts".to_string(), age_band)
+          .initialize_with_world(&mut world)
+          .unwrap()
+    );
+    schedule.run(&mut world);
+    let cohort_reporter = world.remove_resource::<CohortReporter<Status, &'static str>>().unwrap();
+    let path = cohort_reporter.output_path().unwrap().to_path_buf();
+    drop(cohort_reporter);
+
+    assert_eq!(
+      fs::read_to_string(path).unwrap(),
+      "time,cohort,compartment,count\n0.0,65_plus,Infected,1\n0.0,under_65,Susceptible,3\n"
+    );
+  }
+}
@@ -0,0 +1,182 @@
+/*!
+
+Compares the results of two runs of a model, e.g. before and after a refactor that should not change them.
+
+A `RunComparison` runs two `Model`s to completion and compares what they end with: always the final time and the
+number of events run, plus any resources registered with `RunComparison::resource`, such as summary statistics.
+Resources are compared by their serialized form, field by field, so the `RunDiff` says which fields differ and how,
+rather than just that something did. The two models should be built with the same seed; if they aren't, the seeds show
+up as a difference too.
+
+*/
+
+use std::fmt::{Display, Formatter};
+
+use bevy_ecs::prelude::*;
+use serde::Serialize;
+use serde_json::Value;
+
+use crate::{
+  model::Model,
+  random::SeedResource,
+  timeline::Timeline
+};
+
+/// Extracts a named, comparable value from a finished run.
+type Extractor = Box<dyn Fn(&World) -> Value>;
+
+/// A field whose value differs between the two runs.
+#[derive(Clone, PartialEq, Debug)]
+pub struct FieldDiff {
+  /// The path of the field, e.g. `"PopulationStatistics.infected"`.
+  pub field: String,
+  pub a    : Value,
+  pub b    : Value,
+}
+
+/// The differences between two runs. Empty if the runs match.
+#[derive(Clone, PartialEq, Default, Debug)]
+pub struct RunDiff {
+  pub differences: Vec<FieldDiff>,
+}
+
+impl RunDiff {
+  /// Whether the runs match in every compared field.
+  pub fn is_match(&self) -> bool {
+    self.differences.is_empty()
+  }
+}
+
+impl Display for RunDiff {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    if self.is_match() {
+      return write!(f, "runs match");
+    }
+    writeln!(f, "runs differ in {} field(s):", self.differences.len())?;
+    for FieldDiff{ field, a, b } in &self.differences {
+      writeln!(f, "  {field}: {a} != {b}")?;
+    }
+    Ok(())
+  }
+}
+
+/// What to compare between two runs. See the module documentation.
+pub struct RunComparison {
+  extractors: Vec<(String, Extractor)>,
+}
+
+impl Default for RunComparison {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl RunComparison {
+  /// Compares the seed, the final time, and the number of events run.
+  pub fn new() -> Self {
+    let mut comparison = RunComparison{ extractors: Vec::new() };
+    comparison.extract("seed", |world| to_value(world.get_resource::<SeedResource>().map(|seed| seed.0)));
+    comparison.extract("final_time", |world| to_value(world.resource::<Timeline>().now().0));
+    comparison.extract("events_run", |world| to_value(world.resource::<Timeline>().events_run()));
+    comparison
+  }
+
+  /// Also compares the resource `R`, field by field. A run without the resource compares as `null`.
+  #[must_use]
+  pub fn resource<R: Resource + Serialize>(mut self) -> Self {
+    let type_name = std::any::type_name::<R>();
+    let name = type_name.rsplit("::").next().unwrap_or(type_name);
+    self.extract(name, |world| to_value(world.get_resource::<R>()));
+    self
+  }
+
+  fn extract(&mut self, name: &str, extract: impl Fn(&World) -> Value + 'static) {
+    self.extractors.push((name.to_string(), Box::new(extract)));
+  }
+
+  /// Runs both models to completion and compares them.
+  pub fn run(&self, model_a: &mut Model, model_b: &mut Model) -> RunDiff {
+    model_a.run();
+    model_b.run();
+    self.compare(model_a.world(), model_b.world())
+  }
+
+  /// Compares two worlds that have already been run.
+  pub fn compare(&self, world_a: &World, world_b: &World) -> RunDiff {
+    let mut diff = RunDiff::default();
+    for (name, extract) in &self.extractors {
+      diff_values(name.clone(), extract(world_a), extract(world_b), &mut diff.differences);
+    }
+    diff
+  }
+}
+
+/// Runs two models with the default `RunComparison` plus the given resources. Shorthand for
+/// `RunComparison::new().resource::<R>().run(model_a, model_b)` with a single resource.
+pub fn compare_runs<R: Resource + Serialize>(model_a: &mut Model, model_b: &mut Model) -> RunDiff {
+  RunComparison::new().resource::<R>().run(model_a, model_b)
+}
+
+/// Values that fail to serialize compare as `null`.
+fn to_value<T: Serialize>(value: T) -> Value {
+  serde_json::to_value(value).unwrap_or(Value::Null)
+}
+
+/// Records the differences between `a` and `b`, descending into objects so that each differing field is listed.
+fn diff_values(field: String, a: Value, b: Value, differences: &mut Vec<FieldDiff>) {
+  match (a, b) {
+    (Value::Object(mut a), Value::Object(b)) => {
+      for (key, b_value) in b {
+        let a_value = a.remove(&key).unwrap_or(Value::Null);
+        diff_values(format!("{field}.{key}"), a_value, b_value, differences);
+      }
+      for (key, a_value) in a {
+        diff_values(format!("{field}.{key}"), a_value, Value::Null, differences);
+      }
+    }
+    (a, b) if a != b => differences.push(FieldDiff{ field, a, b }),
+    _ => {}
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use crate::timeline_event::{Event, EventCommand};
+  use super::*;
+
+  #[derive(Resource, Serialize, Default)]
+  struct Counts {
+    recovered: u32,
+    infected : u32,
+  }
+
+  /// A model with a recovery at each of the times `1.0, 2.0, ..., count`.
+  fn model_with_recoveries(seed: u64, count: u32) -> Model {
+    let mut model = Model::with_random_seed(seed);
+    model.world_mut().init_resource::<Counts>();
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    for time in 1..=count {
+      timeline.push(Event{
+        time: OrderedFloat(time as f64),
+        command: EventCommand::infallible(|world: &mut World| world.resource_mut::<Counts>().recovered += 1),
+        entity: None,
+        label: "recovery",
+      }).unwrap();
+    }
+    model
+  }
+
+  #[test]
+  fn test_compare_runs() {
+    let diff = compare_runs::<Counts>(&mut model_with_recoveries(1, 3), &mut model_with_recoveries(1, 3));
+    assert!(diff.is_match(), "{diff}");
+
+    let diff = compare_runs::<Counts>(&mut model_with_recoveries(1, 3), &mut model_with_recoveries(1, 4));
+    let fields: Vec<&str> = diff.differences.iter().map(|difference| difference.field.as_str()).collect();
+    assert_eq!(fields, vec!["final_time", "events_run", "Counts.recovered"]);
+    assert_eq!(diff.differences[2].a, Value::from(3));
+    assert_eq!(diff.differences[2].b, Value::from(4));
+  }
+}
@@ -0,0 +1,318 @@
+/*!
+
+Counts of entities by compartment, and how infectious each compartment is.
+
+`CompartmentCounts<C>` is a resource that keeps the number of entities with each value of the compartment component
+`C`, such as an infection status, up to date. It is updated by a system in the `Last` phase of every iteration, so an
+event's command, which is applied after the `Last` phase, sees the counts as of the end of the previous event. Entities
+that are despawned or lose their `C` leave their compartment. Entities spawned before the module is added are counted
+in the first iteration.
+
+`Infectiousness<C>` gives each infectious compartment a relative weight. A disease with a pre-symptomatic phase that
+transmits at half the rate of the symptomatic phase has weights 0.5 and 1, and a model with a single infectious
+compartment, like SIR, has just that one with weight 1 (`Infectiousness::single`). Compartments without a weight are
+not infectious. A transmission model that would use the number of infected people uses
+`Infectiousness::weighted_infectious` instead, the sum over compartments of weight times count, so splitting a
+compartment into stages with the same weight changes nothing about transmission. Counts kept some other way, e.g. by
+population, can be weighted too, by collecting them into a `CompartmentCounts`.
+
+*/
+
+use std::collections::{BTreeMap, HashMap};
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use crate::{
+  errors::IxaError,
+  model::ExecutionPhase,
+  module::Module
+};
+
+/// The number of entities in each value of the compartment `C`. See the module documentation.
+#[derive(Resource, Clone, PartialEq, Eq, Debug)]
+pub struct CompartmentCounts<C> {
+  counts: BTreeMap<C, usize>,
+}
+
+impl<C> Default for CompartmentCounts<C> {
+  fn default() -> Self {
+    CompartmentCounts{ counts: BTreeMap::new() }
+  }
+}
+
+impl<C: Copy + Ord> CompartmentCounts<C> {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// The number of entities in `compartment`.
+  #[must_use]
+  pub fn get(&self, compartment: C) -> usize {
+    self.counts.get(&compartment).copied().unwrap_or(0)
+  }
+
+  /// The number of entities in every compartment.
+  #[must_use]
+  pub fn total(&self) -> usize {
+    self.counts.values().sum()
+  }
+
+  /// The nonempty compartments and their counts, in order.
+  pub fn iter(&self) -> impl Iterator<Item = (C, usize)> + '_ {
+    self.counts.iter().filter(|(_, count)| **count > 0).map(|(compartment, count)| (*compartment, *count))
+  }
+
+  fn add(&mut self, compartment: C) {
+    *self.counts.entry(compartment).or_insert(0) += 1;
+  }
+
+  fn remove(&mut self, compartment: C) {
+    let count = self.counts.get_mut(&compartment).expect("an entity left a compartment that was never entered");
+    *count -= 1;
+  }
+}
+
+impl<C: Copy + Ord> FromIterator<(C, usize)> for CompartmentCounts<C> {
+  /// The given counts, which add up if a compartment is given more than once.
+  fn from_iter<I: IntoIterator<Item = (C, usize)>>(counts: I) -> Self {
+    let mut compartment_counts = CompartmentCounts::new();
+    for (compartment, count) in counts {
+      *compartment_counts.counts.entry(compartment).or_insert(0) += count;
+    }
+    compartment_counts
+  }
+}
+
+/// Moves the entities whose `C` changed since the last iteration to their new compartments. `previous` remembers
+/// every entity's compartment, like `StatusTransitions`, but removals are needed here too.
+fn update_compartment_counts<C>(
+  mut counts: ResMut<CompartmentCounts<C>>,
+  query: Query<(Entity, &C), Changed<C>>,
+  mut removed: RemovedComponents<C>,
+  mut previous: Local<HashMap<Entity, C>>,
+)
+  where C: Component + Copy + Ord
+{
+  for entity in removed.read() {
+    if let Some(compartment) = previous.remove(&entity) {
+      counts.remove(compartment);
+    }
+  }
+  for (entity, compartment) in &query {
+    match previous.insert(entity, *compartment) {
+      Some(from) if from == *compartment => {}
+      Some(from) => {
+        counts.remove(from);
+        counts.add(*compartment);
+      }
+      None => counts.add(*compartment),
+    }
+  }
+}
+
+impl<C> Module for CompartmentCounts<C>
+  where C: Component + Copy + Ord
+{
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module CompartmentCounts");
+
+    Some(update_compartment_counts::<C>.in_set(ExecutionPhase::Last))
+  }
+}
+
+/// The relative infectiousness of each infectious compartment of `C`. See the module documentation.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Infectiousness<C> {
+  weights: BTreeMap<C, f64>,
+}
+
+impl<C: Copy + Ord> Infectiousness<C> {
+  /// A single infectious compartment with weight 1, as in SIR.
+  pub fn single(infectious: C) -> Self {
+    Infectiousness{ weights: BTreeMap::from([(infectious, 1.0)]) }
+  }
+
+  /// The given compartments with the given weights, which must be non-negative and finite.
+  pub fn new(weights: impl IntoIterator<Item = (C, f64)>) -> Result<Self, IxaError> {
+    let weights: BTreeMap<C, f64> = weights.into_iter().collect();
+    if let Some(weight) = weights.values().find(|weight| !(weight.is_finite() && **weight >= 0.0)) {
+      return Err(IxaError::IxaError(
+        format!("infectiousness weights must be non-negative and finite, but found {weight}")
+      ));
+    }
+    Ok(Infectiousness{ weights })
+  }
+
+  /// The weight of `compartment`, which is 0 if it is not infectious.
+  #[must_use]
+  pub fn weight(&self, compartment: C) -> f64 {
+    self.weights.get(&compartment).copied().unwrap_or(0.0)
+  }
+
+  /// The infectiousness-weighted number of infectious entities.
+  #[must_use]
+  pub fn weighted_infectious(&self, counts: &CompartmentCounts<C>) -> f64 {
+    self.weights.iter().map(|(compartment, weight)| weight * counts.get(*compartment) as f64).sum()
+  }
+}
+
+impl<C> Module for Infectiousness<C>
+  where C: Copy + Ord + Send + Sync + 'static
+{
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module Infectiousness");
+
+    None // No systems
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use rand::distr::StandardUniform;
+  use crate::{
+    model::Model,
+    random::RngResource,
+    timeline::{Time, Timeline},
+    timeline_event::{Event, EventCommand}
+  };
+  use super::*;
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+  enum Status {
+    Susceptible,
+    /// The only infectious compartment of the SIR model.
+    Infected,
+    /// The first and second halves of `Infected`, in the staged model.
+    Early,
+    Late,
+    Recovered,
+  }
+
+  const POPULATION: usize = 200;
+  const TRANSMISSION_RATE: f64 = 0.6;
+  /// Not a multiple of the time step, so that no stage ends at the same time as an infection attempt.
+  const INFECTIOUS_PERIOD: f64 = 5.03;
+  const TIME_STEP: f64 = 0.1;
+  const STEPS: usize = 400;
+
+  /// Schedules `status` for `person` at `time`.
+  fn schedule_status(world: &mut World, person: Entity, status: Status, time: Time) {
+    world.resource_mut::<Timeline>().push(Event{
+      time,
+      command: EventCommand::infallible(move |world: &mut World| *world.get_mut::<Status>(person).unwrap() = status),
+      entity: Some(person),
+      label: "progression",
+    }).unwrap();
+  }
+
+  /// Infects `person` now, scheduling the stages of their infection.
+  fn infect(world: &mut World, person: Entity, staged: bool) {
+    let now = world.resource::<Timeline>().now();
+    if staged {
+      *world.get_mut::<Status>(person).unwrap() = Status::Early;
+      schedule_status(world, person, Status::Late, now + INFECTIOUS_PERIOD / 2.0);
+    } else {
+      *world.get_mut::<Status>(person).unwrap() = Status::Infected;
+    }
+    schedule_status(world, person, Status::Recovered, now + INFECTIOUS_PERIOD);
+  }
+
+  /// Every time step, each susceptible person is infected with a probability given by the weighted number of
+  /// infectious people. Records `(susceptible, infectious, recovered)` at every step.
+  fn transmission_step(world: &mut World, step: usize, staged: bool) {
+    let counts = world.resource::<CompartmentCounts<Status>>();
+    let weighted_infectious = world.resource::<Infectiousness<Status>>().weighted_infectious(counts);
+    let infectious = counts.get(Status::Infected) + counts.get(Status::Early) + counts.get(Status::Late);
+    let record = (counts.get(Status::Susceptible), infectious, counts.get(Status::Recovered));
+    world.resource_mut::<Trajectory>().0.push(record);
+
+    let probability = 1.0 - (-TRANSMISSION_RATE * weighted_infectious / POPULATION as f64 * TIME_STEP).exp();
+    let mut susceptible: Vec<Entity> = world.query::<(Entity, &Status)>()
+                                            .iter(world)
+                                            .filter(|(_, status)| **status == Status::Susceptible)
+                                            .map(|(person, _)| person)
+                                            .collect();
+    susceptible.sort();
+    let now = world.resource::<Timeline>().now();
+    for person in susceptible {
+      let draw: f64 = world.resource_mut::<RngResource>().sample_labeled(now, "transmission", StandardUniform);
+      if draw < probability {
+        infect(world, person, staged);
+      }
+    }
+
+    if step < STEPS {
+      world.resource_mut::<Timeline>().push(Event{
+        time: OrderedFloat((step + 1) as f64 * TIME_STEP + TIME_STEP / 2.0),
+        command: EventCommand::infallible(move |world: &mut World| transmission_step(world, step + 1, staged)),
+        entity: None,
+        label: "transmission",
+      }).unwrap();
+    }
+  }
+
+  #[derive(Resource, Default)]
+  struct Trajectory(Vec<(usize, usize, usize)>);
+
+  fn trajectory(infectiousness: Infectiousness<Status>, staged: bool) -> Vec<(usize, usize, usize)> {
+    let mut model = Model::with_random_seed(11);
+    model.add_module(CompartmentCounts::<Status>::new());
+    model.add_module(infectiousness);
+    model.world_mut().init_resource::<Trajectory>();
+    let world = model.world_mut();
+    let people: Vec<Entity> = world.spawn_batch((0..POPULATION).map(|_| Status::Susceptible)).collect();
+    for person in &people[..3] {
+      infect(world, *person, staged);
+    }
+    world.resource_mut::<Timeline>().push(Event{
+      time: OrderedFloat(TIME_STEP / 2.0),
+      command: EventCommand::infallible(move |world: &mut World| transmission_step(world, 0, staged)),
+      entity: None,
+      label: "transmission",
+    }).unwrap();
+    model.run();
+
+    model.world_mut().remove_resource::<Trajectory>().unwrap().0
+  }
+
+  #[test]
+  fn test_equal_weight_stages_match_a_single_compartment() {
+    let single = trajectory(Infectiousness::single(Status::Infected), false);
+    let staged = trajectory(Infectiousness::new([(Status::Early, 1.0), (Status::Late, 1.0)]).unwrap(), true);
+
+    assert_eq!(single, staged);
+    let (susceptible, infectious, recovered) = *single.last().unwrap();
+    assert_eq!(susceptible + infectious + recovered, POPULATION);
+    assert!(recovered > POPULATION / 2, "{:?}", single.last());
+  }
+
+  #[test]
+  fn test_weights() {
+    let mut counts = CompartmentCounts::new();
+    for status in [Status::Early, Status::Late, Status::Late, Status::Susceptible] {
+      counts.add(status);
+    }
+    counts.remove(Status::Susceptible);
+    assert_eq!(counts.iter().collect::<Vec<_>>(), vec![(Status::Early, 1), (Status::Late, 2)]);
+
+    let infectiousness = Infectiousness::new([(Status::Early, 0.5), (Status::Late, 1.0)]).unwrap();
+    assert_eq!(infectiousness.weighted_infectious(&counts), 2.5);
+    // Collected counts add up, and match the counts kept by adding and removing entities.
+    let entries = [(Status::Late, 1), (Status::Early, 1), (Status::Late, 1)];
+    let collected: CompartmentCounts<Status> = entries.into_iter().collect();
+    assert!(collected.iter().eq(counts.iter()));
+    assert_eq!(infectiousness.weight(Status::Susceptible), 0.0);
+    assert!(Infectiousness::new([(Status::Early, -1.0)]).is_err());
+  }
+}
@@ -0,0 +1,136 @@
+/*!
+
+A `ControlHandle` lets another thread, like the UI thread of an interactive front-end, pause, resume, and abort a model
+that is running on its own thread. Get one with `Model::control_handle`; clones share the same model.
+
+A request is a flag that the run loop polls after every iteration of the event loop, as it does for `Ctrl-C` (see the
+`interrupt` module), so it takes effect between iterations, never in the middle of one, and costs an atomic load per
+iteration. The loop also checks for an abort once before its first iteration, so that an abort requested while the model
+was stopped is not preceded by another iteration. Only the most recent request is kept, except that an abort is never
+replaced by a pause or resume.
+
+ - `ControlHandle::pause` sets `ModelControl::Paused` if the model is running, which stops the loop with the world
+   intact. See the module documentation of `model` for the resume contract.
+ - `ControlHandle::abort` sets `ModelControl::Aborted`, running or paused. A paused model is aborted when it is next
+   run, before the loop runs another iteration, or at once in `Model::run_interactive`.
+ - `ControlHandle::resume` resumes a model paused in `Model::run_interactive`, which waits for it, and cancels a pause
+   that has not taken effect yet. A model paused in any other `run*` method is resumed by running it again instead.
+
+*/
+
+use std::sync::{
+  atomic::{AtomicU8, Ordering},
+  Arc, Condvar, Mutex
+};
+
+const NO_REQUEST: u8 = 0;
+const PAUSE: u8 = 1;
+const RESUME: u8 = 2;
+const ABORT: u8 = 3;
+
+/// What a `ControlHandle` asked of the model.
+#[derive(Copy, Clone, Eq, PartialEq, Debug)]
+pub(crate) enum ControlRequest {
+  Pause,
+  Resume,
+  Abort,
+}
+
+#[derive(Default, Debug)]
+struct Shared {
+  request: AtomicU8,
+  /// Held while waiting for `wake`, and by a request while it notifies, so that no notification is missed.
+  lock   : Mutex<()>,
+  wake   : Condvar,
+}
+
+/// A handle through which another thread can pause, resume, or abort a model. See the module documentation.
+#[derive(Clone, Default, Debug)]
+pub struct ControlHandle(Arc<Shared>);
+
+impl ControlHandle {
+  /// Requests that the model pause after the current iteration of the event loop.
+  pub fn pause(&self) {
+    self.request(PAUSE);
+  }
+
+  /// Requests that a paused model resume, or cancels a pause that has not taken effect yet.
+  pub fn resume(&self) {
+    self.request(RESUME);
+  }
+
+  /// Requests that the model abort after the current iteration of the event loop. A paused model is aborted when it is
+  /// next run, without running another iteration.
+  pub fn abort(&self) {
+    self.request(ABORT);
+  }
+
+  fn request(&self, request: u8) {
+    // An abort is final.
+    let _ = self.0.request.fetch_update(Ordering::SeqCst, Ordering::SeqCst, |pending| {
+      (pending != ABORT || request == ABORT).then_some(request)
+    });
+    let _guard = self.0.lock.lock().unwrap();
+    self.0.wake.notify_all();
+  }
+
+  /// Returns the pending request, if any, clearing it.
+  pub(crate) fn take(&self) -> Option<ControlRequest> {
+    match self.0.request.swap(NO_REQUEST, Ordering::SeqCst) {
+      PAUSE => Some(ControlRequest::Pause),
+      RESUME => Some(ControlRequest::Resume),
+      ABORT => Some(ControlRequest::Abort),
+      _ => None,
+    }
+  }
+
+  /// Clears the pending request and returns `true` if it is an abort. Any other request is left pending.
+  pub(crate) fn take_abort(&self) -> bool {
+    self.0.request.compare_exchange(ABORT, NO_REQUEST, Ordering::SeqCst, Ordering::SeqCst).is_ok()
+  }
+
+  /// Blocks until there is a request to resume or abort, and returns it, clearing it. Pause requests are dropped.
+  pub(crate) fn wait_for_resume(&self) -> ControlRequest {
+    let mut guard = self.0.lock.lock().unwrap();
+    loop {
+      match self.take() {
+        Some(ControlRequest::Pause) | None => guard = self.0.wake.wait(guard).unwrap(),
+        Some(request) => return request,
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_requests() {
+    let handle = ControlHandle::default();
+    assert_eq!(handle.take(), None);
+    handle.pause();
+    handle.resume();
+    assert_eq!(handle.take(), Some(ControlRequest::Resume));
+    assert_eq!(handle.take(), None);
+
+    // An abort is not replaced by a later request.
+    handle.clone().abort();
+    handle.pause();
+    assert_eq!(handle.take(), Some(ControlRequest::Abort));
+
+    // Only an abort is taken by `take_abort`.
+    handle.pause();
+    assert!(!handle.take_abort());
+    handle.abort();
+    assert!(handle.take_abort());
+    assert_eq!(handle.take(), None);
+
+    let waiting = handle.clone();
+    let waiter = std::thread::spawn(move || waiting.wait_for_resume());
+    handle.pause();
+    handle.resume();
+    assert_eq!(waiter.join().unwrap(), ControlRequest::Resume);
+  }
+}
@@ -0,0 +1,142 @@
+/*!
+
+Diagnostics for understanding the memory use and iteration speed of large models. `Model::diagnostics` returns a
+`ModelDiagnostics` snapshot of the number of entities and of how Bevy ECS has laid them out, and `Model::count_by`
+counts entities by the value of a component, such as an infection status.
+
+Bevy ECS stores entities with the same set of components together in an archetype, and iterating a query visits every
+archetype that matches it. Inserting a component mid-run, like `InfectedAt` when a person is infected, moves the
+entity to another archetype, so a model that does this for several optional components spreads its population over
+many archetypes. Many small archetypes (fragmentation) make queries slower, and `ModelDiagnostics` makes it visible:
+it lists every non-empty archetype with its components and size. A component inserted at spawn and mutated in place,
+like the `LifeHistory` of the `infection_times` module, avoids the moves.
+
+Cost:
+ - `Model::diagnostics` is cheap. It reads counts Bevy ECS already keeps and visits each archetype once, so it takes
+   time proportional to the number of archetypes and their components, not to the number of entities.
+ - `Model::count_by` is a full scan of every entity with the component.
+
+With the `diagnostics` feature, the `ModelDiagnostics` are printed at the end of every run.
+
+*/
+
+use std::fmt::{Display, Formatter};
+
+use bevy_ecs::prelude::*;
+
+/// An archetype: a set of components and how many entities have exactly that set.
+#[derive(Clone, Eq, PartialEq, Debug)]
+pub struct ArchetypeDiagnostics {
+  /// The (shortened) type names of the components, in Bevy's order.
+  pub components: Vec<String>,
+  pub entities  : usize,
+}
+
+/// A snapshot of the size and layout of a `World`. See the module documentation.
+#[derive(Clone, Eq, PartialEq, Default, Debug)]
+pub struct ModelDiagnostics {
+  /// The number of live entities.
+  pub entities  : usize,
+  /// The number of archetypes, including empty ones.
+  pub archetypes: usize,
+  /// The number of tables, which store the table (default) components of one or more archetypes.
+  pub tables    : usize,
+  /// The archetypes that have entities, largest first.
+  pub occupied  : Vec<ArchetypeDiagnostics>,
+}
+
+impl ModelDiagnostics {
+  /// Collects the diagnostics of `world`.
+  pub fn of(world: &World) -> Self {
+    let mut occupied: Vec<ArchetypeDiagnostics> = world.archetypes()
+        .iter()
+        .filter(|archetype| !archetype.is_empty())
+        .map(|archetype| ArchetypeDiagnostics{
+          components: archetype.components()
+                               .filter_map(|id| world.components().get_name(id))
+                               .map(short_type_name)
+                               .collect(),
+          entities: archetype.len(),
+        })
+        .collect();
+    occupied.sort_by(|a, b| b.entities.cmp(&a.entities).then_with(|| a.components.cmp(&b.components)));
+
+    ModelDiagnostics{
+      entities: world.entities().len() as usize,
+      archetypes: world.archetypes().len(),
+      tables: world.storages().tables.len(),
+      occupied,
+    }
+  }
+}
+
+impl Display for ModelDiagnostics {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(
+      f,
+      "{} entities in {} occupied archetypes ({} archetypes, {} tables)",
+      self.entities,
+      self.occupied.len(),
+      self.archetypes,
+      self.tables
+    )?;
+    for archetype in &self.occupied {
+      writeln!(f, "{:>10}  ({})", archetype.entities, archetype.components.join(", "))?;
+    }
+    Ok(())
+  }
+}
+
+/// Strips the module paths from a type name, e.g. `a::B<c::D>` becomes `B<D>`.
+pub(crate) fn short_type_name(name: &str) -> String {
+  let mut short = String::with_capacity(name.len());
+  let mut path = String::new();
+  for character in name.chars() {
+    if character.is_alphanumeric() || character == '_' || character == ':' {
+      path.push(character);
+    } else {
+      short.push_str(path.rsplit("::").next().unwrap_or_default());
+      path.clear();
+      short.push(character);
+    }
+  }
+  short.push_str(path.rsplit("::").next().unwrap_or_default());
+  short
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Component)]
+  struct Infected;
+
+  #[derive(Component)]
+  struct InfectedAt;
+
+  #[test]
+  fn test_fragmentation_is_visible() {
+    let mut world = World::default();
+    let people: Vec<Entity> = world.spawn_batch((0..10).map(|_| Infected)).collect();
+    for person in &people[..3] {
+      world.entity_mut(*person).insert(InfectedAt);
+    }
+
+    let diagnostics = ModelDiagnostics::of(&world);
+    assert_eq!(diagnostics.entities, 10);
+    assert_eq!(
+      diagnostics.occupied,
+      vec![
+        ArchetypeDiagnostics{ components: vec!["Infected".to_string()], entities: 7 },
+        ArchetypeDiagnostics{ components: vec!["Infected".to_string(), "InfectedAt".to_string()], entities: 3 },
+      ]
+    );
+  }
+
+  #[test]
+  fn test_short_type_name() {
+    assert_eq!(short_type_name("a::B<c::D, E>"), "B<D, E>");
+    assert_eq!(short_type_name("Plain"), "Plain");
+  }
+}
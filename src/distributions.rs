@@ -0,0 +1,130 @@
+/*!
+
+The `Distributions` resource constructs each distribution a model samples from once and hands out the cached copy,
+rather than having every manager rebuild (and revalidate) the distribution on every draw. Distributions are keyed by
+their parameters, so a manager that samples recovery times and one that samples infection attempts share nothing but
+the resource.
+
+Models typically sample from only a handful of distinct distributions, e.g. a fixed force of infection and a fixed
+recovery rate, so the cache is a short list searched linearly rather than a hash map. Even so, constructing an `Exp`
+is only a check and a division, so a cache lookup is somewhat slower than constructing one inline, and both are slower
+than copying a distribution that was constructed once (see `benches/distributions.rs`). A manager whose distribution is
+fixed when it is constructed should therefore construct it then, with `Distributions::validated_exp`, and keep it. The
+cache is for distributions whose parameters are only known during the run.
+
+Sampling from a cached distribution through `RngResource::sample_labeled` keeps the draw in the `RngTrace`:
+
+```ignore
+let recovery = distributions.exp(Rate::from_mean(infection_duration))?;
+let duration = Duration(rng.sample_labeled(now, "recovery", recovery));
+```
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use rand::Rng;
+use rand_distr::{Distribution, Exp, ExpError};
+
+use crate::{
+  errors::IxaError,
+  module::Module,
+  units::{Duration, Rate}
+};
+
+/// A cache of constructed distributions, keyed by their parameters.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct Distributions {
+  /// Exponential distributions keyed by the bits of their rate, so that every rate, even `NaN`, has a key.
+  exp: Vec<(u64, Exp<f64>)>,
+}
+
+impl Distributions {
+  /// The exponential distribution with the given `rate`, for a manager to construct once and keep. Fails with an error
+  /// naming the `parameter` the rate is the value of if it is not a positive, finite rate.
+  pub fn validated_exp(rate: Rate, parameter: &str) -> Result<Exp<f64>, IxaError> {
+    rate.validate(parameter)?
+        .exp()
+        .map_err(|error| IxaError::IxaError(format!("{parameter} does not give an exponential distribution: {error}")))
+  }
+
+  /// The exponential distribution with the given `rate`, constructed on first use. Invalid rates are not cached.
+  pub fn exp(&mut self, rate: Rate) -> Result<Exp<f64>, ExpError> {
+    let key = rate.0.to_bits();
+    if let Some((_, exp)) = self.exp.iter().find(|(cached, _)| *cached == key) {
+      return Ok(*exp);
+    }
+
+    let exp = rate.exp()?;
+    self.exp.push((key, exp));
+    Ok(exp)
+  }
+
+  /// Draws a waiting time for a process with the given `rate` directly from `rng`. The draw is not recorded in the
+  /// `RngTrace`; use `Distributions::exp` with `RngResource::sample_labeled` for that.
+  pub fn sample_exp<R: Rng + ?Sized>(&mut self, rate: Rate, rng: &mut R) -> Result<Duration, ExpError> {
+    Ok(Duration(self.exp(rate)?.sample(rng)))
+  }
+
+  /// The number of distinct distributions constructed so far.
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.exp.len()
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.exp.is_empty()
+  }
+}
+
+impl Module for Distributions {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module Distributions");
+    None // No systems
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use rand::{rngs::SmallRng, SeedableRng};
+  use super::*;
+
+  #[test]
+  fn test_exp_is_cached() {
+    let mut distributions = Distributions::default();
+    assert_eq!(distributions.exp(Rate(0.5)).unwrap(), Exp::new(0.5).unwrap());
+    distributions.exp(Rate(0.5)).unwrap();
+    distributions.exp(Rate(2.0)).unwrap();
+    assert_eq!(distributions.len(), 2);
+
+    assert!(distributions.exp(Rate(-1.0)).is_err());
+    assert_eq!(distributions.len(), 2);
+  }
+
+  #[test]
+  fn test_validated_exp() {
+    assert_eq!(Distributions::validated_exp(Rate(0.5), "the rate").unwrap(), Exp::new(0.5).unwrap());
+    let error = Distributions::validated_exp(Rate(f64::INFINITY), "the rate").unwrap_err();
+    assert!(error.to_string().contains("the rate must be a positive, finite rate, but is inf"), "{error}");
+    assert!(Distributions::validated_exp(Rate(0.0), "the rate").is_err());
+  }
+
+  #[test]
+  fn test_sample_exp_matches_inline_construction() {
+    let mut distributions = Distributions::default();
+    let mut cached_rng = SmallRng::seed_from_u64(3);
+    let mut inline_rng = SmallRng::seed_from_u64(3);
+    for _ in 0..10 {
+      assert_eq!(
+        distributions.sample_exp(Rate(0.25), &mut cached_rng).unwrap().0,
+        Exp::new(0.25).unwrap().sample(&mut inline_rng)
+      );
+    }
+  }
+}
@@ -0,0 +1,175 @@
+/*!
+
+An `EpidemicCurve` is the number of people in each compartment over time, e.g. S, I, and R, in a form a plotting tool
+can read directly. It is the aggregate curve, as opposed to the per-event incidence report: a model builds it after a
+run from whatever in-memory history it kept, such as the snapshots of the basic infection example's
+`StatisticsHistory`, and exports it.
+
+Both exports are "long" (tidy): one record per time and compartment, with the fields `time`, `compartment`, and
+`count`.
+ - `EpidemicCurve::to_long_csv` writes a CSV file with the header `time,compartment,count`.
+ - `EpidemicCurve::to_json` writes a JSON array of `{"time": ..., "compartment": ..., "count": ...}` objects, the shape
+   charting libraries like Vega-Lite take as data.
+
+Records are in time order, and within a time in the order the compartments were given to `EpidemicCurve::new`, so the
+output is the same for the same curve.
+
+*/
+
+use std::{
+  fs::File,
+  io::{BufWriter, Write},
+  path::Path
+};
+
+use csv::Writer as CsvWriter;
+use serde::Serialize;
+
+use crate::{
+  errors::IxaError,
+  timeline::Time
+};
+
+/// Counts by compartment over time. See the module documentation.
+#[derive(Clone, PartialEq, Debug)]
+pub struct EpidemicCurve {
+  compartments: Vec<String>,
+  points: Vec<(Time, Vec<u64>)>,
+}
+
+/// A record of the long exports.
+#[derive(Serialize)]
+struct CurveRecord<'a> {
+  time: f64,
+  compartment: &'a str,
+  count: u64,
+}
+
+impl EpidemicCurve {
+  /// An empty curve of the given compartments, in the order the exports list them.
+  pub fn new<S: Into<String>>(compartments: impl IntoIterator<Item = S>) -> Self {
+    EpidemicCurve{
+      compartments: compartments.into_iter().map(Into::into).collect(),
+      points: Vec::new(),
+    }
+  }
+
+  /// Adds the counts at `time`, one for each compartment in order. Times must not decrease.
+  pub fn push(&mut self, time: Time, counts: impl IntoIterator<Item = u64>) -> Result<(), IxaError> {
+    let counts: Vec<u64> = counts.into_iter().collect();
+    if counts.len() != self.compartments.len() {
+      return Err(IxaError::IxaError(format!(
+        "expected {} counts, one for each compartment, but got {}",
+        self.compartments.len(),
+        counts.len()
+      )));
+    }
+    if let Some((last, _)) = self.points.last() && time < *last {
+      return Err(IxaError::IxaError(format!("time {time} is before the previous time {last}")));
+    }
+    self.points.push((time, counts));
+    Ok(())
+  }
+
+  /// The compartments, in order.
+  #[must_use]
+  pub fn compartments(&self) -> &[String] {
+    &self.compartments
+  }
+
+  /// The times and counts, in time order.
+  pub fn iter(&self) -> impl Iterator<Item = (Time, &[u64])> {
+    self.points.iter().map(|(time, counts)| (*time, counts.as_slice()))
+  }
+
+  /// The number of times.
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.points.len()
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.points.is_empty()
+  }
+
+  /// The records of the long exports, in order.
+  fn records(&self) -> impl Iterator<Item = CurveRecord<'_>> {
+    self.points.iter().flat_map(move |(time, counts)| {
+      self.compartments.iter().zip(counts).map(|(compartment, count)| {
+        CurveRecord{ time: time.0, compartment, count: *count }
+      })
+    })
+  }
+
+  /// Writes the curve to `path` as a long CSV file. See the module documentation.
+  pub fn to_long_csv(&self, path: &Path) -> Result<(), IxaError> {
+    let mut writer = CsvWriter::from_path(path)?;
+    // The header is written with the first record, so an empty curve needs it written explicitly.
+    if self.is_empty() {
+      writer.write_record(["time", "compartment", "count"])?;
+    }
+    for record in self.records() {
+      writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+  }
+
+  /// Writes the curve to `path` as a JSON array of records. See the module documentation.
+  pub fn to_json(&self, path: &Path) -> Result<(), IxaError> {
+    let mut writer = BufWriter::new(File::create(path)?);
+    serde_json::to_writer(&mut writer, &self.records().collect::<Vec<_>>())?;
+    writer.flush()?;
+    Ok(())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::{env, fs};
+  use ordered_float::OrderedFloat;
+  use super::*;
+
+  fn sir_curve() -> EpidemicCurve {
+    let mut curve = EpidemicCurve::new(["susceptible", "infected", "recovered"]);
+    curve.push(OrderedFloat(0.0), [9, 1, 0]).unwrap();
+    curve.push(OrderedFloat(1.5), [7, 2, 1]).unwrap();
+    curve
+  }
+
+  #[test]
+  fn test_exports() {
+    let directory = env::temp_dir().join("ecs_disease_models_tests").join("epidemic_curve");
+    fs::create_dir_all(&directory).unwrap();
+    let curve = sir_curve();
+
+    let csv_path = directory.join("curve.csv");
+    curve.to_long_csv(&csv_path).unwrap();
+    assert_eq!(
+      fs::read_to_string(&csv_path).unwrap(),
+      "time,compartment,count\n\
+       0.0,susceptible,9\n0.0,infected,1\n0.0,recovered,0\n\
+       1.5,susceptible,7\n1.5,infected,2\n1.5,recovered,1\n"
+    );
+
+    let json_path = directory.join("curve.json");
+    curve.to_json(&json_path).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&fs::read_to_string(&json_path).unwrap()).unwrap();
+    let records = json.as_array().unwrap();
+    assert_eq!(records.len(), 6);
+    assert_eq!(records[4], serde_json::json!({ "time": 1.5, "compartment": "infected", "count": 2 }));
+
+    EpidemicCurve::new(["susceptible"]).to_long_csv(&csv_path).unwrap();
+    assert_eq!(fs::read_to_string(&csv_path).unwrap(), "time,compartment,count\n");
+  }
+
+  #[test]
+  fn test_invalid_points() {
+    let mut curve = sir_curve();
+    assert!(curve.push(OrderedFloat(2.0), [1, 2]).is_err());
+    assert!(curve.push(OrderedFloat(1.0), [7, 2, 1]).is_err());
+    assert_eq!(curve.len(), 2);
+  }
+}
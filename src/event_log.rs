@@ -0,0 +1,265 @@
+/*!
+
+A trace of the `Timeline` events as they fire, for debugging and for animating an outbreak: each event's time, label,
+and entity, in the order the events were popped.
+
+The log is off by default and costs nothing then but a check per event. `Timeline::enable_event_log` turns it on with a
+capacity, and the `EventLog` keeps only the most recent `capacity` events, so memory stays bounded on long runs; it
+still counts every event logged, so `EventLog::dropped` says how many fell out of the buffer. The log can be read from
+the `Timeline` during or after a run, with `Timeline::event_log`.
+
+For a trace of the whole run, the `EventLogReporter` module streams the log to a report instead, with one
+`(time, label, entity)` row per event, the entity being its index as in the other reports. It turns the log on if it is
+off, and writes the events logged in each iteration of the event loop from a system in the `Last` phase, so the buffer
+needs to hold only one iteration's events; any that fall out of it before they are written are counted in a warning of
+`Model::warnings`. Events are logged when they are popped, before their commands run. An event
+that is popped but not run because the model aborts for lack of time progress is logged too.
+
+*/
+
+use std::{
+  collections::VecDeque,
+  path::Path
+};
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::Serialize;
+
+use crate::{
+  model::{ExecutionPhase, ModelWarnings},
+  module::Module,
+  report::{Reporter, ReporterConfiguration},
+  timeline::{Time, Timeline},
+  timeline_event::Event
+};
+
+/// The capacity of the log an `EventLogReporter` turns on unless configured otherwise.
+pub const DEFAULT_EVENT_LOG_CAPACITY: usize = 1024;
+
+/// An event in the `EventLog`.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct LoggedEvent {
+  pub time  : Time,
+  pub label : &'static str,
+  pub entity: Option<Entity>,
+}
+
+/// The most recent events popped from the `Timeline`, oldest first. See the module documentation.
+#[derive(Clone, Debug)]
+pub struct EventLog {
+  events  : VecDeque<LoggedEvent>,
+  capacity: usize,
+  /// The number of events logged, including those no longer in the buffer.
+  logged  : u64,
+}
+
+impl EventLog {
+  /// An empty log that keeps the `capacity` most recent events.
+  #[must_use]
+  pub fn new(capacity: usize) -> Self {
+    EventLog{ events: VecDeque::with_capacity(capacity), capacity, logged: 0 }
+  }
+
+  /// The events in the buffer, oldest first.
+  pub fn iter(&self) -> impl Iterator<Item = &LoggedEvent> {
+    self.events.iter()
+  }
+
+  /// The events in the buffer logged after the first `logged` events, oldest first, e.g. those logged since `logged()`
+  /// was last read.
+  pub fn since(&self, logged: u64) -> impl Iterator<Item = &LoggedEvent> {
+    let oldest = self.logged - self.events.len() as u64;
+    self.events.iter().skip(logged.saturating_sub(oldest) as usize)
+  }
+
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.events.len()
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.events.is_empty()
+  }
+
+  #[must_use]
+  pub fn capacity(&self) -> usize {
+    self.capacity
+  }
+
+  /// The number of events logged so far, including those no longer in the buffer.
+  #[must_use]
+  pub fn logged(&self) -> u64 {
+    self.logged
+  }
+
+  /// The number of events logged that are no longer in the buffer.
+  #[must_use]
+  pub fn dropped(&self) -> u64 {
+    self.logged - self.events.len() as u64
+  }
+
+  /// Logs `event`, dropping the oldest event if the buffer is full.
+  pub(crate) fn record(&mut self, event: &Event) {
+    self.logged += 1;
+    if self.capacity == 0 {
+      return;
+    }
+    if self.events.len() == self.capacity {
+      self.events.pop_front();
+    }
+    self.events.push_back(LoggedEvent{ time: event.time, label: event.label, entity: event.entity });
+  }
+}
+
+/// A row of the event log report.
+#[derive(Serialize)]
+struct EventLogRow {
+  time  : f64,
+  label : &'static str,
+  entity: Option<u32>,
+}
+
+/// Streams the `Timeline`'s `EventLog` to a report. See the module documentation.
+#[derive(Resource)]
+pub struct EventLogReporter {
+  reporter: Reporter<()>,
+  capacity: usize,
+  /// The number of logged events written so far.
+  written : u64,
+}
+
+impl EventLogReporter {
+  /// Creates an `EventLogReporter` with the provided short name.
+  #[must_use]
+  pub fn new(short_name: String) -> Self {
+    EventLogReporter{ reporter: Reporter::new(short_name), capacity: DEFAULT_EVENT_LOG_CAPACITY, written: 0 }
+  }
+
+  /// Turns the log on with this capacity, at least 1, if it is off when the module is initialized.
+  #[must_use]
+  pub fn with_capacity(mut self, capacity: usize) -> Self {
+    self.capacity = capacity.max(1);
+    self
+  }
+
+  /// The path of the report file. See `Reporter::output_path`.
+  #[must_use]
+  pub fn output_path(&self) -> Option<&Path> {
+    self.reporter.output_path()
+  }
+}
+
+/// A system that writes the events logged since it last ran, and warns, as a `ModelWarnings` warning, of any that fell
+/// out of the log before it could.
+fn write_event_log(mut event_log_reporter: ResMut<EventLogReporter>, timeline: Res<Timeline>, mut commands: Commands) {
+  let Some(event_log) = timeline.event_log() else {
+    return;
+  };
+  let event_log_reporter = event_log_reporter.as_mut();
+  if event_log.dropped() > event_log_reporter.written {
+    let warning = format!(
+      "{} events fell out of the event log before they were written",
+      event_log.dropped() - event_log_reporter.written
+    );
+    commands.queue(move |world: &mut World| ModelWarnings::warn(world, warning));
+  }
+  for event in event_log.since(event_log_reporter.written) {
+    let row = EventLogRow{ time: event.time.0, label: event.label, entity: event.entity.map(|entity| entity.index()) };
+    event_log_reporter.reporter.write_row(row).expect("Failed to write row.");
+  }
+  event_log_reporter.written = event_log.logged();
+}
+
+impl Module for EventLogReporter {
+  fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs> {
+    #[cfg(feature = "print_messages")]
+    println!("Initializing module EventLogReporter");
+
+    let config = ReporterConfiguration::for_reporter(world);
+    self.reporter.initialize(config).expect("Failed to initialize EventLogReporter");
+
+    let mut timeline = world.resource_mut::<Timeline>();
+    if timeline.event_log().is_none() {
+      timeline.enable_event_log(self.capacity);
+    }
+    self.written = timeline.event_log().map_or(0, EventLog::logged);
+    world.insert_resource(self);
+
+    Some(write_event_log.in_set(ExecutionPhase::Last))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use crate::{
+    model::Model,
+    report::FilenamePolicy,
+    timeline_event::EventCommand
+  };
+  use super::*;
+
+  #[test]
+  fn test_event_log() {
+    let mut timeline = Timeline::default();
+    assert!(timeline.event_log().is_none());
+    timeline.enable_event_log(2);
+    for (time, label) in [(1.0, "a"), (2.0, "b"), (3.0, "c")] {
+      timeline.schedule_at(time, EventCommand::infallible(|_| {}), label).unwrap();
+    }
+    while timeline.pop().is_some() {}
+
+    // Only the two most recent events are kept.
+    let event_log = timeline.event_log().unwrap();
+    let labels: Vec<&str> = event_log.iter().map(|event| event.label).collect();
+    assert_eq!(labels, ["b", "c"]);
+    assert_eq!((event_log.logged(), event_log.dropped()), (3, 1));
+    assert_eq!(event_log.since(2).map(|event| event.time.0).collect::<Vec<_>>(), [3.0]);
+    assert_eq!(timeline.disable_event_log().unwrap().len(), 2);
+    assert!(timeline.event_log().is_none());
+  }
+
+  #[test]
+  fn test_event_log_reporter() {
+    let output_directory = std::env::temp_dir().join("ecs_disease_models_tests").join("event_log");
+    fs::create_dir_all(&output_directory).unwrap();
+
+    let mut model = Model::with_random_seed(1);
+    model.add_module(ReporterConfiguration::new(String::new(), output_directory.clone(), FilenamePolicy::Overwrite));
+    let person = model.world_mut().spawn_empty().id();
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    timeline.push(Event::builder(0.5).entity(person).label("infection").build()).unwrap();
+    timeline.schedule_at(2.0, EventCommand::infallible(|_| {}), "report").unwrap();
+    // A smaller capacity than the number of events, which is enough for one iteration's events.
+    model.add_module(EventLogReporter::new("events".to_string()).with_capacity(1));
+    model.run();
+    assert!(model.warnings().is_empty(), "{:?}", model.warnings());
+
+    let path = model.world().resource::<EventLogReporter>().output_path().unwrap().to_path_buf();
+    model.world_mut().remove_resource::<EventLogReporter>();
+    let expected = format!("time,label,entity\n0.5,infection,{}\n2.0,report,\n", person.index());
+    assert_eq!(fs::read_to_string(path).unwrap(), expected);
+
+    // Three events popped in one iteration, here by an event that pops the others, do not fit in a capacity of one.
+    let mut model = Model::with_random_seed(1);
+    model.add_module(ReporterConfiguration::new(String::new(), output_directory, FilenamePolicy::Overwrite));
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    let pop_others = EventCommand::infallible(|world| {
+      let mut timeline = world.resource_mut::<Timeline>();
+      while timeline.pop().is_some() {}
+    });
+    timeline.schedule_at(1.0, pop_others, "pop_others").unwrap();
+    for time in [2.0, 3.0] {
+      timeline.schedule_at(time, EventCommand::infallible(|_| {}), "popped").unwrap();
+    }
+    model.add_module(EventLogReporter::new("dropped_events".to_string()).with_capacity(1));
+    model.run();
+    assert_eq!(model.warnings(), ["2 events fell out of the event log before they were written"]);
+    model.world_mut().remove_resource::<EventLogReporter>();
+  }
+}
@@ -0,0 +1,288 @@
+/*!
+
+A `Serialize` wrapper that writes every floating point value it encounters with a fixed number of decimal places.
+
+The wrapper intercepts serialization rather than rounding the data itself, so it works for any report item without
+changes to its type, including floats nested inside newtypes like `Time`. Every other kind of value is passed through
+to the wrapped serializer unchanged. Floats are written as strings, which is indistinguishable from a number in CSV.
+
+*/
+
+use serde::ser::{
+  self,
+  Serialize,
+  Serializer
+};
+
+/// Serializes `value` with every `f32` and `f64` formatted to `precision` decimal places.
+pub(crate) struct WithFloatPrecision<'a, T: ?Sized> {
+  pub(crate) value    : &'a T,
+  pub(crate) precision: usize,
+}
+
+impl<T: Serialize + ?Sized> Serialize for WithFloatPrecision<'_, T> {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    self.value.serialize(FloatPrecisionSerializer{ inner: serializer, precision: self.precision })
+  }
+}
+
+struct FloatPrecisionSerializer<S> {
+  inner    : S,
+  precision: usize,
+}
+
+/// Wraps the serializer of a compound value (struct, sequence, map, ...) so that its elements are wrapped in turn.
+struct Compound<C> {
+  inner    : C,
+  precision: usize,
+}
+
+/// Forwards methods that take a single value straight to the inner serializer.
+macro_rules! forward_to_inner {
+  ($($method:ident($ty:ty)),* $(,)?) => {
+    $(
+      fn $method(self, value: $ty) -> Result<Self::Ok, Self::Error> {
+        self.inner.$method(value)
+      }
+    )*
+  };
+}
+
+impl<S: Serializer> Serializer for FloatPrecisionSerializer<S> {
+  type Ok                     = S::Ok;
+  type Error                  = S::Error;
+  type SerializeSeq           = Compound<S::SerializeSeq>;
+  type SerializeTuple         = Compound<S::SerializeTuple>;
+  type SerializeTupleStruct   = Compound<S::SerializeTupleStruct>;
+  type SerializeTupleVariant  = Compound<S::SerializeTupleVariant>;
+  type SerializeMap           = Compound<S::SerializeMap>;
+  type SerializeStruct        = Compound<S::SerializeStruct>;
+  type SerializeStructVariant = Compound<S::SerializeStructVariant>;
+
+  forward_to_inner!(
+    serialize_bool(bool),
+    serialize_i8(i8),
+    serialize_i16(i16),
+    serialize_i32(i32),
+    serialize_i64(i64),
+    serialize_i128(i128),
+    serialize_u8(u8),
+    serialize_u16(u16),
+    serialize_u32(u32),
+    serialize_u64(u64),
+    serialize_u128(u128),
+    serialize_char(char),
+    serialize_str(&str),
+    serialize_bytes(&[u8]),
+    serialize_unit_struct(&'static str),
+  );
+
+  fn serialize_f32(self, value: f32) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_str(&format!("{value:.precision$}", precision = self.precision))
+  }
+
+  fn serialize_f64(self, value: f64) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_str(&format!("{value:.precision$}", precision = self.precision))
+  }
+
+  fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_none()
+  }
+
+  fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_some(&WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_unit()
+  }
+
+  fn serialize_unit_variant(
+    self,
+    name: &'static str,
+    variant_index: u32,
+    variant: &'static str
+  ) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_unit_variant(name, variant_index, variant)
+  }
+
+  fn serialize_newtype_struct<T: Serialize + ?Sized>(
+    self,
+    name: &'static str,
+    value: &T
+  ) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_newtype_struct(name, &WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn serialize_newtype_variant<T: Serialize + ?Sized>(
+    self,
+    name: &'static str,
+    variant_index: u32,
+    variant: &'static str,
+    value: &T
+  ) -> Result<Self::Ok, Self::Error> {
+    self.inner.serialize_newtype_variant(
+      name,
+      variant_index,
+      variant,
+      &WithFloatPrecision{ value, precision: self.precision }
+    )
+  }
+
+  fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
+    Ok(Compound{ inner: self.inner.serialize_seq(len)?, precision: self.precision })
+  }
+
+  fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Self::Error> {
+    Ok(Compound{ inner: self.inner.serialize_tuple(len)?, precision: self.precision })
+  }
+
+  fn serialize_tuple_struct(
+    self,
+    name: &'static str,
+    len: usize
+  ) -> Result<Self::SerializeTupleStruct, Self::Error> {
+    Ok(Compound{ inner: self.inner.serialize_tuple_struct(name, len)?, precision: self.precision })
+  }
+
+  fn serialize_tuple_variant(
+    self,
+    name: &'static str,
+    variant_index: u32,
+    variant: &'static str,
+    len: usize
+  ) -> Result<Self::SerializeTupleVariant, Self::Error> {
+    Ok(Compound{
+      inner: self.inner.serialize_tuple_variant(name, variant_index, variant, len)?,
+      precision: self.precision
+    })
+  }
+
+  fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+    Ok(Compound{ inner: self.inner.serialize_map(len)?, precision: self.precision })
+  }
+
+  fn serialize_struct(self, name: &'static str, len: usize) -> Result<Self::SerializeStruct, Self::Error> {
+    Ok(Compound{ inner: self.inner.serialize_struct(name, len)?, precision: self.precision })
+  }
+
+  fn serialize_struct_variant(
+    self,
+    name: &'static str,
+    variant_index: u32,
+    variant: &'static str,
+    len: usize
+  ) -> Result<Self::SerializeStructVariant, Self::Error> {
+    Ok(Compound{
+      inner: self.inner.serialize_struct_variant(name, variant_index, variant, len)?,
+      precision: self.precision
+    })
+  }
+
+  fn is_human_readable(&self) -> bool {
+    self.inner.is_human_readable()
+  }
+}
+
+impl<C: ser::SerializeSeq> ser::SerializeSeq for Compound<C> {
+  type Ok    = C::Ok;
+  type Error = C::Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_element(&WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.end()
+  }
+}
+
+impl<C: ser::SerializeTuple> ser::SerializeTuple for Compound<C> {
+  type Ok    = C::Ok;
+  type Error = C::Error;
+
+  fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_element(&WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.end()
+  }
+}
+
+impl<C: ser::SerializeTupleStruct> ser::SerializeTupleStruct for Compound<C> {
+  type Ok    = C::Ok;
+  type Error = C::Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_field(&WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.end()
+  }
+}
+
+impl<C: ser::SerializeTupleVariant> ser::SerializeTupleVariant for Compound<C> {
+  type Ok    = C::Ok;
+  type Error = C::Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_field(&WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.end()
+  }
+}
+
+impl<C: ser::SerializeMap> ser::SerializeMap for Compound<C> {
+  type Ok    = C::Ok;
+  type Error = C::Error;
+
+  fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_key(&WithFloatPrecision{ value: key, precision: self.precision })
+  }
+
+  fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_value(&WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.end()
+  }
+}
+
+impl<C: ser::SerializeStruct> ser::SerializeStruct for Compound<C> {
+  type Ok    = C::Ok;
+  type Error = C::Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_field(key, &WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+    self.inner.skip_field(key)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.end()
+  }
+}
+
+impl<C: ser::SerializeStructVariant> ser::SerializeStructVariant for Compound<C> {
+  type Ok    = C::Ok;
+  type Error = C::Error;
+
+  fn serialize_field<T: Serialize + ?Sized>(&mut self, key: &'static str, value: &T) -> Result<(), Self::Error> {
+    self.inner.serialize_field(key, &WithFloatPrecision{ value, precision: self.precision })
+  }
+
+  fn skip_field(&mut self, key: &'static str) -> Result<(), Self::Error> {
+    self.inner.skip_field(key)
+  }
+
+  fn end(self) -> Result<Self::Ok, Self::Error> {
+    self.inner.end()
+  }
+}
@@ -0,0 +1,158 @@
+/*!
+
+The times at which an entity was infected and recovered, recorded as components by the modules that cause those
+transitions, so that realized durations can be computed after a run, e.g. to compare the distribution of realized
+infectious periods against the one the model was configured with, without parsing a report.
+
+Both times should be set at the instant of the transition, from `Timeline::now()`. An entity that recovers by being
+despawned keeps no record.
+
+There are two ways to record the times:
+ - The `InfectedAt` and `RecoveredAt` components, inserted at each transition. Inserting a component moves the entity
+   to another archetype, which is expensive for large populations and fragments them (see the `diagnostics` module).
+ - A single `LifeHistory` component, inserted when the entity is spawned, whose fields are set in place at each
+   transition. Setting a field changes no archetype. `record_infection` and `record_recovery` set the fields,
+   inserting the `LifeHistory` if the entity has none, which costs one move for an entity not spawned with it.
+
+`infectious_period` reads the `LifeHistory` if the entity has one and the separate components otherwise. Run
+`cargo bench --bench life_history` to compare the two on a million people. For a record of every transition of a status
+component, not only these two, see the `status_history` module.
+
+*/
+
+use std::fmt::{Display, Formatter};
+
+use bevy_ecs::prelude::*;
+
+use crate::{
+  timeline::Time,
+  units::Duration
+};
+
+/// When the entity was infected.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct InfectedAt(pub Time);
+
+/// When the entity recovered.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct RecoveredAt(pub Time);
+
+/// When the entity was infected and recovered, in one component that is mutated in place. See the module
+/// documentation.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Default, Debug)]
+pub struct LifeHistory {
+  infected_at : Option<Time>,
+  recovered_at: Option<Time>,
+}
+
+impl LifeHistory {
+  /// The history of an entity spawned at the instant it is infected.
+  #[must_use]
+  pub fn infected(time: Time) -> Self {
+    LifeHistory{ infected_at: Some(time), recovered_at: None }
+  }
+
+  #[must_use]
+  pub fn infected_at(&self) -> Option<Time> {
+    self.infected_at
+  }
+
+  #[must_use]
+  pub fn recovered_at(&self) -> Option<Time> {
+    self.recovered_at
+  }
+
+  pub fn set_infected_at(&mut self, time: Time) {
+    self.infected_at = Some(time);
+  }
+
+  pub fn set_recovered_at(&mut self, time: Time) {
+    self.recovered_at = Some(time);
+  }
+
+  /// How long the entity was infectious, if it has both been infected and recovered.
+  #[must_use]
+  pub fn infectious_period(&self) -> Option<Duration> {
+    Some(Duration::between(self.infected_at?, self.recovered_at?))
+  }
+}
+
+impl Display for LifeHistory {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let time = |time: Option<Time>| time.map_or("never".to_string(), |time| time.to_string());
+    write!(f, "infected at {}, recovered at {}", time(self.infected_at), time(self.recovered_at))
+  }
+}
+
+/// Sets the `LifeHistory` field of `entity`, inserting a `LifeHistory` if it has none.
+fn update_life_history(world: &mut World, entity: Entity, update: impl FnOnce(&mut LifeHistory)) {
+  match world.get_mut::<LifeHistory>(entity) {
+    Some(mut history) => update(&mut history),
+    None => {
+      let mut history = LifeHistory::default();
+      update(&mut history);
+      world.entity_mut(entity).insert(history);
+    }
+  }
+}
+
+/// Records in the `LifeHistory` of `entity` that it was infected at `time`. See the module documentation.
+pub fn record_infection(world: &mut World, entity: Entity, time: Time) {
+  update_life_history(world, entity, |history| history.set_infected_at(time));
+}
+
+/// Records in the `LifeHistory` of `entity` that it recovered at `time`. See the module documentation.
+pub fn record_recovery(world: &mut World, entity: Entity, time: Time) {
+  update_life_history(world, entity, |history| history.set_recovered_at(time));
+}
+
+/// How long `entity` was infectious, if it has both been infected and recovered, from its `LifeHistory` if it has one
+/// and from its `InfectedAt` and `RecoveredAt` otherwise.
+pub fn infectious_period(world: &World, entity: Entity) -> Option<Duration> {
+  if let Some(history) = world.get::<LifeHistory>(entity) {
+    return history.infectious_period();
+  }
+  let infected_at = world.get::<InfectedAt>(entity)?;
+  let recovered_at = world.get::<RecoveredAt>(entity)?;
+  Some(Duration::between(infected_at.0, recovered_at.0))
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use super::*;
+
+  #[test]
+  fn test_infectious_period() {
+    let mut world = World::default();
+    let recovered = world.spawn((InfectedAt(OrderedFloat(1.0)), RecoveredAt(OrderedFloat(3.5)))).id();
+    let infected = world.spawn(InfectedAt(OrderedFloat(1.0))).id();
+
+    assert_eq!(infectious_period(&world, recovered), Some(Duration(2.5)));
+    assert_eq!(infectious_period(&world, infected), None);
+  }
+
+  #[test]
+  fn test_life_history() {
+    let mut world = World::default();
+    let person = world.spawn(LifeHistory::default()).id();
+    let unrecorded = world.spawn_empty().id();
+    let archetypes = world.archetypes().len();
+
+    record_infection(&mut world, person, OrderedFloat(1.0));
+    assert_eq!(infectious_period(&world, person), None);
+    record_recovery(&mut world, person, OrderedFloat(3.5));
+    // Mutated in place, so no archetype was created.
+    assert_eq!(world.archetypes().len(), archetypes);
+    let history = world.get::<LifeHistory>(person).unwrap();
+    assert_eq!(history.infected_at(), Some(OrderedFloat(1.0)));
+    assert_eq!(infectious_period(&world, person), Some(Duration(2.5)));
+    assert_eq!(history.to_string(), "infected at 1, recovered at 3.5");
+
+    // An entity without a `LifeHistory` gets one.
+    record_infection(&mut world, unrecorded, OrderedFloat(2.0));
+    assert_eq!(world.get::<LifeHistory>(unrecorded), Some(&LifeHistory::infected(OrderedFloat(2.0))));
+    assert_eq!(LifeHistory::default().to_string(), "infected at never, recovered at never");
+  }
+}
@@ -0,0 +1,46 @@
+/*!
+
+Graceful handling of `Ctrl-C`, enabled with the `ctrlc` feature.
+
+The first time a `Model` runs, a signal handler is installed that sets a process-wide flag instead of killing the
+process. The run loop checks the flag after every iteration; when it is set, the loop sets `ModelControl::Aborted` and
+returns normally, so reporters are flushed when they are dropped along with the `Model`. A second `Ctrl-C` before the
+run loop has noticed the first exits the process immediately, in case the model is stuck inside a system.
+
+The handler's notices go to stderr, whatever the `print_messages` feature, so that they never mix into a report written
+to stdout.
+
+*/
+
+use std::sync::{
+  atomic::{AtomicBool, Ordering},
+  Once
+};
+
+/// The exit code of a process terminated by `SIGINT`.
+const INTERRUPTED_EXIT_CODE: i32 = 130;
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// Installs the `Ctrl-C` handler. Only the first call has any effect.
+pub(crate) fn install() {
+  static INSTALL: Once = Once::new();
+  INSTALL.call_once(|| {
+    if let Err(error) = ctrlc::set_handler(on_signal) {
+      eprintln!("Failed to install the Ctrl-C handler: {error}");
+    }
+  });
+}
+
+fn on_signal() {
+  if INTERRUPTED.swap(true, Ordering::SeqCst) {
+    eprintln!("Interrupted again; exiting immediately");
+    std::process::exit(INTERRUPTED_EXIT_CODE);
+  }
+  eprintln!("Interrupted; stopping after the current iteration (press Ctrl-C again to exit immediately)");
+}
+
+/// Returns whether `Ctrl-C` was pressed since the last call, clearing the flag.
+pub(crate) fn take() -> bool {
+  INTERRUPTED.swap(false, Ordering::SeqCst)
+}
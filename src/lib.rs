@@ -3,6 +3,38 @@ pub mod model;
 pub mod random;
 pub mod module;
 pub mod timeline_event;
+pub mod timeline_backend;
 pub mod errors;
 pub mod report;
+pub mod report_sink;
+pub mod cohort_report;
+pub mod aggregate_incidence;
+pub mod compare;
+pub mod compartments;
+pub mod control_handle;
+pub mod diagnostics;
+pub mod distributions;
+pub mod epidemic_curve;
+pub mod event_log;
+pub mod infection_times;
+pub mod metapopulation;
+pub mod milestones;
+pub mod partitioned_report;
+pub mod person_id;
+pub mod result_hash;
+pub mod scenario;
+pub mod sim_constants;
+pub mod status_callbacks;
+pub mod status_history;
+pub mod stop_condition;
+pub mod status_transition;
+pub mod transmission_tree;
+pub mod units;
+mod float_precision;
+#[cfg(feature = "profile")]
+pub mod profile;
+#[cfg(any(test, feature = "test-util"))]
+pub mod test_utils;
+#[cfg(feature = "ctrlc")]
+mod interrupt;
 
@@ -0,0 +1,192 @@
+/*!
+
+Support for metapopulation models, in which several spatially separated populations (patches) share one `World`,
+each with its own transmission dynamics, and occasionally exchange individuals.
+
+Every entity belonging to a patch carries a `PopulationId` component. Entities without one belong to the default
+population, `PopulationId(0)`, so a single-population model needs no changes. Modules that compute anything relative to
+the population, like the denominator of an infection probability, should do so per `PopulationId`.
+
+The `Migration` module moves entities between populations on a schedule. Each `MigrationRoute` moves `count` entities
+chosen uniformly at random from the source population to the destination every `interval`, until the migration's end
+time. The entities are drawn through `RngResource::sample_indices`, so the draws are labeled `migration` in the
+`RngTrace`. Only entities with a `PopulationId` component are moved. Moving an entity just changes its `PopulationId`,
+so modules that keep per-population statistics should watch for changes to it, e.g. with
+`StatusTransitions<PopulationId>`.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+  errors::IxaError,
+  model::AbortReason,
+  module::Module,
+  random::RngResource,
+  timeline::{Time, Timeline},
+  timeline_event::{Event, EventCommand},
+  units::Duration
+};
+
+/// The population (patch) an entity belongs to.
+#[derive(
+  Component, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Default, Debug, Hash, Serialize, Deserialize
+)]
+pub struct PopulationId(pub u32);
+
+impl PopulationId {
+  /// The population of `entity`, which is the default population if it has no `PopulationId`.
+  pub fn of(world: &World, entity: Entity) -> PopulationId {
+    world.get::<PopulationId>(entity).copied().unwrap_or_default()
+  }
+}
+
+/// A scheduled movement of entities from one population to another.
+#[derive(Copy, Clone, PartialEq, Debug)]
+pub struct MigrationRoute {
+  pub from    : PopulationId,
+  pub to      : PopulationId,
+  /// How many entities move each time. If the source population has fewer, all of them move.
+  pub count   : usize,
+  /// The time between moves. The first move is at `interval`.
+  pub interval: Duration,
+}
+
+/// Moves entities between populations on a schedule. See the module documentation.
+#[derive(Resource, Clone, PartialEq, Debug)]
+pub struct Migration {
+  routes: Vec<MigrationRoute>,
+  /// No moves are scheduled after this time.
+  end   : Time,
+}
+
+impl Migration {
+  /// A migration without routes that ends at `end`.
+  pub fn until(end: impl Into<Time>) -> Self {
+    Migration{ routes: Vec::new(), end: end.into() }
+  }
+
+  /// Adds a route moving `count` entities from `from` to `to` every `interval`.
+  #[must_use]
+  pub fn route(mut self, from: PopulationId, to: PopulationId, count: usize, interval: Duration) -> Self {
+    self.routes.push(MigrationRoute{ from, to, count, interval });
+    self
+  }
+
+  pub fn routes(&self) -> &[MigrationRoute] {
+    &self.routes
+  }
+}
+
+/// Schedules the next move along `route` at `time`, unless it is past the end of the migration or the route would
+/// never advance. Fails if the `Timeline` refuses the event.
+fn schedule_move(timeline: &mut Timeline, route: MigrationRoute, time: Time, end: Time) -> Result<(), IxaError> {
+  if time > end || route.interval.0 <= 0.0 {
+    return Ok(());
+  }
+  timeline.push(Event{
+    time,
+    command: EventCommand::fallible(move |world| migrate(world, route)),
+    entity: None,
+    label: "migration",
+  })
+}
+
+/// Moves up to `route.count` entities from `route.from` to `route.to` and schedules the next move.
+fn migrate(world: &mut World, route: MigrationRoute) -> Result<(), IxaError> {
+  let mut candidates: Vec<Entity> = world.query::<(Entity, &PopulationId)>()
+                                         .iter(world)
+                                         .filter(|(_, population)| **population == route.from)
+                                         .map(|(entity, _)| entity)
+                                         .collect();
+  // Query order depends on the archetype layout, so sort for reproducibility.
+  candidates.sort();
+
+  let count = route.count.min(candidates.len());
+  let now = world.resource::<Timeline>().now();
+  let chosen = world.resource_mut::<RngResource>().sample_indices(now, "migration", candidates.len(), count);
+  for index in chosen {
+    *world.get_mut::<PopulationId>(candidates[index]).unwrap() = route.to;
+  }
+
+  #[cfg(feature = "print_messages")]
+  println!("Moved {} entities from population {} to population {}", count, route.from.0, route.to.0);
+
+  let end = world.resource::<Migration>().end;
+  let mut timeline = world.resource_mut::<Timeline>();
+  let next = timeline.now() + route.interval;
+  schedule_move(&mut timeline, route, next, end)
+}
+
+impl Module for Migration {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    let mut timeline = world.resource_mut::<Timeline>();
+    let start = timeline.now();
+    let scheduled = self.routes
+                        .iter()
+                        .try_for_each(|route| schedule_move(&mut timeline, *route, start + route.interval, self.end));
+    world.insert_resource(self);
+    if let Err(error) = scheduled {
+      AbortReason::abort(world, format!("the first migration could not be scheduled: {error}"));
+    }
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module Migration");
+
+    None // No systems
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use crate::model::{Model, ModelControl};
+  use super::*;
+
+  fn count_in(world: &mut World, population: PopulationId) -> usize {
+    world.query::<&PopulationId>().iter(world).filter(|id| **id == population).count()
+  }
+
+  #[test]
+  fn test_migration() {
+    let mut model = Model::with_random_seed(1);
+    model.world_mut().spawn_batch((0..10).map(|_| PopulationId(0)));
+    model.add_module(
+      Migration::until(OrderedFloat(3.0))
+          .route(PopulationId(0), PopulationId(1), 2, Duration(1.0))
+          .route(PopulationId(1), PopulationId(2), 1, Duration(1.5))
+    );
+    model.run();
+
+    // Two move from 0 to 1 at each of the times 1, 2, and 3, and one moves from 1 to 2 at each of the times 1.5 and 3.
+    let world = model.world_mut();
+    assert_eq!(count_in(world, PopulationId(0)), 4);
+    assert_eq!(count_in(world, PopulationId(1)) + count_in(world, PopulationId(2)), 6);
+    assert_eq!(count_in(world, PopulationId(2)), 2);
+    assert_eq!(world.resource::<Timeline>().events_run(), 5);
+  }
+
+  #[test]
+  fn test_migration_that_cannot_be_scheduled_aborts() {
+    // When the module is initialized.
+    let mut model = Model::with_random_seed(1);
+    model.world_mut().resource_mut::<Timeline>().set_max_pending_events(Some(0));
+    model.add_module(Migration::until(OrderedFloat(3.0)).route(PopulationId(0), PopulationId(1), 1, Duration(1.0)));
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Aborted);
+    assert!(model.abort_reason().unwrap().contains("the first migration could not be scheduled"));
+
+    // When the next move is scheduled.
+    let mut model = Model::with_random_seed(1);
+    model.add_module(Migration::until(OrderedFloat(3.0)).route(PopulationId(0), PopulationId(1), 1, Duration(1.0)));
+    model.world_mut().resource_mut::<Timeline>().set_max_pending_events(Some(0));
+    model.run();
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Aborted);
+    let reason = model.abort_reason().unwrap();
+    assert!(reason.contains("\"migration\" at time 1 failed") && reason.contains("full"), "{reason}");
+  }
+}
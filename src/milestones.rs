@@ -0,0 +1,173 @@
+/*!
+
+Milestones are named moments that are not known in advance, like the peak of the epidemic or the 1000th case, and
+events can be scheduled relative to them: "start isolating 3 days after the peak".
+
+`Timeline::on_milestone` defers an event until a milestone is reached, with a delay relative to the time it is reached.
+A system or an event's command marks a milestone as reached at a given time with `Milestones::reach`. Each milestone
+is reached at most once; marking it again changes nothing. Every model has a `Milestones` resource, and a system in
+the `First` phase moves the deferred events of reached milestones onto the `Timeline`, at the time the milestone was
+reached plus their delay. So an event deferred with no delay until a milestone that an event's command reaches runs
+at the same time, in a later iteration of the event loop. An event deferred until a milestone that was already reached
+is scheduled the same way, and is in the past if the delay has passed already, in which case the `Timeline`'s
+`PastEventPolicy` applies.
+
+Events deferred until a milestone that is never reached are never run. They are dropped with the `Timeline`.
+
+*/
+
+use std::collections::HashMap;
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use crate::{
+  model::{AbortReason, ExecutionPhase, ModelControl},
+  module::Module,
+  timeline::{Time, Timeline},
+  timeline_event::Event
+};
+
+/// The milestones reached so far and when. See the module documentation.
+#[derive(Resource, Clone, PartialEq, Default, Debug)]
+pub struct Milestones {
+  reached: HashMap<String, Time>,
+}
+
+impl Milestones {
+  /// Marks `milestone` as reached at `time`. Returns whether it was newly reached; a milestone that was already reached
+  /// keeps its time.
+  pub fn reach(&mut self, milestone: &str, time: impl Into<Time>) -> bool {
+    if self.reached.contains_key(milestone) {
+      return false;
+    }
+    self.reached.insert(milestone.to_string(), time.into());
+    true
+  }
+
+  /// When `milestone` was reached, if it has been.
+  #[must_use]
+  pub fn reached_at(&self, milestone: &str) -> Option<Time> {
+    self.reached.get(milestone).copied()
+  }
+}
+
+/// Schedules the deferred events of the milestones that have been reached.
+fn schedule_milestone_events(
+  milestones: Res<Milestones>,
+  mut timeline: ResMut<Timeline>,
+  mut commands: Commands,
+) {
+  let ready = timeline.take_deferred(|milestone| milestones.reached_at(milestone));
+  for (reached_at, deferred) in ready {
+    let time = reached_at + deferred.delay;
+    let event = Event{ time, command: deferred.command, entity: None, label: deferred.label };
+    if let Err(error) = timeline.push(event) {
+      let reason = format!(
+        "failed to schedule an event deferred until the milestone \"{}\": {error}",
+        deferred.milestone
+      );
+      #[cfg(feature = "print_messages")]
+      println!("{reason}. Aborting.");
+      commands.insert_resource(AbortReason(reason));
+      commands.insert_resource(ModelControl::Aborted);
+    }
+  }
+}
+
+impl Module for Milestones {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module Milestones");
+
+    Some(
+      schedule_milestone_events
+          .run_if(|timeline: Res<Timeline>| timeline.has_deferred())
+          .in_set(ExecutionPhase::First)
+    )
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{Arc, Mutex};
+  use ordered_float::OrderedFloat;
+  use crate::{
+    model::Model,
+    timeline_event::EventCommand,
+    units::Duration
+  };
+  use super::*;
+
+  #[derive(Resource, Default)]
+  struct Cases(u32);
+
+  /// A model with a case at each of the times `1.0, 2.0, ..., 10.0` that reaches the milestone "5 cases" with the
+  /// fifth.
+  fn model_with_cases() -> Model {
+    let mut model = Model::new();
+    model.world_mut().init_resource::<Cases>();
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    for time in 1..=10 {
+      timeline.push(Event{
+        time: OrderedFloat(time as f64),
+        command: EventCommand::infallible(|world: &mut World| {
+          world.resource_mut::<Cases>().0 += 1;
+          if world.resource::<Cases>().0 == 5 {
+            let now = world.resource::<Timeline>().now();
+            world.resource_mut::<Milestones>().reach("5 cases", now);
+          }
+        }),
+        entity: None,
+        label: "case",
+      }).unwrap();
+    }
+    model
+  }
+
+  /// Defers recording the time and number of cases until `milestone` plus `delay`.
+  fn record_on(model: &mut Model, milestone: &str, delay: f64) -> Arc<Mutex<Vec<(f64, u32)>>> {
+    let records = Arc::new(Mutex::new(Vec::new()));
+    let recorded = records.clone();
+    model.world_mut().resource_mut::<Timeline>().on_milestone(
+      milestone,
+      Duration(delay),
+      EventCommand::infallible(move |world: &mut World| {
+        let now = world.resource::<Timeline>().now();
+        recorded.lock().unwrap().push((now.0, world.resource::<Cases>().0));
+      }),
+      "record"
+    );
+    records
+  }
+
+  #[test]
+  fn test_case_count_milestone() {
+    let mut model = model_with_cases();
+    let after_peak = record_on(&mut model, "5 cases", 2.5);
+    let at_peak = record_on(&mut model, "5 cases", 0.0);
+    let never = record_on(&mut model, "100 cases", 0.0);
+    model.run();
+
+    assert_eq!(model.world().resource::<Milestones>().reached_at("5 cases"), Some(OrderedFloat(5.0)));
+    assert_eq!(*at_peak.lock().unwrap(), vec![(5.0, 5)]);
+    assert_eq!(*after_peak.lock().unwrap(), vec![(7.5, 7)]);
+    // A milestone that is never reached leaves its events unrun.
+    assert!(never.lock().unwrap().is_empty());
+    assert!(model.world().resource::<Timeline>().has_deferred());
+  }
+
+  #[test]
+  fn test_reach_once() {
+    let mut milestones = Milestones::default();
+    assert!(milestones.reach("peak", OrderedFloat(3.0)));
+    assert!(!milestones.reach("peak", OrderedFloat(4.0)));
+    assert_eq!(milestones.reached_at("peak"), Some(OrderedFloat(3.0)));
+    assert_eq!(milestones.reached_at("end"), None);
+  }
+}
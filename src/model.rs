@@ -3,20 +3,127 @@
 A `Model` manages the execution loop and abstracts over Bevy ECS specific implementation details.
 
 This is not the best design. It's just a demo. For example, modules have unfettered access to the entire schedule.
-I think a little more is needed to enable parallelism, also.
 
 Names are hard. `Context` is used in Ixa to mean wht Bevy ECS calls `World`, and of course `World` is taken. `Model`
 plays the role of `App` in full Bevy.
 
+# Bevy events
+
+Besides the discrete events on the `Timeline`, modules can communicate with Bevy ECS events (messages), which let a
+producer announce that something happened, like an infection, without knowing who is interested. Register an event
+type with `Model::add_event` (or `EventRegistry::register_event` from within a module), send events with an
+`EventWriter` or `World::send_event`, and read them with an `EventReader`.
+
+Like `App` in full Bevy, the model updates the event buffers once per iteration of the event loop, in the `First`
+phase. An iteration of the event loop runs at most one `Timeline` event, so an event sent while handling a `Timeline`
+event is readable for the rest of that iteration and all of the next, and a reader that runs every iteration sees every
+event exactly once. Events sent from a `Timeline` event are sent when its command is applied, which is after the
+`Normal` phase systems that ran alongside it, so those systems see them in the next iteration.
+
+# Warnings
+
+Modules are initialized eagerly, as they are added, so the order in which they are added matters. As a best-effort check
+of the model's setup, when the model starts running, it warns about any system that uses a resource that is not in the
+world, which makes the system panic when it runs unless it can do without the resource. Each system is checked once,
+at the first run after it was added. Bevy ECS does not tell optional parameters like `Option<Res<T>>` apart from
+required ones, so a model whose systems can do without a resource says so with `Model::allow_missing_resource`, and
+that resource is not reported. Adding a `ReporterConfiguration` after a reporter, which silently used the configuration
+it replaces, the default one unless another was added before, is also warned about. Warnings are collected in
+`Model::warnings`, and printed as they are found with the `print_messages` feature.
+
+# Finalizers
+
+A module that has something to do when the run ends, like writing a summary, adds a finalizer to the world's
+`Finalizers` with `Finalizers::add`. When the event loop stops for any reason but `ModelControl::Paused`, the model
+runs the finalizers in the order they were added, each once: a finalizer is removed when it runs, so a model that is
+run again does not run it again.
+
+# Pausing and stepping
+
+A module pauses the model by setting `ModelControl::Paused`, and another thread, like the UI thread of an interactive
+front-end, does so through a `ControlHandle` (see the `control_handle` module). Either way, the event loop stops after
+the current iteration and `Model::run` returns with the world intact: the `Timeline` keeps its pending events and its
+current time, which is that of the last event run, nothing is despawned or flushed, and the finalizers do not run. The
+resume contract is:
+
+ - Running a paused model, with any `run*` method, resumes it: the model is set `Running` and the loop continues with
+   the next event. A run that is paused and resumed any number of times gives the same results as one that is not,
+   because the model pauses only between iterations and pausing draws no random numbers.
+ - While paused, the world can be inspected and changed, e.g. to edit parameters or remove a module's resource, and
+   the changes take effect when the model resumes. Events scheduled while paused must not be in the past.
+ - A model stopped any other way, `Finished`, `Aborted`, or `TimedOut`, is not resumed by running it again.
+
+`Model::run_interactive` does not return when the model pauses, but waits for the `ControlHandle` to resume it, so
+that a front-end only needs the handle. It returns once the model stops any other way, or is aborted while paused.
+
+An external controller, like a debugger, can also advance the model one event at a time through the `ModelControl`
+resource alone: set it to `ModelControl::Step` and call `Model::run`, which runs exactly one iteration of the event loop,
+and so at most one `Timeline` event, and returns with the model `ModelControl::Paused`. Systems and events see the
+model `Running` during that iteration, so stop conditions and the like work as usual, and if one of them stops the model
+during the step, its `ModelControl` wins over the pause. A module that sets `ModelControl::Step` during a run pauses
+the model after the current iteration, as if it had set `ModelControl::Paused`. Paused models do not run their
+finalizers, so a model can be stepped through to the end and the finalizers run when the model stops for good.
+
+# Parallelism
+
+With the `multi_threaded` feature, Bevy ECS's multithreaded executor runs the systems of each phase in parallel, as far
+as their data access allows: two systems run at the same time only if neither writes anything the other reads or writes.
+The `Timeline` system, which runs between the `First` and `Last` phases alongside the `Normal` phase, writes only the
+`Timeline` and `ModelControl`. The event it pops is run as a command, which is applied at a sync point, when no system is
+running, so event commands keep their exclusive access to the world. Systems that only read, like most statistics and
+reporters with their own resources, can therefore run side by side. Exclusive systems, with a `&mut World` parameter,
+still run alone, so modules should prefer narrower parameters: the stop condition system, for example, reads the world
+with `&World` and finishes the model with a command.
+
+Parallel systems run in no particular order unless ordered with `.before` and `.after`. Any two systems that draw from
+the `RngResource` must be ordered, or the order of their draws, and so the results of a seeded run, vary from run to
+run. Commands are applied in the order of the systems in the schedule either way (see the `person_id` module).
+`Model::set_multithreaded` switches executors, e.g. to compare the two, and the `profile` feature starts the model
+single-threaded so that it can attribute time to systems.
+
+# Removing and disabling modules
+
+A module cannot be removed from a model, but what it does can be stopped, e.g. to edit a scenario between runs or from
+an event at some time. `Model::remove_resource` (or `World::remove_resource` from an event) removes a module's resource.
+Events a module scheduled for itself typically look its resource up when they run, and should do nothing if it is gone,
+as the infection attempts of the basic infection example do. Its systems stay in the schedule, though, and Bevy ECS
+panics when a system with a `Res<R>` parameter runs without `R`, so a module whose resource may be removed should add
+its systems with the run condition `.run_if(resource_exists::<R>)`. They are then skipped while the resource is
+missing, and run again if it is inserted again.
+
+A module can be turned off and on again without losing its state the same way, with a separate resource the run
+condition tests, e.g. a `struct Enabled(bool)` and `.run_if(|enabled: Res<Enabled>| enabled.0)`.
+
 */
 
+use std::{
+  collections::{BTreeMap, HashSet},
+  time::Instant
+};
+
 use bevy_ecs::prelude::*;
-use bevy_ecs::schedule::SystemConfigs;
+use bevy_ecs::{
+  component::ComponentId,
+  event::{event_update_system, EventRegistry},
+  schedule::{ExecutorKind, NodeId, SystemConfigs}
+};
+use rand::{Rng, RngCore, SeedableRng};
 use crate::{
-  random::RngResource,
-  module::Module,
-  timeline::Timeline
+  control_handle::{ControlHandle, ControlRequest},
+  diagnostics::{short_type_name, ModelDiagnostics},
+  milestones::Milestones,
+  random::{log_default_seed_used, random_entity_weighted, random_entity_where, RngResource, SeedResource, DEFAULT_SEED},
+  errors::IxaError,
+  module::{Module, ModuleRegistry},
+  report::ReporterConfiguration,
+  result_hash::ResultHash,
+  scenario::Scenario,
+  sim_constants::SimConstants,
+  stop_condition::{InfectionCap, StopConditions},
+  timeline::{event_fired, Timeline, TimelineSystem}
 };
+#[cfg(feature = "indicatif")]
+use crate::timeline::Time;
 // ToDo: `Model` should use the builder pattern.
 
 /// A `Model` has three execution phases that it runs in order within the event loop.
@@ -27,9 +134,56 @@ pub enum ExecutionPhase {
   Last,
 }
 
+/// A callback registered with `Model::on_control_change`.
+pub type ControlChangeCallback = Box<dyn Fn(ModelControl, &World) + Send + Sync>;
+
 pub struct Model {
   schedule: Schedule,
-  world: World
+  world: World,
+  /// The systems already checked by `Model::check_system_resources`.
+  checked_systems: HashSet<NodeId>,
+  /// The resources systems can do without. See `Model::allow_missing_resource`.
+  optional_resources: HashSet<ComponentId>,
+  /// Called by the run loop when `ModelControl` changes.
+  control_change_callbacks: Vec<ControlChangeCallback>,
+  /// Polled by the run loop after every iteration, once one has been handed out. See `Model::control_handle`.
+  control_handle: Option<ControlHandle>,
+}
+
+/// Work to do once when a run ends. See `Finalizers`.
+pub type Finalizer = Box<dyn FnOnce(&mut World) + Send + Sync>;
+
+/// The finalizers to run when the run ends. See the module documentation.
+#[derive(Resource, Default)]
+pub struct Finalizers(Vec<Finalizer>);
+
+impl Finalizers {
+  /// Adds `finalizer` to the world's `Finalizers`.
+  pub fn add(world: &mut World, finalizer: impl FnOnce(&mut World) + Send + Sync + 'static) {
+    world.get_resource_or_insert_with(Finalizers::default).0.push(Box::new(finalizer));
+  }
+}
+
+/// The configuration mistakes noticed while a model was built. See the module documentation.
+#[derive(Resource, Clone, Eq, PartialEq, Default, Debug)]
+pub struct ModelWarnings(pub Vec<String>);
+
+impl ModelWarnings {
+  /// Adds `warning` to the world's `ModelWarnings`, printing it with the `print_messages` feature.
+  pub fn warn(world: &mut World, warning: String) {
+    #[cfg(feature = "print_messages")]
+    println!("Warning: {warning}");
+    world.get_resource_or_insert_with(ModelWarnings::default).0.push(warning);
+  }
+}
+
+/// What `Model::dry_run` found.
+#[derive(Clone, Eq, PartialEq, Default, Debug)]
+pub struct DryRunReport {
+  /// The number of iterations of the event loop that ran.
+  pub iterations: usize,
+  /// Anything that looks like a configuration mistake. Empty if all looks well.
+  pub warnings: Vec<String>,
 }
 
 /// The `ModelControl` resource is how modules communicate to the `Model` to effect the event loop.
@@ -37,9 +191,26 @@ pub struct Model {
 pub enum ModelControl {
   #[default]
   Running, // The simulation can run as normal
-  Paused,  // Debug mode, perhaps
+  Paused,  // Stopped between iterations, to be resumed by the next run. See the module documentation.
   Aborted, // Aborted do due error condition or user request
-  Finished // The simulation has run to completion
+  Finished, // The simulation has run to completion
+  TimedOut, // The wall-clock budget of `Model::run_for` ran out
+  Step,     // Run one iteration of the event loop, then pause. See the module documentation.
+}
+
+/// Why the model was aborted, if it was aborted because of an error, such as a fallible `Event` command failing.
+#[derive(Resource, Clone, Eq, PartialEq, Debug)]
+pub struct AbortReason(pub String);
+
+impl AbortReason {
+  /// Aborts the model of `world` because of `reason`, printing it with the `print_messages` feature. For errors outside
+  /// of an event command, e.g. while a module is initialized; a fallible `EventCommand` returns its error instead.
+  pub fn abort(world: &mut World, reason: String) {
+    #[cfg(feature = "print_messages")]
+    println!("{reason}. Aborting.");
+    world.insert_resource(AbortReason(reason));
+    world.insert_resource(ModelControl::Aborted);
+  }
 }
 
 fn system_for_first_phase() {
@@ -61,23 +232,75 @@ impl Default for Model {
 }
 
 impl Model {
+  /// Creates a model seeded with `DEFAULT_SEED`. Use `Model::with_random_seed` for anything other than a demo.
   pub fn new() -> Self {
-    Self::with_random_seed(42)
+    log_default_seed_used();
+    Self::with_random_seed(DEFAULT_SEED)
+  }
+
+  /// Creates a model seeded from operating system entropy. The seed drawn is logged and recorded in the
+  /// `SeedResource` (see `Model::seed`) so the run can be replayed with `Model::with_random_seed`.
+  pub fn with_entropy_seed() -> Self {
+    let seed: u64 = rand::rng().random();
+    #[cfg(feature = "print_messages")]
+    println!("Using entropy seed {seed}");
+    Self::with_random_seed(seed)
   }
 
   pub fn with_random_seed(seed: u64) -> Self {
+    Self::with_rng_resource(RngResource::with_random_seed(seed))
+  }
+
+  /// Creates a model with the given `SimConstants`, seeded with `constants.seed`.
+  pub fn with_constants(constants: SimConstants) -> Self {
+    let mut model = Self::with_random_seed(constants.seed);
+    model.world.insert_resource(constants);
+    model
+  }
+
+  /// Creates a model from the scenario file at `path`, constructing its modules with `registry`. See the `scenario`
+  /// module.
+  pub fn from_scenario(path: impl AsRef<std::path::Path>, registry: &ModuleRegistry) -> Result<Self, IxaError> {
+    Scenario::from_path(path.as_ref())?.build(registry)
+  }
+
+  /// Creates a model whose PRNG is an `R` seeded with `seed` rather than the default `SmallRng`. See the `random`
+  /// module for the cost of doing so.
+  pub fn with_rng<R: RngCore + SeedableRng + Send + Sync + 'static>(seed: u64) -> Self {
+    Self::with_rng_resource(RngResource::with_rng::<R>(seed))
+  }
+
+  fn with_rng_resource(rng_resource: RngResource) -> Self {
+    // Must be installed before any systems are constructed.
+    #[cfg(feature = "profile")]
+    crate::profile::install();
+
     let mut model = Model {
       schedule: Schedule::default(),
       world: World::default(),
+      checked_systems: HashSet::new(),
+      optional_resources: HashSet::new(),
+      control_change_callbacks: Vec::new(),
+      control_handle: None,
     };
 
+    #[cfg(feature = "profile")]
+    {
+      // Profiling attributes time to the model running on the current thread.
+      model.schedule.set_executor_kind(ExecutorKind::SingleThreaded);
+      model.world.init_resource::<crate::profile::SystemProfile>();
+    }
+
     // Insert the system control resource
     model.world.insert_resource(ModelControl::default());
+    // Every reporter finds a configuration, which adding a `ReporterConfiguration` module replaces.
+    model.world.insert_resource(ReporterConfiguration::default());
 
     // Add the phase schedules to the parent schedule with labels
     model.schedule.add_systems(
       (
         system_for_first_phase.in_set(ExecutionPhase::First),
+        event_update_system.in_set(ExecutionPhase::First),
         system_for_normal_phase.in_set(ExecutionPhase::Normal),
         system_for_last_phase.in_set(ExecutionPhase::Last)
       )
@@ -88,16 +311,114 @@ impl Model {
       (
         ExecutionPhase::First.before(ExecutionPhase::Normal),
         ExecutionPhase::Normal.before(ExecutionPhase::Last),
+        TimelineSystem.after(ExecutionPhase::First).before(ExecutionPhase::Last),
       )
     );
 
     // Every `World` gets these modules
     model.add_module(Timeline::default());
-    model.add_module(RngResource::with_random_seed(seed));
+    model.add_module(rng_resource);
+    model.add_module(StopConditions::default());
+    model.add_module(Milestones::default());
+    let seed = model.seed();
+    model.world.insert_resource(SimConstants::builder().seed(seed).build());
 
     model
   }
 
+  /// The `World` this model runs on, for inspecting state before or after a run.
+  #[must_use]
+  pub fn world(&self) -> &World {
+    &self.world
+  }
+
+  /// Mutable access to the `World` this model runs on, for example to configure a resource such as the `Timeline`.
+  #[must_use]
+  pub fn world_mut(&mut self) -> &mut World {
+    &mut self.world
+  }
+
+  /// Removes the resource `R`, e.g. a module's, and returns it, or `None` if there was none. See the module
+  /// documentation.
+  pub fn remove_resource<R: Resource>(&mut self) -> Option<R> {
+    self.world.remove_resource::<R>()
+  }
+
+  /// Runs the systems of each phase in parallel if `multithreaded` is true, and one at a time on the current thread
+  /// otherwise. Without the `multi_threaded` feature, systems run one at a time either way. See the module
+  /// documentation.
+  pub fn set_multithreaded(&mut self, multithreaded: bool) {
+    let executor = if multithreaded { ExecutorKind::MultiThreaded } else { ExecutorKind::SingleThreaded };
+    self.schedule.set_executor_kind(executor);
+  }
+
+  /// The master seed this model was created with.
+  #[must_use]
+  pub fn seed(&self) -> u64 {
+    self.world.resource::<SeedResource>().0
+  }
+
+  /// The simulation-wide constants this model was created with.
+  #[must_use]
+  pub fn constants(&self) -> &SimConstants {
+    self.world.resource::<SimConstants>()
+  }
+
+  /// Why the model was aborted, if it was aborted because of an error. A model that aborts only because its timeline
+  /// ran out of events has no reason.
+  #[must_use]
+  pub fn abort_reason(&self) -> Option<&str> {
+    self.world.get_resource::<AbortReason>().map(|reason| reason.0.as_str())
+  }
+
+  /// The hash of the status changes so far, for regression tests, or `None` if no `HashStatusChanges` module was
+  /// added. See the `result_hash` module.
+  #[must_use]
+  pub fn result_hash(&self) -> Option<u64> {
+    self.world.get_resource::<ResultHash>().map(ResultHash::value)
+  }
+
+  /// The configuration mistakes noticed so far. See the module documentation.
+  #[must_use]
+  pub fn warnings(&self) -> &[String] {
+    self.world.get_resource::<ModelWarnings>().map_or(&[], |warnings| warnings.0.as_slice())
+  }
+
+  /// A snapshot of the number of entities and their archetypes. Cheap; see the `diagnostics` module.
+  #[must_use]
+  pub fn diagnostics(&self) -> ModelDiagnostics {
+    ModelDiagnostics::of(&self.world)
+  }
+
+  /// The number of entities with each value of the component `C`, e.g. each infection status. This scans every entity
+  /// with a `C`.
+  pub fn count_by<C: Component + Copy + Ord>(&mut self) -> BTreeMap<C, usize> {
+    let mut counts = BTreeMap::new();
+    for value in self.world.query::<&C>().iter(&self.world) {
+      *counts.entry(*value).or_insert(0) += 1;
+    }
+    counts
+  }
+
+  /// A uniformly random entity with the component `C`, or `None` if there is none. See `random_entity_where`.
+  pub fn random_entity_with<C: Component>(&mut self) -> Option<Entity> {
+    random_entity_where::<C>(&mut self.world, |_| true)
+  }
+
+  /// A uniformly random entity whose `C` satisfies `predicate`, e.g. a random susceptible person, or `None` if there
+  /// is none. Draws from the model's `RngResource`, so the choice is reproducible for a given seed. See the `random`
+  /// module.
+  pub fn random_entity_where<C: Component>(&mut self, predicate: impl Fn(&C) -> bool) -> Option<Entity> {
+    random_entity_where(&mut self.world, predicate)
+  }
+
+  /// A random entity with the component `C`, chosen with probability proportional to `weight` of its `C`, e.g. a
+  /// susceptible person weighted by their risk, or `None` if no entity has a positive weight. Entities of zero weight
+  /// are never chosen. See `random_entity_weighted`.
+  pub fn random_entity_weighted<C: Component>(&mut self, weight: impl Fn(&C) -> f64) -> Option<Entity> {
+    random_entity_weighted(&mut self.world, weight)
+  }
+
   /// Adds the module `M` to this model. Notice that `M` is a generic parameter. The model will call the static
   /// constructor of `M` to create a new instance of the model.
   pub fn add_module<M: Module>(&mut self, module: M) {
@@ -106,33 +427,1117 @@ impl Model {
     }
   }
 
-  /// Adds the systems to the schedule. This is used for systems that aren't added by a module.
+  /// Registers the Bevy event type `E` so that it can be sent and read by systems. See the module documentation.
+  pub fn add_event<E: Event>(&mut self) {
+    EventRegistry::register_event::<E>(&mut self.world);
+  }
+
+  /// Adds a module whose type is only known at runtime, for example one constructed by a `ModuleRegistry`.
+  pub fn add_module_boxed(&mut self, module: Box<dyn Module>) {
+    if let Some(systems) = module.initialize_boxed_with_world(&mut self.world) {
+      self.schedule.add_systems(systems);
+    }
+  }
+
+  /// Adds a condition under which the simulation stops. Conditions are checked in the `Last` phase of every
+  /// iteration, and the model finishes as soon as any one of them is true.
+  pub fn add_stop_condition(&mut self, condition: impl Fn(&World) -> bool + Send + Sync + 'static) {
+    self.world.resource_mut::<StopConditions>().push(condition);
+  }
+
+  /// Registers `callback` to be called with the new value and the world whenever `ModelControl` changes between
+  /// iterations of the event loop, e.g. when the model finishes or aborts. Callbacks are called in the order they were
+  /// registered, as soon as the change is seen after the iteration in which it happened. A value set and reset within
+  /// one iteration is not seen.
+  pub fn on_control_change(&mut self, callback: impl Fn(ModelControl, &World) + Send + Sync + 'static) {
+    self.control_change_callbacks.push(Box::new(callback));
+  }
+
+  /// A handle through which another thread can pause, resume, or abort the model. Clones share the model. See the
+  /// `control_handle` module.
+  pub fn control_handle(&mut self) -> ControlHandle {
+    self.control_handle.get_or_insert_with(ControlHandle::default).clone()
+  }
+
+  /// Stops the model once `cap` entities have transitioned into the `infected` value of the status component `C`,
+  /// after the rest of the events at that time. See `InfectionCap`.
+  pub fn stop_after_infections<C: Component + Copy + PartialEq>(&mut self, infected: C, cap: usize) {
+    self.add_module(InfectionCap::new(infected, cap));
+  }
+
+  /// Adds the systems to the schedule. This is used for systems that aren't added by a module. The systems should
+  /// already be in an `ExecutionPhase`; otherwise use `Model::add_system_to_phase`.
   pub fn add_systems(&mut self, systems: SystemConfigs) {
     self.schedule.add_systems(systems);
   }
 
+  /// Adds one or more systems to the schedule in the given `phase`.
+  pub fn add_system_to_phase<M>(&mut self, systems: impl IntoSystemConfigs<M>, phase: ExecutionPhase) {
+    self.schedule.add_systems(systems.in_set(phase));
+  }
+
+  /// Declares that the model's systems can do without the resource `R`, e.g. because they take it as
+  /// `Option<Res<R>>`, so that it is not warned about when it is missing. See the module documentation.
+  pub fn allow_missing_resource<R: Resource>(&mut self) {
+    let resource = self.world.register_resource::<R>();
+    self.optional_resources.insert(resource);
+  }
+
+  /// Warns about every system added since the last check that uses a resource that is not in the world and not
+  /// allowed to be missing. Called when the model starts running. See the module documentation.
+  fn check_system_resources(&mut self) {
+    // Initializing a system registers the resources it uses. It would happen on the first run anyway.
+    self.schedule.graph_mut().initialize(&mut self.world);
+
+    let mut warnings = Vec::new();
+    for (node, system, _) in self.schedule.graph().systems() {
+      if !self.checked_systems.insert(node) {
+        continue;
+      }
+      for resource in system.component_access().resource_reads_and_writes() {
+        let present = self.world.storages().resources.get(resource).is_some_and(|data| data.is_present());
+        if !present && !self.optional_resources.contains(&resource) {
+          warnings.push(format!(
+            "the system {} uses the resource {}, which is not in the world",
+            system.name(),
+            short_type_name(self.world.components().get_name(resource).unwrap_or("unknown"))
+          ));
+        }
+      }
+    }
+    for warning in warnings {
+      ModelWarnings::warn(&mut self.world, warning);
+    }
+  }
+
+  /// Switches the model to sparse mode, in which the `Normal` phase runs only in iterations of the event loop in which
+  /// the `Timeline` ran an event. The `Timeline` then runs before the `Normal` phase rather than alongside it, and its
+  /// event's command is applied in between, so `Normal` phase systems see the effects of the event at the event's time
+  /// instead of in the next iteration. This cannot be undone.
+  ///
+  /// The loop never iterates through idle time, since the current time jumps from event to event, and every iteration
+  /// but the last runs an event, so skipping the `Normal` phase saves little. The main effect is on what `Normal` phase
+  /// systems see.
+  pub fn enable_sparse_mode(&mut self) {
+    self.schedule.configure_sets(
+      (
+        TimelineSystem.before(ExecutionPhase::Normal),
+        ExecutionPhase::Normal.run_if(event_fired),
+      )
+    );
+  }
 
-  /// Runs the simulation
+  /// Runs the simulation. With the `ctrlc` feature, `Ctrl-C` stops the run with `ModelControl::Aborted`.
   pub fn run(&mut self) {
+    self.run_loop(|_| {});
+  }
+
+  /// Runs the simulation like `Model::run`, except that when the model pauses, it waits for a `ControlHandle` to resume
+  /// or abort it instead of returning. See the module documentation.
+  pub fn run_interactive(&mut self) {
+    let handle = self.control_handle();
+    loop {
+      self.run();
+      if *self.world.resource::<ModelControl>() != ModelControl::Paused {
+        return;
+      }
+      if handle.wait_for_resume() == ControlRequest::Abort {
+        self.world.insert_resource(ModelControl::Aborted);
+        self.notify_control_change(ModelControl::Aborted);
+        self.run_finalizers();
+        return;
+      }
+    }
+  }
+
+  /// Runs the simulation for at most `budget` of wall-clock time, regardless of how far simulated time has got, for
+  /// bounded demos and "does it run" tests. If the budget runs out first, the model stops with
+  /// `ModelControl::TimedOut`. The run returns normally either way, so reporters are flushed as usual.
+  ///
+  /// The elapsed time is checked between iterations of the event loop, so a single long iteration can overrun the
+  /// budget slightly.
+  pub fn run_for(&mut self, budget: std::time::Duration) {
+    let start = Instant::now();
+    self.run_loop(|world| {
+      if start.elapsed() >= budget && *world.resource::<ModelControl>() == ModelControl::Running {
+        #[cfg(feature = "print_messages")]
+        println!("Wall-clock budget of {budget:?} exceeded. Requesting ModelControl::TimedOut");
+        world.insert_resource(ModelControl::TimedOut);
+      }
+    });
+  }
+
+  /// Runs the simulation while showing a progress bar of the current simulated time against `max_time`. The bar is
+  /// drawn to stderr and is hidden automatically when stderr is not a terminal, so piped output is unaffected.
+  #[cfg(feature = "indicatif")]
+  pub fn run_with_bar(&mut self, max_time: impl Into<Time>) {
+    let max_time: Time = max_time.into();
+    use indicatif::{ProgressBar, ProgressStyle};
+
+    let bar = ProgressBar::new(max_time.ceil() as u64);
+    bar.set_style(
+      ProgressStyle::with_template("[{elapsed_precise}] {bar:40} t = {pos}/{len}")
+          .expect("progress bar template is valid")
+    );
+
+    self.run_loop(|world| {
+      if let Some(timeline) = world.get_resource::<Timeline>() {
+        bar.set_position(timeline.now().floor() as u64);
+      }
+    });
+
+    bar.finish();
+  }
+
+  /// Runs at most `max_events` iterations of the event loop with report output suppressed, to check that the model is
+  /// configured correctly before committing to a full run. Module initialization and schedule construction errors
+  /// surface as they would in a real run; the returned report lists suspicious conditions seen while running.
+  ///
+  /// Reporters added before the dry run have already created their (empty) report files. The model is left where the
+  /// dry run stopped: the events that ran are not undone, and running the model afterward continues from there, with
+  /// report output no longer suppressed. A model meant for a full run should therefore be built anew.
+  pub fn dry_run(&mut self, max_events: usize) -> DryRunReport {
+    let mut report = DryRunReport::default();
+    let suppressed = {
+      let config = self.world.resource::<ReporterConfiguration>();
+      let was_suppressed = config.is_suppressed();
+      config.set_suppressed(true);
+      was_suppressed
+    };
+    let past_event_count = self.world.resource::<Timeline>().past_event_count();
+    self.check_system_resources();
+
+    while report.iterations < max_events {
+      self.schedule.run(&mut self.world);
+      report.iterations += 1;
+      if *self.world.resource::<ModelControl>() != ModelControl::Running {
+        break;
+      }
+    }
+
+    self.world.resource::<ReporterConfiguration>().set_suppressed(suppressed);
+
+    let timeline = self.world.resource::<Timeline>();
+    let past_events = timeline.past_event_count() - past_event_count;
+    if past_events > 0 {
+      report.warnings.push(format!("{past_events} event(s) were scheduled before the current time"));
+    }
+    if timeline.pending().next().is_none() {
+      report.warnings.push(format!("the timeline is empty after {} iteration(s)", report.iterations));
+    }
+    match *self.world.resource::<ModelControl>() {
+      ModelControl::Running => {}
+      control => {
+        report.warnings.push(format!("the model stopped ({control:?}) after {} iteration(s)", report.iterations));
+      }
+    }
+
+    report
+  }
+
+  /// Runs every phase of the schedule once without running an event, for testing systems: set up the state of the
+  /// world, tick, and check what the systems did. The `Timeline` does not advance, so `now` and the scheduled events
+  /// are unchanged, and `ModelControl` is neither checked nor reset. Commands queued by systems are applied as in any
+  /// iteration. In sparse mode the `Normal` phase is skipped, as in any iteration in which no event fires.
+  pub fn tick_systems_only(&mut self) {
+    self.world.resource_mut::<Timeline>().hold_next_event();
+    self.schedule.run(&mut self.world);
+  }
+
+  /// The event loop shared by the `run*` methods. The `on_iteration` callback is called after every run of the
+  /// schedule, before `ModelControl` is checked.
+  fn run_loop(&mut self, mut on_iteration: impl FnMut(&mut World)) {
+    // An abort requested while the model was stopped takes effect before another iteration runs.
+    let control = *self.world.resource::<ModelControl>();
+    if matches!(control, ModelControl::Running | ModelControl::Paused | ModelControl::Step)
+      && self.control_handle.as_ref().is_some_and(ControlHandle::take_abort)
+    {
+      self.world.insert_resource(ModelControl::Aborted);
+      self.notify_control_change(ModelControl::Aborted);
+      self.run_finalizers();
+      return;
+    }
+
+    #[cfg(feature = "profile")]
+    crate::profile::start();
+    #[cfg(feature = "ctrlc")]
+    {
+      crate::interrupt::install();
+      // Forget any `Ctrl-C` that arrived between runs.
+      crate::interrupt::take();
+    }
+
+    self.check_system_resources();
+
+    let mut previous_control = *self.world.resource::<ModelControl>();
+    // A paused model resumes where it stopped.
+    if previous_control == ModelControl::Paused {
+      self.world.insert_resource(ModelControl::Running);
+    }
+    // A step runs as a normal iteration and pauses afterward unless the model was stopped during it.
+    let stepping = previous_control == ModelControl::Step;
+    if stepping {
+      self.world.insert_resource(ModelControl::Running);
+    }
+
     // limit loops for debug purposes
     loop {
 
       self.schedule.run(&mut self.world);
+      on_iteration(&mut self.world);
+
+      #[cfg(feature = "ctrlc")]
+      if crate::interrupt::take() {
+        self.world.insert_resource(ModelControl::Aborted);
+      }
+
+      // A step requested during the iteration ends with it.
+      if *self.world.resource::<ModelControl>() == ModelControl::Step {
+        self.world.insert_resource(ModelControl::Paused);
+      }
+
+      if let Some(handle) = &self.control_handle {
+        let control = *self.world.resource::<ModelControl>();
+        match handle.take() {
+          Some(ControlRequest::Pause) if control == ModelControl::Running => {
+            self.world.insert_resource(ModelControl::Paused);
+          }
+          Some(ControlRequest::Abort) if matches!(control, ModelControl::Running | ModelControl::Paused) => {
+            self.world.insert_resource(ModelControl::Aborted);
+          }
+          _ => {}
+        }
+      }
+
+      if stepping && *self.world.resource::<ModelControl>() == ModelControl::Running {
+        self.world.insert_resource(ModelControl::Paused);
+      }
+
+      let control = *self.world.resource::<ModelControl>();
+      if control != previous_control {
+        self.notify_control_change(control);
+        previous_control = control;
+      }
 
       // We act on `ModelControl` requests
       match self.world.get_resource::<ModelControl>().unwrap() {
         ModelControl::Paused
         | ModelControl::Aborted
-        | ModelControl::Finished => {
+        | ModelControl::Finished
+        | ModelControl::TimedOut => {
           // For this demo these all do the same thing.
           #[cfg(feature = "print_messages")]
-          println!("Stopping model");
+          println!("Stopping model (seed {})", self.seed());
           break;
         }
 
         ModelControl::Running => { /* pass */ }
+
+        ModelControl::Step => unreachable!("a step is replaced by `Running` before the loop and `Paused` in it"),
+      }
+
+    }
+
+    if *self.world.resource::<ModelControl>() != ModelControl::Paused {
+      self.run_finalizers();
+    }
+
+    #[cfg(feature = "profile")]
+    {
+      let mut profile = self.world.resource_mut::<crate::profile::SystemProfile>();
+      crate::profile::finish(&mut profile);
+      println!("System profile:\n{}", *profile);
+    }
+    #[cfg(feature = "diagnostics")]
+    println!("Model diagnostics:\n{}", self.diagnostics());
+  }
+
+  fn notify_control_change(&self, control: ModelControl) {
+    for callback in &self.control_change_callbacks {
+      callback(control, &self.world);
+    }
+  }
+
+  /// Runs the finalizers not yet run. See the module documentation.
+  fn run_finalizers(&mut self) {
+    if let Some(mut finalizers) = self.world.get_resource_mut::<Finalizers>() {
+      for finalizer in std::mem::take(&mut finalizers.0) {
+        finalizer(&mut self.world);
+      }
+    }
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use rand::Rng;
+  use crate::{
+    errors::IxaError,
+    report::Reporter,
+    report_sink::MemoryRowSink,
+    timeline_event::{Event, EventCommand}
+  };
+  use super::*;
+
+  #[derive(Resource, Default)]
+  struct RecoveredCount(u32);
+
+  /// Schedules a recovery at each of the times `1.0, 2.0, ..., count`.
+  fn schedule_recoveries(model: &mut Model, count: u32) {
+    model.world.init_resource::<RecoveredCount>();
+    let mut timeline = model.world.resource_mut::<Timeline>();
+    for time in 1..=count {
+      timeline.push(Event{
+        time: OrderedFloat(time as f64),
+        command: EventCommand::infallible(|world: &mut World| world.resource_mut::<RecoveredCount>().0 += 1),
+        entity: None,
+        label: "recovery",
+      }).unwrap();
+    }
+  }
+
+  #[test]
+  fn test_stop_after_recoveries() {
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 10);
+    model.add_stop_condition(|world| world.resource::<RecoveredCount>().0 >= 3);
+    model.run();
+
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 3);
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Finished);
+  }
+
+  #[test]
+  fn test_any_stop_condition_stops() {
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 10);
+    model.add_stop_condition(|world| world.resource::<RecoveredCount>().0 >= 8);
+    model.add_stop_condition(|world| world.resource::<Timeline>().now() >= OrderedFloat(5.0));
+    model.run();
+
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 5);
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Finished);
+  }
+
+  #[test]
+  fn test_model_is_send() {
+    fn assert_send<T: Send>() {}
+    // A model can be built on one thread and run on another, e.g. a worker thread of an interactive front-end.
+    assert_send::<Model>();
+  }
+
+  #[test]
+  fn test_on_control_change() {
+    use std::sync::{Arc, Mutex};
+
+    fn record_changes(model: &mut Model) -> Arc<Mutex<Vec<(ModelControl, u32)>>> {
+      let changes = Arc::new(Mutex::new(Vec::new()));
+      let recorded = changes.clone();
+      model.on_control_change(move |control, world| {
+        recorded.lock().unwrap().push((control, world.resource::<RecoveredCount>().0));
+      });
+      changes
+    }
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 10);
+    model.add_stop_condition(|world| world.resource::<RecoveredCount>().0 >= 3);
+    let changes = record_changes(&mut model);
+    model.run();
+    assert_eq!(*changes.lock().unwrap(), vec![(ModelControl::Finished, 3)]);
+
+    // Running out of events aborts the model.
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 2);
+    let changes = record_changes(&mut model);
+    model.run();
+    assert_eq!(*changes.lock().unwrap(), vec![(ModelControl::Aborted, 2)]);
+  }
+
+  #[test]
+  fn test_step() {
+    use std::sync::{Arc, Mutex};
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 3);
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = changes.clone();
+    model.on_control_change(move |control, _| recorded.lock().unwrap().push(control));
+
+    for step in 1..=3 {
+      *model.world.resource_mut::<ModelControl>() = ModelControl::Step;
+      model.run();
+      assert_eq!(model.world.resource::<Timeline>().now(), OrderedFloat(step as f64));
+      assert_eq!(model.world.resource::<RecoveredCount>().0, step);
+      assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Paused);
+    }
+    assert_eq!(*changes.lock().unwrap(), vec![ModelControl::Paused; 3]);
+
+    // Stepping past the last event stops the model as running would.
+    *model.world.resource_mut::<ModelControl>() = ModelControl::Step;
+    model.run();
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 3);
+  }
+
+  #[test]
+  fn test_step_requested_mid_run() {
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 5);
+    model.add_system_to_phase(
+      |count: Res<RecoveredCount>, mut control: ResMut<ModelControl>, mut stepped: Local<bool>| {
+        if count.0 == 2 && !*stepped {
+          *stepped = true;
+          *control = ModelControl::Step;
+        }
+      },
+      ExecutionPhase::Last
+    );
+
+    // The model pauses after the iteration in which the step was requested.
+    model.run();
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Paused);
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 2);
+    assert_eq!(model.world.resource::<Timeline>().now(), OrderedFloat(2.0));
+
+    // And resumes as from any pause.
+    model.run();
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 5);
+  }
+
+  #[test]
+  fn test_pause_and_resume() {
+    use std::sync::{Arc, Mutex};
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 5);
+    let pause = EventCommand::infallible(|world| world.insert_resource(ModelControl::Paused));
+    model.world.resource_mut::<Timeline>().schedule_at(2.5, pause, "pause").unwrap();
+    Finalizers::add(&mut model.world, |world| world.resource_mut::<RecoveredCount>().0 += 100);
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = changes.clone();
+    model.on_control_change(move |control, _| recorded.lock().unwrap().push(control));
+
+    model.run();
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Paused);
+    assert_eq!(model.world.resource::<Timeline>().now(), OrderedFloat(2.5));
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 2);
+    assert_eq!(model.world.resource::<Timeline>().len(), 3);
+
+    // Running again resumes where the model paused, and the finalizer runs once, at the end.
+    model.run();
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 105);
+    assert_eq!(*changes.lock().unwrap(), [ModelControl::Paused, ModelControl::Running, ModelControl::Aborted]);
+  }
+
+  #[test]
+  fn test_control_handle() {
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 10);
+    Finalizers::add(&mut model.world, |world| world.resource_mut::<RecoveredCount>().0 += 100);
+    let handle = model.control_handle();
+
+    // A front-end on another thread, told when the model pauses, resumes it the first time and aborts it the second.
+    let (paused, on_pause) = std::sync::mpsc::channel();
+    model.on_control_change(move |control, world| {
+      if control == ModelControl::Paused {
+        paused.send(world.resource::<RecoveredCount>().0).unwrap();
       }
+    });
+    let front_end_handle = handle.clone();
+    let front_end = std::thread::spawn(move || {
+      let first = on_pause.recv().unwrap();
+      front_end_handle.resume();
+      let second = on_pause.recv().unwrap();
+      front_end_handle.abort();
+      (first, second)
+    });
+    // The user asks to pause at times 2.5 and 5.5.
+    for time in [2.5, 5.5] {
+      let clicking = handle.clone();
+      let click = EventCommand::infallible(move |_| clicking.pause());
+      model.world.resource_mut::<Timeline>().schedule_at(time, click, "click").unwrap();
+    }
+
+    model.run_interactive();
+    assert_eq!(front_end.join().unwrap(), (2, 5));
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 105);
+  }
 
+  #[test]
+  fn test_control_handle_abort_while_paused() {
+    use std::sync::{Arc, Mutex};
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 5);
+    Finalizers::add(&mut model.world, |world| world.resource_mut::<RecoveredCount>().0 += 100);
+    let handle = model.control_handle();
+    let clicking = handle.clone();
+    let click = EventCommand::infallible(move |_| clicking.pause());
+    model.world.resource_mut::<Timeline>().schedule_at(2.5, click, "click").unwrap();
+    let changes = Arc::new(Mutex::new(Vec::new()));
+    let recorded = changes.clone();
+    model.on_control_change(move |control, _| recorded.lock().unwrap().push(control));
+
+    model.run();
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Paused);
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 2);
+
+    // The abort takes effect when the model is run again, without running another event.
+    handle.abort();
+    model.run();
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 102);
+    assert_eq!(model.world.resource::<Timeline>().now(), OrderedFloat(2.5));
+    assert_eq!(*changes.lock().unwrap(), [ModelControl::Paused, ModelControl::Aborted]);
+  }
+
+  #[test]
+  fn test_remove_resource() {
+    #[derive(Resource)]
+    struct Counter(u32);
+
+    fn count(mut counter: ResMut<Counter>) {
+      counter.0 += 1;
+    }
+
+    let mut model = Model::new();
+    model.world.insert_resource(Counter(0));
+    model.add_system_to_phase(count.run_if(resource_exists::<Counter>), ExecutionPhase::Normal);
+    model.tick_systems_only();
+    assert_eq!(model.world.resource::<Counter>().0, 1);
+
+    // Without the resource, the system is skipped rather than panicking.
+    assert_eq!(model.remove_resource::<Counter>().map(|counter| counter.0), Some(1));
+    assert!(model.remove_resource::<Counter>().is_none());
+    model.tick_systems_only();
+    model.world.insert_resource(Counter(10));
+    model.tick_systems_only();
+    assert_eq!(model.world.resource::<Counter>().0, 11);
+  }
+
+  #[test]
+  fn test_random_entity() {
+    #[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+    enum Status {
+      Susceptible,
+      Infected,
+    }
+
+    let mut model = Model::with_random_seed(3);
+    assert_eq!(model.random_entity_with::<Status>(), None);
+    model.world.spawn(Status::Infected);
+    assert_eq!(model.random_entity_where(|status: &Status| *status == Status::Susceptible), None);
+    let susceptible: Vec<Entity> = model.world.spawn_batch([Status::Susceptible; 4]).collect();
+
+    let mut chosen: BTreeMap<Entity, usize> = BTreeMap::new();
+    for _ in 0..4000 {
+      let person = model.random_entity_where(|status: &Status| *status == Status::Susceptible).unwrap();
+      *chosen.entry(person).or_insert(0) += 1;
+    }
+    assert_eq!(chosen.keys().copied().collect::<Vec<_>>(), susceptible);
+    assert!(chosen.values().all(|count| (850..1150).contains(count)), "{chosen:?}");
+    assert_ne!(model.random_entity_with::<Status>(), None);
+
+    // The same seed makes the same choices.
+    let choices = |seed: u64| {
+      let mut model = Model::with_random_seed(seed);
+      model.world.spawn_batch([Status::Susceptible; 10]);
+      (0..5).map(|_| model.random_entity_with::<Status>().unwrap()).collect::<Vec<_>>()
+    };
+    assert_eq!(choices(7), choices(7));
+  }
+
+  #[test]
+  fn test_random_entity_weighted() {
+    #[derive(Component, Copy, Clone, PartialEq, Debug)]
+    struct Risk(f64);
+
+    let mut model = Model::with_random_seed(3);
+    assert_eq!(model.random_entity_weighted(|risk: &Risk| risk.0), None);
+    model.world.spawn_batch([Risk(0.0), Risk(0.0)]);
+    // All-zero weights choose nobody.
+    assert_eq!(model.random_entity_weighted(|risk: &Risk| risk.0), None);
+    let people: Vec<Entity> = model.world
+                                   .spawn_batch([Risk(1.0), Risk(2.0), Risk(0.0), Risk(3.0), Risk(4.0)])
+                                   .collect();
+
+    let mut chosen: BTreeMap<Entity, usize> = BTreeMap::new();
+    for _ in 0..10_000 {
+      let person = model.random_entity_weighted(|risk: &Risk| risk.0).unwrap();
+      *chosen.entry(person).or_insert(0) += 1;
+    }
+    // People of zero weight are never chosen, and the others are chosen in proportion to their weights, each within
+    // about four standard deviations.
+    assert_eq!(chosen.keys().copied().collect::<Vec<_>>(), [people[0], people[1], people[3], people[4]]);
+    for (person, expected) in [(people[0], 1000), (people[1], 2000), (people[3], 3000), (people[4], 4000)] {
+      assert!(chosen[&person].abs_diff(expected) < 200, "{chosen:?}");
     }
   }
+
+  #[test]
+  fn test_multithreaded() {
+    #[derive(Resource, Default)]
+    struct Counts(Vec<u32>);
+
+    #[derive(Resource, Default)]
+    struct Doubled(Vec<u32>);
+
+    fn record_count(recovered: Res<RecoveredCount>, mut counts: ResMut<Counts>) {
+      counts.0.push(recovered.0);
+    }
+
+    fn record_double(recovered: Res<RecoveredCount>, mut doubled: ResMut<Doubled>) {
+      doubled.0.push(2 * recovered.0);
+    }
+
+    let run = |multithreaded: bool| {
+      let mut model = Model::new();
+      model.set_multithreaded(multithreaded);
+      schedule_recoveries(&mut model, 10);
+      model.world.init_resource::<Counts>();
+      model.world.init_resource::<Doubled>();
+      model.add_system_to_phase((record_count, record_double), ExecutionPhase::Last);
+      model.add_stop_condition(|world| world.resource::<RecoveredCount>().0 >= 5);
+      model.run();
+      assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Finished);
+      (model.world.remove_resource::<Counts>().unwrap().0, model.world.remove_resource::<Doubled>().unwrap().0)
+    };
+
+    let (counts, doubled) = run(true);
+    assert_eq!(counts, [1, 2, 3, 4, 5]);
+    assert_eq!(doubled, counts.iter().map(|count| 2 * count).collect::<Vec<_>>());
+    assert_eq!(run(false), (counts, doubled));
+  }
+
+  #[test]
+  fn test_bevy_events() {
+    #[derive(Event)]
+    struct Recovered;
+
+    #[derive(Resource, Default)]
+    struct RecoveredEvents(u32);
+
+    fn count_recovered(mut count: ResMut<RecoveredEvents>, mut recovered: EventReader<Recovered>) {
+      count.0 += recovered.read().count() as u32;
+    }
+
+    let mut model = Model::new();
+    model.add_event::<Recovered>();
+    model.world.init_resource::<RecoveredEvents>();
+    model.add_systems(count_recovered.in_set(ExecutionPhase::Normal));
+    let mut timeline = model.world.resource_mut::<Timeline>();
+    for time in 1..=5 {
+      timeline.push(Event{
+        time: OrderedFloat(time as f64),
+        command: EventCommand::infallible(|world: &mut World| { world.send_event(Recovered); }),
+        entity: None,
+        label: "recovery",
+      }).unwrap();
+    }
+    model.run();
+
+    assert_eq!(model.world.resource::<RecoveredEvents>().0, 5);
+  }
+
+  #[test]
+  fn test_failing_event_aborts() {
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 3);
+    model.world.resource_mut::<Timeline>().push(Event{
+      time: OrderedFloat(1.5),
+      command: EventCommand::fallible(|_| Err(IxaError::IxaError("nobody to recover".to_string()))),
+      entity: None,
+      label: "recovery",
+    }).unwrap();
+    model.run();
+
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 1);
+    let reason = model.abort_reason().unwrap();
+    assert!(reason.starts_with("event \"recovery\" at time 1.5 failed"), "{reason}");
+    assert!(reason.contains("nobody to recover"), "{reason}");
+  }
+
+  #[test]
+  fn test_zero_progress_aborts() {
+    /// Reschedules itself with no delay, forever.
+    fn reschedule(world: &mut World) {
+      world.resource_mut::<RecoveredCount>().0 += 1;
+      world.resource_mut::<Timeline>().schedule_in(0.0, EventCommand::infallible(reschedule), "loop").unwrap();
+    }
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 1);
+    let mut timeline = model.world.resource_mut::<Timeline>();
+    timeline.set_max_stalled_events(1_000);
+    timeline.schedule_at(1.0, EventCommand::infallible(reschedule), "loop").unwrap();
+    model.run();
+
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    // The recovery and the first 1,000 loop events ran at time 1.
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 1_001);
+    let reason = model.abort_reason().unwrap();
+    assert!(reason.starts_with("no time progress: 1001 consecutive events ran at time 1"), "{reason}");
+  }
+
+  #[test]
+  fn test_full_timeline_aborts() {
+    /// Schedules itself twice, one time unit later, so that the pending events double every time unit.
+    fn fork(world: &mut World) -> Result<(), IxaError> {
+      world.resource_mut::<RecoveredCount>().0 += 1;
+      let mut timeline = world.resource_mut::<Timeline>();
+      for _ in 0..2 {
+        timeline.schedule_in(1.0, EventCommand::fallible(fork), "fork")?;
+      }
+      Ok(())
+    }
+
+    /// The same, ignoring the error.
+    fn fork_carelessly(world: &mut World) {
+      let mut timeline = world.resource_mut::<Timeline>();
+      for _ in 0..2 {
+        let _ = timeline.schedule_in(1.0, EventCommand::infallible(fork_carelessly), "fork");
+      }
+    }
+
+    let mut model = Model::new();
+    model.world.init_resource::<RecoveredCount>();
+    let mut timeline = model.world.resource_mut::<Timeline>();
+    timeline.set_max_pending_events(Some(100));
+    timeline.schedule_at(0.0, EventCommand::fallible(fork), "fork").unwrap();
+    model.run();
+
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world.resource::<Timeline>().len(), 100);
+    // Each event adds one pending event, so the 100th is the first that cannot schedule both of its own.
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 100);
+    let reason = model.abort_reason().unwrap();
+    assert!(reason.contains("because the timeline is full, with 100 events pending"), "{reason}");
+
+    let mut model = Model::new();
+    let mut timeline = model.world.resource_mut::<Timeline>();
+    timeline.set_max_pending_events(Some(100));
+    timeline.schedule_at(0.0, EventCommand::infallible(fork_carelessly), "fork").unwrap();
+    model.run();
+
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.world.resource::<Timeline>().len(), 100);
+    let reason = model.abort_reason().unwrap();
+    assert!(reason.starts_with("the timeline is full: 1 event(s) could not be scheduled with 100 pending"), "{reason}");
+  }
+
+  #[test]
+  fn test_add_system_to_phase() {
+    #[derive(Resource, Default)]
+    struct Order(Vec<ExecutionPhase>);
+
+    let mut model = Model::new();
+    model.world.init_resource::<Order>();
+    // Added in reverse order of their phases.
+    model.add_system_to_phase(|mut order: ResMut<Order>| order.0.push(ExecutionPhase::Last), ExecutionPhase::Last);
+    model.add_system_to_phase(|mut order: ResMut<Order>| order.0.push(ExecutionPhase::Normal), ExecutionPhase::Normal);
+    model.add_system_to_phase(|mut order: ResMut<Order>| order.0.push(ExecutionPhase::First), ExecutionPhase::First);
+    model.run();
+
+    let order = &model.world.resource::<Order>().0;
+    assert_eq!(order, &[ExecutionPhase::First, ExecutionPhase::Normal, ExecutionPhase::Last]);
+  }
+
+  #[test]
+  fn test_sparse_mode() {
+    #[derive(Resource, Default)]
+    struct Seen(Vec<(f64, u32)>);
+
+    fn record(timeline: Res<Timeline>, count: Res<RecoveredCount>, mut seen: ResMut<Seen>) {
+      seen.0.push((timeline.now().0, count.0));
+    }
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 3);
+    model.world.init_resource::<Seen>();
+    model.add_systems(record.in_set(ExecutionPhase::Normal));
+    model.enable_sparse_mode();
+    model.run();
+
+    // Each recovery is seen at its own time, and the `Normal` phase is skipped once the timeline is empty.
+    assert_eq!(model.world.resource::<Seen>().0, vec![(1.0, 1), (2.0, 2), (3.0, 3)]);
+  }
+
+  #[test]
+  fn test_run_for_times_out() {
+    /// Reschedules itself a day later, forever.
+    fn tick(world: &mut World) {
+      world.resource_mut::<RecoveredCount>().0 += 1;
+      let mut timeline = world.resource_mut::<Timeline>();
+      let time = timeline.now() + 1.0;
+      timeline.push(Event{ time, command: EventCommand::infallible(tick), entity: None, label: "tick" }).unwrap();
+    }
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 0);
+    model.world.resource_mut::<Timeline>().push(Event{
+      time: OrderedFloat(0.0),
+      command: EventCommand::infallible(tick),
+      entity: None,
+      label: "tick",
+    }).unwrap();
+    let start = Instant::now();
+    model.run_for(std::time::Duration::from_millis(50));
+
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::TimedOut);
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    assert!(model.world.resource::<RecoveredCount>().0 > 0);
+
+    // A model that finishes within its budget stops as usual.
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 3);
+    model.run_for(std::time::Duration::from_secs(60));
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 3);
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Aborted);
+  }
+
+  #[test]
+  fn test_missing_resources_are_warned_about() {
+    #[derive(Resource, Default)]
+    struct Seen(u32);
+    #[derive(Resource)]
+    struct Unseen;
+
+    let mut model = Model::new();
+    // Resources are checked when the model runs, so it does not matter that this one is added after its system.
+    model.add_system_to_phase(|mut seen: ResMut<Seen>| seen.0 += 1, ExecutionPhase::Normal);
+    model.world.init_resource::<Seen>();
+    // A system that never runs, so that it does not panic.
+    model.add_system_to_phase((|_: Res<Unseen>| {}).run_if(|| false), ExecutionPhase::Normal);
+    assert!(model.warnings().is_empty(), "{:?}", model.warnings());
+
+    model.run();
+    assert_eq!(model.world.resource::<Seen>().0, 1);
+    assert_eq!(model.warnings().len(), 1);
+    let warning = &model.warnings()[0];
+    assert!(warning.contains("uses the resource Unseen, which is not in the world"), "{warning}");
+
+    // Each system is checked once.
+    model.run();
+    assert_eq!(model.warnings().len(), 1);
+  }
+
+  #[test]
+  fn test_allowed_missing_resources_are_not_warned_about() {
+    #[derive(Resource)]
+    struct Unseen;
+
+    let mut model = Model::new();
+    model.allow_missing_resource::<Unseen>();
+    model.add_system_to_phase(|unseen: Option<Res<Unseen>>| assert!(unseen.is_none()), ExecutionPhase::Normal);
+    model.run();
+    assert!(model.warnings().is_empty(), "{:?}", model.warnings());
+  }
+
+  #[test]
+  fn test_late_reporter_configuration_is_warned_about() {
+    let mut model = Model::new();
+    model.add_module(ReporterConfiguration::default());
+    assert!(model.warnings().is_empty());
+
+    let mut model = Model::new();
+    // What a reporter does when it is initialized before any `ReporterConfiguration` is added.
+    ReporterConfiguration::for_reporter(&mut model.world);
+    model.add_module(ReporterConfiguration::default());
+    assert_eq!(model.warnings().len(), 1);
+    assert!(model.warnings()[0].contains("added after a reporter"), "{:?}", model.warnings());
+  }
+
+  struct CountMarker;
+
+  /// Reports the `RecoveredCount` after every iteration.
+  type CountReporter = Reporter<CountMarker, MemoryRowSink>;
+
+  /// A model with recoveries at the times `1.0, 2.0, ..., 10.0`, whose `CountReporter` writes to the returned sink.
+  fn model_with_count_report() -> (Model, MemoryRowSink) {
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 10);
+    let rows = MemoryRowSink::new();
+    model.add_module(CountReporter::new_with_sink("counts".to_string(), rows.clone()));
+    model.add_system_to_phase(
+      |count: Res<RecoveredCount>, mut reporter: ResMut<CountReporter>| reporter.write_row(count.0).unwrap(),
+      ExecutionPhase::Last
+    );
+    (model, rows)
+  }
+
+  #[test]
+  fn test_dry_run() {
+    let (mut model, rows) = model_with_count_report();
+    let report = model.dry_run(3);
+    assert_eq!(report, DryRunReport{ iterations: 3, warnings: vec![] });
+    // The events ran, but no report rows were written.
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 3);
+    assert!(rows.is_empty());
+
+    let report = model.dry_run(100);
+    assert_eq!(report.iterations, 8);
+    // The timeline is empty, so the model aborted.
+    assert_eq!(
+      report.warnings,
+      ["the timeline is empty after 8 iteration(s)", "the model stopped (Aborted) after 8 iteration(s)"]
+    );
+    assert!(rows.is_empty());
+  }
+
+  #[test]
+  fn test_run_after_dry_run() {
+    let (mut model, rows) = model_with_count_report();
+    model.dry_run(3);
+    model.run();
+
+    // The run continued from where the dry run stopped, and wrote rows only for its own iterations.
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 10);
+    let counts = rows.take_rows();
+    assert_eq!(counts.first(), Some(&serde_json::json!(4)));
+    assert_eq!(counts.last(), Some(&serde_json::json!(10)));
+  }
+
+  #[test]
+  fn test_entropy_seeds_differ() {
+    let mut first = Model::with_entropy_seed();
+    let mut second = Model::with_entropy_seed();
+    assert_ne!(first.seed(), second.seed());
+
+    let first_draw: u64 = first.world.resource_mut::<RngResource>().rng.random();
+    let second_draw: u64 = second.world.resource_mut::<RngResource>().rng.random();
+    assert_ne!(first_draw, second_draw);
+  }
+
+  #[test]
+  fn test_entropy_seed_is_replayable() {
+    let mut original = Model::with_entropy_seed();
+    let mut replay = Model::with_random_seed(original.seed());
+
+    let original_draw: u64 = original.world.resource_mut::<RngResource>().rng.random();
+    let replay_draw: u64 = replay.world.resource_mut::<RngResource>().rng.random();
+    assert_eq!(original_draw, replay_draw);
+  }
+
+  #[test]
+  fn test_custom_rng() {
+    let draw = |mut model: Model| -> u64 { model.world.resource_mut::<RngResource>().rng.random() };
+    let std_draw = draw(Model::with_rng::<rand::rngs::StdRng>(5));
+    assert_eq!(std_draw, draw(Model::with_rng::<rand::rngs::StdRng>(5)));
+    assert_ne!(std_draw, draw(Model::with_random_seed(5)));
+    assert_eq!(Model::with_rng::<rand::rngs::StdRng>(5).seed(), 5);
+  }
+
+  #[test]
+  fn test_shuffle_entities() {
+    use crate::random::{random_order_iter, shuffle_entities};
+
+    let shuffled = |seed: u64, reversed: bool| {
+      let mut model = Model::with_random_seed(seed);
+      let mut people: Vec<Entity> = model.world.spawn_batch([Status::Susceptible; 20]).collect();
+      if reversed {
+        people.reverse();
+      }
+      shuffle_entities(&mut people, &mut model.world.resource_mut::<RngResource>().rng);
+      people
+    };
+
+    // The same seed gives the same permutation, whatever the order beforehand.
+    let permutation = shuffled(8, false);
+    assert_eq!(permutation, shuffled(8, true));
+    assert_ne!(permutation, shuffled(9, false));
+    let mut sorted = permutation.clone();
+    sorted.sort();
+    assert_ne!(permutation, sorted);
+
+    let mut model = Model::with_random_seed(8);
+    let people: Vec<Entity> = model.world.spawn_batch([Status::Susceptible; 20]).collect();
+    let rng = &mut model.world.resource_mut::<RngResource>().rng;
+    assert_eq!(random_order_iter(people, rng).collect::<Vec<_>>(), permutation);
+  }
+
+  #[test]
+  fn test_module_streams() {
+    use crate::random::derive_seed;
+
+    let model = Model::with_random_seed(8);
+    let seed = model.world.resource::<SeedResource>();
+    let draws = |mut rng: rand::rngs::SmallRng| (0..10_000).map(|_| rng.random::<f64>()).collect::<Vec<f64>>();
+    let timeline = draws(seed.for_module::<Timeline>());
+    let stop_conditions = draws(seed.for_module::<StopConditions>());
+
+    // Two modules' streams are uncorrelated.
+    let n = timeline.len() as f64;
+    let mean = |draws: &[f64]| draws.iter().sum::<f64>() / n;
+    let (mean_a, mean_b) = (mean(&timeline), mean(&stop_conditions));
+    let covariance: f64 =
+        timeline.iter().zip(&stop_conditions).map(|(a, b)| (a - mean_a) * (b - mean_b)).sum::<f64>() / n;
+    let variance = |draws: &[f64], mean: f64| draws.iter().map(|draw| (draw - mean).powi(2)).sum::<f64>() / n;
+    let correlation = covariance / (variance(&timeline, mean_a) * variance(&stop_conditions, mean_b)).sqrt();
+    assert!(correlation.abs() < 0.05, "correlation {correlation}");
+
+    // A module's stream depends only on the seed and its name.
+    assert_eq!(timeline, draws(seed.stream("Timeline")));
+    assert_ne!(timeline, draws(SeedResource(9).for_module::<Timeline>()));
+    // The derivation is stable.
+    assert_eq!(derive_seed(42, "TransmissionManager"), 4_615_340_019_806_401_344);
+  }
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+  enum Status {
+    Susceptible,
+    Infected,
+  }
+
+  #[test]
+  fn test_tick_systems_only() {
+    #[derive(Resource, Default)]
+    struct NewlyInfected(Vec<Entity>);
+
+    fn track_infections(query: Query<(Entity, &Status), Changed<Status>>, mut newly_infected: ResMut<NewlyInfected>) {
+      newly_infected.0.extend(query.iter().filter(|(_, status)| **status == Status::Infected).map(|(entity, _)| entity));
+    }
+
+    let mut model = Model::new();
+    schedule_recoveries(&mut model, 2);
+    model.world.init_resource::<NewlyInfected>();
+    model.add_systems(track_infections.in_set(ExecutionPhase::Normal));
+    let person = model.world.spawn(Status::Susceptible).id();
+    model.tick_systems_only();
+    assert!(model.world.resource::<NewlyInfected>().0.is_empty());
+
+    *model.world.get_mut::<Status>(person).unwrap() = Status::Infected;
+    model.tick_systems_only();
+    assert_eq!(model.world.resource::<NewlyInfected>().0, vec![person]);
+
+    // No event was run.
+    let timeline = model.world.resource::<Timeline>();
+    assert_eq!((timeline.now(), timeline.len(), timeline.events_run()), (OrderedFloat(0.0), 2, 0));
+    assert_eq!(model.world.resource::<RecoveredCount>().0, 0);
+    assert_eq!(*model.world.resource::<ModelControl>(), ModelControl::Running);
+  }
+
+  #[test]
+  fn test_count_by() {
+    let mut model = Model::new();
+    model.world.spawn_batch([Status::Infected, Status::Susceptible, Status::Infected]);
+    assert_eq!(model.count_by::<Status>(), BTreeMap::from([(Status::Susceptible, 1), (Status::Infected, 2)]));
+    assert_eq!(model.diagnostics().entities, 3);
+  }
+
+  #[test]
+  fn test_constants() {
+    let constants = SimConstants::builder().population_size(100).max_time(50.0).seed(9).build();
+    let model = Model::with_constants(constants);
+    assert_eq!(*model.constants(), constants);
+    assert_eq!(model.seed(), 9);
+
+    let model = Model::with_random_seed(3);
+    assert_eq!(*model.constants(), SimConstants{ seed: 3, ..SimConstants::default() });
+  }
 }
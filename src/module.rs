@@ -14,12 +14,127 @@ An alternative design could be that the `Module`'s constructor takes a `&mut Mod
 implementation details, at least the public API, of `Model` everywhere, whereas `World` has (for our purposes)
 a fixed API. Full Bevy takes this route: https://bevy-cheatbook.github.io/programming/plugins.html.
 
+Modules are usually added with `Model::add_module`, which is generic over the module type. When the set of modules
+is only known at runtime, for example because it is read from a configuration file, modules can be added as
+`Box<dyn Module>` with `Model::add_module_boxed`. A `ModuleRegistry` maps module names to constructors for this case.
+
 */
 
+use std::collections::HashMap;
+
 use bevy_ecs::prelude::*;
 use bevy_ecs::schedule::SystemConfigs;
+use serde::de::DeserializeOwned;
 
-pub trait Module {
+use crate::errors::IxaError;
+
+pub trait Module: BoxedModule {
   /// This method is a constructor and is called to initialize a new `Module` with the provided `World`.
   fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs>;
 }
+
+/// The object-safe counterpart of `Module::initialize_with_world`, which takes `self` by value and so cannot be called
+/// on a `dyn Module`. This is implemented automatically for every `Module`.
+pub trait BoxedModule {
+  fn initialize_boxed_with_world(self: Box<Self>, world: &mut World) -> Option<SystemConfigs>;
+}
+
+impl<M: Module> BoxedModule for M {
+  fn initialize_boxed_with_world(self: Box<Self>, world: &mut World) -> Option<SystemConfigs> {
+    (*self).initialize_with_world(world)
+  }
+}
+
+/// Constructs a module from its (possibly empty) JSON configuration.
+pub type ModuleConstructor = Box<dyn Fn(&serde_json::Value) -> Result<Box<dyn Module>, IxaError> + Send + Sync>;
+
+/// Maps module names to constructors so that modules can be chosen at runtime.
+#[derive(Default)]
+pub struct ModuleRegistry {
+  constructors: HashMap<String, ModuleConstructor>,
+}
+
+impl ModuleRegistry {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers `constructor` under `name`, replacing any constructor previously registered under that name.
+  pub fn register<F>(&mut self, name: &str, constructor: F)
+      where F: Fn(&serde_json::Value) -> Result<Box<dyn Module>, IxaError> + Send + Sync + 'static
+  {
+    self.constructors.insert(name.to_string(), Box::new(constructor));
+  }
+
+  /// Registers `M` under `name`, constructing it by deserializing its configuration.
+  pub fn register_deserializable<M>(&mut self, name: &str)
+      where M: Module + DeserializeOwned + 'static
+  {
+    self.register(name, |configuration| {
+      let module: M = serde_json::from_value(configuration.clone())?;
+      Ok(Box::new(module))
+    });
+  }
+
+  /// Constructs the module registered under `name` from the given configuration.
+  pub fn construct(&self, name: &str, configuration: &serde_json::Value) -> Result<Box<dyn Module>, IxaError> {
+    match self.constructors.get(name) {
+      Some(constructor) => constructor(configuration),
+      None => Err(IxaError::IxaError(format!(
+        "unknown module `{}`; known modules are {:?}",
+        name,
+        self.names()
+      ))),
+    }
+  }
+
+  /// The registered module names, sorted.
+  pub fn names(&self) -> Vec<&str> {
+    let mut names: Vec<&str> = self.constructors.keys().map(String::as_str).collect();
+    names.sort_unstable();
+    names
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use serde::Deserialize;
+  use crate::model::Model;
+  use super::*;
+
+  #[derive(Resource, Deserialize, Debug, PartialEq)]
+  struct Counter {
+    start: u32,
+  }
+
+  impl Module for Counter {
+    fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+      world.insert_resource(self);
+      None
+    }
+  }
+
+  #[test]
+  fn test_registry_constructs_boxed_modules() {
+    let mut registry = ModuleRegistry::new();
+    registry.register_deserializable::<Counter>("counter");
+
+    let module = registry.construct("counter", &serde_json::json!({ "start": 3 })).unwrap();
+    let mut model = Model::new();
+    model.add_module_boxed(module);
+
+    assert_eq!(model.world().resource::<Counter>(), &Counter{ start: 3 });
+  }
+
+  #[test]
+  fn test_registry_unknown_module() {
+    let mut registry = ModuleRegistry::new();
+    registry.register_deserializable::<Counter>("counter");
+
+    let Err(IxaError::IxaError(message)) = registry.construct("missing", &serde_json::Value::Null) else {
+      panic!("constructing an unregistered module should fail");
+    };
+    assert!(message.contains("counter"));
+  }
+}
@@ -0,0 +1,253 @@
+/*!
+
+A `PartitionedReporter` writes one report file per key instead of one combined file with a key column, e.g. one file
+per census tract. A key function computes the key of each row, and the row is written to the file of that key, which
+is created the first time the key is seen and named `{prefix}{short_name}_{key}.csv` after the `Display` of the key,
+so the key must display as something that can be part of a filename. The files are created according to the
+`ReporterConfiguration`, like the file of a `Reporter`, and each has its own header.
+
+With many keys, keeping every file open would exhaust the process's file descriptors, so at most
+`PartitionedReporter::with_max_open_files` files are open at once (`DEFAULT_MAX_OPEN_FILES` by default). When another
+is needed, the least recently written one is flushed and closed, and it is reopened in append mode the next time a row
+has its key.
+
+The `float_precision`, `quote_style`, `flush_each_row`, and suppression of the configuration apply as for a
+`Reporter`. Selecting and renaming columns, `flush_every`, and final rows are not supported.
+
+*/
+
+use std::{
+  collections::HashMap,
+  fmt::Display,
+  fs::{File, OpenOptions},
+  hash::Hash,
+  marker::PhantomData,
+  path::{Path, PathBuf}
+};
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use csv::{Writer as CsvWriter, WriterBuilder};
+use serde::Serialize;
+
+use crate::{
+  errors::IxaError,
+  float_precision::WithFloatPrecision,
+  module::Module,
+  report::ReporterConfiguration
+};
+
+/// The number of files a `PartitionedReporter` keeps open at once unless configured otherwise.
+pub const DEFAULT_MAX_OPEN_FILES: usize = 64;
+
+/// Computes the key of a row.
+pub type PartitionKey<Row, K> = Box<dyn Fn(&Row) -> K + Send + Sync>;
+
+/// An open file of a partition.
+struct OpenPartition {
+  writer   : CsvWriter<File>,
+  /// When a row was last written to the file, in rows written by the reporter.
+  last_used: u64,
+}
+
+/// Writes rows to one file per key. See the module documentation.
+#[derive(Resource)]
+pub struct PartitionedReporter<Marker, K, Row>
+  where Marker: Send + Sync + 'static,
+        K: Eq + Hash + Clone + Display + Send + Sync + 'static,
+        Row: Serialize + 'static
+{
+  short_name    : String,
+  key           : PartitionKey<Row, K>,
+  max_open_files: usize,
+  /// Copied on initialization. `None` until then.
+  configuration : Option<ReporterConfiguration>,
+  /// The path of the file of every key seen so far.
+  paths         : HashMap<K, PathBuf>,
+  open          : HashMap<K, OpenPartition>,
+  rows_written  : u64,
+  marker        : PhantomData<fn() -> Marker>,
+}
+
+impl<Marker, K, Row> PartitionedReporter<Marker, K, Row>
+  where Marker: Send + Sync + 'static,
+        K: Eq + Hash + Clone + Display + Send + Sync + 'static,
+        Row: Serialize + 'static
+{
+  /// Creates a `PartitionedReporter` with the provided short name that writes each row to the file of its `key`.
+  pub fn new(short_name: String, key: impl Fn(&Row) -> K + Send + Sync + 'static) -> Self {
+    PartitionedReporter{
+      short_name,
+      key: Box::new(key),
+      max_open_files: DEFAULT_MAX_OPEN_FILES,
+      configuration: None,
+      paths: HashMap::new(),
+      open: HashMap::new(),
+      rows_written: 0,
+      marker: PhantomData,
+    }
+  }
+
+  /// Keeps at most `max_open_files` files open at once, and at least one.
+  #[must_use]
+  pub fn with_max_open_files(mut self, max_open_files: usize) -> Self {
+    self.max_open_files = max_open_files.max(1);
+    self
+  }
+
+  /// This is called from `initialize_with_world`, so it could be private.
+  pub fn initialize(&mut self, report_configuration: &ReporterConfiguration) {
+    self.configuration = Some(report_configuration.clone());
+  }
+
+  /// The path of the file of `key`, if a row with that key has been written.
+  #[must_use]
+  pub fn output_path(&self, key: &K) -> Option<&Path> {
+    self.paths.get(key).map(PathBuf::as_path)
+  }
+
+  /// The keys of the rows written so far and the paths of their files, in no particular order.
+  pub fn output_paths(&self) -> impl Iterator<Item = (&K, &Path)> {
+    self.paths.iter().map(|(key, path)| (key, path.as_path()))
+  }
+
+  /// The number of files open at the moment.
+  #[must_use]
+  pub fn open_files(&self) -> usize {
+    self.open.len()
+  }
+
+  /// Writes `row` to the file of its key, creating or reopening the file if necessary.
+  pub fn write_row(&mut self, row: Row) -> Result<(), IxaError> {
+    let Some(configuration) = &self.configuration else {
+      return Err(IxaError::IxaError(format!("reporter '{}' was not initialized", self.short_name)));
+    };
+    if configuration.is_suppressed() {
+      return Ok(());
+    }
+    let (float_precision, flush_each_row) = (configuration.float_precision, configuration.flush_each_row);
+
+    let key = (self.key)(&row);
+    if !self.open.contains_key(&key) {
+      self.open_partition(&key)?;
+    }
+    self.rows_written += 1;
+    let partition = self.open.get_mut(&key).expect("the partition was just opened");
+    partition.last_used = self.rows_written;
+    match float_precision {
+      Some(precision) => partition.writer.serialize(WithFloatPrecision{ value: &row, precision })?,
+      None => partition.writer.serialize(&row)?,
+    }
+    if flush_each_row {
+      partition.writer.flush()?;
+    }
+    Ok(())
+  }
+
+  /// Writes any buffered rows to the open files.
+  pub fn flush(&mut self) -> Result<(), IxaError> {
+    for partition in self.open.values_mut() {
+      partition.writer.flush()?;
+    }
+    Ok(())
+  }
+
+  /// Opens the file of `key`, closing the least recently used file first if too many are open. A new key gets a new
+  /// file, which is written with a header; a key seen before has its file reopened for appending.
+  fn open_partition(&mut self, key: &K) -> Result<(), IxaError> {
+    if self.open.len() >= self.max_open_files {
+      let least_recent = self.open
+                             .iter()
+                             .min_by_key(|(_, partition)| partition.last_used)
+                             .map(|(key, _)| key.clone())
+                             .expect("at least one file is open");
+      let mut closed = self.open.remove(&least_recent).expect("the key is open");
+      closed.writer.flush()?;
+    }
+
+    let configuration = self.configuration.as_ref().expect("checked in `write_row`");
+    let mut builder = WriterBuilder::new();
+    builder.quote_style(configuration.quote_style.into());
+    let writer = match self.paths.get(key) {
+      Some(path) => builder.has_headers(false).from_writer(OpenOptions::new().append(true).open(path)?),
+      None => {
+        let (file, path) = configuration.create_report_file(&format!("{}_{}", self.short_name, key))?;
+        self.paths.insert(key.clone(), path);
+        builder.from_writer(file)
+      }
+    };
+    self.open.insert(key.clone(), OpenPartition{ writer, last_used: self.rows_written });
+    Ok(())
+  }
+}
+
+impl<Marker, K, Row> Drop for PartitionedReporter<Marker, K, Row>
+  where Marker: Send + Sync + 'static,
+        K: Eq + Hash + Clone + Display + Send + Sync + 'static,
+        Row: Serialize + 'static
+{
+  fn drop(&mut self) {
+    // As for `Reporter`, errors are ignored.
+    let _ = self.flush();
+  }
+}
+
+impl<Marker, K, Row> Module for PartitionedReporter<Marker, K, Row>
+  where Marker: Send + Sync + 'static,
+        K: Eq + Hash + Clone + Display + Send + Sync + 'static,
+        Row: Serialize + 'static
+{
+  /// Inserts self into world. The caller needs to schedule the system that writes rows.
+  fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs> {
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module PartitionedReporter");
+
+    self.initialize(ReporterConfiguration::for_reporter(world));
+    world.insert_resource(self);
+    None
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::{env, fs};
+  use crate::report::FilenamePolicy;
+  use super::*;
+
+  #[derive(Serialize)]
+  struct TractRow {
+    time : f64,
+    tract: u32,
+    cases: u32,
+  }
+
+  #[test]
+  fn test_one_file_per_key() {
+    let directory = env::temp_dir().join("ecs_disease_models_tests").join("partitioned_report");
+    let configuration = ReporterConfiguration::new("test_".to_string(), directory.clone(), FilenamePolicy::Overwrite);
+    // Fewer open files than keys, so that files are closed and reopened.
+    let mut reporter = PartitionedReporter::<(), u32, TractRow>::new("cases".to_string(), |row| row.tract)
+        .with_max_open_files(2);
+    reporter.initialize(&configuration);
+
+    for (time, tract, cases) in [(1.0, 10, 1), (1.0, 20, 2), (1.0, 30, 3), (2.0, 10, 4), (2.0, 20, 5), (3.0, 30, 6)] {
+      reporter.write_row(TractRow{ time, tract, cases }).unwrap();
+      assert!(reporter.open_files() <= 2);
+    }
+    reporter.flush().unwrap();
+
+    assert_eq!(reporter.output_paths().count(), 3);
+    for (tract, expected) in [
+      (10, "time,tract,cases\n1.0,10,1\n2.0,10,4\n"),
+      (20, "time,tract,cases\n1.0,20,2\n2.0,20,5\n"),
+      (30, "time,tract,cases\n1.0,30,3\n3.0,30,6\n"),
+    ] {
+      let path = reporter.output_path(&tract).unwrap();
+      assert_eq!(path, directory.join(format!("test_cases_{tract}.csv")));
+      assert_eq!(fs::read_to_string(path).unwrap(), expected);
+    }
+  }
+}
@@ -0,0 +1,141 @@
+/*!
+
+Stable, reproducible person ids. An `Entity` is a handle into the `World`, not an identity: its index depends on
+which slots are free, and entities reserved by systems running in parallel get indices in whatever order the threads
+happen to reserve them. A `PersonId` is assigned by the `PersonIdAllocator` in a deterministic order instead, so that
+the same seeded model assigns the same ids to the same people on every run, however its systems are executed.
+
+Spawn people with `SpawnPerson::spawn_person`, from `Commands` in a system or directly on the `World` in an event's
+command. From `Commands`, the id is not assigned when the system runs but when its commands are applied, by a command
+queued right after the spawn. Bevy ECS applies the command buffers of the systems in a schedule in the order of the
+systems in the (built) schedule, not in the order the systems happened to finish, and applies each buffer in the order
+its commands were queued, with either executor. So ids are assigned:
+ - in the order of the systems in the schedule, and within a system, in the order of its `spawn_person` calls;
+ - identically whether the schedule runs its systems in parallel or serially.
+
+The guarantee is only about ids. Systems that draw from the shared `RngResource` must still be ordered relative to one
+another for their draws, and therefore what they spawn, to be reproducible.
+
+Entities spawned without `spawn_person` get no `PersonId`.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::{Deserialize, Serialize};
+
+use crate::module::Module;
+
+/// The stable id of a person. See the module documentation.
+#[derive(
+  Component, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Default, Debug, Hash, Serialize, Deserialize
+)]
+pub struct PersonId(pub u64);
+
+/// Hands out `PersonId`s in sequence, starting from 0.
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub struct PersonIdAllocator {
+  next: u64,
+}
+
+impl PersonIdAllocator {
+  pub fn allocate(&mut self) -> PersonId {
+    let id = PersonId(self.next);
+    self.next += 1;
+    id
+  }
+
+  /// The number of ids handed out so far.
+  #[must_use]
+  pub fn allocated(&self) -> u64 {
+    self.next
+  }
+}
+
+impl Module for PersonIdAllocator {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module PersonIdAllocator");
+
+    None // No systems
+  }
+}
+
+/// Gives `entity` the next `PersonId`.
+fn assign_person_id(world: &mut World, entity: Entity) {
+  world.init_resource::<PersonIdAllocator>();
+  let id = world.resource_mut::<PersonIdAllocator>().allocate();
+  world.entity_mut(entity).insert(id);
+}
+
+/// Spawns people with a `PersonId`.
+pub trait SpawnPerson {
+  /// Spawns an entity with `bundle` and the next `PersonId`.
+  fn spawn_person(&mut self, bundle: impl Bundle) -> Entity;
+}
+
+impl SpawnPerson for World {
+  fn spawn_person(&mut self, bundle: impl Bundle) -> Entity {
+    let entity = self.spawn(bundle).id();
+    assign_person_id(self, entity);
+    entity
+  }
+}
+
+impl SpawnPerson for Commands<'_, '_> {
+  /// The id is assigned when the commands are applied. See the module documentation.
+  fn spawn_person(&mut self, bundle: impl Bundle) -> Entity {
+    let entity = self.spawn(bundle).id();
+    self.queue(move |world: &mut World| assign_person_id(world, entity));
+    entity
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::collections::BTreeMap;
+  use bevy_ecs::schedule::ExecutorKind;
+  use super::*;
+
+  /// Which system spawned a person, and in which of its spawns.
+  #[derive(Component, Copy, Clone, Eq, PartialEq, Debug)]
+  struct SpawnedBy(u32, u32);
+
+  fn spawner<const SYSTEM: u32>(mut commands: Commands) {
+    for spawn in 0..5 {
+      commands.spawn_person(SpawnedBy(SYSTEM, spawn));
+    }
+  }
+
+  /// Runs three independent spawning systems three times and returns who got which id.
+  fn assigned_ids(executor: ExecutorKind) -> BTreeMap<PersonId, (u32, u32)> {
+    let mut world = World::default();
+    world.spawn_person(SpawnedBy(0, 0));
+    let mut schedule = Schedule::default();
+    schedule.set_executor_kind(executor);
+    schedule.add_systems((spawner::<1>, spawner::<2>, spawner::<3>));
+    for _ in 0..3 {
+      schedule.run(&mut world);
+    }
+
+    world.query::<(&PersonId, &SpawnedBy)>()
+         .iter(&world)
+         .map(|(id, spawned_by)| (*id, (spawned_by.0, spawned_by.1)))
+         .collect()
+  }
+
+  #[test]
+  fn test_ids_do_not_depend_on_the_executor() {
+    let serial = assigned_ids(ExecutorKind::SingleThreaded);
+    assert_eq!(serial.len(), 46);
+    assert_eq!(serial.keys().copied().collect::<Vec<_>>(), (0..46).map(PersonId).collect::<Vec<_>>());
+    for _ in 0..10 {
+      assert_eq!(assigned_ids(ExecutorKind::MultiThreaded), serial);
+    }
+  }
+}
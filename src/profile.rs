@@ -0,0 +1,216 @@
+/*!
+
+Per-system profiling, enabled with the `profile` feature. Each time a `Model` runs, the time spent in every system is
+accumulated into the `SystemProfile` resource, which is printed at the end of the run sorted by total time.
+
+Bevy ECS (with its `trace` feature) wraps every run of a system in a tracing span named after the system, and every
+application of the system's deferred `Commands` in a second span. We install a minimal tracing subscriber that times
+those spans. Time spent applying commands is reported separately as `"<system> (commands)"`. Events popped off the
+`Timeline` are run as commands, so their cost shows up under `run_timeline_event (commands)`.
+
+Two caveats:
+ - The subscriber is installed as the process-wide default the first time a `Model` is created, so it will not
+   install if the program has already set its own default tracing subscriber (a message is printed in that case).
+   Likewise, systems constructed before the first `Model` is created are not profiled.
+ - Timings are attributed to the model running on the current thread, so with profiling enabled the schedule uses
+   the single-threaded executor. This also keeps timings from being confounded by systems running in parallel.
+
+*/
+
+use std::{
+  cell::RefCell,
+  collections::HashMap,
+  fmt::{Display, Formatter},
+  sync::{
+    atomic::{AtomicU64, Ordering},
+    LazyLock,
+    Mutex,
+    Once
+  },
+  time::{Duration, Instant}
+};
+
+use bevy_ecs::prelude::Resource;
+use tracing::{
+  field::{Field, Visit},
+  span,
+  Event,
+  Metadata,
+  Subscriber
+};
+
+/// The accumulated running time of a single system.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug)]
+pub struct SystemTiming {
+  pub total: Duration,
+  pub calls: u64,
+}
+
+/// Accumulated running time for each system, keyed by system name.
+#[derive(Resource, Clone, Default, Debug)]
+pub struct SystemProfile {
+  timings: HashMap<String, SystemTiming>,
+}
+
+impl SystemProfile {
+  /// The timing of the system with the given (fully qualified) name, if it has run.
+  pub fn get(&self, system: &str) -> Option<&SystemTiming> {
+    self.timings.get(system)
+  }
+
+  /// All timings, slowest system first.
+  pub fn sorted(&self) -> Vec<(&str, &SystemTiming)> {
+    let mut sorted: Vec<(&str, &SystemTiming)> = self.timings
+        .iter()
+        .map(|(name, timing)| (name.as_str(), timing))
+        .collect();
+    sorted.sort_by(|a, b| b.1.total.cmp(&a.1.total).then_with(|| a.0.cmp(b.0)));
+    sorted
+  }
+
+  fn merge(&mut self, other: SystemProfile) {
+    for (name, timing) in other.timings {
+      let entry = self.timings.entry(name).or_default();
+      entry.total += timing.total;
+      entry.calls += timing.calls;
+    }
+  }
+}
+
+impl Display for SystemProfile {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "{:>12} {:>10} {:>12}  system", "total (ms)", "calls", "mean (us)")?;
+    for (name, timing) in self.sorted() {
+      let mean = timing.total.as_secs_f64() * 1e6 / timing.calls.max(1) as f64;
+      writeln!(
+        f,
+        "{:>12.3} {:>10} {:>12.3}  {}",
+        timing.total.as_secs_f64() * 1e3,
+        timing.calls,
+        mean,
+        name
+      )?;
+    }
+    Ok(())
+  }
+}
+
+/// Installs the profiling subscriber as the process-wide default. Only the first call has any effect.
+pub(crate) fn install() {
+  static INSTALL: Once = Once::new();
+  INSTALL.call_once(|| {
+    if tracing::subscriber::set_global_default(ProfilingSubscriber::default()).is_err() {
+      println!("A tracing subscriber is already installed. System profiling is disabled.");
+    }
+  });
+}
+
+/// Begins attributing system time to a new profile on the current thread.
+pub(crate) fn start() {
+  ACTIVE_PROFILE.with(|active| *active.borrow_mut() = Some(ActiveProfile::default()));
+}
+
+/// Stops profiling on the current thread and merges the time recorded since `start` into `profile`.
+pub(crate) fn finish(profile: &mut SystemProfile) {
+  let Some(active) = ACTIVE_PROFILE.with(|active| active.borrow_mut().take()) else {
+    return;
+  };
+
+  let span_names = SPAN_NAMES.lock().unwrap();
+  let timings = active.timings
+      .into_iter()
+      .filter_map(|(id, timing)| span_names.get(&id).map(|name| (name.clone(), timing)))
+      .collect();
+  profile.merge(SystemProfile{ timings });
+}
+
+/// Timings recorded on the current thread, keyed by span ID until they are resolved to names in `finish`.
+#[derive(Default)]
+struct ActiveProfile {
+  timings: HashMap<u64, SystemTiming>,
+  entered: HashMap<u64, Instant>,
+}
+
+thread_local! {
+  static ACTIVE_PROFILE: RefCell<Option<ActiveProfile>> = const { RefCell::new(None) };
+}
+
+/// The system name of each span the subscriber has created.
+static SPAN_NAMES: LazyLock<Mutex<HashMap<u64, String>>> = LazyLock::new(Mutex::default);
+
+const SYSTEM_SPAN: &str = "system";
+const COMMANDS_SPAN: &str = "system_commands";
+
+/// Records the `name` field of a Bevy system span.
+#[derive(Default)]
+struct NameVisitor(Option<String>);
+
+impl Visit for NameVisitor {
+  fn record_str(&mut self, field: &Field, value: &str) {
+    if field.name() == "name" {
+      self.0 = Some(value.to_string());
+    }
+  }
+
+  fn record_debug(&mut self, field: &Field, value: &dyn std::fmt::Debug) {
+    if field.name() == "name" {
+      self.0 = Some(format!("{value:?}"));
+    }
+  }
+}
+
+#[derive(Default)]
+struct ProfilingSubscriber {
+  next_id: AtomicU64,
+}
+
+impl Subscriber for ProfilingSubscriber {
+  fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+    metadata.is_span()
+        && metadata.target().starts_with("bevy_ecs")
+        && (metadata.name() == SYSTEM_SPAN || metadata.name() == COMMANDS_SPAN)
+  }
+
+  fn new_span(&self, span: &span::Attributes<'_>) -> span::Id {
+    // IDs must be nonzero.
+    let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+
+    let mut visitor = NameVisitor::default();
+    span.record(&mut visitor);
+    let system = visitor.0.unwrap_or_else(|| "<unnamed>".to_string());
+    let name = match span.metadata().name() {
+      COMMANDS_SPAN => format!("{system} (commands)"),
+      _ => system,
+    };
+    SPAN_NAMES.lock().unwrap().insert(id, name);
+
+    span::Id::from_u64(id)
+  }
+
+  fn record(&self, _span: &span::Id, _values: &span::Record<'_>) {}
+
+  fn record_follows_from(&self, _span: &span::Id, _follows: &span::Id) {}
+
+  fn event(&self, _event: &Event<'_>) {}
+
+  fn enter(&self, span: &span::Id) {
+    ACTIVE_PROFILE.with(|active| {
+      if let Some(active) = active.borrow_mut().as_mut() {
+        active.entered.insert(span.into_u64(), Instant::now());
+      }
+    });
+  }
+
+  fn exit(&self, span: &span::Id) {
+    let exited = Instant::now();
+    ACTIVE_PROFILE.with(|active| {
+      if let Some(active) = active.borrow_mut().as_mut()
+          && let Some(entered) = active.entered.remove(&span.into_u64())
+      {
+        let timing = active.timings.entry(span.into_u64()).or_default();
+        timing.total += exited - entered;
+        timing.calls += 1;
+      }
+    });
+  }
+}
@@ -1,17 +1,164 @@
+/*!
+
+Random number generation. The `RngResource` holds the model's PRNG, and the `SeedResource` records the master seed
+the PRNG was seeded with so that every run is reproducible and the seed in use is auditable.
+
+A model that is not given an explicit seed uses `DEFAULT_SEED`. Because a silently fixed seed is easy to mistake for
+a random one, using the default seed is logged (once per process).
+
+The PRNG is a `SmallRng` unless the model is constructed with another one via `RngResource::with_rng` (or
+`Model::with_rng`), e.g. a cryptographically strong or a serializable generator. The `SmallRng` is stored and called
+directly, while any other generator is boxed and called through a trait object, which costs a virtual call per draw
+and prevents inlining the generator into the sampling code. That is negligible next to the work done per event in most
+models, but performance-sensitive models should keep the default.
+
+`RngResource::sample_one` chooses one item of an iterator uniformly at random in a single pass, by reservoir sampling,
+without collecting or indexing the items. `random_entity_where` (and `Model::random_entity_with`) uses it to choose a
+random entity, such as a random susceptible person. `RngResource::sample_weighted` chooses one of `(item, weight)` pairs
+with probability proportional to its weight, also in a single pass, by weighted reservoir sampling: each item with a
+positive weight replaces the choice so far with probability its weight over the total weight seen so far. Items of zero
+weight are never chosen, and if no item has a positive weight, nothing is. `random_entity_weighted` (and
+`Model::random_entity_weighted`) uses it to choose an entity with probability proportional to a weight computed from one
+of its components, e.g. a susceptible person weighted by their susceptibility. Either choice depends on the order in
+which the query visits entities, which Bevy ECS determines from the history of the `World`, so it is reproducible for a
+given seed whenever the model is.
+
+`RngResource::sample_indices` chooses several distinct indices of a slice uniformly at random, e.g. the people to move
+or to infect, by a partial Fisher-Yates shuffle, with one labeled draw per index chosen.
+
+`shuffle_entities` and `random_order_iter` put entities in a random order, e.g. the queue of an intervention that
+processes people "in random order". They sort the entities before shuffling, so the order depends only on the set of
+entities and the state of the PRNG, not on the order in which a query happened to collect them. Pass them the model's
+`rng` for the order to be reproducible for a given seed.
+
+A module that draws from the shared `RngResource` gets different numbers whenever another module makes more or fewer
+draws before it, so adding a module to a model shifts the draws of every other. A module can instead have a stream of
+its own, a `SmallRng` seeded with a sub-seed derived from the master seed and a name: `SeedResource::stream(name)`, or
+`SeedResource::for_module::<M>()`, which uses the short type name of `M`, e.g. `TransmissionManager`. The sub-seed is
+computed from the name by `derive_seed` as follows, and so is the same for a given name on every platform:
+ 1. Hash the UTF-8 bytes of the name with 64-bit FNV-1a.
+ 2. XOR the hash with the master seed.
+ 3. Apply the SplitMix64 finalizer, so that related seeds and names do not give related sub-seeds.
+
+The `SmallRng` itself is the `rand` crate's, whose algorithm may change between versions of `rand`, as for the
+`RngResource`. Each call returns a new generator at the start of the stream, so a module should create its stream
+once, when it is initialized, and keep it, e.g. in its resource. Draws from a stream are not recorded in the `RngTrace`.
+The names `for_module` uses come from `std::any::type_name`, whose output is not guaranteed to be the same between
+versions of Rust, and change when a module is renamed, so a model that must keep its streams should name them
+explicitly with `stream`.
+
+Draws made through `RngResource::sample_labeled` are recorded in an `RngTrace` when the `rng_trace` feature is enabled.
+Comparing the traces of two runs that should be identical pinpoints the first draw at which they diverge. Draws made
+directly on the `rng` field are not recorded, which keeps raw access available for performance critical code.
+
+*/
+
+use std::{
+  fmt::Debug,
+  sync::Once
+};
+#[cfg(feature = "rng_trace")]
+use std::path::Path;
+
 use bevy_ecs::{
   prelude::*,
   schedule::SystemConfigs
 };
 use rand::{
+  distr::{Distribution, StandardUniform, Uniform},
   rngs::SmallRng,
+  seq::SliceRandom,
+  Rng,
+  RngCore,
   SeedableRng
 };
+#[cfg(feature = "rng_trace")]
+use serde::Serialize;
+
+use crate::{
+  diagnostics::short_type_name,
+  module::Module,
+  timeline::{Time, Timeline}
+};
+#[cfg(feature = "rng_trace")]
+use crate::errors::IxaError;
+
+/// The seed used when none is provided.
+pub const DEFAULT_SEED: u64 = 42;
+
+/// The master seed of the model. Anything that derives randomness from the model's seed should read it from here.
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct SeedResource(pub u64);
+
+impl SeedResource {
+  /// A generator for the stream called `name`, seeded with `derive_seed(self.0, name)`. See the module documentation.
+  #[must_use]
+  pub fn stream(&self, name: &str) -> SmallRng {
+    SmallRng::seed_from_u64(derive_seed(self.0, name))
+  }
+
+  /// A generator for the stream of the module `M`, named by its short type name, which is not guaranteed to be the same
+  /// between versions of Rust. See the module documentation.
+  #[must_use]
+  pub fn for_module<M: Module>(&self) -> SmallRng {
+    self.stream(&short_type_name(std::any::type_name::<M>()))
+  }
+}
+
+/// The sub-seed of the stream called `name`, derived from the master `seed`. The derivation is part of the public API
+/// and does not change; see the module documentation.
+#[must_use]
+pub fn derive_seed(seed: u64, name: &str) -> u64 {
+  const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+  const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+  let hash = name.bytes().fold(FNV_OFFSET_BASIS, |hash, byte| (hash ^ byte as u64).wrapping_mul(FNV_PRIME));
 
-use crate::module::Module;
+  let mut mixed = hash ^ seed;
+  mixed = (mixed ^ (mixed >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+  mixed = (mixed ^ (mixed >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+  mixed ^ (mixed >> 31)
+}
+
+/// The model's PRNG. See the module documentation.
+pub enum ModelRng {
+  /// The default.
+  Small(SmallRng),
+  /// Any other generator.
+  Boxed(Box<dyn RngCore + Send + Sync>),
+}
+
+impl RngCore for ModelRng {
+  #[inline]
+  fn next_u32(&mut self) -> u32 {
+    match self {
+      ModelRng::Small(rng) => rng.next_u32(),
+      ModelRng::Boxed(rng) => rng.next_u32(),
+    }
+  }
+
+  #[inline]
+  fn next_u64(&mut self) -> u64 {
+    match self {
+      ModelRng::Small(rng) => rng.next_u64(),
+      ModelRng::Boxed(rng) => rng.next_u64(),
+    }
+  }
+
+  #[inline]
+  fn fill_bytes(&mut self, destination: &mut [u8]) {
+    match self {
+      ModelRng::Small(rng) => rng.fill_bytes(destination),
+      ModelRng::Boxed(rng) => rng.fill_bytes(destination),
+    }
+  }
+}
 
 #[derive(Resource)]
 pub struct RngResource {
-  pub rng: SmallRng
+  pub rng: ModelRng,
+  seed: u64,
+  #[cfg(feature = "rng_trace")]
+  trace: RngTrace,
 }
 
 impl Default for RngResource {
@@ -21,23 +168,263 @@ impl Default for RngResource {
 }
 
 impl RngResource {
+  /// Creates an `RngResource` seeded with `DEFAULT_SEED`.
   pub fn new() -> Self {
-    Self::with_random_seed(42)
+    log_default_seed_used();
+    Self::with_random_seed(DEFAULT_SEED)
   }
 
   pub fn with_random_seed(seed: u64) -> Self {
+    Self::from_model_rng(ModelRng::Small(SmallRng::seed_from_u64(seed)), seed)
+  }
+
+  /// Creates an `RngResource` whose PRNG is an `R` seeded with `seed`. Draws go through dynamic dispatch; see the
+  /// module documentation.
+  pub fn with_rng<R: RngCore + SeedableRng + Send + Sync + 'static>(seed: u64) -> Self {
+    Self::from_model_rng(ModelRng::Boxed(Box::new(R::seed_from_u64(seed))), seed)
+  }
+
+  fn from_model_rng(rng: ModelRng, seed: u64) -> Self {
     RngResource {
-      rng: SmallRng::seed_from_u64(seed)
+      rng,
+      seed,
+      #[cfg(feature = "rng_trace")]
+      trace: RngTrace::default(),
+    }
+  }
+
+  /// Draws a sample from `distribution`. With the `rng_trace` feature, the draw is recorded in this resource's
+  /// `RngTrace` along with the current simulated time `now` and `label`, which should say what the draw is for.
+  pub fn sample_labeled<T, D>(&mut self, now: Time, label: &'static str, distribution: D) -> T
+      where T: Debug, D: Distribution<T>
+  {
+    let value = distribution.sample(&mut self.rng);
+
+    #[cfg(feature = "rng_trace")]
+    self.trace.records.push(RngTraceRecord{
+      time: now.0,
+      label,
+      value: format!("{value:?}"),
+    });
+    #[cfg(not(feature = "rng_trace"))]
+    let _ = (now, label);
+
+    value
+  }
+
+  /// Chooses one of `items` uniformly at random, or returns `None` if there are none. This takes a single pass and one
+  /// draw per item after the first, each labeled `label`.
+  pub fn sample_one<T>(&mut self, now: Time, label: &'static str, items: impl IntoIterator<Item = T>) -> Option<T> {
+    let mut chosen = None;
+    for (seen, item) in items.into_iter().enumerate() {
+      // The `seen + 1`-th item replaces the choice so far with probability `1 / (seen + 1)`.
+      let replace = seen == 0 || self.sample_labeled(now, label, Uniform::new_inclusive(0, seen).unwrap()) == 0;
+      if replace {
+        chosen = Some(item);
+      }
     }
+    chosen
   }
+
+  /// Chooses the item of one of `items`, which are `(item, weight)` pairs, with probability proportional to its weight,
+  /// or returns `None` if no weight is positive. Weights must be finite; zero, negative, and NaN weights are never
+  /// chosen. This takes a single pass and one draw per item of positive weight after the first, each labeled `label`.
+  pub fn sample_weighted<T>(
+    &mut self,
+    now: Time,
+    label: &'static str,
+    items: impl IntoIterator<Item = (T, f64)>
+  ) -> Option<T> {
+    let mut chosen = None;
+    let mut total_weight = 0.0;
+    for (item, weight) in items {
+      if weight.is_nan() || weight <= 0.0 {
+        continue;
+      }
+      total_weight += weight;
+      // The item replaces the choice so far with probability `weight / total_weight`.
+      let replace = chosen.is_none() || {
+        let draw: f64 = self.sample_labeled(now, label, StandardUniform);
+        draw * total_weight < weight
+      };
+      if replace {
+        chosen = Some(item);
+      }
+    }
+    chosen
+  }
+
+  /// Chooses `amount` distinct indices of `0..length` uniformly at random, or all of them, in random order, if `amount`
+  /// is at least `length`. This takes one draw per index chosen, each labeled `label`.
+  pub fn sample_indices(&mut self, now: Time, label: &'static str, length: usize, amount: usize) -> Vec<usize> {
+    let amount = amount.min(length);
+    let mut indices: Vec<usize> = (0..length).collect();
+    // A partial Fisher-Yates shuffle: the first `amount` indices end up a uniformly random choice.
+    for chosen in 0..amount {
+      let swap_with = self.sample_labeled(now, label, Uniform::new(chosen, length).unwrap());
+      indices.swap(chosen, swap_with);
+    }
+    indices.truncate(amount);
+    indices
+  }
+
+  /// The draws recorded so far.
+  #[cfg(feature = "rng_trace")]
+  #[must_use]
+  pub fn trace(&self) -> &RngTrace {
+    &self.trace
+  }
+
+  /// The seed this `RngResource` was created with.
+  #[must_use]
+  pub fn seed(&self) -> u64 {
+    self.seed
+  }
+}
+
+/// A single recorded draw.
+#[cfg(feature = "rng_trace")]
+#[derive(Serialize, Clone, PartialEq, Debug)]
+pub struct RngTraceRecord {
+  pub time: f64,
+  pub label: &'static str,
+  /// The `Debug` representation of the value drawn.
+  pub value: String,
+}
+
+/// Every draw made through `RngResource::sample_labeled`, in order.
+#[cfg(feature = "rng_trace")]
+#[derive(Clone, Default, Debug)]
+pub struct RngTrace {
+  records: Vec<RngTraceRecord>,
+}
+
+#[cfg(feature = "rng_trace")]
+impl RngTrace {
+  pub fn records(&self) -> &[RngTraceRecord] {
+    &self.records
+  }
+
+  /// The index of the first draw that differs between `self` and `other`, or `None` if the traces are identical. If
+  /// one trace is a prefix of the other, the first draw missing from the shorter trace is reported.
+  pub fn first_divergence(&self, other: &RngTrace) -> Option<usize> {
+    match self.records.iter().zip(&other.records).position(|(ours, theirs)| ours != theirs) {
+      Some(index) => Some(index),
+      None if self.records.len() != other.records.len() => Some(self.records.len().min(other.records.len())),
+      None => None,
+    }
+  }
+
+  /// Writes the trace as a CSV file with columns `time`, `label`, and `value`.
+  pub fn write_csv(&self, path: &Path) -> Result<(), IxaError> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for record in &self.records {
+      writer.serialize(record)?;
+    }
+    writer.flush()?;
+    Ok(())
+  }
+}
+
+/// Chooses an entity with a `C` for which `predicate` is true uniformly at random, using the world's `RngResource`, or
+/// returns `None` if there is none. See the module documentation.
+pub fn random_entity_where<C: Component>(world: &mut World, predicate: impl Fn(&C) -> bool) -> Option<Entity> {
+  let now = world.resource::<Timeline>().now();
+  let mut query = world.query::<(Entity, &C)>();
+  world.resource_scope(|world, mut rng: Mut<RngResource>| {
+    let candidates = query.iter(world).filter(|(_, component)| predicate(component)).map(|(entity, _)| entity);
+    rng.sample_one(now, "random_entity", candidates)
+  })
+}
+
+/// Chooses an entity with a `C` with probability proportional to `weight` of its `C`, using the world's
+/// `RngResource`, or returns `None` if no entity has a positive weight. See `RngResource::sample_weighted`.
+pub fn random_entity_weighted<C: Component>(world: &mut World, weight: impl Fn(&C) -> f64) -> Option<Entity> {
+  let now = world.resource::<Timeline>().now();
+  let mut query = world.query::<(Entity, &C)>();
+  world.resource_scope(|world, mut rng: Mut<RngResource>| {
+    let candidates = query.iter(world).map(|(entity, component)| (entity, weight(component)));
+    rng.sample_weighted(now, "random_entity_weighted", candidates)
+  })
+}
+
+/// Shuffles `entities` into a uniformly random order. The result does not depend on their order beforehand. See the
+/// module documentation.
+pub fn shuffle_entities(entities: &mut [Entity], rng: &mut impl Rng) {
+  entities.sort_unstable();
+  entities.shuffle(rng);
+}
+
+/// The `entities` in a uniformly random order. See `shuffle_entities`.
+pub fn random_order_iter(
+  entities: impl IntoIterator<Item = Entity>,
+  rng: &mut impl Rng
+) -> impl Iterator<Item = Entity> {
+  let mut entities: Vec<Entity> = entities.into_iter().collect();
+  shuffle_entities(&mut entities, rng);
+  entities.into_iter()
+}
+
+/// Logs that `DEFAULT_SEED` is in use. Only the first call has any effect.
+pub(crate) fn log_default_seed_used() {
+  static DEFAULT_SEED_LOGGED: Once = Once::new();
+  DEFAULT_SEED_LOGGED.call_once(|| {
+    #[cfg(feature = "print_messages")]
+    println!("No random seed was provided. Using the default seed {DEFAULT_SEED}.");
+  });
 }
 
-// ToDo: Do something better with the initial seed. There's a half-hearted attempt littered throughout this demo.
 impl Module for RngResource {
   fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(SeedResource(self.seed));
     world.insert_resource(self);
     #[cfg(feature = "print_messages")]
     println!("Initialized module Random");
     None // No systems
   }
 }
+
+
+#[cfg(all(test, feature = "rng_trace"))]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use rand::distr::StandardUniform;
+  use super::*;
+
+  fn draw_trace(seed: u64) -> RngTrace {
+    let mut rng = RngResource::with_random_seed(seed);
+    for step in 0..3 {
+      let _: f64 = rng.sample_labeled(OrderedFloat(step as f64), "uniform", StandardUniform);
+    }
+    rng.trace().clone()
+  }
+
+  #[test]
+  fn test_identical_traces() {
+    let trace = draw_trace(7);
+    assert_eq!(trace.records().len(), 3);
+    assert_eq!(trace.records()[2].time, 2.0);
+    assert_eq!(trace.first_divergence(&draw_trace(7)), None);
+  }
+
+  #[test]
+  fn test_divergent_traces() {
+    assert_eq!(draw_trace(7).first_divergence(&draw_trace(8)), Some(0));
+  }
+
+  #[test]
+  fn test_sample_indices() {
+    let mut rng = RngResource::with_random_seed(7);
+    let mut indices = rng.sample_indices(OrderedFloat(1.0), "indices", 10, 4);
+    assert_eq!(rng.trace().records().len(), 4);
+    assert!(rng.trace().records().iter().all(|record| record.label == "indices"));
+    indices.sort();
+    indices.dedup();
+    assert_eq!(indices.len(), 4);
+    assert!(indices.iter().all(|&index| index < 10));
+
+    let mut all = rng.sample_indices(OrderedFloat(1.0), "indices", 3, 5);
+    all.sort();
+    assert_eq!(all, [0, 1, 2]);
+  }
+}
@@ -1,9 +1,13 @@
 /*!
 
-The `Reporter` module is responsible for writing out data according to the `ReporterConfiguration`. If no
-`ReporterConfiguration` exists, a default `ReporterConfiguration` will be created.
+The `Reporter` module is responsible for writing out data according to the `ReporterConfiguration`. Every `Model`
+starts with the default `ReporterConfiguration`, which adding the `ReporterConfiguration` module replaces, so every
+reporter finds one when it is initialized, whatever order the modules are added in.
 
-Ixa uses a global `ReporterConfiguration`.
+Ixa uses a global `ReporterConfiguration`. Since reporters are initialized, and create their files, as they are added,
+a reporter uses the configuration of the model at that time, and replacing the configuration afterward does not change
+it. So add the `ReporterConfiguration` before any reporters; the model warns if a configuration replaces one that a
+reporter has already used.
 
 The `Reporter<Marker>` takes a `Marker` type so that multiple `Reporter`'s can exist at once in a single `World`
 instance. Instead of `Reporter<Marker>` singletons, we could just have instances of reporter systems with `Local<D>`
@@ -12,59 +16,360 @@ data. This local data is not stored in the world. See https://bevy-cheatbook.git
 
 ToDo: This API needs some work. Some questions are recorded in To-Do's below. Questions:
         - Where is the system that triggers a write added to the schedule?
-        - Whose responsibility is it to add the `ReporterConfiguration`? What if there is none?
         - Right now it is only possible to produce a CSV file with rows from a single struct.
           This seems overly restrictive.
 
+Reports are buffered and written out when the buffer fills and when the `Reporter` is dropped, so a crash loses the
+rows still in the buffer. For long unattended runs, `ReporterConfiguration` can trade throughput for durability:
+ - `flush_each_row` flushes after every row. When set, it takes precedence and `flush_every` has no effect.
+ - `flush_every` flushes at most once per the given interval of simulated time, from a system in the `Last` phase.
+   If such a flush fails, the model aborts with an `AbortReason` naming the report.
+
+In either case the buffer is still flushed when the `Reporter` is dropped.
+
+The `output_directory` is created, along with any missing parents, when a `Reporter` creates its file, unless
+`create_dirs` is turned off, in which case it must already exist. Either way, an `output_directory` that exists but is
+not a directory is an error.
+
+Output can be suppressed with `ReporterConfiguration::set_suppressed`, which `Model::dry_run` uses. The flag is shared
+with every `Reporter` initialized from the configuration, including ones initialized before it was set. While it is
+set, `write_row` discards rows, and reporters initialized in the meantime do not create their files.
+
+The file prefix, output directory, and whether to overwrite can be read from a configuration file along with a model's
+other parameters, as a `ReportSettings` section, and turned into a configuration with
+`ReporterConfiguration::from_settings`, which validates them.
+
+Fields are quoted only when necessary, as the `csv` crate does by default. A downstream parser that is stricter about
+quoting can be accommodated with `ReporterConfiguration::quote_style`, e.g. `QuoteStyle::Always` to quote every field.
+
+A `Reporter` can override the `output_directory` and `file_prefix` of the global configuration with
+`Reporter::with_output_directory` and `Reporter::with_file_prefix`, e.g. to write one report somewhere else. The rest of
+the configuration still applies to it. A `PartitionedReporter` writes one file per key instead, e.g. per census tract;
+see the `partitioned_report` module.
+
+A `Reporter` can write to any `Write` instead of a file with `Reporter::with_sink`, e.g. an in-memory buffer in a test
+(see the `test_utils` module). It then creates no file, and its `output_path` is `None`. To write rows somewhere other
+than a CSV, e.g. to a database or a live plot, a `Reporter` can be created with any `ReportSink` instead, with
+`Reporter::new_with_sink`; see the `report_sink` module.
+
+A `Reporter` can end its report with a summary row, such as the final attack rate, with `Reporter::with_final_row`.
+The row is computed from the world when the run ends, by a finalizer (see the `model` module), and written after every
+other row, before the report is flushed. It is written at most once. It must serialize to the same fields as the other
+rows; a final row with other fields is not written, and the model warns instead.
+
+A `Reporter` is a resource, so after a run it can be retrieved from the world, e.g.
+`model.world().resource::<IncidenceReporter>()`, and `Reporter::output_path` says which file it actually wrote. Tests
+should open that path rather than rebuild it from the `FilenamePolicy`, which for some policies they cannot.
+
 */
 
 use std::{
+  collections::HashMap,
   env,
-  path::PathBuf,
-  fs::File,
-  marker::PhantomData
+  io::{ErrorKind, Write},
+  path::{Path, PathBuf},
+  fs::{self, File},
+  marker::PhantomData,
+  sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc
+  },
+  time::{SystemTime, UNIX_EPOCH}
 };
-use csv::Writer as CsvWriter;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use bevy_ecs::{
-  prelude::{Resource, World},
+  prelude::*,
   schedule::SystemConfigs
 };
 use crate::{
   errors::IxaError,
-  module::Module
+  float_precision::WithFloatPrecision,
+  model::{AbortReason, ExecutionPhase, Finalizers, ModelControl, ModelWarnings},
+  module::Module,
+  report_sink::{CsvSink, ReportSink},
+  timeline::{Time, Timeline}
 };
 
-#[derive(Resource)]
+/// What a `Reporter` does when its report file already exists.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, Hash)]
+pub enum FilenamePolicy {
+  /// Fail to initialize the reporter.
+  #[default]
+  FailIfExists,
+  /// Replace the existing file.
+  Overwrite,
+  /// Suffix the filename with the UTC time the report was created, e.g. `incidence_20250102T030405Z.csv`. If that
+  /// file also exists, a counter is appended as well.
+  Timestamped,
+  /// Suffix the filename with the first counter that gives a new file: `incidence_1.csv`, `incidence_2.csv`, ...
+  Numbered,
+}
+
+impl FilenamePolicy {
+  /// The policy equivalent to the old `overwrite` flag.
+  #[must_use]
+  pub fn from_overwrite(overwrite: bool) -> Self {
+    if overwrite {
+      FilenamePolicy::Overwrite
+    } else {
+      FilenamePolicy::FailIfExists
+    }
+  }
+}
+
+impl From<bool> for FilenamePolicy {
+  fn from(overwrite: bool) -> Self {
+    FilenamePolicy::from_overwrite(overwrite)
+  }
+}
+
+/// When a `Reporter` quotes fields. These are the quote styles of the `csv` crate.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, Hash)]
+pub enum QuoteStyle {
+  /// Quote only fields that need it, such as those containing the delimiter, a quote, or a line break.
+  #[default]
+  Necessary,
+  /// Quote every field.
+  Always,
+  /// Quote every field that is not a number.
+  NonNumeric,
+  /// Never quote fields, even if that produces invalid CSV.
+  Never,
+}
+
+impl From<QuoteStyle> for csv::QuoteStyle {
+  fn from(quote_style: QuoteStyle) -> Self {
+    match quote_style {
+      QuoteStyle::Necessary  => csv::QuoteStyle::Necessary,
+      QuoteStyle::Always     => csv::QuoteStyle::Always,
+      QuoteStyle::NonNumeric => csv::QuoteStyle::NonNumeric,
+      QuoteStyle::Never      => csv::QuoteStyle::Never,
+    }
+  }
+}
+
+/// The settings of a `ReporterConfiguration` that can be read from a configuration file, e.g. as a `report` section
+/// of a model's parameters. See `ReporterConfiguration::from_settings`.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ReportSettings {
+  /// Defaults to the empty string.
+  #[serde(default)]
+  pub file_prefix: String,
+  pub output_directory: PathBuf,
+  /// Whether existing report files are overwritten rather than being an error. Defaults to `false`.
+  #[serde(default)]
+  pub overwrite: bool,
+}
+
+impl ReportSettings {
+  /// Checks that the output directory is not empty and, if it exists, is a directory.
+  pub fn validate(&self) -> Result<(), IxaError> {
+    if self.output_directory.as_os_str().is_empty() {
+      return Err(IxaError::IxaError("the report output directory is empty".to_string()));
+    }
+    if self.output_directory.exists() && !self.output_directory.is_dir() {
+      return Err(IxaError::IxaError(format!(
+        "the report output directory {} exists but is not a directory",
+        self.output_directory.display()
+      )));
+    }
+    Ok(())
+  }
+}
+
+#[derive(Resource, Clone)]
 pub struct ReporterConfiguration {
   /// Precedes the report name in the filename. An example of a potential prefix might be scenario or simulation name.
   /// Defaults to empty string.
   pub file_prefix: String,
   /// Location that the CSVs are written to. An example of this might be "/data/". Defaults to current active directory.
   pub output_directory: PathBuf,
-  /// If `true`, will overwrite existing files in the same location. Default is `false`.
-  pub overwrite: bool,
+  /// What to do if a report file already exists. Default is `FilenamePolicy::FailIfExists`.
+  pub filename_policy: FilenamePolicy,
+  /// If set, floating point values are written with this many decimal places. Defaults to `None`, which writes
+  /// floats at full precision.
+  pub float_precision: Option<usize>,
+  /// If `true`, every row is flushed to the file as soon as it is written. Takes precedence over `flush_every`.
+  /// Default is `false`.
+  pub flush_each_row: bool,
+  /// If set, rows are flushed to the file at most once per this interval of simulated time. Default is `None`.
+  pub flush_every: Option<Time>,
+  /// If `true`, the `output_directory` is created if it does not exist. Default is `true`.
+  pub create_dirs: bool,
+  /// When fields are quoted. Default is `QuoteStyle::Necessary`.
+  pub quote_style: QuoteStyle,
+  /// Shared with every `Reporter` initialized from this configuration. See `ReporterConfiguration::set_suppressed`.
+  suppressed: Arc<AtomicBool>,
 }
 
 impl ReporterConfiguration {
   /// Creates a `ReporterConfiguration` with the given parameters.
   #[must_use]
-  pub fn new(file_prefix: String, output_directory: PathBuf, overwrite: bool) -> Self {
+  pub fn new(file_prefix: String, output_directory: PathBuf, filename_policy: FilenamePolicy) -> Self {
     ReporterConfiguration {
       file_prefix,
       output_directory,
-      overwrite
+      filename_policy,
+      float_precision: None,
+      flush_each_row: false,
+      flush_every: None,
+      create_dirs: true,
+      quote_style: QuoteStyle::default(),
+      suppressed: Arc::default(),
     }
   }
 
+  /// Creates a `ReporterConfiguration` that overwrites existing files if `overwrite` is true and otherwise fails.
+  #[must_use]
+  pub fn from_overwrite(file_prefix: String, output_directory: PathBuf, overwrite: bool) -> Self {
+    Self::new(file_prefix, output_directory, FilenamePolicy::from_overwrite(overwrite))
+  }
+
+  /// Creates a `ReporterConfiguration` from validated `settings`, with defaults for everything else.
+  pub fn from_settings(settings: &ReportSettings) -> Result<Self, IxaError> {
+    settings.validate()?;
+    Ok(Self::from_overwrite(settings.file_prefix.clone(), settings.output_directory.clone(), settings.overwrite))
+  }
+
+  /// Sets the number of decimal places floating point values are written with.
+  #[must_use]
+  pub fn with_float_precision(mut self, float_precision: usize) -> Self {
+    self.float_precision = Some(float_precision);
+    self
+  }
+
+  /// Sets when fields are quoted.
+  #[must_use]
+  pub fn with_quote_style(mut self, quote_style: QuoteStyle) -> Self {
+    self.quote_style = quote_style;
+    self
+  }
+
+  /// Suppresses (or resumes) output from every `Reporter` initialized from this configuration.
+  pub fn set_suppressed(&self, suppressed: bool) {
+    self.suppressed.store(suppressed, Ordering::Relaxed);
+  }
+
+  /// Whether output is suppressed. See `ReporterConfiguration::set_suppressed`.
+  pub fn is_suppressed(&self) -> bool {
+    self.suppressed.load(Ordering::Relaxed)
+  }
+
   /// Builds the filename. Called by `add_report`, `short_name` refers to the
   /// report type. The three main components are `prefix`, `directory`, and
   /// `short_name`.
   fn generate_filename(&self, short_name: &str) -> PathBuf {
-    let basename = format!("{}{}", self.file_prefix, short_name);
+    self.generate_suffixed_filename(short_name, "")
+  }
+
+  /// Builds the filename as in `generate_filename`, with `suffix` appended to the basename.
+  fn generate_suffixed_filename(&self, short_name: &str, suffix: &str) -> PathBuf {
+    let basename = format!("{}{}{}", self.file_prefix, short_name, suffix);
     self.output_directory.join( basename).with_extension("csv")
   }
+
+  /// Makes sure the `output_directory` is a directory, creating it if it does not exist and `create_dirs` is set.
+  fn prepare_output_directory(&self) -> Result<(), IxaError> {
+    if self.output_directory.exists() && !self.output_directory.is_dir() {
+      return Err(IxaError::IxaError(format!(
+        "the output directory {} exists but is not a directory",
+        self.output_directory.display()
+      )));
+    }
+    if self.create_dirs {
+      fs::create_dir_all(&self.output_directory)?;
+    }
+    Ok(())
+  }
+
+  /// Creates the report file for `short_name` according to the `filename_policy`, returning the file and its path.
+  pub(crate) fn create_report_file(&self, short_name: &str) -> Result<(File, PathBuf), IxaError> {
+    self.prepare_output_directory()?;
+    match self.filename_policy {
+
+      FilenamePolicy::FailIfExists => {
+        let path = self.generate_filename(short_name);
+        match File::create_new(&path) {
+          Ok(file) => Ok((file, path)),
+          Err(e) => {
+            if e.kind() == ErrorKind::AlreadyExists {
+              println!(
+                "File already exists: {}. Please choose a different `FilenamePolicy` in the file configuration and \
+                 rerun.",
+                path.display()
+              );
+            }
+            Err(IxaError::IoError(e))
+          }
+        }
+      }
+
+      FilenamePolicy::Overwrite => {
+        let path = self.generate_filename(short_name);
+        Ok((File::create(&path)?, path))
+      }
+
+      FilenamePolicy::Timestamped => {
+        let timestamp = format!("_{}", utc_timestamp(SystemTime::now()));
+        self.create_first_new_file(
+          short_name,
+          std::iter::once(timestamp.clone()).chain((2..).map(|n| format!("{timestamp}_{n}")))
+        )
+      }
+
+      FilenamePolicy::Numbered => {
+        self.create_first_new_file(short_name, (1..).map(|n| format!("_{n}")))
+      }
+
+    }
+  }
+
+  /// Creates the first file in the sequence of filenames given by `suffixes` that does not already exist.
+  fn create_first_new_file(
+    &self,
+    short_name: &str,
+    suffixes: impl Iterator<Item = String>
+  ) -> Result<(File, PathBuf), IxaError> {
+    for suffix in suffixes {
+      let path = self.generate_suffixed_filename(short_name, &suffix);
+      match File::create_new(&path) {
+        Ok(file) => return Ok((file, path)),
+        Err(e) if e.kind() == ErrorKind::AlreadyExists => continue,
+        Err(e) => return Err(IxaError::IoError(e)),
+      }
+    }
+    unreachable!("the sequence of suffixes is unbounded")
+  }
+}
+
+/// Formats `time` as a compact ISO 8601 UTC timestamp, e.g. `20250102T030405Z`.
+fn utc_timestamp(time: SystemTime) -> String {
+  let seconds = time.duration_since(UNIX_EPOCH).map(|duration| duration.as_secs()).unwrap_or(0);
+  let (days, seconds_of_day) = (seconds / 86_400, seconds % 86_400);
+
+  // Converts days since the epoch to a civil date. See
+  // http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+  let days = days as i64 + 719_468;
+  let era = days.div_euclid(146_097);
+  let day_of_era = days.rem_euclid(146_097);
+  let year_of_era = (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let shifted_month = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+  let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+  let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+  format!(
+    "{:04}{:02}{:02}T{:02}{:02}{:02}Z",
+    year,
+    month,
+    day,
+    seconds_of_day / 3_600,
+    seconds_of_day % 3_600 / 60,
+    seconds_of_day % 60
+  )
 }
 
 impl Default for ReporterConfiguration {
@@ -72,22 +377,76 @@ impl Default for ReporterConfiguration {
     ReporterConfiguration {
       file_prefix: String::new(),
       output_directory: env::current_dir().expect("Failed to get current directory"),
-      overwrite: false,
+      filename_policy: FilenamePolicy::default(),
+      float_precision: None,
+      flush_each_row: false,
+      flush_every: None,
+      create_dirs: true,
+      quote_style: QuoteStyle::default(),
+      suppressed: Arc::default(),
     }
   }
 }
 
+/// Marks that a reporter has been initialized with the world's current `ReporterConfiguration`.
+#[derive(Resource)]
+struct ReporterConfigurationUsed;
+
+impl ReporterConfiguration {
+  /// The world's `ReporterConfiguration` for the initialization of a reporter, inserting the default one if the world
+  /// has none, as a world not made by `Model::new` may not. Records that a reporter used it, so that replacing it is
+  /// warned about.
+  pub(crate) fn for_reporter(world: &mut World) -> &ReporterConfiguration {
+    world.insert_resource(ReporterConfigurationUsed);
+    if world.get_resource::<ReporterConfiguration>().is_none() {
+      world.insert_resource(ReporterConfiguration::default());
+    }
+    world.resource::<ReporterConfiguration>()
+  }
+}
+
 impl Module for ReporterConfiguration {
+  /// Replaces the model's configuration. Warns if a reporter was added before this configuration and so was
+  /// initialized with the one it replaces, by default the default one.
   fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    if world.remove_resource::<ReporterConfigurationUsed>().is_some() {
+      ModelWarnings::warn(
+        world,
+        "a ReporterConfiguration was added after a reporter, which used the configuration it replaces instead. Add \
+         the ReporterConfiguration before any reporters".to_string()
+      );
+    }
     world.insert_resource(self);
     None
   }
 }
 
+/// Writes the final row of a report, computed from the world, to the sink with the given float precision. Returns
+/// `None` if there is no final row. See `Reporter::with_final_row`.
+type FinalRow<S> = Box<dyn Fn(&World, &mut S, Option<usize>) -> Option<Result<(), IxaError>> + Send + Sync>;
+
 #[derive(Resource)]
-pub struct Reporter<Marker: Send + Sync + 'static> {
+pub struct Reporter<Marker: Send + Sync + 'static, S: ReportSink = CsvSink> {
   short_name: String,
-  writer: Option<CsvWriter<File>>,
+  sink: S,
+  /// Whether the sink was opened on initialization, which it is not if output was suppressed then.
+  opened: bool,
+  /// The path of the file created on initialization.
+  output_path: Option<PathBuf>,
+  /// See `Reporter::with_final_row`.
+  final_row: Option<FinalRow<S>>,
+  /// Overrides the `output_directory` of the `ReporterConfiguration`. See `Reporter::with_output_directory`.
+  output_directory: Option<PathBuf>,
+  /// Overrides the `file_prefix` of the `ReporterConfiguration`. See `Reporter::with_file_prefix`.
+  file_prefix: Option<String>,
+  /// Copied from the `ReporterConfiguration` on initialization.
+  float_precision: Option<usize>,
+  /// Copied from the `ReporterConfiguration` on initialization.
+  flush_each_row: bool,
+  /// The simulated time of the last flush made by `flush_on_cadence`.
+  last_flush: Time,
+  /// Shared with the `ReporterConfiguration` on initialization. `None` until then.
+  suppressed: Option<Arc<AtomicBool>>,
   marker: PhantomData<Marker>
 }
 
@@ -95,83 +454,729 @@ impl<Marker: Send + Sync + 'static> Reporter<Marker> {
 
   /// Creates a `Reporter` with the provided short name according to the provided `ReporterConfiguration`.
   pub fn new(short_name: String) -> Reporter<Marker> {
+    Reporter::new_with_sink(short_name, CsvSink::new())
+  }
+
+  /// Restricts the report to the named columns, written in the given order. Column names are the field names of the
+  /// report item and are validated against the first row written; unknown names cause `write_row` to fail.
+  ///
+  /// This is specific to CSV output.
+  #[must_use]
+  pub fn with_columns(mut self, columns: &[&str]) -> Self {
+    self.sink = std::mem::take(&mut self.sink).with_columns(columns);
+    self
+  }
+
+  /// Writes the header with the given names instead of the field names of the report item, e.g. `t` for the field
+  /// `time`, to match an external schema without renaming the fields. Fields without a new name keep theirs. The
+  /// renamed fields are validated against the first row written; unknown names cause `write_row` to fail. Combined
+  /// with `with_columns`, the columns are still selected by field name.
+  ///
+  /// This is specific to CSV output.
+  #[must_use]
+  pub fn with_header_names(mut self, header_names: HashMap<&str, &str>) -> Self {
+    self.sink = std::mem::take(&mut self.sink).with_header_names(header_names);
+    self
+  }
+
+  /// Writes `header` as the header instead of the field names of the report item, one name per column in order, e.g.
+  /// to match an external schema without renaming the fields. With `with_columns`, it names the selected columns, in
+  /// their selected order, so the two together rename and reorder the columns. It takes precedence over
+  /// `with_header_names`. Its length is validated against the first row written; a header with more or fewer names
+  /// than there are columns causes `write_row` to fail.
+  ///
+  /// This is specific to CSV output.
+  #[must_use]
+  pub fn with_header(mut self, header: &[&str]) -> Self {
+    self.sink = std::mem::take(&mut self.sink).with_header(header);
+    self
+  }
+
+  /// Writes the report to `sink` instead of a file. The rest of the configuration still applies, and no file is
+  /// created.
+  #[must_use]
+  pub fn with_sink(mut self, sink: impl Write + Send + Sync + 'static) -> Self {
+    self.sink.set_output(sink);
+    self
+  }
+}
+
+impl<Marker: Send + Sync + 'static, S: ReportSink> Reporter<Marker, S> {
+
+  /// Creates a `Reporter` with the provided short name that writes its rows to `sink`. See the `report_sink` module.
+  pub fn new_with_sink(short_name: String, sink: S) -> Reporter<Marker, S> {
     Reporter{
       short_name,
-      writer: None,
+      sink,
+      opened: false,
+      output_path: None,
+      final_row: None,
+      output_directory: None,
+      file_prefix: None,
+      float_precision: None,
+      flush_each_row: false,
+      last_flush: Time::default(),
+      suppressed: None,
       marker: PhantomData
     }
   }
 
+  /// Ends the report with the row `final_row` computes from the world when the run ends, if it computes one. See the
+  /// module documentation.
+  #[must_use]
+  pub fn with_final_row<Row: Serialize>(
+    mut self,
+    final_row: impl Fn(&World) -> Option<Row> + Send + Sync + 'static
+  ) -> Self {
+    self.final_row = Some(Box::new(move |world, sink, float_precision| {
+      let row = final_row(world)?;
+      Some(match float_precision {
+        Some(precision) => sink.write_final_row(&WithFloatPrecision{ value: &row, precision }),
+        None => sink.write_final_row(&row),
+      })
+    }));
+    self
+  }
+
+  /// Writes the report to `output_directory` instead of the `output_directory` of the `ReporterConfiguration`.
+  #[must_use]
+  pub fn with_output_directory(mut self, output_directory: PathBuf) -> Self {
+    self.output_directory = Some(output_directory);
+    self
+  }
+
+  /// Prefixes the report's filename with `file_prefix` instead of the `file_prefix` of the `ReporterConfiguration`.
+  #[must_use]
+  pub fn with_file_prefix(mut self, file_prefix: String) -> Self {
+    self.file_prefix = Some(file_prefix);
+    self
+  }
+
   /// This is called from `initialize_with_world`, so it could be private.
   pub fn initialize(&mut self, report_configuration: &ReporterConfiguration) -> Result<(), IxaError> {
-    let path = report_configuration.generate_filename(self.short_name.as_str());
-    let created_file = match File::create_new(&path) {
-
-      Ok(file) => file,
-
-      Err(e) => {
-        match e.kind() {
-          std::io::ErrorKind::AlreadyExists => {
-            if report_configuration.overwrite {
-              File::create(&path)?
-            } else {
-              println!("File already exists: {}. Please set `overwrite` to true in the file configuration and rerun.", path.display());
-              return Err(IxaError::IoError(e));
-            }
-          }
-          _ => {
-            return Err(IxaError::IoError(e));
-          }
-        }
-      }
-
+    let overridden;
+    let report_configuration = if self.output_directory.is_none() && self.file_prefix.is_none() {
+      report_configuration
+    } else {
+      overridden = ReporterConfiguration{
+        output_directory: self.output_directory.clone().unwrap_or_else(|| report_configuration.output_directory.clone()),
+        file_prefix: self.file_prefix.clone().unwrap_or_else(|| report_configuration.file_prefix.clone()),
+        ..report_configuration.clone()
+      };
+      &overridden
     };
 
-    self.writer = Some(CsvWriter::from_writer(created_file));
+    if !report_configuration.is_suppressed() {
+      self.output_path = self.sink.open(self.short_name.as_str(), report_configuration)?;
+      self.opened = true;
+    }
+
+    self.suppressed = Some(report_configuration.suppressed.clone());
+    self.float_precision = report_configuration.float_precision;
+    self.flush_each_row = report_configuration.flush_each_row;
 
     Ok(())
   }
 
-  // ToDo: Have this return an `IxaError`
+  /// The path of the report file, once it has been created. `None` before initialization, or if output was suppressed
+  /// when the reporter was initialized, or if the sink is not a file.
+  #[must_use]
+  pub fn output_path(&self) -> Option<&Path> {
+    self.output_path.as_deref()
+  }
+
+  /// The sink the rows are written to.
+  #[must_use]
+  pub fn sink(&self) -> &S {
+    &self.sink
+  }
+
   /// Write a row of data from an IncidenceReportItem instance to the CSV
-  pub fn write_row<ReportItem>(&mut self, item: ReportItem) -> csv::Result<()>
+  pub fn write_row<ReportItem>(&mut self, item: ReportItem) -> Result<(), IxaError>
       where ReportItem: Serialize + Send + Sync + Sized
   {
-    // ToDo: What if self isn't initialized?
-    let writer = self.writer.as_mut().expect("Failed to get writer");
-    writer.serialize(item)
+    match &self.suppressed {
+      None => {
+        return Err(IxaError::IxaError(format!("reporter '{}' was not initialized", self.short_name)));
+      }
+      Some(suppressed) if suppressed.load(Ordering::Relaxed) => return Ok(()),
+      Some(_) => {}
+    }
+    if !self.opened {
+      return Err(IxaError::IxaError(format!(
+        "reporter '{}' was initialized while output was suppressed and has no file",
+        self.short_name
+      )));
+    }
+
+    match self.float_precision {
+      Some(precision) => self.sink.write_row(&WithFloatPrecision{ value: &item, precision })?,
+      None => self.sink.write_row(&item)?,
+    }
+
+    if self.flush_each_row {
+      self.flush()?;
+    }
+
+    Ok(())
+  }
+
+  /// Writes any buffered rows to the file.
+  pub fn flush(&mut self) -> Result<(), IxaError> {
+    if self.opened {
+      self.sink.flush()?;
+    }
+    Ok(())
+  }
+
+  /// Writes the final row, if there is one, and flushes either way. See `Reporter::with_final_row`.
+  fn write_final_row(&mut self, world: &World) -> Result<(), IxaError> {
+    let suppressed = self.suppressed.as_ref().is_none_or(|suppressed| suppressed.load(Ordering::Relaxed));
+    if suppressed || !self.opened {
+      return Ok(());
+    }
+    let final_row = self.final_row.as_ref();
+    let written = final_row.and_then(|final_row| final_row(world, &mut self.sink, self.float_precision));
+    self.flush()?;
+    written.unwrap_or(Ok(()))
   }
 
 }
 
-impl<Marker: Send + Sync + 'static> Drop for Reporter<Marker> {
+impl<Marker: Send + Sync + 'static, S: ReportSink> Drop for Reporter<Marker, S> {
   fn drop(&mut self) {
-    if let Some(mut writer) = self.writer.take() {
-      // ToDo: Determine error handling needs here. Right now we ignore it.
-      let _ = writer.flush();
-    }
+    // ToDo: Determine error handling needs here. Right now we ignore it.
+    let _ = self.flush();
   }
 }
 
 
-impl<Marker: Send + Sync + 'static> Module for Reporter<Marker> {
+impl<Marker: Send + Sync + 'static, S: ReportSink> Module for Reporter<Marker, S> {
   /// Inserts self into world. The caller needs to schedule the system.
   fn initialize_with_world(mut self, world: &mut World) -> Option<SystemConfigs> {
     #[cfg(feature = "print_messages")]
     println!("Initialized module Reporter");
 
-    let config = // get or insert ReporterConfiguration
-        match world.get_resource::<ReporterConfiguration>() {
-          Some(config) => config,
-          None => {
-            world.insert_resource(ReporterConfiguration::default());
-            world.get_resource::<ReporterConfiguration>().unwrap()
-          }
-        };
+    let config = ReporterConfiguration::for_reporter(world);
+
+    // `flush_each_row` takes precedence over `flush_every`.
+    let flush_every = config.flush_every.filter(|_| !config.flush_each_row);
 
     self.initialize(config).expect("Failed to initialize Reporter");
+    let has_final_row = self.final_row.is_some();
     world.insert_resource(self);
+    if has_final_row {
+      Finalizers::add(world, |world| {
+        let result = world.resource_scope(|world, mut reporter: Mut<Reporter<Marker, S>>| {
+          reporter.write_final_row(world).map_err(|error| (reporter.short_name.clone(), error))
+        });
+        if let Err((short_name, error)) = result {
+          let reason = match error {
+            IxaError::IxaError(message) => message,
+            error => error.to_string(),
+          };
+          ModelWarnings::warn(world, format!("the final row of report '{short_name}' was not written: {reason}"));
+        }
+      });
+    }
+
+    flush_every.map(|interval| {
+      (move |reporter: ResMut<Reporter<Marker, S>>, timeline: Res<Timeline>, commands: Commands| {
+        flush_on_cadence(reporter, timeline, commands, interval)
+      }).in_set(ExecutionPhase::Last)
+    })
+  }
+}
+
+/// Flushes `reporter` if at least `interval` simulated time has passed since it was last flushed this way, aborting
+/// the model if the flush fails.
+fn flush_on_cadence<Marker: Send + Sync + 'static, S: ReportSink>(
+  mut reporter: ResMut<Reporter<Marker, S>>,
+  timeline: Res<Timeline>,
+  mut commands: Commands,
+  interval: Time,
+) {
+  let now = timeline.now();
+  if now - reporter.last_flush >= interval {
+    reporter.last_flush = now;
+    if let Err(error) = reporter.flush() {
+      let reason = format!("flushing report '{}' failed: {error}", reporter.short_name);
+      #[cfg(feature = "print_messages")]
+      println!("{reason}. Aborting.");
+      commands.insert_resource(AbortReason(reason));
+      commands.insert_resource(ModelControl::Aborted);
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use std::fs;
+  use ordered_float::OrderedFloat;
+  use serde::Serialize;
+  use super::*;
+
+  #[derive(Serialize)]
+  struct TestItem {
+    time: f64,
+    person_id: u32,
+    internal: &'static str,
+  }
+
+  struct TestMarker;
+
+  fn test_configuration(name: &str) -> ReporterConfiguration {
+    let output_directory = env::temp_dir().join("ecs_disease_models_tests").join(name);
+    fs::create_dir_all(&output_directory).unwrap();
+    ReporterConfiguration::new(String::new(), output_directory, FilenamePolicy::Overwrite)
+  }
+
+  #[test]
+  fn test_output_directory_is_created() {
+    let mut config = test_configuration("created_output_directory");
+    fs::remove_dir_all(&config.output_directory).unwrap();
+    config.output_directory = config.output_directory.join("nested").join("output");
+
+    config.create_dirs = false;
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string());
+    assert!(matches!(reporter.initialize(&config), Err(IxaError::IoError(_))));
+
+    config.create_dirs = true;
+    reporter.initialize(&config).unwrap();
+    assert_eq!(reporter.output_path(), Some(config.output_directory.join("report.csv").as_path()));
+
+    // A file where the directory should be.
+    let file_path = config.output_directory.join("not_a_directory");
+    fs::write(&file_path, "").unwrap();
+    config.output_directory = file_path;
+    let error = Reporter::<TestMarker>::new("report".to_string()).initialize(&config).unwrap_err();
+    assert!(error.to_string().contains("is not a directory"), "{error}");
+  }
+
+  #[test]
+  fn test_output_overrides() {
+    let config = test_configuration("output_overrides");
+    let elsewhere = config.output_directory.join("elsewhere");
+    let mut moved = Reporter::<TestMarker>::new("report".to_string()).with_output_directory(elsewhere.clone());
+    let mut prefixed = Reporter::<TestMarker>::new("report".to_string()).with_file_prefix("other_".to_string());
+    let mut default = Reporter::<TestMarker>::new("report".to_string());
+    for reporter in [&mut moved, &mut prefixed, &mut default] {
+      reporter.initialize(&config).unwrap();
+    }
+
+    assert_eq!(moved.output_path(), Some(elsewhere.join("report.csv").as_path()));
+    assert_eq!(prefixed.output_path(), Some(config.output_directory.join("other_report.csv").as_path()));
+    assert_eq!(default.output_path(), Some(config.output_directory.join("report.csv").as_path()));
+    assert!(elsewhere.join("report.csv").exists());
+  }
+
+  #[test]
+  fn test_numbered_filenames() {
+    let mut config = test_configuration("numbered_filenames");
+    fs::remove_dir_all(&config.output_directory).unwrap();
+    fs::create_dir_all(&config.output_directory).unwrap();
+    config.filename_policy = FilenamePolicy::Numbered;
+
+    for n in 1..=2 {
+      let mut reporter = Reporter::<TestMarker>::new("report".to_string());
+      assert_eq!(reporter.output_path(), None);
+      reporter.initialize(&config).unwrap();
+      assert_eq!(reporter.output_path(), Some(config.output_directory.join(format!("report_{n}.csv")).as_path()));
+    }
+
+    assert!(config.output_directory.join("report_1.csv").exists());
+    assert!(config.output_directory.join("report_2.csv").exists());
+    assert!(!config.output_directory.join("report.csv").exists());
+  }
+
+  #[test]
+  fn test_fail_if_exists() {
+    let mut config = test_configuration("fail_if_exists");
+    Reporter::<TestMarker>::new("report".to_string()).initialize(&config).unwrap();
+
+    config.filename_policy = FilenamePolicy::FailIfExists;
+    let result = Reporter::<TestMarker>::new("report".to_string()).initialize(&config);
+    assert!(matches!(result, Err(IxaError::IoError(e)) if e.kind() == ErrorKind::AlreadyExists));
+  }
+
+  #[test]
+  fn test_utc_timestamp() {
+    let time = UNIX_EPOCH + std::time::Duration::from_secs(1_735_787_045);
+    assert_eq!(utc_timestamp(time), "20250102T030405Z");
+  }
+
+  #[test]
+  fn test_with_columns() {
+    let config = test_configuration("with_columns");
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string()).with_columns(&["person_id", "time"]);
+    reporter.initialize(&config).unwrap();
+
+    reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "hidden" }).unwrap();
+    reporter.write_row(TestItem{ time: 2.5, person_id: 8, internal: "hidden" }).unwrap();
+    drop(reporter);
+
+    let contents = fs::read_to_string(config.generate_filename("report")).unwrap();
+    assert_eq!(contents, "person_id,time\n7,1.5\n8,2.5\n");
+  }
+
+  #[test]
+  fn test_with_header_names() {
+    let config = test_configuration("with_header_names");
+    let header_names = HashMap::from([("time", "t"), ("person_id", "PersonId")]);
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string()).with_header_names(header_names.clone());
+    reporter.initialize(&config).unwrap();
+    reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "" }).unwrap();
+    drop(reporter);
+    let contents = fs::read_to_string(config.generate_filename("report")).unwrap();
+    assert_eq!(contents, "t,PersonId,internal\n1.5,7,\n");
+
+    let mut reporter = Reporter::<TestMarker>::new("selected".to_string())
+        .with_columns(&["person_id", "time"])
+        .with_header_names(header_names);
+    reporter.initialize(&config).unwrap();
+    reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "hidden" }).unwrap();
+    drop(reporter);
+    let contents = fs::read_to_string(config.generate_filename("selected")).unwrap();
+    assert_eq!(contents, "PersonId,t\n7,1.5\n");
+
+    let mut reporter = Reporter::<TestMarker>::new("unknown".to_string())
+        .with_header_names(HashMap::from([("age", "Age")]));
+    reporter.initialize(&config).unwrap();
+    let result = reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "" });
+    assert!(matches!(result, Err(IxaError::IxaError(message)) if message.contains("age")));
+  }
+
+  #[test]
+  fn test_with_header() {
+    let config = test_configuration("with_header");
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string()).with_header(&["t", "PersonId", "Internal"]);
+    reporter.initialize(&config).unwrap();
+    reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "" }).unwrap();
+    reporter.write_row(TestItem{ time: 2.5, person_id: 8, internal: "" }).unwrap();
+    drop(reporter);
+    let contents = fs::read_to_string(config.generate_filename("report")).unwrap();
+    assert_eq!(contents, "t,PersonId,Internal\n1.5,7,\n2.5,8,\n");
+
+    // Reordered by field name, then named by position.
+    let mut reporter = Reporter::<TestMarker>::new("reordered".to_string())
+        .with_columns(&["person_id", "time"])
+        .with_header(&["PersonId", "t"]);
+    reporter.initialize(&config).unwrap();
+    reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "hidden" }).unwrap();
+    drop(reporter);
+    let contents = fs::read_to_string(config.generate_filename("reordered")).unwrap();
+    assert_eq!(contents, "PersonId,t\n7,1.5\n");
+
+    let mut reporter = Reporter::<TestMarker>::new("short".to_string()).with_header(&["t", "PersonId"]);
+    reporter.initialize(&config).unwrap();
+    let result = reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "" });
+    assert!(matches!(result, Err(IxaError::IxaError(message)) if message.contains("has 2 name(s)")));
+  }
+
+  #[test]
+  fn test_float_precision() {
+    let rows = |config: ReporterConfiguration| {
+      let mut reporter = Reporter::<TestMarker>::new("report".to_string());
+      reporter.initialize(&config).unwrap();
+      reporter.write_row(TestItem{ time: 1.23456, person_id: 7, internal: "hidden" }).unwrap();
+      reporter.write_row(TestItem{ time: 2.0, person_id: 8, internal: "hidden" }).unwrap();
+      drop(reporter);
+      fs::read_to_string(config.generate_filename("report")).unwrap()
+    };
+
+    let full_precision = rows(test_configuration("float_precision_none"));
+    assert_eq!(full_precision, "time,person_id,internal\n1.23456,7,hidden\n2.0,8,hidden\n");
+
+    let rounded = rows(test_configuration("float_precision_some").with_float_precision(2));
+    assert_eq!(rounded, "time,person_id,internal\n1.23,7,hidden\n2.00,8,hidden\n");
+  }
+
+  #[test]
+  fn test_quote_style() {
+    let rows = |quote_style: QuoteStyle| {
+      let config = test_configuration(&format!("quote_style_{quote_style:?}")).with_quote_style(quote_style);
+      let mut reporter = Reporter::<TestMarker>::new("report".to_string());
+      reporter.initialize(&config).unwrap();
+      reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "a, b" }).unwrap();
+      drop(reporter);
+      fs::read_to_string(config.generate_filename("report")).unwrap()
+    };
+
+    assert_eq!(rows(QuoteStyle::default()), "time,person_id,internal\n1.5,7,\"a, b\"\n");
+    assert_eq!(rows(QuoteStyle::Always), "\"time\",\"person_id\",\"internal\"\n\"1.5\",\"7\",\"a, b\"\n");
+    assert_eq!(rows(QuoteStyle::NonNumeric), "\"time\",\"person_id\",\"internal\"\n1.5,7,\"a, b\"\n");
+    assert_eq!(rows(QuoteStyle::Never), "time,person_id,internal\n1.5,7,a, b\n");
+  }
+
+  #[test]
+  fn test_reporters_share_the_configuration() {
+    use crate::test_utils::{add_memory_reporter, flushed_report, model_at};
+
+    struct OtherMarker;
+
+    /// The rows written by a `TestMarker` and an `OtherMarker` reporter, added in the given order, with a configuration
+    /// that rounds to two digits added before or after them, and the number of warnings.
+    fn reports(other_first: bool, configuration_first: bool) -> (String, String, usize) {
+      let rounded = || ReporterConfiguration::default().with_float_precision(2);
+      let mut model = model_at(0.0);
+      if configuration_first {
+        model.add_module(rounded());
+      }
+      let (sink, other_sink) = if other_first {
+        let other_sink = add_memory_reporter(&mut model, Reporter::<OtherMarker>::new("other".to_string()));
+        (add_memory_reporter(&mut model, Reporter::<TestMarker>::new("report".to_string())), other_sink)
+      } else {
+        let sink = add_memory_reporter(&mut model, Reporter::<TestMarker>::new("report".to_string()));
+        (sink, add_memory_reporter(&mut model, Reporter::<OtherMarker>::new("other".to_string())))
+      };
+      if !configuration_first {
+        model.add_module(rounded());
+      }
+
+      let world = model.world_mut();
+      let row = |person_id| TestItem{ time: 1.23456, person_id, internal: "" };
+      world.resource_mut::<Reporter<TestMarker>>().write_row(row(1)).unwrap();
+      world.resource_mut::<Reporter<OtherMarker>>().write_row(row(2)).unwrap();
+      (
+        flushed_report::<TestMarker>(&mut model, &sink),
+        flushed_report::<OtherMarker>(&mut model, &other_sink),
+        model.warnings().len()
+      )
+    }
+
+    let report = |time: &str, person_id: u32| format!("time,person_id,internal\n{time},{person_id},\n");
+    for other_first in [false, true] {
+      assert_eq!(reports(other_first, true), (report("1.23", 1), report("1.23", 2), 0));
+      // Both reporters were initialized with the default configuration, which the late one does not change.
+      assert_eq!(reports(other_first, false), (report("1.23456", 1), report("1.23456", 2), 1));
+    }
+  }
+
+  #[test]
+  fn test_from_settings() {
+    let settings: ReportSettings = serde_json::from_str(r#"{ "file_prefix": "run_", "output_directory": "out" }"#)
+        .unwrap();
+    let config = ReporterConfiguration::from_settings(&settings).unwrap();
+    assert_eq!(config.file_prefix, "run_");
+    assert_eq!(config.output_directory, PathBuf::from("out"));
+    assert_eq!(config.filename_policy, FilenamePolicy::FailIfExists);
+
+    let empty = ReportSettings{ output_directory: PathBuf::new(), ..settings.clone() };
+    assert!(ReporterConfiguration::from_settings(&empty).is_err());
+    // An existing file is not a directory.
+    let file = ReportSettings{ output_directory: PathBuf::from(file!()), ..settings };
+    let Err(IxaError::IxaError(message)) = ReporterConfiguration::from_settings(&file) else {
+      panic!("a file is not a valid output directory");
+    };
+    assert!(message.contains("is not a directory"), "{message}");
+  }
+
+  #[derive(Serialize)]
+  struct Summary {
+    total: u32,
+  }
+
+  /// A model with `reporter` that writes a row at each of the times `1.0, 2.0, 3.0` and counts them.
+  fn model_with_rows(name: &str, reporter: Reporter<TestMarker>) -> crate::model::Model {
+    use crate::timeline_event::{Event, EventCommand};
+
+    let mut model = crate::model::Model::new();
+    model.add_module(test_configuration(name));
+    model.add_module(reporter);
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    for time in 1..=3 {
+      timeline.push(Event{
+        time: OrderedFloat(time as f64),
+        command: EventCommand::fallible(move |world: &mut World| {
+          let item = TestItem{ time: time as f64, person_id: time, internal: "" };
+          world.resource_mut::<Reporter<TestMarker>>().write_row(item)
+        }),
+        entity: None,
+        label: "row",
+      }).unwrap();
+    }
+    model
+  }
+
+  #[test]
+  fn test_final_row() {
+    let reporter = Reporter::<TestMarker>::new("report".to_string()).with_final_row(|world: &World| {
+      let time = world.resource::<Timeline>().now().0;
+      Some(TestItem{ time, person_id: 0, internal: "total" })
+    });
+    let mut model = model_with_rows("final_row", reporter);
+    model.run();
+    // Running again does not write the final row again.
+    model.run();
+
+    let path = model.world().resource::<Reporter<TestMarker>>().output_path().unwrap().to_path_buf();
+    assert_eq!(
+      fs::read_to_string(path).unwrap(),
+      "time,person_id,internal\n1.0,1,\n2.0,2,\n3.0,3,\n3.0,0,total\n"
+    );
+    assert!(model.warnings().is_empty());
+  }
+
+  #[test]
+  fn test_incompatible_final_row() {
+    let reporter = Reporter::<TestMarker>::new("report".to_string())
+        .with_columns(&["person_id", "time"])
+        .with_final_row(|_: &World| Some(Summary{ total: 3 }));
+    let mut model = model_with_rows("incompatible_final_row", reporter);
+    model.run();
+
+    let path = model.world().resource::<Reporter<TestMarker>>().output_path().unwrap().to_path_buf();
+    assert_eq!(fs::read_to_string(path).unwrap(), "person_id,time\n1,1.0\n2,2.0\n3,3.0\n");
+    assert_eq!(model.warnings().len(), 1);
+    assert!(model.warnings()[0].contains("are not the fields"), "{:?}", model.warnings());
+  }
+
+  /// Simulates a crash by leaking the reporter so that it is never dropped (and so never flushed on drop).
+  fn crash<Marker: Send + Sync + 'static>(reporter: Reporter<Marker>) {
+    std::mem::forget(reporter);
+  }
+
+  #[test]
+  fn test_suppressed() {
+    let config = test_configuration("suppressed");
+    let path = config.generate_filename("report");
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string());
+    reporter.initialize(&config).unwrap();
+
+    config.set_suppressed(true);
+    reporter.write_row(TestItem{ time: 1.0, person_id: 1, internal: "" }).unwrap();
+    config.set_suppressed(false);
+    reporter.write_row(TestItem{ time: 2.0, person_id: 2, internal: "" }).unwrap();
+    drop(reporter);
+
+    assert_eq!(fs::read_to_string(path).unwrap(), "time,person_id,internal\n2.0,2,\n");
+  }
+
+  #[test]
+  fn test_output_path_after_run() {
+    let mut config = test_configuration("output_path_after_run");
+    config.filename_policy = FilenamePolicy::Timestamped;
+
+    let mut model = crate::model::Model::new();
+    model.add_module(config);
+    model.add_module(Reporter::<TestMarker>::new("report".to_string()));
+    model.world_mut().resource_mut::<Reporter<TestMarker>>()
+         .write_row(TestItem{ time: 1.0, person_id: 1, internal: "" }).unwrap();
+    model.run();
+
+    let mut reporter = model.world_mut().resource_mut::<Reporter<TestMarker>>();
+    reporter.flush().unwrap();
+    let path = reporter.output_path().unwrap();
+    assert!(path.file_name().unwrap().to_str().unwrap().starts_with("report_"));
+    assert_eq!(fs::read_to_string(path).unwrap(), "time,person_id,internal\n1.0,1,\n");
+    fs::remove_file(path).unwrap();
+  }
+
+  #[test]
+  fn test_uninitialized() {
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string());
+    assert!(reporter.write_row(TestItem{ time: 1.0, person_id: 1, internal: "" }).is_err());
+  }
+
+  #[test]
+  fn test_flush_each_row() {
+    let mut config = test_configuration("flush_each_row");
+    config.flush_each_row = true;
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string());
+    reporter.initialize(&config).unwrap();
+
+    reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "a" }).unwrap();
+    reporter.write_row(TestItem{ time: 2.5, person_id: 8, internal: "b" }).unwrap();
+    crash(reporter);
+
+    let contents = fs::read_to_string(config.generate_filename("report")).unwrap();
+    assert_eq!(contents, "time,person_id,internal\n1.5,7,a\n2.5,8,b\n");
+  }
+
+  #[test]
+  fn test_flush_every() {
+    let mut config = test_configuration("flush_every");
+    config.flush_every = Some(OrderedFloat(1.0));
+    let path = config.generate_filename("report");
+
+    let mut world = World::default();
+    world.insert_resource(Timeline::default());
+    world.insert_resource(config);
+    let mut schedule = Schedule::default();
+    schedule.add_systems(
+      Reporter::<TestMarker>::new("report".to_string()).initialize_with_world(&mut world).unwrap()
+    );
+
+    let mut write_at = |world: &mut World, time: f64, person_id: u32| {
+      world.resource_mut::<Timeline>().set_now(OrderedFloat(time));
+      world.resource_mut::<Reporter<TestMarker>>()
+           .write_row(TestItem{ time, person_id, internal: "" })
+           .unwrap();
+      schedule.run(world);
+    };
+
+    write_at(&mut world, 0.5, 1); // Not yet due
+    assert_eq!(fs::read_to_string(&path).unwrap(), "");
+    write_at(&mut world, 1.0, 2); // Due: flushes both rows
+    write_at(&mut world, 1.5, 3); // Not yet due
+
+    crash(world.remove_resource::<Reporter<TestMarker>>().unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "time,person_id,internal\n0.5,1,\n1.0,2,\n");
+  }
+
+  #[test]
+  fn test_failed_flush_aborts() {
+    struct FailingSink;
+
+    impl ReportSink for FailingSink {
+      fn write_row<T: Serialize>(&mut self, _item: &T) -> Result<(), IxaError> {
+        Ok(())
+      }
+
+      fn flush(&mut self) -> Result<(), IxaError> {
+        Err(IxaError::IxaError("disk full".to_string()))
+      }
+    }
+
+    let mut config = test_configuration("failed_flush");
+    config.flush_every = Some(OrderedFloat(1.0));
+    let mut world = World::default();
+    world.insert_resource(Timeline::default());
+    world.insert_resource(ModelControl::Running);
+    world.insert_resource(config);
+    let mut schedule = Schedule::default();
+    schedule.add_systems(
+      Reporter::<TestMarker, _>::new_with_sink("failing".to_string(), FailingSink)
+          .initialize_with_world(&mut world)
+          .unwrap()
+    );
+
+    world.resource_mut::<Timeline>().set_now(OrderedFloat(0.5));
+    schedule.run(&mut world);
+    assert_eq!(*world.resource::<ModelControl>(), ModelControl::Running);
+    world.resource_mut::<Timeline>().set_now(OrderedFloat(1.0));
+    schedule.run(&mut world);
+    assert_eq!(*world.resource::<ModelControl>(), ModelControl::Aborted);
+    let reason = &world.resource::<AbortReason>().0;
+    assert!(reason.contains("flushing report 'failing' failed") && reason.contains("disk full"), "{reason}");
+  }
+
+  #[test]
+  fn test_with_unknown_column() {
+    let config = test_configuration("with_unknown_column");
+    let mut reporter = Reporter::<TestMarker>::new("report".to_string()).with_columns(&["time", "age"]);
+    reporter.initialize(&config).unwrap();
+
+    let result = reporter.write_row(TestItem{ time: 1.5, person_id: 7, internal: "hidden" });
+    assert!(matches!(result, Err(IxaError::IxaError(message)) if message.contains("age")));
+  }
 
-    None // No systems?
+  #[test]
+  fn test_for_reporter_without_configuration() {
+    let mut world = World::new();
+    let config = ReporterConfiguration::for_reporter(&mut world);
+    assert_eq!(config.file_prefix, "");
+    assert!(world.contains_resource::<ReporterConfiguration>());
+    assert!(world.contains_resource::<ReporterConfigurationUsed>());
   }
 }
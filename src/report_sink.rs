@@ -0,0 +1,360 @@
+/*!
+
+Where a `Reporter` writes its rows. A `ReportSink` takes each row as a `Serialize` item, so a sink can write it in any
+format to anything, e.g. a database, an HTTP endpoint, or a channel that feeds a live plot. `Reporter<Marker, S>` is
+generic over its sink, which is a `CsvSink` unless it is created with `Reporter::new_with_sink`.
+
+The `Reporter` keeps what applies to every sink: suppression, the float precision (which wraps each row before the
+sink sees it, so that floats reach the sink as strings), flushing according to the `ReporterConfiguration`, and the
+final row. The sink is opened when the `Reporter` is initialized, unless output is suppressed then, and is flushed when
+the `Reporter` is dropped.
+
+Two sinks are provided:
+ - `CsvSink`, which writes a CSV file created according to the `ReporterConfiguration`, or any `Write` given with
+   `CsvSink::to_writer`. It also does the column selection and renaming of `Reporter::with_columns`,
+   `Reporter::with_header_names`, and `Reporter::with_header`, which are specific to CSV output.
+ - `MemoryRowSink`, which keeps the rows in memory as JSON values. Clones share the rows, so a clone kept outside the
+   world can read the rows while the model runs, e.g. from a GUI thread.
+
+*/
+
+use std::{
+  collections::HashMap,
+  io::Write,
+  path::PathBuf,
+  sync::{Arc, Mutex}
+};
+
+use csv::{ReaderBuilder, StringRecord, Writer as CsvWriter, WriterBuilder};
+use serde::Serialize;
+
+use crate::{
+  errors::IxaError,
+  report::ReporterConfiguration
+};
+
+/// A destination for the rows of a `Reporter`. See the module documentation.
+pub trait ReportSink: Send + Sync + 'static {
+  /// Prepares the sink for the report named `short_name`, returning the path of the file it created, if any. Called
+  /// once, when the `Reporter` is initialized, unless output is suppressed then.
+  fn open(&mut self, _short_name: &str, _configuration: &ReporterConfiguration) -> Result<Option<PathBuf>, IxaError> {
+    Ok(None)
+  }
+
+  /// Writes a row.
+  fn write_row<T: Serialize>(&mut self, item: &T) -> Result<(), IxaError>;
+
+  /// Writes the final row of the report. See `Reporter::with_final_row`. A sink that requires every row to have the
+  /// same fields checks them here.
+  fn write_final_row<T: Serialize>(&mut self, item: &T) -> Result<(), IxaError> {
+    self.write_row(item)
+  }
+
+  /// Writes any buffered rows.
+  fn flush(&mut self) -> Result<(), IxaError>;
+}
+
+/// Where a `CsvSink` writes, unless it writes to a file.
+type ByteSink = Box<dyn Write + Send + Sync>;
+
+/// Writes rows as CSV, to a file by default. See the module documentation.
+#[derive(Default)]
+pub struct CsvSink {
+  writer: Option<CsvWriter<ByteSink>>,
+  /// Written to instead of a file. Moved into the writer when the sink is opened. See `CsvSink::to_writer`.
+  output: Option<ByteSink>,
+  /// The columns to write, in order, if not all of them. See `Reporter::with_columns`.
+  columns: Option<Vec<String>>,
+  /// The position of each of `columns` within a serialized row. Resolved on the first write.
+  column_indices: Option<Vec<usize>>,
+  /// Header names to write instead of field names, by field name. See `Reporter::with_header_names`.
+  header_names: Option<HashMap<String, String>>,
+  /// The header to write instead of the field names, by position. See `Reporter::with_header`.
+  header: Option<Vec<String>>,
+  /// The field names of the rows, once one has been written.
+  field_names: Option<StringRecord>,
+}
+
+impl CsvSink {
+  /// A sink that writes to a file created according to the `ReporterConfiguration`.
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A sink that writes to `output` instead of a file.
+  #[must_use]
+  pub fn to_writer(output: impl Write + Send + Sync + 'static) -> Self {
+    CsvSink{ output: Some(Box::new(output)), ..Self::default() }
+  }
+
+  /// See `Reporter::with_columns`.
+  #[must_use]
+  pub fn with_columns(mut self, columns: &[&str]) -> Self {
+    self.columns = Some(columns.iter().map(|column| column.to_string()).collect());
+    self
+  }
+
+  /// See `Reporter::with_header_names`.
+  #[must_use]
+  pub fn with_header_names(mut self, header_names: HashMap<&str, &str>) -> Self {
+    self.header_names = Some(
+      header_names.into_iter().map(|(field, header)| (field.to_string(), header.to_string())).collect()
+    );
+    self
+  }
+
+  /// See `Reporter::with_header`.
+  #[must_use]
+  pub fn with_header(mut self, header: &[&str]) -> Self {
+    self.header = Some(header.iter().map(|name| name.to_string()).collect());
+    self
+  }
+
+  pub(crate) fn set_output(&mut self, output: impl Write + Send + Sync + 'static) {
+    self.output = Some(Box::new(output));
+  }
+
+  /// Writes the `values` of a row with the given `field_names`, preceded by the header if this is the first row.
+  fn write_record(&mut self, field_names: StringRecord, values: &StringRecord) -> Result<(), IxaError> {
+    let writer = self.writer.as_mut().ok_or_else(not_opened)?;
+
+    if self.column_indices.is_none() {
+      let column_indices = match &self.columns {
+        Some(columns) => resolve_columns(columns, &field_names)?,
+        None => (0..field_names.len()).collect(),
+      };
+      let header: Vec<&str> = match (&self.header, &self.header_names) {
+        (Some(header), _) if header.len() != column_indices.len() => {
+          return Err(IxaError::IxaError(format!(
+            "the report header {:?} has {} name(s), but the report has {} column(s) {:?}",
+            header,
+            header.len(),
+            column_indices.len(),
+            column_indices.iter().map(|&index| &field_names[index]).collect::<Vec<_>>()
+          )));
+        }
+        (Some(header), _) => header.iter().map(String::as_str).collect(),
+        (None, Some(header_names)) => rename_columns(&column_indices, &field_names, header_names)?,
+        (None, None) => column_indices.iter().map(|&index| &field_names[index]).collect(),
+      };
+      writer.write_record(header)?;
+      self.column_indices = Some(column_indices);
+      self.field_names = Some(field_names);
+    }
+
+    let column_indices = self.column_indices.as_ref().unwrap();
+    writer.write_record(column_indices.iter().map(|&index| &values[index]))?;
+
+    Ok(())
+  }
+}
+
+impl ReportSink for CsvSink {
+  fn open(&mut self, short_name: &str, configuration: &ReporterConfiguration) -> Result<Option<PathBuf>, IxaError> {
+    let (output, path): (ByteSink, Option<PathBuf>) = match self.output.take() {
+      Some(output) => (output, None),
+      None => {
+        let (created_file, path) = configuration.create_report_file(short_name)?;
+        (Box::new(created_file), Some(path))
+      }
+    };
+    self.writer = Some(WriterBuilder::new().quote_style(configuration.quote_style.into()).from_writer(output));
+    Ok(path)
+  }
+
+  fn write_row<T: Serialize>(&mut self, item: &T) -> Result<(), IxaError> {
+    if self.columns.is_none() && self.header_names.is_none() && self.header.is_none() {
+      if self.field_names.is_none() {
+        self.field_names = Some(serialize_to_record(item)?.0);
+      }
+      self.writer.as_mut().ok_or_else(not_opened)?.serialize(item)?;
+      return Ok(());
+    }
+
+    let (field_names, values) = serialize_to_record(item)?;
+    self.write_record(field_names, &values)
+  }
+
+  fn write_final_row<T: Serialize>(&mut self, item: &T) -> Result<(), IxaError> {
+    let (field_names, values) = serialize_to_record(item)?;
+    match &self.field_names {
+      Some(expected) if *expected != field_names => {
+        return Err(IxaError::IxaError(format!(
+          "its fields {:?} are not the fields {:?} of the other rows",
+          field_names.iter().collect::<Vec<_>>(),
+          expected.iter().collect::<Vec<_>>()
+        )));
+      }
+      // The rows were serialized directly, header and all.
+      Some(_) if self.column_indices.is_none() => self.column_indices = Some((0..field_names.len()).collect()),
+      _ => {}
+    }
+    self.write_record(field_names, &values)
+  }
+
+  fn flush(&mut self) -> Result<(), IxaError> {
+    if let Some(writer) = self.writer.as_mut() {
+      writer.flush()?;
+    }
+    Ok(())
+  }
+}
+
+/// Keeps the rows in memory as JSON values. Clones share the same rows. See the module documentation.
+#[derive(Clone, Default, Debug)]
+pub struct MemoryRowSink(Arc<Mutex<Vec<serde_json::Value>>>);
+
+impl MemoryRowSink {
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// A copy of the rows written so far.
+  #[must_use]
+  pub fn rows(&self) -> Vec<serde_json::Value> {
+    self.0.lock().unwrap().clone()
+  }
+
+  /// Removes and returns the rows written so far, e.g. to consume them as they arrive.
+  pub fn take_rows(&self) -> Vec<serde_json::Value> {
+    std::mem::take(&mut self.0.lock().unwrap())
+  }
+
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.0.lock().unwrap().len()
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.0.lock().unwrap().is_empty()
+  }
+}
+
+impl ReportSink for MemoryRowSink {
+  fn write_row<T: Serialize>(&mut self, item: &T) -> Result<(), IxaError> {
+    let row = serde_json::to_value(item)?;
+    self.0.lock().unwrap().push(row);
+    Ok(())
+  }
+
+  fn flush(&mut self) -> Result<(), IxaError> {
+    Ok(())
+  }
+}
+
+fn not_opened() -> IxaError {
+  IxaError::IxaError("the CSV sink was not opened".to_string())
+}
+
+/// Serializes `item` as a CSV row, returning its field names and its values.
+fn serialize_to_record<ReportItem: Serialize>(item: &ReportItem) -> Result<(StringRecord, StringRecord), IxaError> {
+  let mut buffer = CsvWriter::from_writer(vec![]);
+  buffer.serialize(item)?;
+  let bytes = buffer.into_inner().map_err(|error| error.into_error())?;
+
+  let mut reader = ReaderBuilder::new().has_headers(false).from_reader(bytes.as_slice());
+  let mut records = reader.records();
+  match (records.next(), records.next()) {
+    (Some(field_names), Some(values)) => Ok((field_names?, values?)),
+    _ => Err(IxaError::IxaError("report item did not serialize to a named row".to_string())),
+  }
+}
+
+/// The header of the columns at `column_indices` within `field_names`, renamed according to `header_names`, which
+/// must only rename fields that exist.
+fn rename_columns<'a>(
+  column_indices: &[usize],
+  field_names: &'a StringRecord,
+  header_names: &'a HashMap<String, String>,
+) -> Result<Vec<&'a str>, IxaError> {
+  let mut unknown: Vec<&str> = header_names.keys()
+                                           .map(String::as_str)
+                                           .filter(|field| !field_names.iter().any(|field_name| field_name == *field))
+                                           .collect();
+  if !unknown.is_empty() {
+    unknown.sort_unstable();
+    return Err(IxaError::IxaError(format!(
+      "cannot rename unknown report column(s) {:?}; the report item has columns {:?}",
+      unknown,
+      field_names.iter().collect::<Vec<_>>()
+    )));
+  }
+
+  Ok(column_indices.iter()
+                   .map(|&index| {
+                     let field_name = &field_names[index];
+                     header_names.get(field_name).map_or(field_name, String::as_str)
+                   })
+                   .collect())
+}
+
+/// Finds the position of each of `columns` within `field_names`.
+fn resolve_columns(columns: &[String], field_names: &StringRecord) -> Result<Vec<usize>, IxaError> {
+  let mut indices = Vec::with_capacity(columns.len());
+  let mut unknown = Vec::new();
+
+  for column in columns {
+    match field_names.iter().position(|field_name| field_name == column) {
+      Some(index) => indices.push(index),
+      None => unknown.push(column.as_str()),
+    }
+  }
+
+  if !unknown.is_empty() {
+    return Err(IxaError::IxaError(format!(
+      "unknown report column(s) {:?}; the report item has columns {:?}",
+      unknown,
+      field_names.iter().collect::<Vec<_>>()
+    )));
+  }
+
+  Ok(indices)
+}
+
+
+#[cfg(test)]
+mod tests {
+  use bevy_ecs::prelude::*;
+  use serde_json::json;
+  use crate::{
+    model::Model,
+    report::Reporter,
+    timeline::Timeline,
+    timeline_event::EventCommand
+  };
+  use super::*;
+
+  #[derive(Serialize)]
+  struct Row {
+    time    : f64,
+    infected: u32,
+  }
+
+  struct RowMarker;
+
+  #[test]
+  fn test_memory_row_sink() {
+    let rows = MemoryRowSink::new();
+    let reporter = Reporter::<RowMarker, MemoryRowSink>::new_with_sink("rows".to_string(), rows.clone())
+        .with_final_row(|world: &World| Some(Row{ time: world.resource::<Timeline>().now().0, infected: 0 }));
+    let mut model = Model::with_random_seed(1);
+    model.add_module(reporter);
+    for (time, infected) in [(1.0, 3), (2.0, 5)] {
+      let command = EventCommand::infallible(move |world| {
+        world.resource_mut::<Reporter<RowMarker, MemoryRowSink>>().write_row(Row{ time, infected }).unwrap();
+      });
+      model.world_mut().resource_mut::<Timeline>().schedule_at(time, command, "row").unwrap();
+    }
+    model.run();
+
+    assert_eq!(
+      rows.take_rows(),
+      [json!({"time": 1.0, "infected": 3}), json!({"time": 2.0, "infected": 5}), json!({"time": 2.0, "infected": 0})]
+    );
+    assert!(rows.is_empty());
+    assert!(model.world().resource::<Reporter<RowMarker, MemoryRowSink>>().output_path().is_none());
+  }
+}
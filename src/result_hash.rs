@@ -0,0 +1,175 @@
+/*!
+
+A hash of everything that happened in a run, for regression tests: a test or a user's CI runs the model with a fixed
+seed and parameters and asserts that `Model::result_hash` is what it was, which catches any change in behavior, not just
+the changes that show in a summary like the final counts.
+
+The hash is opt-in. Add a `HashStatusChanges<C>` module for every status component `C` whose changes should be hashed.
+Each change, including the first status seen for an entity as with `StatusTransitions::iter`, is folded into the
+`ResultHash` resource as its time, entity, and new status, from a system in the `Last` phase. Within an iteration of
+the event loop, the changes are hashed in order of entity, so the hash does not depend on query order. Across
+iterations, it depends on the order the model runs its events in, so it is only as deterministic as the model is.
+
+The hash is a 64-bit FNV-1a, which is stable, unlike `std`'s `DefaultHasher`. Statuses are fed to it with their `Hash`
+implementation, though, and entity ids are Bevy ECS's bit representation, so a hash is only comparable with hashes
+computed by the same version of this crate and its dependencies, on the same platform.
+
+*/
+
+use std::{
+  hash::{Hash, Hasher},
+  marker::PhantomData
+};
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use crate::{
+  model::ExecutionPhase,
+  module::Module,
+  status_transition::StatusTransitions,
+  timeline::Timeline
+};
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// The rolling hash of the status changes of a run. See the module documentation.
+#[derive(Resource, Copy, Clone, Eq, PartialEq, Debug, Hash)]
+pub struct ResultHash(u64);
+
+impl Default for ResultHash {
+  fn default() -> Self {
+    ResultHash(FNV_OFFSET_BASIS)
+  }
+}
+
+impl ResultHash {
+  /// The hash so far.
+  #[must_use]
+  pub fn value(&self) -> u64 {
+    self.0
+  }
+}
+
+impl Hasher for ResultHash {
+  fn finish(&self) -> u64 {
+    self.0
+  }
+
+  fn write(&mut self, bytes: &[u8]) {
+    for byte in bytes {
+      self.0 = (self.0 ^ u64::from(*byte)).wrapping_mul(FNV_PRIME);
+    }
+  }
+}
+
+/// Hashes the changes of the `C` component into the `ResultHash`. See the module documentation.
+pub struct HashStatusChanges<C: Component + Copy + PartialEq + Hash> {
+  status: PhantomData<fn() -> C>,
+}
+
+impl<C: Component + Copy + PartialEq + Hash> Default for HashStatusChanges<C> {
+  fn default() -> Self {
+    HashStatusChanges{ status: PhantomData }
+  }
+}
+
+impl<C: Component + Copy + PartialEq + Hash> HashStatusChanges<C> {
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+}
+
+/// A system that folds the changes of `C` since it last ran into the `ResultHash`.
+fn hash_status_changes<C: Component + Copy + PartialEq + Hash>(
+  mut result_hash: ResMut<ResultHash>,
+  timeline: Res<Timeline>,
+  mut transitions: StatusTransitions<C>,
+) {
+  let mut changes: Vec<(Entity, C)> = transitions.iter().map(|transition| (transition.entity, transition.to)).collect();
+  if changes.is_empty() {
+    return;
+  }
+  changes.sort_by_key(|(entity, _)| *entity);
+
+  let time = timeline.now().0.to_bits();
+  for (entity, status) in changes {
+    result_hash.write_u64(time);
+    result_hash.write_u64(entity.to_bits());
+    status.hash(result_hash.as_mut());
+  }
+}
+
+impl<C: Component + Copy + PartialEq + Hash> Module for HashStatusChanges<C> {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.init_resource::<ResultHash>();
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module HashStatusChanges");
+
+    Some(hash_status_changes::<C>.in_set(ExecutionPhase::Last))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use rand::distr::{StandardUniform, Uniform};
+  use crate::{
+    model::Model,
+    random::RngResource,
+    timeline_event::Event
+  };
+  use super::*;
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, Hash, Debug)]
+  enum Status {
+    Healthy,
+    Sick,
+  }
+
+  /// A model of 20 people, one of whom is made sick at each of the times `1.0, 2.0, ..., 10.0`, after a random delay.
+  fn model(seed: u64) -> Model {
+    let mut model = Model::with_random_seed(seed);
+    model.add_module(HashStatusChanges::<Status>::new());
+    let people: Vec<Entity> = model.world_mut().spawn_batch([Status::Healthy; 20]).collect();
+    for time in 1..=10 {
+      let people = people.clone();
+      let event = Event::builder(time as f64).command(move |world| {
+        let now = world.resource::<Timeline>().now();
+        let mut rng = world.resource_mut::<RngResource>();
+        let person = people[rng.sample_labeled(now, "person", Uniform::new(0, people.len()).unwrap())];
+        let delay: f64 = rng.sample_labeled(now, "delay", StandardUniform);
+        let sicken = Event::builder(now.0 + delay)
+            .command(move |world| *world.get_mut::<Status>(person).unwrap() = Status::Sick)
+            .build();
+        world.resource_mut::<Timeline>().push(sicken).unwrap();
+      }).build();
+      model.world_mut().resource_mut::<Timeline>().push(event).unwrap();
+    }
+    model
+  }
+
+  fn result_hash(seed: u64) -> u64 {
+    let mut model = model(seed);
+    model.run();
+    model.result_hash().unwrap()
+  }
+
+  #[test]
+  fn test_result_hash() {
+    assert_eq!(result_hash(1), result_hash(1));
+    assert_ne!(result_hash(1), result_hash(2));
+    assert_ne!(result_hash(1), ResultHash::default().value());
+    assert_eq!(Model::new().result_hash(), None);
+
+    // FNV-1a of "a".
+    let mut hash = ResultHash::default();
+    hash.write(b"a");
+    assert_eq!(hash.value(), 0xaf63_dc4c_8601_ec8c);
+  }
+}
@@ -0,0 +1,161 @@
+/*!
+
+A `Scenario` configures a run from a file instead of code: which modules to add, in order, with what configuration,
+and the `SimConstants` of the run. The modules are constructed by a `ModuleRegistry`, which the model supplies with
+the modules it makes available, so a scenario can only use modules registered under a name.
+
+Scenarios are JSON:
+
+```json
+{
+  "seed": 123,
+  "population_size": 1000,
+  "max_time": 303.0,
+  "modules": [
+    { "module": "population_statistics" },
+    { "module": "transmission_manager", "config": { "foi": 0.1 } }
+  ]
+}
+```
+
+Every field but `modules` is optional and defaults as in `SimConstants`. A module's `config` is passed to its
+constructor in the registry and defaults to `null`. Unknown fields are errors, so that a misspelled field is not
+silently ignored.
+
+`Model::from_scenario` reads a scenario file and builds the model. Every listed module is constructed before any is
+added, so an unknown module name, which is an error listing the known names, or an invalid configuration fails before
+the model does anything.
+
+*/
+
+use std::{
+  fs,
+  path::Path
+};
+
+use serde::Deserialize;
+
+use crate::{
+  errors::IxaError,
+  model::Model,
+  module::{Module, ModuleRegistry},
+  sim_constants::SimConstants
+};
+
+/// A module to add and its configuration. See the module documentation.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct ModuleConfig {
+  /// The name the module is registered under.
+  pub module: String,
+  #[serde(default)]
+  pub config: serde_json::Value,
+}
+
+/// The modules and constants of a run. See the module documentation.
+#[derive(Deserialize, Clone, PartialEq, Debug)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+  #[serde(default)]
+  pub seed: Option<u64>,
+  #[serde(default)]
+  pub population_size: Option<u32>,
+  #[serde(default)]
+  pub max_time: Option<f64>,
+  pub modules: Vec<ModuleConfig>,
+}
+
+impl Scenario {
+  /// Reads a scenario from the JSON file at `path`.
+  pub fn from_path(path: &Path) -> Result<Scenario, IxaError> {
+    let contents = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+  }
+
+  /// The `SimConstants` of the scenario, with defaults for the values it does not set.
+  #[must_use]
+  pub fn constants(&self) -> SimConstants {
+    let mut builder = SimConstants::builder();
+    if let Some(seed) = self.seed {
+      builder = builder.seed(seed);
+    }
+    if let Some(population_size) = self.population_size {
+      builder = builder.population_size(population_size);
+    }
+    if let Some(max_time) = self.max_time {
+      builder = builder.max_time(max_time);
+    }
+    builder.build()
+  }
+
+  /// Builds the model, constructing the modules with `registry`.
+  pub fn build(&self, registry: &ModuleRegistry) -> Result<Model, IxaError> {
+    let modules: Vec<Box<dyn Module>> = self.modules
+                                            .iter()
+                                            .map(|module| registry.construct(&module.module, &module.config))
+                                            .collect::<Result<_, _>>()?;
+
+    let mut model = Model::with_constants(self.constants());
+    for module in modules {
+      model.add_module_boxed(module);
+    }
+    Ok(model)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::env;
+  use bevy_ecs::{prelude::*, schedule::SystemConfigs};
+  use ordered_float::OrderedFloat;
+  use super::*;
+
+  #[derive(Resource, Deserialize, Debug, PartialEq)]
+  struct Counter {
+    start: u32,
+  }
+
+  impl Module for Counter {
+    fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+      world.insert_resource(self);
+      None
+    }
+  }
+
+  fn registry() -> ModuleRegistry {
+    let mut registry = ModuleRegistry::new();
+    registry.register_deserializable::<Counter>("counter");
+    registry
+  }
+
+  #[test]
+  fn test_from_path() {
+    let directory = env::temp_dir().join("ecs_disease_models_tests").join("scenario");
+    fs::create_dir_all(&directory).unwrap();
+    let path = directory.join("scenario.json");
+    let scenario = r#"{ "seed": 5, "max_time": 10.0, "modules": [{ "module": "counter", "config": { "start": 3 } }] }"#;
+    fs::write(&path, scenario).unwrap();
+
+    let model = Model::from_scenario(&path, &registry()).unwrap();
+    assert_eq!(model.seed(), 5);
+    assert_eq!(model.constants().max_time, OrderedFloat(10.0));
+    assert_eq!(model.constants().population_size, 0);
+    assert_eq!(model.world().resource::<Counter>(), &Counter{ start: 3 });
+  }
+
+  #[test]
+  fn test_invalid_scenarios() {
+    let scenario: Scenario = serde_json::from_str(r#"{ "modules": [{ "module": "timer" }] }"#).unwrap();
+    let Err(IxaError::IxaError(message)) = scenario.build(&registry()) else {
+      panic!("building a scenario with an unknown module should fail");
+    };
+    assert!(message.contains("timer") && message.contains("counter"), "{message}");
+
+    // A misspelled field.
+    assert!(serde_json::from_str::<Scenario>(r#"{ "sead": 5, "modules": [] }"#).is_err());
+    // A module with an invalid configuration.
+    let scenario: Scenario = serde_json::from_str(r#"{ "modules": [{ "module": "counter" }] }"#).unwrap();
+    assert!(matches!(scenario.build(&registry()), Err(IxaError::JsonError(_))));
+  }
+}
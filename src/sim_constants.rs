@@ -0,0 +1,80 @@
+/*!
+
+`SimConstants` holds the values that describe a simulation as a whole rather than any one module: the population size,
+the time after which nothing new should be scheduled, and the seed. It is the library-provided counterpart of a model's
+own `Parameters`, so modules that need these values read them from the `SimConstants` resource instead of having them
+passed in (or hard-wired) one module at a time, and the same modules can run under different configurations.
+
+Every `Model` inserts a `SimConstants` when it is constructed. `Model::with_constants` takes a complete one, built with
+`SimConstants::builder`; the other constructors insert one with just their seed and the defaults below. Values that are
+not set have these defaults:
+ - `population_size`: 0, meaning unknown. Modules that need a population size should say so rather than divide by it.
+ - `max_time`: infinity, meaning no limit.
+ - `seed`: `DEFAULT_SEED`.
+
+The seed in `SimConstants` is the seed the model was constructed with, the same as `SeedResource`.
+
+*/
+
+use bevy_ecs::prelude::*;
+use ordered_float::OrderedFloat;
+
+use crate::{
+  random::DEFAULT_SEED,
+  timeline::Time
+};
+
+/// Simulation-wide constants. See the module documentation.
+#[derive(Resource, Copy, Clone, PartialEq, Debug)]
+pub struct SimConstants {
+  pub population_size: u32,
+  pub max_time       : Time,
+  pub seed           : u64,
+}
+
+impl Default for SimConstants {
+  fn default() -> Self {
+    SimConstants{
+      population_size: 0,
+      max_time: OrderedFloat(f64::INFINITY),
+      seed: DEFAULT_SEED,
+    }
+  }
+}
+
+impl SimConstants {
+  pub fn builder() -> SimConstantsBuilder {
+    SimConstantsBuilder::default()
+  }
+}
+
+/// Builds `SimConstants`, starting from the defaults.
+#[derive(Copy, Clone, PartialEq, Default, Debug)]
+pub struct SimConstantsBuilder {
+  constants: SimConstants,
+}
+
+impl SimConstantsBuilder {
+  #[must_use]
+  pub fn population_size(mut self, population_size: u32) -> Self {
+    self.constants.population_size = population_size;
+    self
+  }
+
+  #[must_use]
+  pub fn max_time(mut self, max_time: impl Into<Time>) -> Self {
+    self.constants.max_time = max_time.into();
+    self
+  }
+
+  #[must_use]
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.constants.seed = seed;
+    self
+  }
+
+  #[must_use]
+  pub fn build(self) -> SimConstants {
+    self.constants
+  }
+}
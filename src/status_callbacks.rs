@@ -0,0 +1,159 @@
+/*!
+
+`StatusCallbacks<C>` pushes every change of an entity's `C` component, typically an infection status, to callbacks
+registered in process, e.g. to feed a live viewer without polling the world or tailing a report. Each change is
+delivered as a `StatusEvent` with the entity, its old and new status, and the time.
+
+Add the `StatusCallbacks<C>` module once, with its first callbacks registered with `StatusCallbacks::with_callback`.
+More can be registered later with `StatusCallbacks::add` on the resource. The changes are found with
+`StatusTransitions`, so the first status seen for an entity is delivered too, with no old status, which in models that
+spawn people when they are infected is their infection.
+
+Callbacks are called synchronously, in the order they were registered, from a system in the `Last` phase of the
+iteration of the event loop in which the change is first visible to systems. They are given the event and nothing else,
+so they cannot borrow the world and cannot deadlock on it; a callback that needs to act on the model can send the
+event over a channel and have it handled outside the run. Within an iteration, the changes of different entities are
+delivered in no particular order.
+
+Relative to a reporter that writes the same changes from a system in the `Normal` phase, a change is delivered to the
+callbacks in the same iteration, and so at the same time, after the reporter has written its row. The row may still be
+buffered, though, so it is not necessarily in the file yet.
+
+*/
+
+use std::marker::PhantomData;
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use crate::{
+  model::ExecutionPhase,
+  module::Module,
+  status_transition::StatusTransitions,
+  timeline::{Time, Timeline}
+};
+
+/// A change of an entity's `C` component, as delivered to callbacks.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StatusEvent<C> {
+  pub entity    : Entity,
+  /// `None` if this is the first status seen for the entity.
+  pub old_status: Option<C>,
+  pub new_status: C,
+  pub time      : Time,
+}
+
+/// A callback for `StatusEvent`s.
+pub type StatusCallback<C> = Box<dyn Fn(&StatusEvent<C>) + Send + Sync>;
+
+/// The callbacks to call on every change of a `C` component. See the module documentation.
+#[derive(Resource)]
+pub struct StatusCallbacks<C: Component + Copy + PartialEq> {
+  callbacks: Vec<StatusCallback<C>>,
+  status   : PhantomData<fn() -> C>,
+}
+
+impl<C: Component + Copy + PartialEq> Default for StatusCallbacks<C> {
+  fn default() -> Self {
+    StatusCallbacks{ callbacks: Vec::new(), status: PhantomData }
+  }
+}
+
+impl<C: Component + Copy + PartialEq> StatusCallbacks<C> {
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Registers `callback`, to be called after the ones already registered.
+  #[must_use]
+  pub fn with_callback(mut self, callback: impl Fn(&StatusEvent<C>) + Send + Sync + 'static) -> Self {
+    self.add(callback);
+    self
+  }
+
+  /// Registers `callback`, to be called after the ones already registered.
+  pub fn add(&mut self, callback: impl Fn(&StatusEvent<C>) + Send + Sync + 'static) {
+    self.callbacks.push(Box::new(callback));
+  }
+
+  /// The number of callbacks registered.
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.callbacks.len()
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.callbacks.is_empty()
+  }
+}
+
+/// A system that delivers the changes of `C` since it last ran to the callbacks.
+fn notify_status_callbacks<C: Component + Copy + PartialEq>(
+  callbacks: Res<StatusCallbacks<C>>,
+  timeline: Res<Timeline>,
+  mut transitions: StatusTransitions<C>,
+) {
+  let time = timeline.now();
+  for transition in transitions.iter() {
+    let event = StatusEvent{ entity: transition.entity, old_status: transition.from, new_status: transition.to, time };
+    for callback in &callbacks.callbacks {
+      callback(&event);
+    }
+  }
+}
+
+impl<C: Component + Copy + PartialEq> Module for StatusCallbacks<C> {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module StatusCallbacks");
+
+    Some(notify_status_callbacks::<C>.in_set(ExecutionPhase::Last))
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use std::sync::{mpsc, Arc, Mutex};
+  use crate::test_utils::{model_at, tick_at};
+  use super::*;
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+  enum Status {
+    Infected,
+    Recovered,
+  }
+
+  #[test]
+  fn test_status_callbacks() {
+    let (sender, receiver) = mpsc::channel();
+    let count = Arc::new(Mutex::new(0));
+    let counted = count.clone();
+    let mut model = model_at(0.0);
+    model.add_module(
+      StatusCallbacks::<Status>::new().with_callback(move |event| sender.send(*event).unwrap())
+    );
+    model.world_mut().resource_mut::<StatusCallbacks<Status>>().add(move |_| *counted.lock().unwrap() += 1);
+
+    let entity = model.world_mut().spawn(Status::Infected).id();
+    tick_at(&mut model, 1.0);
+    // A write of the same value is not a change.
+    *model.world_mut().get_mut::<Status>(entity).unwrap() = Status::Infected;
+    tick_at(&mut model, 2.0);
+    *model.world_mut().get_mut::<Status>(entity).unwrap() = Status::Recovered;
+    tick_at(&mut model, 3.5);
+
+    let events: Vec<StatusEvent<Status>> = receiver.try_iter().collect();
+    assert_eq!(events, vec![
+      StatusEvent{ entity, old_status: None, new_status: Status::Infected, time: Time::from(1.0) },
+      StatusEvent{ entity, old_status: Some(Status::Infected), new_status: Status::Recovered, time: Time::from(3.5) },
+    ]);
+    assert_eq!(*count.lock().unwrap(), 2);
+  }
+}
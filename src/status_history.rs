@@ -0,0 +1,219 @@
+/*!
+
+A per-entity record of every value a status component has taken and when, so that individual sojourn times, e.g. how
+long each person was infectious, can be computed after a run. It is richer than the `LifeHistory` of the
+`infection_times` module, which has only the infection and recovery times, and than an incidence report, which is one
+stream for the whole population.
+
+Add the `RecordStatusHistory<C>` module for the status component `C`. A system in the `Last` phase appends each
+transition of `C`, as seen by `StatusTransitions::iter` (so including the first status seen for an entity), to the
+entity's `StatusHistory<C>`, with the current time. Recording is opt-in per entity: only entities that have a
+`StatusHistory<C>`, e.g. because they were spawned with one, are recorded, from their next transition on. With
+`RecordStatusHistory::for_all_entities`, every entity with a `C` is given a `StatusHistory<C>` when its first status is
+seen, which moves it to another archetype once.
+
+Transitions are seen once per iteration of the event loop, so if an entity's status changes more than once in one
+iteration, only the last value is recorded. A despawned entity takes its history with it.
+
+*/
+
+use std::marker::PhantomData;
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use crate::{
+  model::ExecutionPhase,
+  module::Module,
+  status_transition::StatusTransitions,
+  timeline::{Time, Timeline},
+  units::Duration
+};
+
+/// The values an entity's `C` component has taken, with the times it took them, oldest first. See the module
+/// documentation.
+#[derive(Component, Clone, PartialEq, Debug)]
+pub struct StatusHistory<C: Copy + PartialEq + Send + Sync + 'static> {
+  transitions: Vec<(Time, C)>,
+}
+
+impl<C: Copy + PartialEq + Send + Sync + 'static> Default for StatusHistory<C> {
+  fn default() -> Self {
+    StatusHistory{ transitions: Vec::new() }
+  }
+}
+
+impl<C: Copy + PartialEq + Send + Sync + 'static> StatusHistory<C> {
+  /// An empty history, for an entity to be spawned with so that its transitions are recorded.
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records that the entity took the value `status` at `time`.
+  pub fn record(&mut self, time: Time, status: C) {
+    self.transitions.push((time, status));
+  }
+
+  /// Every value the entity has taken, with the time it took it, oldest first.
+  #[must_use]
+  pub fn transitions(&self) -> &[(Time, C)] {
+    &self.transitions
+  }
+
+  /// The most recently recorded value.
+  #[must_use]
+  pub fn current(&self) -> Option<C> {
+    self.transitions.last().map(|&(_, status)| status)
+  }
+
+  /// When the entity first took the value `status`.
+  #[must_use]
+  pub fn entered_at(&self, status: C) -> Option<Time> {
+    self.transitions.iter().find(|(_, recorded)| *recorded == status).map(|&(time, _)| time)
+  }
+
+  /// How long each stay in `status` that has ended lasted, oldest first.
+  pub fn sojourns(&self, status: C) -> impl Iterator<Item = Duration> + '_ {
+    self.transitions.windows(2)
+                    .filter(move |pair| pair[0].1 == status)
+                    .map(|pair| Duration::between(pair[0].0, pair[1].0))
+  }
+
+  /// How long the first stay in `status` lasted, if it has ended, e.g. the infectious period for the infected status.
+  #[must_use]
+  pub fn sojourn(&self, status: C) -> Option<Duration> {
+    self.sojourns(status).next()
+  }
+}
+
+/// Records the transitions of the `C` component in each entity's `StatusHistory<C>`. See the module documentation.
+pub struct RecordStatusHistory<C: Component + Copy + PartialEq> {
+  all_entities: bool,
+  status      : PhantomData<fn() -> C>,
+}
+
+impl<C: Component + Copy + PartialEq> Default for RecordStatusHistory<C> {
+  fn default() -> Self {
+    RecordStatusHistory{ all_entities: false, status: PhantomData }
+  }
+}
+
+impl<C: Component + Copy + PartialEq> RecordStatusHistory<C> {
+  /// Records the transitions of the entities that have a `StatusHistory<C>`.
+  #[must_use]
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Records the transitions of every entity with a `C`, giving each a `StatusHistory<C>` if it has none.
+  #[must_use]
+  pub fn for_all_entities(mut self) -> Self {
+    self.all_entities = true;
+    self
+  }
+}
+
+/// A system that appends the transitions of `C` since it last ran to the entities' histories, giving entities without
+/// one a history if `all_entities` is set.
+fn record_status_history<C: Component + Copy + PartialEq>(
+  all_entities: bool,
+  timeline: Res<Timeline>,
+  mut transitions: StatusTransitions<C>,
+  mut histories: Query<&mut StatusHistory<C>>,
+  mut commands: Commands,
+) {
+  let now = timeline.now();
+  for transition in transitions.iter() {
+    if let Ok(mut history) = histories.get_mut(transition.entity) {
+      history.record(now, transition.to);
+    } else if all_entities {
+      let mut history = StatusHistory::new();
+      history.record(now, transition.to);
+      commands.entity(transition.entity).try_insert(history);
+    }
+  }
+}
+
+impl<C: Component + Copy + PartialEq> Module for RecordStatusHistory<C> {
+  fn initialize_with_world(self, _world: &mut World) -> Option<SystemConfigs> {
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module RecordStatusHistory");
+
+    let all_entities = self.all_entities;
+    Some(
+      (move |timeline: Res<Timeline>,
+             transitions: StatusTransitions<C>,
+             histories: Query<&mut StatusHistory<C>>,
+             commands: Commands| {
+        record_status_history(all_entities, timeline, transitions, histories, commands)
+      }).in_set(ExecutionPhase::Last)
+    )
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use crate::{
+    model::Model,
+    timeline_event::EventCommand
+  };
+  use super::*;
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+  enum Status {
+    Susceptible,
+    Infected,
+    Recovered,
+  }
+
+  /// A model with two people who are infected at time 1 and recover at times 3.5 and 4, the first of whom has a
+  /// `StatusHistory`.
+  fn model(recording: RecordStatusHistory<Status>) -> (Model, Entity, Entity) {
+    let mut model = Model::with_random_seed(1);
+    model.add_module(recording);
+    let recorded = model.world_mut().spawn((Status::Susceptible, StatusHistory::<Status>::new())).id();
+    let unrecorded = model.world_mut().spawn(Status::Susceptible).id();
+    let mut timeline = model.world_mut().resource_mut::<Timeline>();
+    for (time, person, status) in [
+      (1.0, recorded, Status::Infected),
+      (1.0, unrecorded, Status::Infected),
+      (3.5, recorded, Status::Recovered),
+      (4.0, unrecorded, Status::Recovered),
+    ] {
+      let command = EventCommand::infallible(move |world| *world.get_mut::<Status>(person).unwrap() = status);
+      timeline.schedule_at(time, command, "transition").unwrap();
+    }
+    (model, recorded, unrecorded)
+  }
+
+  #[test]
+  fn test_status_history() {
+    let (mut model, recorded, unrecorded) = model(RecordStatusHistory::new());
+    model.run();
+
+    let history = model.world().get::<StatusHistory<Status>>(recorded).unwrap();
+    // The first iteration of the event loop is at time 1, by when the person is already infected, so their initial
+    // status is never seen.
+    assert_eq!(history.transitions(), [(OrderedFloat(1.0), Status::Infected), (OrderedFloat(3.5), Status::Recovered)]);
+    assert_eq!(history.current(), Some(Status::Recovered));
+    assert_eq!(history.entered_at(Status::Infected), Some(OrderedFloat(1.0)));
+    assert_eq!(history.sojourn(Status::Infected), Some(Duration(2.5)));
+    assert_eq!(history.sojourn(Status::Recovered), None);
+    assert!(model.world().get::<StatusHistory<Status>>(unrecorded).is_none());
+  }
+
+  #[test]
+  fn test_status_history_for_all_entities() {
+    let (mut model, recorded, unrecorded) = model(RecordStatusHistory::new().for_all_entities());
+    model.run();
+
+    let sojourn = |person| model.world().get::<StatusHistory<Status>>(person).unwrap().sojourn(Status::Infected);
+    assert_eq!(sojourn(recorded), Some(Duration(2.5)));
+    assert_eq!(sojourn(unrecorded), Some(Duration(3.0)));
+  }
+}
@@ -0,0 +1,107 @@
+/*!
+
+Bevy ECS counts spawning an entity, and any mutable access to a component, as a change to the component, so a
+`Changed<C>` query cannot tell a genuine change of status from an initial spawn or a write of the same value.
+`StatusTransitions<C>` is a system parameter that remembers the last value of `C` it saw for each entity and turns
+change detection into clean `StatusTransition`s with both the old and new values:
+
+ - `StatusTransitions::changes` yields only genuine changes, where the old value differs from the new one.
+ - `StatusTransitions::iter` also yields the first value seen for an entity, with `from: None`. This is for models in
+   which spawning an entity is itself a transition, e.g. when people are only spawned once they are infected.
+
+Either method must be called every time the system runs, or transitions in between are missed. The memory is per
+system, so every system using `StatusTransitions<C>` sees every transition. Entries for entities that lose the
+component (or are despawned) are dropped.
+
+*/
+
+use std::collections::HashMap;
+
+use bevy_ecs::{
+  prelude::*,
+  system::SystemParam
+};
+
+/// A change of an entity's `C` component.
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub struct StatusTransition<C> {
+  pub entity: Entity,
+  /// The previous value, or `None` if this is the first value seen for the entity.
+  pub from  : Option<C>,
+  pub to    : C,
+}
+
+/// A system parameter yielding the transitions of the `C` component since the system last ran.
+#[derive(SystemParam)]
+pub struct StatusTransitions<'w, 's, C: Component + Copy + PartialEq> {
+  query   : Query<'w, 's, (Entity, &'static C), Changed<C>>,
+  removed : RemovedComponents<'w, 's, C>,
+  previous: Local<'s, HashMap<Entity, C>>,
+}
+
+impl<C: Component + Copy + PartialEq> StatusTransitions<'_, '_, C> {
+  /// Every transition, including the first value seen for an entity. No-op writes are skipped.
+  pub fn iter(&mut self) -> impl Iterator<Item = StatusTransition<C>> + '_ {
+    for entity in self.removed.read() {
+      self.previous.remove(&entity);
+    }
+
+    let previous = &mut *self.previous;
+    self.query.iter().filter_map(move |(entity, to)| {
+      let from = previous.insert(entity, *to);
+      (from != Some(*to)).then_some(StatusTransition{ entity, from, to: *to })
+    })
+  }
+
+  /// The genuine changes, skipping the first value seen for an entity.
+  pub fn changes(&mut self) -> impl Iterator<Item = StatusTransition<C>> + '_ {
+    self.iter().filter(|transition| transition.from.is_some())
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+  enum Status {
+    Infected,
+    Recovered,
+  }
+
+  #[derive(Resource, Default)]
+  struct Seen {
+    all    : Vec<StatusTransition<Status>>,
+    changes: Vec<StatusTransition<Status>>,
+  }
+
+  fn record_all(mut transitions: StatusTransitions<Status>, mut seen: ResMut<Seen>) {
+    seen.all.extend(transitions.iter());
+  }
+
+  fn record_changes(mut transitions: StatusTransitions<Status>, mut seen: ResMut<Seen>) {
+    seen.changes.extend(transitions.changes());
+  }
+
+  #[test]
+  fn test_status_transitions() {
+    let mut world = World::default();
+    world.init_resource::<Seen>();
+    let mut schedule = Schedule::default();
+    schedule.add_systems((record_all, record_changes));
+
+    let entity = world.spawn(Status::Infected).id();
+    schedule.run(&mut world);
+    // A no-op write.
+    *world.get_mut::<Status>(entity).unwrap() = Status::Infected;
+    schedule.run(&mut world);
+    *world.get_mut::<Status>(entity).unwrap() = Status::Recovered;
+    schedule.run(&mut world);
+
+    let seen = world.resource::<Seen>();
+    let recovery = StatusTransition{ entity, from: Some(Status::Infected), to: Status::Recovered };
+    assert_eq!(seen.all, vec![StatusTransition{ entity, from: None, to: Status::Infected }, recovery]);
+    assert_eq!(seen.changes, vec![recovery]);
+  }
+}
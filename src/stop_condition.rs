@@ -0,0 +1,222 @@
+/*!
+
+Stop conditions centralize the decision of when a simulation is done. A stop condition is a predicate on the `World`
+that is evaluated once per iteration of the event loop, in the `Last` phase. As soon as any stop condition holds, the
+model is marked `ModelControl::Finished`.
+
+Conditions are added with `Model::add_stop_condition` and compose by disjunction: the model stops if _any_ condition
+is true. Modules can still set `ModelControl` themselves; stop conditions just make the common case explicit instead of
+burying it in an unrelated system.
+
+`Model::stop_after_infections` stops the model after a fixed number of infections, whatever the time, e.g. to study
+outbreak sizes conditional on a cutoff. The `InfectionCap` it adds counts the transitions of a status component into
+its infected value, including entities that are first seen already infected, like initial infections. Once the count
+reaches the cap, the model finishes before the `Timeline` pops its next event, but only after every event scheduled for
+the current time has run, so the state is consistent at that time. The model therefore runs exactly to the cap unless
+other infections happen at the same time as the last one, in which case they count too.
+
+*/
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+
+use crate::{
+  model::{ExecutionPhase, ModelControl},
+  module::Module,
+  status_transition::StatusTransitions,
+  timeline::{Timeline, TimelineSystem}
+};
+
+/// A predicate on the `World` that says whether the simulation should stop.
+pub type StopCondition = Box<dyn Fn(&World) -> bool + Send + Sync>;
+
+/// The stop conditions of a model. The model stops if any of them is true.
+#[derive(Resource, Default)]
+pub struct StopConditions {
+  conditions: Vec<StopCondition>,
+}
+
+impl StopConditions {
+  pub fn push(&mut self, condition: impl Fn(&World) -> bool + Send + Sync + 'static) {
+    self.conditions.push(Box::new(condition));
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.conditions.is_empty()
+  }
+
+  /// Whether any condition is true of `world`.
+  pub fn any(&self, world: &World) -> bool {
+    self.conditions.iter().any(|condition| condition(world))
+  }
+}
+
+impl Module for StopConditions {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module StopConditions");
+
+    Some(
+      check_stop_conditions
+          .run_if(|stop_conditions: Res<StopConditions>| !stop_conditions.is_empty())
+          .in_set(ExecutionPhase::Last)
+    )
+  }
+}
+
+/// The `System` for the `StopConditions` module. It finishes a running model if any stop condition is true.
+///
+/// It only reads the world, so that it can run alongside other systems that do, and finishes the model with a command,
+/// which is applied before the run loop looks at the `ModelControl`. The command checks again that the model is still
+/// running, so that it does not overwrite an abort or a pause requested by another system in the same iteration.
+fn check_stop_conditions(world: &World, mut commands: Commands) {
+  let should_stop = world.resource::<StopConditions>().any(world);
+
+  if should_stop && *world.resource::<ModelControl>() == ModelControl::Running {
+    commands.queue(|world: &mut World| {
+      let mut model_control = world.resource_mut::<ModelControl>();
+      if *model_control == ModelControl::Running {
+        #[cfg(feature = "print_messages")]
+        println!("Stop condition met. Requesting ModelControl::Finished");
+        *model_control = ModelControl::Finished;
+      }
+    });
+  }
+}
+
+/// Finishes the model once there have been `cap` transitions of `C` into the infected value. See the module
+/// documentation.
+#[derive(Resource, Copy, Clone, Debug)]
+pub struct InfectionCap<C> {
+  infected: C,
+  cap     : usize,
+  count   : usize,
+}
+
+impl<C: Component + Copy + PartialEq> InfectionCap<C> {
+  pub fn new(infected: C, cap: usize) -> Self {
+    InfectionCap{ infected, cap, count: 0 }
+  }
+
+  /// The number of infections counted so far.
+  #[must_use]
+  pub fn count(&self) -> usize {
+    self.count
+  }
+}
+
+impl<C: Component + Copy + PartialEq> Module for InfectionCap<C> {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module InfectionCap");
+
+    // Between the `First` phase, in which modules may schedule events for the current time, and the next pop.
+    Some(check_infection_cap::<C>.after(ExecutionPhase::First).before(TimelineSystem))
+  }
+}
+
+/// The `System` for the `InfectionCap` module. It counts new infections and finishes the model once the cap is reached
+/// and no more events are scheduled for the current time.
+fn check_infection_cap<C: Component + Copy + PartialEq>(
+  mut infection_cap: ResMut<InfectionCap<C>>,
+  mut transitions: StatusTransitions<C>,
+  mut timeline: ResMut<Timeline>,
+  mut model_control: ResMut<ModelControl>,
+) {
+  let infected = infection_cap.infected;
+  infection_cap.count += transitions.iter().filter(|transition| transition.to == infected).count();
+
+  let now = timeline.now();
+  if infection_cap.count >= infection_cap.cap
+      && *model_control == ModelControl::Running
+      && timeline.next_time().is_none_or(|next| next > now)
+  {
+    #[cfg(feature = "print_messages")]
+    println!("Infection cap of {} reached. Requesting ModelControl::Finished", infection_cap.cap);
+    *model_control = ModelControl::Finished;
+    timeline.hold_next_event();
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use crate::{
+    model::{AbortReason, Model},
+    timeline_event::{Event, EventCommand}
+  };
+  use super::*;
+
+  #[derive(Component, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+  enum Status {
+    Susceptible,
+    Infected,
+  }
+
+  /// A model in which one person is infected at each of `times`, capped at `cap` infections.
+  fn capped_model(times: &[f64], cap: usize) -> Model {
+    let mut model = Model::with_random_seed(1);
+    for &time in times {
+      let person = model.world_mut().spawn(Status::Susceptible).id();
+      model.world_mut().resource_mut::<Timeline>().push(Event{
+        time: OrderedFloat(time),
+        command: EventCommand::infallible(move |world: &mut World| {
+          *world.get_mut::<Status>(person).unwrap() = Status::Infected;
+        }),
+        entity: Some(person),
+        label: "infection",
+      }).unwrap();
+    }
+    model.stop_after_infections(Status::Infected, cap);
+    model
+  }
+
+  fn infected(model: &mut Model) -> usize {
+    model.count_by::<Status>().get(&Status::Infected).copied().unwrap_or(0)
+  }
+
+  #[test]
+  fn test_stop_after_infections() {
+    let mut model = capped_model(&[1.0, 2.0, 3.0, 4.0, 5.0], 3);
+    model.run();
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Finished);
+    assert_eq!(model.world().resource::<InfectionCap<Status>>().count(), 3);
+    assert_eq!(infected(&mut model), 3);
+    assert_eq!(model.world().resource::<Timeline>().now(), 3.0);
+    assert_eq!(model.world().resource::<Timeline>().events_run(), 3);
+  }
+
+  #[test]
+  fn test_infections_at_the_cutoff_time_complete() {
+    let mut model = capped_model(&[1.0, 2.0, 2.0, 3.0], 2);
+    model.run();
+    assert_eq!(infected(&mut model), 3);
+    assert_eq!(model.world().resource::<InfectionCap<Status>>().count(), 3);
+    assert_eq!(model.world().resource::<Timeline>().now(), 2.0);
+  }
+
+  #[test]
+  fn test_stop_condition_does_not_overwrite_an_abort() {
+    let mut model = Model::with_random_seed(1);
+    model.world_mut().resource_mut::<Timeline>().schedule_at(1.0, EventCommand::infallible(|_| {}), "event").unwrap();
+    model.add_stop_condition(|_| true);
+    // Aborts in the same iteration in which the stop condition is met, after the condition is checked but before the
+    // command that finishes the model is applied.
+    model.add_systems(
+      (|mut model_control: ResMut<ModelControl>, mut commands: Commands| {
+        *model_control = ModelControl::Aborted;
+        commands.insert_resource(AbortReason("test".to_string()));
+      }).in_set(ExecutionPhase::Last).after_ignore_deferred(check_stop_conditions)
+    );
+    model.run();
+    assert_eq!(*model.world().resource::<ModelControl>(), ModelControl::Aborted);
+    assert_eq!(model.abort_reason(), Some("test"));
+  }
+}
@@ -0,0 +1,125 @@
+/*!
+
+Helpers for testing a system, like a reporter or a statistics system, in isolation: at chosen times, without building
+an event schedule that happens to reach them. The pattern is a manual clock:
+
+ 1. Build a model with `model_at`, whose `Timeline` starts at the given time, and add the modules and systems under
+    test. A reporter can write to a `MemorySink` instead of a file with `add_memory_reporter`.
+ 2. Set up the world, e.g. spawn the entities the systems look at.
+ 3. Advance the clock with `tick_at`, which sets the `Timeline`'s time and runs every system once with
+    `Model::tick_systems_only`, without running an event.
+ 4. Check what the systems did, e.g. read the report so far with `flushed_report`.
+
+Steps 2 to 4 can repeat. The clock is set, not advanced by events, so the scheduled events are never run and the time
+can be set to anything, even backwards, which a real run never does.
+
+The module is compiled for the crate's own tests and, for the examples and other crates, with the `test-util` feature.
+See the tests below for an example.
+
+*/
+
+use std::{
+  io::{self, Write},
+  sync::{Arc, Mutex}
+};
+
+use crate::{
+  model::Model,
+  random::DEFAULT_SEED,
+  report::Reporter,
+  timeline::{Time, Timeline}
+};
+
+/// A shared in-memory buffer a `Reporter` can write to. Clones share the same buffer.
+#[derive(Clone, Default, Debug)]
+pub struct MemorySink(Arc<Mutex<Vec<u8>>>);
+
+impl MemorySink {
+  /// Everything written so far, which for a reporter is only what it has flushed.
+  #[must_use]
+  pub fn contents(&self) -> String {
+    String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+  }
+}
+
+impl Write for MemorySink {
+  fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+    self.0.lock().unwrap().extend_from_slice(buf);
+    Ok(buf.len())
+  }
+
+  fn flush(&mut self) -> io::Result<()> {
+    Ok(())
+  }
+}
+
+/// A model seeded with `DEFAULT_SEED` whose `Timeline` is at `now`.
+#[must_use]
+pub fn model_at(now: impl Into<Time>) -> Model {
+  let mut model = Model::with_random_seed(DEFAULT_SEED);
+  model.world_mut().resource_mut::<Timeline>().set_now(now);
+  model
+}
+
+/// Sets the `Timeline`'s time to `now` and runs every system once, without running an event.
+pub fn tick_at(model: &mut Model, now: impl Into<Time>) {
+  model.world_mut().resource_mut::<Timeline>().set_now(now);
+  model.tick_systems_only();
+}
+
+/// Adds `reporter` to `model`, writing to the returned `MemorySink` instead of a file.
+pub fn add_memory_reporter<Marker: Send + Sync + 'static>(model: &mut Model, reporter: Reporter<Marker>) -> MemorySink {
+  let sink = MemorySink::default();
+  model.add_module(reporter.with_sink(sink.clone()));
+  sink
+}
+
+/// Flushes the `Reporter<Marker>` of `model` and returns everything written to `sink`.
+pub fn flushed_report<Marker: Send + Sync + 'static>(model: &mut Model, sink: &MemorySink) -> String {
+  model.world_mut().resource_mut::<Reporter<Marker>>().flush().expect("Failed to flush report");
+  sink.contents()
+}
+
+
+#[cfg(test)]
+mod tests {
+  use bevy_ecs::prelude::*;
+  use serde::Serialize;
+  use crate::model::ExecutionPhase;
+  use super::*;
+
+  #[derive(Component)]
+  struct Infected;
+
+  struct PrevalenceMarker;
+  type PrevalenceReporter = Reporter<PrevalenceMarker>;
+
+  #[derive(Serialize)]
+  struct PrevalenceRow {
+    time    : f64,
+    infected: usize,
+  }
+
+  /// The system under test: reports the number of infected people at every tick.
+  fn report_prevalence(mut reporter: ResMut<PrevalenceReporter>, timeline: Res<Timeline>, infected: Query<&Infected>) {
+    let row = PrevalenceRow{ time: timeline.now().0, infected: infected.iter().count() };
+    reporter.write_row(row).unwrap();
+  }
+
+  #[test]
+  fn test_manual_clock() {
+    let mut model = model_at(1.0);
+    let sink = add_memory_reporter(&mut model, PrevalenceReporter::new("prevalence".to_string()));
+    model.add_system_to_phase(report_prevalence, ExecutionPhase::Normal);
+
+    model.world_mut().spawn(Infected);
+    tick_at(&mut model, 1.0);
+    model.world_mut().spawn_batch([Infected, Infected]);
+    tick_at(&mut model, 2.5);
+
+    assert_eq!(flushed_report::<PrevalenceMarker>(&mut model, &sink), "time,infected\n1.0,1\n2.5,3\n");
+    assert_eq!(model.world().resource::<PrevalenceReporter>().output_path(), None);
+    // No event ran, so the clock stays where it was set.
+    assert_eq!(model.world().resource::<Timeline>().now(), Time::from(2.5));
+  }
+}
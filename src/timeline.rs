@@ -10,6 +10,53 @@ passed between systems, which is completely different from our use here.
 
 An `Event` is just a struct to hold a `(Time, System)` pair.
 
+Pushing to the `Timeline` requires mutable access to it, which means systems that schedule events cannot run in
+parallel with each other or with anything that reads the `Timeline`. Such systems can instead take the
+`TimelineCommands` system parameter, which, like Bevy's `Commands`, buffers the events and adds them to the `Timeline`
+at the next command flush (an `apply_deferred` sync point, or the end of the schedule run at the latest). Buffered
+events are therefore never popped in the iteration of the event loop in which they were scheduled. A buffered event
+that the `Timeline` refuses aborts the model with an `AbortReason`.
+
+The `Timeline` system runs in its own system set, `TimelineSystem`, between the `First` and `Last` phases. The current
+time jumps straight to the time of each event as it is popped, so an idle gap between events costs nothing, and each
+iteration of the event loop runs exactly one event (see `Model::enable_sparse_mode` for skipping the `Normal` phase
+when no event fires).
+
+The scheduled events are stored in a `TimelineBackend`, a binary heap unless the model chooses another with
+`Timeline::with_backend` or `Timeline::set_backend`; see the `timeline_backend` module.
+
+An event can also wait on a named milestone instead of a time, with `Timeline::on_milestone`; see the `milestones`
+module.
+
+Times are `f64`s wherever they are taken as `impl Into<Time>`, such as `Timeline::schedule_at`, and
+`Timeline::schedule_in` schedules a command a plain `f64` (or `Duration`) after the current time. `Time::new`, from the
+`TimeExt` trait, constructs a `Time` from an `f64` where a `Time` is needed. A time that is `NaN` is always a bug, even
+though `OrderedFloat` orders it after every other time: `Timeline::push` refuses to schedule an event at `NaN`, and
+`Time::new` checks for it in debug builds. Times are in the model's unit of time, a day by convention; see the `units`
+module for giving durations in hours or weeks.
+
+An event that keeps rescheduling itself with no delay, or any other bug that schedules events at the same time
+indefinitely, would make the event loop spin forever without the time advancing. As a safeguard, the `Timeline`
+counts the consecutive events that ran at the same time, and once `Timeline::max_stalled_events` of them have run, the
+next event at that time is not run and the model aborts with an `AbortReason` saying that time made no progress. The
+limit, `DEFAULT_MAX_STALLED_EVENTS` by default, is far more than any reasonable model runs at one time, and can be
+changed with `Timeline::set_max_stalled_events`.
+
+A bug that makes events schedule more events than they consume, e.g. an event that reschedules itself twice, would grow
+the scheduled events without bound until the process runs out of memory, which matters most for services that embed a
+model. Each scheduled event takes about the same memory, apart from what its command captures, so the `Timeline` can
+be bounded by the number of events: with `Timeline::set_max_pending_events`, `Timeline::push` refuses to schedule an
+event while that many are pending, returning an error, and the `Timeline` system aborts the model with an
+`AbortReason` at its next run, whether or not the code that scheduled the event handled the error. The `Timeline` is
+unbounded by default.
+
+Every event can be logged as it is popped, for a trace of what happened when, with `Timeline::enable_event_log`; see the
+`event_log` module.
+
+Scheduling an event in the past is always a bug: the event would be popped immediately, out of causal order. What
+`Timeline::push` does about it is governed by the timeline's `PastEventPolicy`. Whatever the policy, an event is never
+scheduled before the current time, so time never goes backward.
+
 */
 
 use std::collections::BinaryHeap;
@@ -19,34 +66,129 @@ use bevy_ecs::{
   prelude::*,
   // system::ExclusiveSystemParamFunction
 };
-use bevy_ecs::schedule::SystemConfigs;
+use bevy_ecs::{
+  schedule::SystemConfigs,
+  system::{Deferred, SystemBuffer, SystemMeta}
+};
 use crate::{
-  model::{ExecutionPhase, ModelControl},
+  errors::IxaError,
+  event_log::EventLog,
+  model::{AbortReason, ModelControl},
   module::Module,
-  timeline_event::Event
+  timeline_backend::TimelineBackend,
+  timeline_event::{Event, EventCommand},
+  units::Duration
 };
 
+/// The number of consecutive events at the same time after which the model aborts unless configured otherwise. See the
+/// module documentation.
+pub const DEFAULT_MAX_STALLED_EVENTS: usize = 10_000_000;
+
 /// `Time` is just an alias for a hashable totally ordered float.
 pub type Time = OrderedFloat<f64>;
 
-/// `Timeline` is a thin wrapper around `BinaryHeap<Event>` that keeps track of the "current time" as events are popped.
+/// Constructs a `Time`, which, as an alias for a foreign type, cannot have inherent constructors of its own.
+pub trait TimeExt {
+  /// The time `time`, which must not be `NaN`. This is checked in debug builds.
+  fn new(time: f64) -> Self;
+}
+
+impl TimeExt for Time {
+  #[inline(always)]
+  fn new(time: f64) -> Self {
+    debug_assert!(!time.is_nan(), "a time is NaN");
+    OrderedFloat(time)
+  }
+}
+
+/// What `Timeline::push` does with an event scheduled before the current time.
+#[derive(Copy, Clone, Eq, PartialEq, Default, Debug, Hash)]
+pub enum PastEventPolicy {
+  /// Print a warning and schedule the event at the current time instead, so that time never goes backward.
+  #[default]
+  Warn,
+  /// Refuse to schedule the event and return an error.
+  Error,
+  /// Schedule the event at the current time instead.
+  Clamp,
+}
+
+/// An event waiting for a milestone. See `Timeline::on_milestone`.
+pub struct DeferredEvent {
+  pub milestone: String,
+  pub delay    : Duration,
+  pub command  : EventCommand,
+  pub label    : &'static str,
+}
+
+/// The system set of the system that pops and runs the next event.
+#[derive(SystemSet, PartialEq, Eq, Clone, Copy, Debug, Hash)]
+pub struct TimelineSystem;
+
+/// `Timeline` is a thin wrapper around a `TimelineBackend`, by default a `BinaryHeap<Event>`, that keeps track of the
+/// "current time" as events are popped.
 #[derive(Resource)]
 pub struct Timeline {
   now        : Time,
-  event_queue: BinaryHeap<Event>,
+  event_queue: Box<dyn TimelineBackend>,
+  past_event_policy: PastEventPolicy,
+  /// The number of events that were scheduled before the current time, regardless of policy.
+  past_event_count : usize,
+  /// The number of events popped so far.
+  events_run       : usize,
+  /// The number of consecutive events popped at the current time, after the first.
+  stalled_events   : usize,
+  /// See `Timeline::set_max_stalled_events`.
+  max_stalled_events: usize,
+  /// See `Timeline::set_max_pending_events`.
+  max_pending_events: Option<usize>,
+  /// The number of events `push` refused because the timeline was full, since the `Timeline` system last ran.
+  refused_events   : usize,
+  /// Whether the `Timeline` system ran an event in the current iteration of the event loop.
+  event_fired      : bool,
+  /// Whether the `Timeline` system should skip its next event. See `Timeline::hold_next_event`.
+  hold_next_event  : bool,
+  /// Events waiting for a milestone. See `Timeline::on_milestone`.
+  deferred         : Vec<DeferredEvent>,
+  /// The log of popped events, if it is on. See `Timeline::enable_event_log`.
+  event_log        : Option<EventLog>,
 }
 
 impl Default for Timeline {
   fn default() -> Self {
-    Self {
-      now        : Time::default(),
-      event_queue: BinaryHeap::new(),
-    }
+    Self::with_backend(BinaryHeap::new())
   }
 }
 
-
 impl Timeline {
+  /// A `Timeline` that stores its events in `backend`. See the `timeline_backend` module.
+  pub fn with_backend(backend: impl TimelineBackend) -> Self {
+    Timeline{
+      now: Time::default(),
+      event_queue: Box::new(backend),
+      past_event_policy: PastEventPolicy::default(),
+      past_event_count: 0,
+      events_run: 0,
+      stalled_events: 0,
+      max_stalled_events: DEFAULT_MAX_STALLED_EVENTS,
+      max_pending_events: None,
+      refused_events: 0,
+      event_fired: false,
+      hold_next_event: false,
+      deferred: Vec::new(),
+      event_log: None,
+    }
+  }
+
+  /// Moves the scheduled events into `backend` and stores events there from now on. See the `timeline_backend`
+  /// module.
+  pub fn set_backend(&mut self, backend: impl TimelineBackend) {
+    let mut backend: Box<dyn TimelineBackend> = Box::new(backend);
+    while let Some(event) = self.event_queue.pop() {
+      backend.push(event);
+    }
+    self.event_queue = backend;
+  }
 
   #[must_use]
   #[inline(always)]
@@ -57,14 +199,205 @@ impl Timeline {
   // We might not want to allow this.
   #[allow(unused)]
   #[inline(always)]
-  pub fn set_now(&mut self, new_time: Time) -> Time {
+  pub fn set_now(&mut self, new_time: impl Into<Time>) -> Time {
+    let new_time = new_time.into();
     self.now = new_time;
     new_time
   }
 
+  #[must_use]
+  pub fn past_event_policy(&self) -> PastEventPolicy {
+    self.past_event_policy
+  }
+
+  pub fn set_past_event_policy(&mut self, policy: PastEventPolicy) {
+    self.past_event_policy = policy;
+  }
+
+  /// The number of consecutive events at the same time after which the model aborts. See the module documentation.
+  #[must_use]
+  pub fn max_stalled_events(&self) -> usize {
+    self.max_stalled_events
+  }
+
+  pub fn set_max_stalled_events(&mut self, max_stalled_events: usize) {
+    self.max_stalled_events = max_stalled_events;
+  }
+
+  /// The most events that can be pending at once, if the `Timeline` is bounded. See the module documentation.
+  #[must_use]
+  pub fn max_pending_events(&self) -> Option<usize> {
+    self.max_pending_events
+  }
+
+  /// Bounds the number of pending events, or unbounds it with `None`. Events already pending are kept, but no more
+  /// can be scheduled while there are `max_pending_events` or more. See the module documentation.
+  pub fn set_max_pending_events(&mut self, max_pending_events: Option<usize>) {
+    self.max_pending_events = max_pending_events;
+  }
+
+  /// The number of times an event was scheduled before the current time, whatever the `PastEventPolicy` did with it.
+  #[must_use]
+  pub fn past_event_count(&self) -> usize {
+    self.past_event_count
+  }
+
+  /// Schedules `event`. An event scheduled before `now` is handled according to the `PastEventPolicy`; only
+  /// `PastEventPolicy::Error`, an event at `NaN`, and a full timeline (see `Timeline::set_max_pending_events`) make
+  /// this fail.
   #[inline(always)]
-  pub fn push(&mut self, event: Event) {
-    self.event_queue.push(event)
+  pub fn push(&mut self, mut event: Event) -> Result<(), IxaError> {
+    if event.time.is_nan() {
+      return Err(IxaError::IxaError(format!("event \"{}\" is scheduled at NaN", event.label)));
+    }
+    if let Some(max_pending_events) = self.max_pending_events
+        && self.event_queue.len() >= max_pending_events
+    {
+      self.refused_events += 1;
+      return Err(IxaError::IxaError(format!(
+        "event \"{}\" was not scheduled because the timeline is full, with {} events pending",
+        event.label,
+        self.event_queue.len()
+      )));
+    }
+    if event.time < self.now {
+      self.past_event_count += 1;
+      match self.past_event_policy {
+        PastEventPolicy::Warn => {
+          #[cfg(feature = "print_messages")]
+          println!(
+            "Warning: event scheduled at {} is before the current time {}; scheduling it at the current time",
+            event.time,
+            self.now
+          );
+          event.time = self.now;
+        }
+        PastEventPolicy::Error => {
+          return Err(IxaError::IxaError(format!(
+            "event scheduled at {} is before the current time {}",
+            event.time,
+            self.now
+          )));
+        }
+        PastEventPolicy::Clamp => {
+          event.time = self.now;
+        }
+      }
+    }
+
+    self.event_queue.push(event);
+    Ok(())
+  }
+
+  /// Schedules `command` for `delay` after the time `milestone` is reached, once it is. If the milestone is never
+  /// reached, the command is never run. See the `milestones` module.
+  pub fn on_milestone(&mut self, milestone: &str, delay: Duration, command: EventCommand, label: &'static str) {
+    self.deferred.push(DeferredEvent{ milestone: milestone.to_string(), delay, command, label });
+  }
+
+  /// Whether any event is waiting for a milestone.
+  #[must_use]
+  pub fn has_deferred(&self) -> bool {
+    !self.deferred.is_empty()
+  }
+
+  /// Removes the events waiting for a milestone that `reached_at` says was reached, returning each with the time its
+  /// milestone was reached, in the order they were deferred.
+  pub(crate) fn take_deferred(
+    &mut self,
+    reached_at: impl Fn(&str) -> Option<Time>,
+  ) -> Vec<(Time, DeferredEvent)> {
+    let mut taken = Vec::new();
+    let mut waiting = Vec::with_capacity(self.deferred.len());
+    for deferred in self.deferred.drain(..) {
+      match reached_at(&deferred.milestone) {
+        Some(time) => taken.push((time, deferred)),
+        None => waiting.push(deferred),
+      }
+    }
+    self.deferred = waiting;
+    taken
+  }
+
+  /// Schedules `command` at `time`, which can be a plain `f64`. See `Timeline::push`.
+  pub fn schedule_at(
+    &mut self,
+    time: impl Into<Time>,
+    command: EventCommand,
+    label: &'static str
+  ) -> Result<(), IxaError> {
+    self.push(Event{ time: time.into(), command, entity: None, label })
+  }
+
+  /// Schedules `command` at `delay` after the current time. `delay` can be a plain `f64`. See `Timeline::push`.
+  pub fn schedule_in(
+    &mut self,
+    delay: impl Into<Duration>,
+    command: EventCommand,
+    label: &'static str
+  ) -> Result<(), IxaError> {
+    self.schedule_at(self.now + delay.into(), command, label)
+  }
+
+  /// The time, entity, and label of every scheduled event, in time order. The events are not run.
+  pub fn pending(&self) -> impl Iterator<Item = (Time, Option<Entity>, &str)> {
+    let mut pending: Vec<&Event> = self.event_queue.iter().collect();
+    pending.sort_by_key(|event| event.time);
+    pending.into_iter().map(|event| (event.time, event.entity, event.label))
+  }
+
+  /// Removes every scheduled event whose `entity` is `entity`, returning how many were removed. Call this before
+  /// despawning an entity so that no event acts on it afterward.
+  pub fn cancel_events_for(&mut self, entity: Entity) -> usize {
+    let scheduled = self.event_queue.len();
+    self.event_queue.retain(&mut |event| event.entity != Some(entity));
+    scheduled - self.event_queue.len()
+  }
+
+  /// Keeps the `Timeline` system from running an event the next time it runs, e.g. because the model is stopping
+  /// before it. Only a system ordered before `TimelineSystem` can reliably hold the event of the current iteration.
+  pub fn hold_next_event(&mut self) {
+    self.hold_next_event = true;
+  }
+
+  /// The time of the next scheduled event, if any.
+  #[must_use]
+  pub fn next_time(&self) -> Option<Time> {
+    self.event_queue.peek().map(|event| event.time)
+  }
+
+  /// The number of events scheduled but not yet run.
+  #[must_use]
+  pub fn len(&self) -> usize {
+    self.event_queue.len()
+  }
+
+  #[must_use]
+  pub fn is_empty(&self) -> bool {
+    self.event_queue.is_empty()
+  }
+
+  /// Logs every event popped from now on, keeping the `capacity` most recent. Replaces the log if it is already on. See
+  /// the `event_log` module.
+  pub fn enable_event_log(&mut self, capacity: usize) {
+    self.event_log = Some(EventLog::new(capacity));
+  }
+
+  /// Stops logging events and returns the log, if it was on.
+  pub fn disable_event_log(&mut self) -> Option<EventLog> {
+    self.event_log.take()
+  }
+
+  /// The log of popped events, if it is on.
+  #[must_use]
+  pub fn event_log(&self) -> Option<&EventLog> {
+    self.event_log.as_ref()
+  }
+
+  /// The number of events popped (and so run) so far.
+  #[must_use]
+  pub fn events_run(&self) -> usize {
+    self.events_run
   }
 
   /// Pop's the next event, updating `self.now` to the new time associated to the event.
@@ -72,40 +405,137 @@ impl Timeline {
   pub fn pop(&mut self) -> Option<Event> {
     let popped = self.event_queue.pop();
     if let Some(Event { time, .. }) = &popped {
-      self.now = time.clone();
+      if *time == self.now && self.events_run > 0 {
+        self.stalled_events += 1;
+      } else {
+        self.stalled_events = 0;
+      }
+      self.now = *time;
+      self.events_run += 1;
+    }
+    if let (Some(event_log), Some(event)) = (&mut self.event_log, &popped) {
+      event_log.record(event);
     }
 
     popped
   }
 }
 
+/// A system parameter for scheduling events from systems that do not have mutable access to the `Timeline`. Events
+/// are added to the `Timeline` at the next command flush.
+pub type TimelineCommands<'s> = Deferred<'s, TimelineBuffer>;
+
+/// The buffer behind `TimelineCommands`.
+#[derive(Default)]
+pub struct TimelineBuffer {
+  events: Vec<Event>,
+}
+
+impl TimelineBuffer {
+  /// Queues `event` to be pushed onto the `Timeline` at the next command flush.
+  pub fn push(&mut self, event: Event) {
+    self.events.push(event);
+  }
+}
+
+impl SystemBuffer for TimelineBuffer {
+  fn apply(&mut self, _system_meta: &SystemMeta, world: &mut World) {
+    if self.events.is_empty() {
+      return;
+    }
+
+    let mut timeline = world.resource_mut::<Timeline>();
+    let mut first_error = None;
+    for event in self.events.drain(..) {
+      if let Err(error) = timeline.push(event) {
+        first_error.get_or_insert(error);
+      }
+    }
+
+    if let Some(error) = first_error {
+      AbortReason::abort(world, format!("a buffered event could not be scheduled: {error}"));
+    }
+  }
+}
+
 impl Module for Timeline {
   fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
     #[cfg(feature = "print_messages")]
     println!("Initialized module Timeline");
 
     // Insert the Timeline resource into the World
-    world.insert_resource(Timeline::default());
+    world.insert_resource(self);
 
     // There is only one system in our implementation, namely the one that runs (at most) a single event.
-    Some(run_timeline_event.in_set(ExecutionPhase::Normal))
+    Some(run_timeline_event.in_set(TimelineSystem))
   }
 }
 
+/// A run condition that is true if the `Timeline` system ran an event in the current iteration of the event loop.
+/// Systems using it must run after `TimelineSystem`.
+pub fn event_fired(timeline: Res<Timeline>) -> bool {
+  timeline.event_fired
+}
+
 /// The `System` for the `Timeline` module. It runs a scheduled event, if one exists.
 fn run_timeline_event(
   mut timeline: ResMut<Timeline>,
   mut model_control: ResMut<ModelControl>,
   mut commands: Commands,
 ) {
-  if let Some(Event{command, ..}) = timeline.pop() {
-    commands.queue(command);
+  if timeline.refused_events > 0 {
+    let reason = format!(
+      "the timeline is full: {} event(s) could not be scheduled with {} pending, the most allowed",
+      timeline.refused_events,
+      timeline.max_pending_events.unwrap_or_default()
+    );
+    #[cfg(feature = "print_messages")]
+    println!("{reason}. Aborting.");
+    commands.insert_resource(AbortReason(reason));
+    *model_control = ModelControl::Aborted;
+    timeline.refused_events = 0;
+    timeline.event_fired = false;
+    return;
+  }
+  if timeline.hold_next_event {
+    timeline.hold_next_event = false;
+    timeline.event_fired = false;
+    return;
   }
-  else {
-    // In this model this only happens if there is a bug, which nobody on our time would ever write.
+  let popped = timeline.pop();
+  timeline.event_fired = popped.is_some();
+  if timeline.stalled_events > timeline.max_stalled_events {
+    let reason = format!(
+      "no time progress: {} consecutive events ran at time {}",
+      timeline.max_stalled_events + 1,
+      timeline.now
+    );
     #[cfg(feature = "print_messages")]
-    println!("Timeline empty. Requesting Abort.");
+    println!("{reason}. Aborting.");
+    commands.insert_resource(AbortReason(reason));
     *model_control = ModelControl::Aborted;
+    timeline.event_fired = false;
+    return;
+  }
+  match popped {
+    Some(Event{ command: EventCommand::Infallible(command), .. }) => commands.queue(command),
+    Some(Event{ command: EventCommand::Fallible(command), time, label, .. }) => {
+      commands.queue(move |world: &mut World| {
+        if let Err(error) = command(world) {
+          let reason = format!("event \"{label}\" at time {time} failed: {error}");
+          #[cfg(feature = "print_messages")]
+          println!("{reason}. Aborting.");
+          world.insert_resource(AbortReason(reason));
+          world.insert_resource(ModelControl::Aborted);
+        }
+      });
+    }
+    None => {
+      // In this model this only happens if there is a bug, which nobody on our time would ever write.
+      #[cfg(feature = "print_messages")]
+      println!("Timeline empty. Requesting Abort.");
+      *model_control = ModelControl::Aborted;
+    }
   }
 }
 
@@ -134,4 +564,159 @@ mod tests {
     let time = world.get_resource::<Timeline>().unwrap();
     assert_eq!(time.now(), OrderedFloat(2.0 * std::f64::consts::PI));
   }
+
+  /// A timeline at time `2.0` with the given policy, and an event scheduled at time `1.0`.
+  fn timeline_and_past_event(policy: PastEventPolicy) -> (Timeline, Event) {
+    let mut timeline = Timeline::default();
+    timeline.set_now(OrderedFloat(2.0));
+    timeline.set_past_event_policy(policy);
+    let event = Event{ time: OrderedFloat(1.0), command: EventCommand::infallible(|_| {}), entity: None, label: "" };
+    (timeline, event)
+  }
+
+  #[test]
+  fn test_past_event_warn() {
+    let (mut timeline, event) = timeline_and_past_event(PastEventPolicy::Warn);
+    assert!(timeline.push(event).is_ok());
+    assert_eq!(timeline.past_event_count(), 1);
+    // The event is clamped to the current time, so time does not go backward.
+    assert_eq!(timeline.pop().unwrap().time, OrderedFloat(2.0));
+    assert_eq!(timeline.now(), OrderedFloat(2.0));
+  }
+
+  #[test]
+  fn test_past_event_error() {
+    let (mut timeline, event) = timeline_and_past_event(PastEventPolicy::Error);
+    assert!(timeline.push(event).is_err());
+    assert!(timeline.pop().is_none());
+  }
+
+  #[test]
+  fn test_past_event_clamp() {
+    let (mut timeline, event) = timeline_and_past_event(PastEventPolicy::Clamp);
+    assert!(timeline.push(event).is_ok());
+    assert_eq!(timeline.pop().unwrap().time, OrderedFloat(2.0));
+  }
+
+  #[test]
+  fn test_timeline_commands() {
+    fn schedule_event(mut timeline_commands: TimelineCommands) {
+      let command = EventCommand::infallible(|_| {});
+      timeline_commands.push(Event{ time: OrderedFloat(3.0), command, entity: None, label: "" });
+    }
+
+    let mut world = World::default();
+    world.insert_resource(Timeline::default());
+    world.insert_resource(ModelControl::default());
+
+    let mut schedule = Schedule::default();
+    schedule.add_systems(schedule_event);
+    schedule.run(&mut world);
+
+    let mut timeline = world.resource_mut::<Timeline>();
+    assert_eq!(timeline.pop().unwrap().time, OrderedFloat(3.0));
+    assert!(timeline.pop().is_none());
+    assert_eq!(*world.resource::<ModelControl>(), ModelControl::Running);
+
+    // An event the timeline refuses aborts the model.
+    fn schedule_nan_event(mut timeline_commands: TimelineCommands) {
+      let command = EventCommand::infallible(|_| {});
+      timeline_commands.push(Event{ time: OrderedFloat(f64::NAN), command, entity: None, label: "nan" });
+    }
+    let mut schedule = Schedule::default();
+    schedule.add_systems(schedule_nan_event);
+    schedule.run(&mut world);
+    assert_eq!(*world.resource::<ModelControl>(), ModelControl::Aborted);
+    let reason = &world.resource::<AbortReason>().0;
+    assert!(reason.contains("is scheduled at NaN"), "{reason}");
+  }
+
+  #[test]
+  fn test_pending() {
+    let mut world = World::default();
+    let entity = world.spawn_empty().id();
+
+    let mut timeline = Timeline::default();
+    for (time, entity, label) in [(2.0, Some(entity), "recovery"), (1.0, None, "attempt"), (3.0, None, "")] {
+      let command = EventCommand::infallible(|_| {});
+      timeline.push(Event{ time: OrderedFloat(time), command, entity, label }).unwrap();
+    }
+
+    assert_eq!(
+      timeline.pending().collect::<Vec<_>>(),
+      vec![
+        (OrderedFloat(1.0), None, "attempt"),
+        (OrderedFloat(2.0), Some(entity), "recovery"),
+        (OrderedFloat(3.0), None, ""),
+      ]
+    );
+    // Nothing was run or removed.
+    assert_eq!(timeline.pop().unwrap().time, OrderedFloat(1.0));
+
+    assert_eq!(timeline.cancel_events_for(entity), 1);
+    assert_eq!(timeline.pending().collect::<Vec<_>>(), vec![(OrderedFloat(3.0), None, "")]);
+  }
+
+  #[test]
+  fn test_set_backend() {
+    use crate::timeline_backend::BucketQueue;
+
+    let mut timeline = Timeline::default();
+    for time in [4.0, 1.0, 2.5] {
+      let command = EventCommand::infallible(|_| {});
+      timeline.push(Event{ time: OrderedFloat(time), command, entity: None, label: "" }).unwrap();
+    }
+    timeline.set_backend(BucketQueue::new(Duration(1.0)).unwrap());
+
+    assert_eq!(timeline.len(), 3);
+    assert_eq!(timeline.next_time(), Some(OrderedFloat(1.0)));
+    let popped: Vec<Time> = std::iter::from_fn(|| timeline.pop().map(|event| event.time)).collect();
+    assert_eq!(popped, vec![OrderedFloat(1.0), OrderedFloat(2.5), OrderedFloat(4.0)]);
+    assert_eq!(timeline.now(), OrderedFloat(4.0));
+  }
+
+  #[test]
+  fn test_schedule_plain_times() {
+    let mut timeline = Timeline::default();
+    timeline.set_now(1.0);
+    timeline.schedule_at(3.0, EventCommand::infallible(|_| {}), "at").unwrap();
+    timeline.schedule_in(0.5, EventCommand::infallible(|_| {}), "in").unwrap();
+    assert_eq!(
+      timeline.pending().collect::<Vec<_>>(),
+      vec![(Time::new(1.5), None, "in"), (Time::new(3.0), None, "at")]
+    );
+
+    let Err(IxaError::IxaError(message)) = timeline.schedule_at(f64::NAN, EventCommand::infallible(|_| {}), "nan")
+    else {
+      panic!("scheduling an event at NaN should fail");
+    };
+    assert!(message.contains("nan") && message.contains("NaN"), "{message}");
+    assert_eq!(timeline.len(), 2);
+  }
+
+  #[test]
+  fn test_event_builder() {
+    let mut world = World::default();
+    let entity = world.spawn_empty().id();
+    let mut timeline = Timeline::default();
+    timeline.push(Event::builder(2.0).command(|world| { world.spawn_empty(); }).entity(entity).label("spawn").build())
+            .unwrap();
+    timeline.push(Event::builder(1.0).build()).unwrap();
+    assert_eq!(
+      timeline.pending().collect::<Vec<_>>(),
+      vec![(Time::new(1.0), None, ""), (Time::new(2.0), Some(entity), "spawn")]
+    );
+
+    // An event without a command does nothing.
+    for _ in 0..2 {
+      match timeline.pop().unwrap().command {
+        EventCommand::Infallible(command) => command(&mut world),
+        EventCommand::Fallible(_) => panic!("the commands are infallible"),
+      }
+    }
+    assert_eq!(world.entities().len(), 2);
+
+    let event = Event::builder(3.0).fallible_command(|_| Err(IxaError::IxaError("failed".to_string()))).build();
+    assert!(matches!(event.command, EventCommand::Fallible(_)));
+  }
 }
@@ -0,0 +1,259 @@
+/*!
+
+The storage of the `Timeline`'s scheduled events. A `TimelineBackend` is a min priority queue of `Event`s by time.
+The default is a `BinaryHeap`, which schedules and pops in `O(log n)` for `n` scheduled events and makes no
+assumptions about when events are scheduled. A model with a very large queue can instead choose a backend that
+matches the distribution of its event times, with `Timeline::with_backend` or `Timeline::set_backend`.
+
+`BucketQueue` is a bucket (calendar) queue for models whose events are spread over a bounded horizon. It divides time
+into buckets of a fixed width, each a small binary heap, so scheduling and popping cost `O(log b)` for the `b` events
+in a bucket, which is `O(1)` amortized when the width is chosen so that buckets hold a handful of events each: roughly
+the horizon over which events are scheduled divided by the typical number of scheduled events. Buckets are only
+created up to `BucketQueue::MAX_BUCKETS` ahead of the earliest one; events further in the future, including events at
+infinity, are kept in a binary heap of their own. Too narrow a width wastes memory on empty buckets, and too wide a
+width degrades to a single binary heap.
+
+Events scheduled at the same time are popped in an order that depends on the backend (and, for the binary heap, on
+the history of the queue), so a model whose results depend on that order gets different results with different
+backends. Each backend is deterministic on its own.
+
+Run `cargo bench --bench timeline_backends` to compare the backends on a uniform-arrival workload.
+
+*/
+
+use std::collections::{BinaryHeap, VecDeque};
+
+use crate::{
+  errors::IxaError,
+  timeline_event::Event,
+  units::Duration
+};
+
+/// The storage of the `Timeline`'s scheduled events. See the module documentation.
+pub trait TimelineBackend: Send + Sync + 'static {
+  /// Schedules `event`.
+  fn push(&mut self, event: Event);
+
+  /// Removes and returns the earliest event.
+  fn pop(&mut self) -> Option<Event>;
+
+  /// The earliest event.
+  fn peek(&self) -> Option<&Event>;
+
+  /// The number of scheduled events.
+  fn len(&self) -> usize;
+
+  fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+
+  /// Every scheduled event, in no particular order.
+  fn iter(&self) -> Box<dyn Iterator<Item = &Event> + '_>;
+
+  /// Keeps only the events for which `keep` is true.
+  fn retain(&mut self, keep: &mut dyn FnMut(&Event) -> bool);
+}
+
+impl TimelineBackend for BinaryHeap<Event> {
+  #[inline]
+  fn push(&mut self, event: Event) {
+    BinaryHeap::push(self, event);
+  }
+
+  #[inline]
+  fn pop(&mut self) -> Option<Event> {
+    BinaryHeap::pop(self)
+  }
+
+  #[inline]
+  fn peek(&self) -> Option<&Event> {
+    BinaryHeap::peek(self)
+  }
+
+  #[inline]
+  fn len(&self) -> usize {
+    BinaryHeap::len(self)
+  }
+
+  fn iter(&self) -> Box<dyn Iterator<Item = &Event> + '_> {
+    Box::new(BinaryHeap::iter(self))
+  }
+
+  fn retain(&mut self, keep: &mut dyn FnMut(&Event) -> bool) {
+    BinaryHeap::retain(self, |event| keep(event));
+  }
+}
+
+/// A bucket (calendar) queue. See the module documentation.
+pub struct BucketQueue {
+  width: f64,
+  /// `buckets[i]` holds the events whose bucket index, `floor(time / width)`, is `first + i`. Events scheduled before
+  /// the first bucket are put in it.
+  buckets: VecDeque<BinaryHeap<Event>>,
+  first: i64,
+  /// The events whose bucket is `MAX_BUCKETS` or more after the first bucket.
+  overflow: BinaryHeap<Event>,
+  len: usize,
+}
+
+impl BucketQueue {
+  /// The number of buckets ahead of the earliest that events are bucketed into.
+  pub const MAX_BUCKETS: usize = 1 << 16;
+
+  /// A bucket queue with buckets of the given `width`, which must be positive and finite.
+  pub fn new(width: Duration) -> Result<Self, IxaError> {
+    let width = width.validate("the bucket width")?;
+    Ok(BucketQueue{
+      width: width.0,
+      buckets: VecDeque::new(),
+      first: 0,
+      overflow: BinaryHeap::new(),
+      len: 0,
+    })
+  }
+
+  /// The position of the bucket of an event at `time` within `buckets`, or `None` if it belongs in the overflow.
+  fn bucket_position(&self, time: f64) -> Option<usize> {
+    let index = (time / self.width).floor();
+    if !index.is_finite() {
+      // An event at negative infinity goes in the first bucket, and one at infinity (or `NaN`) in the overflow.
+      return (index < 0.0).then_some(0);
+    }
+    // The cast saturates, and an index before the first bucket is put in it.
+    let offset = (index as i64).saturating_sub(self.first).max(0) as u64;
+    (offset < Self::MAX_BUCKETS as u64).then_some(offset as usize)
+  }
+
+  /// The earliest nonempty bucket.
+  fn front(&self) -> Option<&BinaryHeap<Event>> {
+    self.buckets.iter().find(|bucket| !bucket.is_empty())
+  }
+
+  /// Drops the empty buckets at the front, so that the first bucket holds the earliest bucketed event.
+  fn drop_empty_front(&mut self) {
+    while self.buckets.front().is_some_and(BinaryHeap::is_empty) {
+      self.buckets.pop_front();
+      self.first += 1;
+    }
+  }
+}
+
+impl TimelineBackend for BucketQueue {
+  fn push(&mut self, event: Event) {
+    if self.buckets.is_empty() {
+      // Start the buckets at this event, so that they do not trail behind the current time.
+      let index = (event.time.0 / self.width).floor();
+      if index.is_finite() {
+        self.first = index as i64;
+      }
+    }
+    match self.bucket_position(event.time.0) {
+      Some(position) => {
+        if position >= self.buckets.len() {
+          self.buckets.resize_with(position + 1, BinaryHeap::new);
+        }
+        self.buckets[position].push(event);
+      }
+      None => self.overflow.push(event),
+    }
+    self.len += 1;
+  }
+
+  fn pop(&mut self) -> Option<Event> {
+    self.drop_empty_front();
+    let from_overflow = match (self.buckets.front().and_then(BinaryHeap::peek), self.overflow.peek()) {
+      (Some(bucketed), Some(overflowed)) => overflowed.time < bucketed.time,
+      (None, Some(_)) => true,
+      (_, None) => false,
+    };
+    let popped = if from_overflow {
+      self.overflow.pop()
+    } else {
+      self.buckets.front_mut().and_then(BinaryHeap::pop)
+    };
+    if popped.is_some() {
+      self.len -= 1;
+    }
+    popped
+  }
+
+  fn peek(&self) -> Option<&Event> {
+    match (self.front().and_then(BinaryHeap::peek), self.overflow.peek()) {
+      (Some(bucketed), Some(overflowed)) if overflowed.time < bucketed.time => Some(overflowed),
+      (Some(bucketed), _) => Some(bucketed),
+      (None, overflowed) => overflowed,
+    }
+  }
+
+  fn len(&self) -> usize {
+    self.len
+  }
+
+  fn iter(&self) -> Box<dyn Iterator<Item = &Event> + '_> {
+    Box::new(self.buckets.iter().flat_map(BinaryHeap::iter).chain(self.overflow.iter()))
+  }
+
+  fn retain(&mut self, keep: &mut dyn FnMut(&Event) -> bool) {
+    for bucket in self.buckets.iter_mut() {
+      bucket.retain(|event| keep(event));
+    }
+    self.overflow.retain(|event| keep(event));
+    self.len = self.buckets.iter().map(BinaryHeap::len).sum::<usize>() + self.overflow.len();
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use rand::{rngs::SmallRng, Rng, SeedableRng};
+  use crate::timeline_event::EventCommand;
+  use super::*;
+
+  fn event(time: f64, label: &'static str) -> Event {
+    Event{ time: OrderedFloat(time), command: EventCommand::infallible(|_| {}), entity: None, label }
+  }
+
+  /// Pushes and pops the same random events on both backends, checking that they pop at the same times.
+  #[test]
+  fn test_bucket_queue_matches_binary_heap() {
+    let mut rng = SmallRng::seed_from_u64(3);
+    let mut heap = BinaryHeap::new();
+    let mut buckets = BucketQueue::new(Duration(0.5)).unwrap();
+    let mut now = 0.0;
+    for step in 0..5_000 {
+      if step % 3 != 2 {
+        let time = match step % 97 {
+          0 => f64::INFINITY,
+          1 => now - 1.0,
+          2 => now + 1e9,
+          _ => now + rng.random::<f64>() * 20.0,
+        };
+        TimelineBackend::push(&mut heap, event(time, ""));
+        buckets.push(event(time, ""));
+      }
+      assert_eq!(TimelineBackend::peek(&heap).map(|event| event.time), buckets.peek().map(|event| event.time));
+      let (from_heap, from_buckets) = (TimelineBackend::pop(&mut heap), buckets.pop());
+      assert_eq!(from_heap.as_ref().map(|event| event.time), from_buckets.map(|event| event.time));
+      if let Some(popped) = from_heap && popped.time.0.is_finite() {
+        now = popped.time.0;
+      }
+      assert_eq!(TimelineBackend::len(&heap), buckets.len());
+    }
+  }
+
+  #[test]
+  fn test_bucket_queue_retain() {
+    let mut buckets = BucketQueue::new(Duration(1.0)).unwrap();
+    for (time, label) in [(0.5, "keep"), (2.5, "drop"), (1e12, "drop"), (3.5, "keep")] {
+      buckets.push(event(time, label));
+    }
+    buckets.retain(&mut |event| event.label == "keep");
+    assert_eq!(buckets.len(), 2);
+    assert_eq!(buckets.iter().count(), 2);
+    assert_eq!(buckets.pop().unwrap().time, OrderedFloat(0.5));
+    assert_eq!(buckets.pop().unwrap().time, OrderedFloat(3.5));
+    assert!(buckets.pop().is_none());
+    assert!(BucketQueue::new(Duration(0.0)).is_err());
+  }
+}
@@ -2,17 +2,108 @@ use std::cmp::{Ordering, Reverse};
 
 use bevy_ecs::prelude::*;
 
-use crate::timeline::Time;
+use crate::{
+  errors::IxaError,
+  timeline::Time
+};
 
+/// The closure of a fallible `EventCommand`.
+pub type FallibleCommand = Box<dyn FnOnce(&mut World) -> Result<(), IxaError> + Send + Sync>;
+
+/// What an `Event` does when it is run. A fallible command that fails aborts the model; see `Model::abort_reason`.
+pub enum EventCommand {
+  Infallible(Box<dyn FnOnce(&mut World) + Send + Sync>),
+  Fallible(FallibleCommand),
+}
+
+impl EventCommand {
+  /// A command that cannot fail.
+  pub fn infallible(command: impl FnOnce(&mut World) + Send + Sync + 'static) -> Self {
+    EventCommand::Infallible(Box::new(command))
+  }
+
+  /// A command that can fail, aborting the model.
+  pub fn fallible(command: impl FnOnce(&mut World) -> Result<(), IxaError> + Send + Sync + 'static) -> Self {
+    EventCommand::Fallible(Box::new(command))
+  }
+}
+
+/// Something to do at a given time on the `Timeline`. Construct it as a struct or, more readably, with `Event::builder`.
 pub struct Event {
   pub time  : Time,
   // ToDo: This might not be the right type, here. We want a thing that is
   //       Send and Sync with which we can put a command on the command
   //       queue.
-  pub command: Box<dyn FnOnce(&mut World) + Send + Sync>,
+  pub command: EventCommand,
+  /// The entity the event acts on, if any. Only used for introspection; see `Timeline::pending`.
+  pub entity: Option<Entity>,
+  /// A human-readable description of the event, e.g. `"recovery"`. Only used for introspection.
+  pub label  : &'static str,
   // We could also record the actor who scheduled the event, etc.
 }
 
+impl Event {
+  /// A builder of an event at `time`, which can be a plain `f64`. For example:
+  ///
+  /// ```ignore
+  /// let event = Event::builder(3.5)
+  ///     .command(move |world| recover(world, person))
+  ///     .entity(person)
+  ///     .label("recovery")
+  ///     .build();
+  /// ```
+  pub fn builder(time: impl Into<Time>) -> EventBuilder {
+    EventBuilder{ time: time.into(), command: None, entity: None, label: "" }
+  }
+}
+
+/// Builds an `Event`. See `Event::builder`. Unset fields are as in a struct literal with `entity: None` and `label: ""`,
+/// and an event without a command does nothing.
+#[must_use]
+pub struct EventBuilder {
+  time   : Time,
+  command: Option<EventCommand>,
+  entity : Option<Entity>,
+  label  : &'static str,
+}
+
+impl EventBuilder {
+  /// Runs `command`, which cannot fail.
+  pub fn command(mut self, command: impl FnOnce(&mut World) + Send + Sync + 'static) -> Self {
+    self.command = Some(EventCommand::infallible(command));
+    self
+  }
+
+  /// Runs `command`, which aborts the model if it fails.
+  pub fn fallible_command(
+    mut self,
+    command: impl FnOnce(&mut World) -> Result<(), IxaError> + Send + Sync + 'static
+  ) -> Self {
+    self.command = Some(EventCommand::fallible(command));
+    self
+  }
+
+  /// The entity the event acts on.
+  pub fn entity(mut self, entity: Entity) -> Self {
+    self.entity = Some(entity);
+    self
+  }
+
+  pub fn label(mut self, label: &'static str) -> Self {
+    self.label = label;
+    self
+  }
+
+  pub fn build(self) -> Event {
+    Event{
+      time: self.time,
+      command: self.command.unwrap_or_else(|| EventCommand::infallible(|_| {})),
+      entity: self.entity,
+      label: self.label,
+    }
+  }
+}
+
 // impl Command for Event {
 //   fn apply(self, world: &mut World) {
 //     #[cfg(feature = "print_messages")]
@@ -35,7 +126,7 @@ impl Eq for Event{}
 
 impl PartialOrd for Event {
   fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-    Some(self.cmp(&other))
+    Some(self.cmp(other))
   }
 }
 
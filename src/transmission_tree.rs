@@ -0,0 +1,175 @@
+/*!
+
+The `TransmissionTree` records who infected whom. Whenever a module causes an infection, it records the infector
+(or `None` if the infection came from outside the population, e.g. an importation) together with the time of
+infection. The record is kept in two places:
+
+ - on the infected entity, as an `InfectedBy` component, so systems can query it, and
+ - in the `TransmissionTree` resource, which keeps every edge in the order the infections happened, so the full tree
+   survives the entities themselves and can be exported for downstream phylodynamic tools.
+
+Infections without an infector are the roots of the tree. Because there may be many roots, the "tree" is in general a
+forest, and the Newick export writes one tree per line.
+
+*/
+
+use std::{
+  collections::HashMap,
+  fmt::Write as _,
+  path::Path
+};
+
+use bevy_ecs::{
+  prelude::*,
+  schedule::SystemConfigs
+};
+use serde::Serialize;
+
+use crate::{
+  errors::IxaError,
+  module::Module,
+  timeline::Time
+};
+
+/// Who infected this entity, and when.
+#[derive(Component, Copy, Clone, PartialEq, Eq, Debug)]
+pub struct InfectedBy {
+  /// `None` if the infection came from outside the population.
+  pub infector: Option<Entity>,
+  pub time    : Time,
+}
+
+/// A row of the edge list export.
+#[derive(Serialize)]
+struct EdgeRecord {
+  infector: Option<u32>,
+  infectee: u32,
+  time    : f64,
+}
+
+/// Every infection recorded so far, in the order in which they were recorded.
+#[derive(Resource, Default, Debug)]
+pub struct TransmissionTree {
+  edges: Vec<(Option<Entity>, Entity, Time)>,
+}
+
+impl TransmissionTree {
+  /// Records that `infector` infected `infectee` at `time`, both on `infectee` and in the `TransmissionTree` resource.
+  pub fn record_infection(world: &mut World, infector: Option<Entity>, infectee: Entity, time: Time) {
+    world.entity_mut(infectee).insert(InfectedBy{ infector, time });
+    world.resource_mut::<TransmissionTree>().edges.push((infector, infectee, time));
+  }
+
+  /// The `(infector, infectee, time)` edges of the tree, in the order the infections happened.
+  pub fn edges(&self) -> impl Iterator<Item = (Option<Entity>, Entity, Time)> + '_ {
+    self.edges.iter().copied()
+  }
+
+  /// The infections that had no infector.
+  pub fn roots(&self) -> impl Iterator<Item = Entity> + '_ {
+    self.edges.iter().filter(|(infector, ..)| infector.is_none()).map(|(_, infectee, _)| *infectee)
+  }
+
+  /// Writes the tree as a CSV edge list with columns `infector`, `infectee`, and `time`. Entities are identified by
+  /// their index, and the infector column is empty for roots.
+  pub fn write_edge_list(&self, path: &Path) -> Result<(), IxaError> {
+    let mut writer = csv::Writer::from_path(path)?;
+    for (infector, infectee, time) in self.edges() {
+      writer.serialize(EdgeRecord{
+        infector: infector.map(|infector| infector.index()),
+        infectee: infectee.index(),
+        time: time.0,
+      })?;
+    }
+    writer.flush()?;
+    Ok(())
+  }
+
+  /// Formats the tree in Newick format, one line per root. Nodes are labeled by entity index, and each branch length
+  /// is the time between the infector's infection and the infectee's.
+  pub fn to_newick(&self) -> String {
+    let mut children: HashMap<Entity, Vec<(Entity, Time)>> = HashMap::new();
+    let mut infection_times: HashMap<Entity, Time> = HashMap::new();
+    for (infector, infectee, time) in self.edges() {
+      infection_times.insert(infectee, time);
+      if let Some(infector) = infector {
+        children.entry(infector).or_default().push((infectee, time));
+      }
+    }
+
+    let mut newick = String::new();
+    for root in self.roots() {
+      write_newick_subtree(&mut newick, root, &children, &infection_times);
+      newick.push_str(";\n");
+    }
+    newick
+  }
+}
+
+/// Writes the subtree rooted at `node` without a trailing branch length.
+fn write_newick_subtree(
+  newick: &mut String,
+  node: Entity,
+  children: &HashMap<Entity, Vec<(Entity, Time)>>,
+  infection_times: &HashMap<Entity, Time>,
+) {
+  if let Some(node_children) = children.get(&node) {
+    newick.push('(');
+    for (position, (child, time)) in node_children.iter().enumerate() {
+      if position > 0 {
+        newick.push(',');
+      }
+      write_newick_subtree(newick, *child, children, infection_times);
+      let branch_length = match infection_times.get(&node) {
+        Some(parent_time) => time.0 - parent_time.0,
+        None => time.0,
+      };
+      let _ = write!(newick, ":{branch_length}");
+    }
+    newick.push(')');
+  }
+  let _ = write!(newick, "{}", node.index());
+}
+
+impl Module for TransmissionTree {
+  fn initialize_with_world(self, world: &mut World) -> Option<SystemConfigs> {
+    world.insert_resource(self);
+
+    #[cfg(feature = "print_messages")]
+    println!("Initialized module TransmissionTree");
+
+    None // No systems
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use ordered_float::OrderedFloat;
+  use super::*;
+
+  #[test]
+  fn test_transmission_tree() {
+    let mut world = World::default();
+    world.insert_resource(TransmissionTree::default());
+    let [a, b, c, d] = [(); 4].map(|_| world.spawn_empty().id());
+
+    TransmissionTree::record_infection(&mut world, None, a, OrderedFloat(0.0));
+    TransmissionTree::record_infection(&mut world, Some(a), b, OrderedFloat(1.5));
+    TransmissionTree::record_infection(&mut world, Some(a), c, OrderedFloat(2.0));
+    TransmissionTree::record_infection(&mut world, None, d, OrderedFloat(3.0));
+
+    assert_eq!(
+      world.get::<InfectedBy>(b),
+      Some(&InfectedBy{ infector: Some(a), time: OrderedFloat(1.5) })
+    );
+
+    let tree = world.resource::<TransmissionTree>();
+    assert_eq!(tree.roots().collect::<Vec<_>>(), vec![a, d]);
+    assert_eq!(tree.edges().count(), 4);
+    assert_eq!(
+      tree.to_newick(),
+      format!("({}:1.5,{}:2){};\n{};\n", b.index(), c.index(), a.index(), d.index())
+    );
+  }
+}
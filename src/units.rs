@@ -0,0 +1,316 @@
+/*!
+
+Newtypes that keep rates and durations apart at the type level.
+
+A force of infection is a `Rate` (events per unit time) and an infectious period is a `Duration` (a mean time to an
+event). Both are just numbers, and mixing them up is easy, especially when parameterizing an exponential distribution,
+which takes a rate: `Exp::new(duration)` compiles but is wrong. With these types, `Rate::exp` is the only way to build
+the distribution, and a mean duration must be converted explicitly with `Rate::from_mean`.
+
+Arithmetic is defined only where the result has a sensible unit: rates add to rates, durations add to durations, both
+scale by plain numbers, and a rate times a duration is a dimensionless `f64`. A `Duration` can also be added to a
+`Time`. `From<f64>` is provided for migrating code that uses bare floats.
+
+Times and durations are in the model's unit of time, which by convention is a day: every rate, duration, and time in
+this crate and its examples is per day or in days. Quantities given in other units are converted on construction, with
+`Duration::hours`, `Duration::weeks`, or `Duration::new` with a `TimeUnit`, and `Rate::per` for rates, so a `Duration`
+or `Rate` is always in days and there is no way to add hours to days by mistake. `Duration::in_unit` and
+`Rate::in_unit` convert back, e.g. for display. In a parameters file, a `Duration` is either a bare number of days or
+an object naming its unit, such as `{"hours": 12}`. The inner `f64` is still public for raw numeric work, and is in
+days.
+
+`Rate::exp` fails on a negative rate, and a rate of zero (or a duration of zero, through `Rate::from_mean`) gives
+infinite (or zero) waiting times, which are never what a parameter was meant to say. Modules validate their rates and
+durations with `Rate::validate` and `Duration::validate` when they are initialized, so that a bad parameter is
+reported by name instead of surfacing as a failed `exp` in the middle of a run.
+
+*/
+
+use std::ops::{Add, Div, Mul, Sub};
+
+use ordered_float::OrderedFloat;
+use rand_distr::{Exp, ExpError};
+use serde::{Deserialize, Deserializer, Serialize};
+
+use crate::{
+  errors::IxaError,
+  timeline::Time
+};
+
+/// A unit of time that durations and rates can be given in. The model's own unit is `TimeUnit::Days`.
+#[derive(Copy, Clone, PartialEq, Eq, Hash, Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TimeUnit {
+  Hours,
+  Days,
+  Weeks,
+}
+
+impl TimeUnit {
+  /// The length of one of this unit in days, the model's unit of time.
+  #[must_use]
+  pub const fn in_days(self) -> f64 {
+    match self {
+      TimeUnit::Hours => 1.0 / 24.0,
+      TimeUnit::Days => 1.0,
+      TimeUnit::Weeks => 7.0,
+    }
+  }
+}
+
+/// A number of events per day.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Default, Debug, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Rate(pub f64);
+
+/// A span of time in days, such as the mean time to an event.
+#[derive(Copy, Clone, PartialEq, PartialOrd, Default, Debug, Serialize)]
+#[serde(transparent)]
+pub struct Duration(pub f64);
+
+impl Rate {
+  /// The rate of `events` per one `unit` of time, converted to events per day.
+  #[must_use]
+  pub const fn per(events: f64, unit: TimeUnit) -> Rate {
+    Rate(events / unit.in_days())
+  }
+
+  /// The number of events per one `unit` of time.
+  #[must_use]
+  pub const fn in_unit(self, unit: TimeUnit) -> f64 {
+    self.0 * unit.in_days()
+  }
+
+  /// The rate of a process whose mean time between events is `mean`.
+  pub fn from_mean(mean: Duration) -> Rate {
+    Rate(1.0 / mean.0)
+  }
+
+  /// The exponential distribution of waiting times for a process with this rate.
+  pub fn exp(self) -> Result<Exp<f64>, ExpError> {
+    Exp::new(self.0)
+  }
+
+  /// Returns the rate if it is positive and finite, and otherwise an error naming the `parameter` it is the value of.
+  pub fn validate(self, parameter: &str) -> Result<Rate, IxaError> {
+    if self.0.is_finite() && self.0 > 0.0 {
+      Ok(self)
+    } else {
+      Err(IxaError::IxaError(format!("{parameter} must be a positive, finite rate, but is {}", self.0)))
+    }
+  }
+}
+
+impl Duration {
+  /// The duration of `amount` of `unit`, converted to days.
+  #[must_use]
+  pub const fn new(amount: f64, unit: TimeUnit) -> Duration {
+    Duration(amount * unit.in_days())
+  }
+
+  #[must_use]
+  pub const fn hours(hours: f64) -> Duration {
+    Duration::new(hours, TimeUnit::Hours)
+  }
+
+  #[must_use]
+  pub const fn days(days: f64) -> Duration {
+    Duration(days)
+  }
+
+  #[must_use]
+  pub const fn weeks(weeks: f64) -> Duration {
+    Duration::new(weeks, TimeUnit::Weeks)
+  }
+
+  /// The time from `start` to `end`, negative if `end` is before `start`.
+  #[must_use]
+  pub fn between(start: Time, end: Time) -> Duration {
+    Duration(end.0 - start.0)
+  }
+
+  /// The number of `unit`s in the duration.
+  #[must_use]
+  pub const fn in_unit(self, unit: TimeUnit) -> f64 {
+    self.0 / unit.in_days()
+  }
+
+  /// The mean time between events of a process with the given `rate`.
+  pub fn mean_of(rate: Rate) -> Duration {
+    Duration(1.0 / rate.0)
+  }
+
+  /// Returns the duration if it is positive and finite, and otherwise an error naming the `parameter` it is the value
+  /// of.
+  pub fn validate(self, parameter: &str) -> Result<Duration, IxaError> {
+    if self.0.is_finite() && self.0 > 0.0 {
+      Ok(self)
+    } else {
+      Err(IxaError::IxaError(format!("{parameter} must be a positive, finite duration, but is {}", self.0)))
+    }
+  }
+}
+
+/// How a `Duration` is written in a parameters file: a number of days, or an amount of a named unit.
+#[derive(Deserialize)]
+#[serde(untagged, expecting = "a number of days or an object with one of the keys `hours`, `days`, or `weeks`")]
+enum DurationInput {
+  Days(f64),
+  InUnit(UnitAmount),
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum UnitAmount {
+  Hours(f64),
+  Days(f64),
+  Weeks(f64),
+}
+
+impl<'de> Deserialize<'de> for Duration {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    Ok(match DurationInput::deserialize(deserializer)? {
+      DurationInput::Days(days) => Duration::days(days),
+      DurationInput::InUnit(UnitAmount::Hours(hours)) => Duration::hours(hours),
+      DurationInput::InUnit(UnitAmount::Days(days)) => Duration::days(days),
+      DurationInput::InUnit(UnitAmount::Weeks(weeks)) => Duration::weeks(weeks),
+    })
+  }
+}
+
+impl From<f64> for Rate {
+  fn from(rate: f64) -> Self {
+    Rate(rate)
+  }
+}
+
+impl From<f64> for Duration {
+  fn from(duration: f64) -> Self {
+    Duration(duration)
+  }
+}
+
+/// Implements addition and subtraction of a unit with itself and scaling by `f64`.
+macro_rules! impl_unit_arithmetic {
+  ($unit:ident) => {
+    impl Add for $unit {
+      type Output = $unit;
+      fn add(self, other: $unit) -> $unit {
+        $unit(self.0 + other.0)
+      }
+    }
+
+    impl Sub for $unit {
+      type Output = $unit;
+      fn sub(self, other: $unit) -> $unit {
+        $unit(self.0 - other.0)
+      }
+    }
+
+    impl Mul<f64> for $unit {
+      type Output = $unit;
+      fn mul(self, factor: f64) -> $unit {
+        $unit(self.0 * factor)
+      }
+    }
+
+    impl Mul<$unit> for f64 {
+      type Output = $unit;
+      fn mul(self, unit: $unit) -> $unit {
+        $unit(self * unit.0)
+      }
+    }
+
+    impl Div<f64> for $unit {
+      type Output = $unit;
+      fn div(self, divisor: f64) -> $unit {
+        $unit(self.0 / divisor)
+      }
+    }
+  };
+}
+
+impl_unit_arithmetic!(Rate);
+impl_unit_arithmetic!(Duration);
+
+/// The expected number of events in the duration.
+impl Mul<Duration> for Rate {
+  type Output = f64;
+  fn mul(self, duration: Duration) -> f64 {
+    self.0 * duration.0
+  }
+}
+
+/// The expected number of events in the duration.
+impl Mul<Rate> for Duration {
+  type Output = f64;
+  fn mul(self, rate: Rate) -> f64 {
+    self.0 * rate.0
+  }
+}
+
+impl Add<Duration> for Time {
+  type Output = Time;
+  fn add(self, duration: Duration) -> Time {
+    OrderedFloat(self.0 + duration.0)
+  }
+}
+
+impl Sub<Duration> for Time {
+  type Output = Time;
+  fn sub(self, duration: Duration) -> Time {
+    OrderedFloat(self.0 - duration.0)
+  }
+}
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_conversions() {
+    assert_eq!(Rate::from_mean(Duration(4.0)), Rate(0.25));
+    assert_eq!(Duration::mean_of(Rate(0.25)), Duration(4.0));
+    assert_eq!(Rate(0.5) * Duration(3.0), 1.5);
+    assert_eq!(2.0 * Rate(0.5) + Rate(1.0), Rate(2.0));
+    assert_eq!(OrderedFloat(1.0) + Duration(2.5), OrderedFloat(3.5));
+  }
+
+  #[test]
+  fn test_time_units() {
+    assert_eq!(Duration::hours(36.0), Duration::days(1.5));
+    assert_eq!(Duration::weeks(2.0), Duration(14.0));
+    assert_eq!(Duration::new(12.0, TimeUnit::Hours).in_unit(TimeUnit::Hours), 12.0);
+    assert_eq!(Duration(3.5).in_unit(TimeUnit::Weeks), 0.5);
+    assert_eq!(Rate::per(7.0, TimeUnit::Weeks), Rate(1.0));
+    assert_eq!(Rate(2.0).in_unit(TimeUnit::Hours), 2.0 / 24.0);
+    assert_eq!(Rate::per(7.0, TimeUnit::Weeks) * Duration::weeks(1.0), 7.0);
+    assert_eq!(Duration::between(OrderedFloat(1.0), OrderedFloat(3.5)), Duration(2.5));
+    assert_eq!(OrderedFloat(3.5) - Duration::hours(12.0), OrderedFloat(3.0));
+  }
+
+  #[test]
+  fn test_deserialize_duration() {
+    let duration = |json: &str| serde_json::from_str::<Duration>(json);
+    assert_eq!(duration("2.5").unwrap(), Duration(2.5));
+    assert_eq!(duration(r#"{"hours": 12}"#).unwrap(), Duration(0.5));
+    assert_eq!(duration(r#"{"days": 3}"#).unwrap(), Duration(3.0));
+    assert_eq!(duration(r#"{"weeks": 1}"#).unwrap(), Duration(7.0));
+    let error = duration(r#"{"minutes": 30}"#).unwrap_err();
+    assert!(error.to_string().contains("a number of days or an object with one of the keys"), "{error}");
+    // Durations are written as a number of days.
+    assert_eq!(serde_json::to_string(&Duration::hours(12.0)).unwrap(), "0.5");
+  }
+
+  #[test]
+  fn test_validate() {
+    assert_eq!(Rate(0.5).validate("rate").unwrap(), Rate(0.5));
+    for invalid in [0.0, -1.0, f64::INFINITY, f64::NAN] {
+      assert!(Rate(invalid).validate("rate").is_err());
+      assert!(Duration(invalid).validate("duration").is_err());
+    }
+    let error = Duration(0.0).validate("the infection duration").unwrap_err();
+    assert!(error.to_string().contains("the infection duration must be a positive, finite duration, but is 0"));
+  }
+}